@@ -3,17 +3,23 @@
 //! A standalone server that enables programmatic control of terminal sessions.
 
 use std::process;
+use std::time::Duration;
 use titi::redititi_server::{RedititiTcpServer, TokenAuth};
 
 #[tokio::main]
 async fn main() {
     // Initialize logger
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    titi::logging::init();
 
     // Parse command-line arguments
     let args: Vec<String> = std::env::args().collect();
     let mut port = 6379;
     let mut _token_file: Option<String> = None;
+    let mut max_queue_len: Option<usize> = None;
+    let mut message_ttl: Option<u64> = None;
+    let mut state_file: Option<String> = None;
+    let mut idle_timeout: Option<u64> = None;
+    let mut connection_timeout: Option<u64> = None;
 
     let mut i = 1;
     while i < args.len() {
@@ -39,6 +45,63 @@ async fn main() {
                     process::exit(1);
                 }
             }
+            "--max-queue-len" => {
+                if i + 1 < args.len() {
+                    max_queue_len = Some(args[i + 1].parse().unwrap_or_else(|_| {
+                        eprintln!("Invalid queue length");
+                        process::exit(1);
+                    }));
+                    i += 2;
+                } else {
+                    eprintln!("--max-queue-len requires a value");
+                    process::exit(1);
+                }
+            }
+            "--message-ttl" => {
+                if i + 1 < args.len() {
+                    message_ttl = Some(args[i + 1].parse().unwrap_or_else(|_| {
+                        eprintln!("Invalid message TTL");
+                        process::exit(1);
+                    }));
+                    i += 2;
+                } else {
+                    eprintln!("--message-ttl requires a value");
+                    process::exit(1);
+                }
+            }
+            "--idle-timeout" => {
+                if i + 1 < args.len() {
+                    idle_timeout = Some(args[i + 1].parse().unwrap_or_else(|_| {
+                        eprintln!("Invalid idle timeout");
+                        process::exit(1);
+                    }));
+                    i += 2;
+                } else {
+                    eprintln!("--idle-timeout requires a value");
+                    process::exit(1);
+                }
+            }
+            "--connection-timeout" => {
+                if i + 1 < args.len() {
+                    connection_timeout = Some(args[i + 1].parse().unwrap_or_else(|_| {
+                        eprintln!("Invalid connection timeout");
+                        process::exit(1);
+                    }));
+                    i += 2;
+                } else {
+                    eprintln!("--connection-timeout requires a value");
+                    process::exit(1);
+                }
+            }
+            "--state-file" => {
+                if i + 1 < args.len() {
+                    state_file = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("--state-file requires a value");
+                    process::exit(1);
+                }
+            }
             "--help" | "-h" => {
                 print_help();
                 process::exit(0);
@@ -80,7 +143,32 @@ async fn main() {
 
     // Create and run server
     let addr = format!("127.0.0.1:{}", port);
-    let server = RedititiTcpServer::new(addr, auth);
+    let mut server = RedititiTcpServer::new(addr, auth);
+    if let Some(max_len) = max_queue_len {
+        server = server.with_max_queue_len(max_len);
+    }
+    if let Some(secs) = message_ttl {
+        server = server.with_message_ttl(Duration::from_secs(secs));
+    }
+    if let Some(secs) = idle_timeout {
+        server = server.with_idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = connection_timeout {
+        server = server.with_connection_timeout(Duration::from_secs(secs));
+    }
+    if let Some(path) = state_file {
+        log::info!("State file: {}", path);
+        server = server.with_persistence(path);
+    }
+
+    // Ctrl-C lets connections finish and saves queues before exiting
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            log::info!("Interrupted, shutting down");
+            shutdown.shutdown();
+        }
+    });
 
     if let Err(e) = server.run().await {
         eprintln!("Server error: {}", e);
@@ -97,6 +185,11 @@ fn print_help() {
     println!("OPTIONS:");
     println!("    -p, --port <PORT>             Port to listen on (default: 6379)");
     println!("    --token-file <FILE>           Custom token file location");
+    println!("    --max-queue-len <N>           Max messages queued per channel (default: 10000)");
+    println!("    --message-ttl <SECS>          Expire queued messages after SECS seconds");
+    println!("    --state-file <FILE>           Persist sessions and queues to FILE across restarts");
+    println!("    --idle-timeout <SECS>         Remove sessions with no activity for SECS seconds");
+    println!("    --connection-timeout <SECS>   Close connections that send nothing for SECS seconds");
     println!("    -h, --help                    Print help information");
     println!("    -v, --version                 Print version information");
     println!();
@@ -1,5 +1,14 @@
+use crate::keybindings::{self, KeyBinding};
+use crate::terminal::CursorShape;
+use crossbeam_channel::{Receiver, RecvTimeoutError};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod theme;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -7,6 +16,77 @@ pub struct Config {
     pub colors: ColorScheme,
     pub window: WindowConfig,
     pub shell: ShellConfig,
+    #[serde(default = "keybindings::default_bindings")]
+    pub keybindings: Vec<KeyBinding>,
+    /// Lines of history kept per pane (0 disables scrollback)
+    #[serde(default = "default_scrollback_lines")]
+    pub scrollback_lines: usize,
+    /// Most bytes of output parsed per pane each frame; the rest waits for
+    /// the next one so bursts don't stall drawing (0 means no limit)
+    #[serde(default = "default_max_parse_per_frame")]
+    pub max_parse_per_frame: usize,
+    /// Ask before pasting more than this many bytes, or several lines, into
+    /// an application without bracketed paste (0 never asks)
+    #[serde(default = "default_paste_confirm_threshold")]
+    pub paste_confirm_threshold: usize,
+    /// Draw bold text in the bright variant of the 8 standard colors
+    #[serde(default)]
+    pub bold_is_bright: bool,
+    /// Focus the pane under the mouse pointer without clicking
+    #[serde(default)]
+    pub focus_follows_mouse: bool,
+    /// Draw a title bar above each pane with its title
+    #[serde(default)]
+    pub pane_title_bars: bool,
+    /// Cursor shape used until an application picks one with DECSCUSR
+    #[serde(default)]
+    pub cursor_shape: CursorShape,
+    /// Draw the cursor in the foreground color of the cell under it, with
+    /// the glyph in the cell's background, instead of `colors.cursor`
+    #[serde(default)]
+    pub cursor_reverse: bool,
+    /// How long the cursor stays on, then off, when blinking (0 disables)
+    #[serde(default)]
+    pub blink_interval_ms: u64,
+    /// Characters that end a word when double-clicking to select one
+    #[serde(default = "default_word_separators")]
+    pub word_separators: String,
+    /// How a pane shows that it is scrolled back into history
+    #[serde(default)]
+    pub scroll_indicator: ScrollIndicator,
+    /// base16 YAML or Windows Terminal JSON scheme replacing `colors`;
+    /// relative paths are resolved against the config file's directory
+    #[serde(default)]
+    pub theme: Option<PathBuf>,
+}
+
+fn default_scrollback_lines() -> usize {
+    crate::terminal::DEFAULT_SCROLLBACK
+}
+
+fn default_max_parse_per_frame() -> usize {
+    crate::terminal::DEFAULT_PARSE_BUDGET
+}
+
+fn default_paste_confirm_threshold() -> usize {
+    crate::terminal::paste::DEFAULT_PASTE_CONFIRM_THRESHOLD
+}
+
+fn default_word_separators() -> String {
+    crate::terminal::DEFAULT_WORD_SEPARATORS.to_string()
+}
+
+/// Cue drawn over a pane while it is scrolled back into history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollIndicator {
+    /// A track along the right edge with a thumb spanning the lines shown
+    #[default]
+    Scrollbar,
+    /// Lines scrolled back out of the history, e.g. `120/2000`, in the
+    /// top-right corner
+    Position,
+    Off,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +99,9 @@ pub struct FontConfig {
 pub struct ColorScheme {
     pub background: [f32; 4],
     pub foreground: [f32; 4],
+    /// Cursor color; the foreground color when unset
+    #[serde(default)]
+    pub cursor: Option<[f32; 4]>,
     pub black: [f32; 4],
     pub red: [f32; 4],
     pub green: [f32; 4],
@@ -42,12 +125,66 @@ pub struct WindowConfig {
     pub width: u32,
     pub height: u32,
     pub title: String,
+    /// Space between each pane's edges and its text
+    #[serde(default)]
+    pub padding: Padding,
+    /// Opacity of the background, from 0.0 (fully transparent) to 1.0;
+    /// text stays opaque. Needs a compositor; ignored where the window
+    /// can't be made transparent.
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
 }
 
+fn default_opacity() -> f32 {
+    1.0
+}
+
+/// Inner padding in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Padding {
+    #[serde(default)]
+    pub top: f32,
+    #[serde(default)]
+    pub right: f32,
+    #[serde(default)]
+    pub bottom: f32,
+    #[serde(default)]
+    pub left: f32,
+}
+
+/// TERM value advertised to child processes; matches the sequences the parser
+/// understands (256-color and truecolor SGR)
+pub const DEFAULT_TERM: &str = "xterm-256color";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShellConfig {
+    /// Program to run instead of the user's shell (defaults to `$SHELL`)
     pub program: Option<String>,
     pub args: Vec<String>,
+    /// Extra environment variables for the child process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Directory the child process starts in (defaults to the home directory)
+    #[serde(default)]
+    pub working_directory: Option<PathBuf>,
+    /// Start the shell as a login shell (`-l`)
+    #[serde(default)]
+    pub login_shell: bool,
+    /// Value of `$TERM` in the child environment
+    #[serde(default = "default_term")]
+    pub term: String,
+    /// Start new panes, splits and tabs in the directory the active pane's
+    /// shell last reported (`OSC 7`) instead of `working_directory`
+    #[serde(default = "default_inherit_working_directory")]
+    pub inherit_working_directory: bool,
+}
+
+fn default_term() -> String {
+    DEFAULT_TERM.to_string()
+}
+
+fn default_inherit_working_directory() -> bool {
+    true
 }
 
 impl Default for Config {
@@ -62,11 +199,37 @@ impl Default for Config {
                 width: 1280,
                 height: 720,
                 title: "Titi Terminal".to_string(),
+                padding: Padding::default(),
+                opacity: default_opacity(),
             },
-            shell: ShellConfig {
-                program: None,
-                args: vec![],
-            },
+            shell: ShellConfig::default(),
+            keybindings: keybindings::default_bindings(),
+            scrollback_lines: default_scrollback_lines(),
+            max_parse_per_frame: default_max_parse_per_frame(),
+            paste_confirm_threshold: default_paste_confirm_threshold(),
+            bold_is_bright: false,
+            focus_follows_mouse: false,
+            pane_title_bars: false,
+            cursor_shape: CursorShape::default(),
+            cursor_reverse: false,
+            blink_interval_ms: 0,
+            word_separators: default_word_separators(),
+            scroll_indicator: ScrollIndicator::default(),
+            theme: None,
+        }
+    }
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            program: None,
+            args: vec![],
+            env: HashMap::new(),
+            working_directory: None,
+            login_shell: false,
+            term: default_term(),
+            inherit_working_directory: default_inherit_working_directory(),
         }
     }
 }
@@ -77,6 +240,7 @@ impl Default for ColorScheme {
         Self {
             background: [0.0, 0.169, 0.212, 1.0],
             foreground: [0.514, 0.580, 0.588, 1.0],
+            cursor: None,
             black: [0.0, 0.169, 0.212, 1.0],
             red: [0.863, 0.196, 0.184, 1.0],
             green: [0.522, 0.600, 0.0, 1.0],
@@ -101,14 +265,38 @@ impl Config {
     pub fn load() -> anyhow::Result<Self> {
         let config_path = Self::config_path()?;
         if config_path.exists() {
-            let content = std::fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&content)?;
-            Ok(config)
+            Self::load_from(&config_path)
         } else {
             Ok(Config::default())
         }
     }
 
+    /// Load and parse a config file at a specific path
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content, path)
+    }
+
+    /// Parse the contents of the config file at `path`, applying its theme
+    fn parse(content: &str, path: &Path) -> anyhow::Result<Self> {
+        let mut config: Config = toml::from_str(content)?;
+
+        if let Some(theme) = config.theme.clone() {
+            let theme = path.parent().map(|dir| dir.join(&theme)).unwrap_or(theme);
+            if let Err(e) = config.load_theme(&theme) {
+                log::error!("Failed to load theme {:?}, keeping configured colors: {}", theme, e);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Replace `colors` with the scheme in a base16 YAML or Windows Terminal
+    /// JSON file; on error the colors are left unchanged
+    pub fn load_theme(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.colors = theme::load_theme(path)?;
+        Ok(())
+    }
+
     pub fn save(&self) -> anyhow::Result<()> {
         let config_path = Self::config_path()?;
         if let Some(parent) = config_path.parent() {
@@ -119,9 +307,202 @@ impl Config {
         Ok(())
     }
 
+    /// Watch a config file and deliver a freshly parsed `Config` each time its
+    /// contents change
+    ///
+    /// Invalid edits are logged and skipped, so the receiver only ever sees
+    /// configs that parsed successfully. The watcher thread exits once the
+    /// returned `ConfigWatcher` is dropped.
+    pub fn watch(path: PathBuf) -> ConfigWatcher {
+        Self::watch_with_interval(path, Duration::from_millis(500))
+    }
+
+    pub fn watch_with_interval(path: PathBuf, interval: Duration) -> ConfigWatcher {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        // Snapshot before spawning so a write racing the thread start isn't
+        // mistaken for the initial contents
+        let mut last_content = std::fs::read_to_string(&path).ok();
+
+        std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                std::thread::sleep(interval);
+
+                let content = std::fs::read_to_string(&path).ok();
+                if content == last_content {
+                    continue;
+                }
+                last_content = content.clone();
+
+                let Some(content) = content else {
+                    continue;
+                };
+
+                match Self::parse(&content, &path) {
+                    Ok(config) => {
+                        log::info!("Config file {:?} changed, reloading", path);
+                        if tx.send(config).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Invalid config in {:?}, keeping previous: {}", path, e);
+                    }
+                }
+            }
+        });
+
+        ConfigWatcher { rx, stop }
+    }
+
+    /// Path of the user's config file (~/.config/titi/config.toml)
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        Self::config_path()
+    }
+
     fn config_path() -> anyhow::Result<PathBuf> {
         let config_dir = dirs::config_dir()
             .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
         Ok(config_dir.join("titi").join("config.toml"))
     }
 }
+
+/// Handle to a running config file watcher
+pub struct ConfigWatcher {
+    rx: Receiver<Config>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+    /// Latest reloaded config, if the file changed since the last call
+    pub fn try_recv(&self) -> Option<Config> {
+        self.rx.try_iter().last()
+    }
+
+    /// Wait up to `timeout` for the next reload
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Config> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(config) => Some(config),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("titi-config-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("config.toml")
+    }
+
+    #[test]
+    fn test_watch_reloads_changed_config() {
+        let path = temp_config_path("reload");
+        let mut config = Config::default();
+        std::fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+
+        let watcher = Config::watch_with_interval(path.clone(), Duration::from_millis(20));
+
+        config.font.size = 18.0;
+        config.scrollback_lines = 500;
+        config.colors.background = [0.1, 0.2, 0.3, 1.0];
+        std::fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+
+        let reloaded = watcher
+            .recv_timeout(Duration::from_secs(5))
+            .expect("Watcher should report the change");
+        assert_eq!(reloaded.font.size, 18.0);
+        assert_eq!(reloaded.scrollback_lines, 500);
+        assert_eq!(reloaded.colors.background, [0.1, 0.2, 0.3, 1.0]);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_watch_skips_invalid_config() {
+        let path = temp_config_path("invalid");
+        let mut config = Config::default();
+        std::fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+
+        let watcher = Config::watch_with_interval(path.clone(), Duration::from_millis(20));
+
+        std::fs::write(&path, "font = \"not a table\"").unwrap();
+        assert!(watcher.recv_timeout(Duration::from_millis(300)).is_none());
+
+        // A later valid edit is still picked up
+        config.font.size = 20.0;
+        std::fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+        let reloaded = watcher
+            .recv_timeout(Duration::from_secs(5))
+            .expect("Watcher should recover after an invalid edit");
+        assert_eq!(reloaded.font.size, 20.0);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_theme_replaces_colors() {
+        let path = temp_config_path("theme");
+        let dir = path.parent().unwrap();
+        std::fs::write(
+            dir.join("scheme.json"),
+            r##"{"background": "#000000", "foreground": "#ffffff", "black": "#000000",
+                "red": "#ff0000", "green": "#00ff00", "yellow": "#ffff00", "blue": "#0000ff",
+                "purple": "#ff00ff", "cyan": "#00ffff", "white": "#ffffff",
+                "brightBlack": "#808080", "brightRed": "#ff8080", "brightGreen": "#80ff80",
+                "brightYellow": "#ffff80", "brightBlue": "#8080ff", "brightPurple": "#ff80ff",
+                "brightCyan": "#80ffff", "brightWhite": "#ffffff"}"##,
+        )
+        .unwrap();
+
+        let config = Config {
+            theme: Some(PathBuf::from("scheme.json")),
+            ..Config::default()
+        };
+        std::fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+
+        let loaded = Config::load_from(&path).unwrap();
+        assert_eq!(loaded.colors.red, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(loaded.colors.background, [0.0, 0.0, 0.0, 1.0]);
+
+        // A broken theme keeps the colors from the config file
+        std::fs::write(dir.join("scheme.json"), "{").unwrap();
+        let loaded = Config::load_from(&path).unwrap();
+        assert_eq!(loaded.colors.red, ColorScheme::default().red);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_sections_use_defaults() {
+        let path = temp_config_path("partial");
+        let mut config = Config::default();
+        config.font.size = 16.0;
+        let mut content = toml::to_string_pretty(&config).unwrap();
+        // Drop everything after the shell section, as in configs written
+        // before keybindings/scrollback existed
+        if let Some(idx) = content.find("[[keybindings]]") {
+            content.truncate(idx);
+        }
+        content = content.replace("scrollback_lines = 10000\n", "");
+        std::fs::write(&path, content).unwrap();
+
+        let loaded = Config::load_from(&path).unwrap();
+        assert_eq!(loaded.font.size, 16.0);
+        assert_eq!(loaded.scrollback_lines, crate::terminal::DEFAULT_SCROLLBACK);
+        assert_eq!(loaded.keybindings, crate::keybindings::default_bindings());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,224 @@
+//! Color scheme import: base16 YAML and Windows Terminal JSON scheme files
+//! resolved into a `ColorScheme`
+
+use super::ColorScheme;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Read a scheme file, picking the format from its extension (`.json` for
+/// Windows Terminal, anything else for base16 YAML)
+pub fn load_theme(path: &Path) -> anyhow::Result<ColorScheme> {
+    let content = std::fs::read_to_string(path)?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    if is_json {
+        parse_windows_terminal(&content)
+    } else {
+        parse_base16(&content)
+    }
+}
+
+/// Parse a base16 scheme: `base00` to `base0F` as hex colors, either at the
+/// top level or under `palette:`, mapped to the terminal colors the way
+/// base16-shell does
+pub fn parse_base16(content: &str) -> anyhow::Result<ColorScheme> {
+    let mut bases = HashMap::new();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        if key.len() == 6 && key.starts_with("base") {
+            // Drop a trailing comment, then quotes
+            let value = value.split(" #").next().unwrap_or("").trim().trim_matches(['"', '\'']);
+            bases.insert(key, parse_hex(value)?);
+        }
+    }
+
+    let base = |n: &str| {
+        bases
+            .get(&format!("base{}", n))
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("base16 scheme is missing base{}", n))
+    };
+
+    Ok(ColorScheme {
+        background: base("00")?,
+        foreground: base("05")?,
+        cursor: Some(base("05")?),
+        black: base("00")?,
+        red: base("08")?,
+        green: base("0b")?,
+        yellow: base("0a")?,
+        blue: base("0d")?,
+        magenta: base("0e")?,
+        cyan: base("0c")?,
+        white: base("05")?,
+        bright_black: base("03")?,
+        bright_red: base("08")?,
+        bright_green: base("0b")?,
+        bright_yellow: base("0a")?,
+        bright_blue: base("0d")?,
+        bright_magenta: base("0e")?,
+        bright_cyan: base("0c")?,
+        bright_white: base("07")?,
+    })
+}
+
+/// Parse a Windows Terminal scheme: a single scheme object, or the first
+/// entry of a settings file's `schemes`
+pub fn parse_windows_terminal(content: &str) -> anyhow::Result<ColorScheme> {
+    let json: Value = serde_json::from_str(content)?;
+    let scheme = match json.get("schemes") {
+        Some(schemes) => schemes
+            .get(0)
+            .ok_or_else(|| anyhow::anyhow!("settings file has no schemes"))?,
+        None => &json,
+    };
+
+    let color = |key: &str| -> anyhow::Result<[f32; 4]> {
+        let value = scheme
+            .get(key)
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("Windows Terminal scheme is missing {}", key))?;
+        parse_hex(value)
+    };
+
+    Ok(ColorScheme {
+        background: color("background")?,
+        foreground: color("foreground")?,
+        cursor: scheme.get("cursorColor").map(|_| color("cursorColor")).transpose()?,
+        black: color("black")?,
+        red: color("red")?,
+        green: color("green")?,
+        yellow: color("yellow")?,
+        blue: color("blue")?,
+        magenta: color("purple")?,
+        cyan: color("cyan")?,
+        white: color("white")?,
+        bright_black: color("brightBlack")?,
+        bright_red: color("brightRed")?,
+        bright_green: color("brightGreen")?,
+        bright_yellow: color("brightYellow")?,
+        bright_blue: color("brightBlue")?,
+        bright_magenta: color("brightPurple")?,
+        bright_cyan: color("brightCyan")?,
+        bright_white: color("brightWhite")?,
+    })
+}
+
+/// `RRGGBB`, with or without a leading `#`, as opaque RGBA
+fn parse_hex(value: &str) -> anyhow::Result<[f32; 4]> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid color '{}', expected RRGGBB", value);
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as f32 / 255.0;
+    Ok([channel(0), channel(2), channel(4), 1.0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(r: u8, g: u8, b: u8) -> [f32; 4] {
+        [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]
+    }
+
+    const BASE16_TOMORROW_NIGHT: &str = r##"
+scheme: "Tomorrow Night"
+author: "Chris Kempson (http://chriskempson.com)"
+base00: "1d1f21"
+base01: "282a2e"
+base02: "373b41"
+base03: "969896"
+base04: "b4b7b4"
+base05: "c5c8c6"
+base06: "e0e0e0"
+base07: "ffffff"
+base08: "cc6666"
+base09: "de935f"
+base0A: "f0c674"
+base0B: "b5bd68"
+base0C: "8abeb7"
+base0D: "81a2be"
+base0E: "b294bb"
+base0F: "a3685a"
+"##;
+
+    #[test]
+    fn test_parse_base16() {
+        let colors = parse_base16(BASE16_TOMORROW_NIGHT).unwrap();
+
+        assert_eq!(colors.background, rgb(0x1d, 0x1f, 0x21));
+        assert_eq!(colors.foreground, rgb(0xc5, 0xc8, 0xc6));
+        assert_eq!(colors.cursor, Some(rgb(0xc5, 0xc8, 0xc6)));
+        assert_eq!(colors.red, rgb(0xcc, 0x66, 0x66));
+        assert_eq!(colors.yellow, rgb(0xf0, 0xc6, 0x74));
+        assert_eq!(colors.blue, rgb(0x81, 0xa2, 0xbe));
+        assert_eq!(colors.bright_black, rgb(0x96, 0x98, 0x96));
+        assert_eq!(colors.bright_white, rgb(0xff, 0xff, 0xff));
+    }
+
+    #[test]
+    fn test_parse_base16_palette_section() {
+        let content = BASE16_TOMORROW_NIGHT
+            .replace("base", "  base")
+            .replace(": \"", ": \"#")
+            .replacen("scheme:", "palette:\nname:", 1);
+
+        let colors = parse_base16(&content).unwrap();
+        assert_eq!(colors.green, rgb(0xb5, 0xbd, 0x68));
+    }
+
+    #[test]
+    fn test_parse_windows_terminal() {
+        let content = r##"{
+            "name": "Campbell",
+            "background": "#0C0C0C",
+            "foreground": "#CCCCCC",
+            "cursorColor": "#FFFFFF",
+            "black": "#0C0C0C",
+            "red": "#C50F1F",
+            "green": "#13A10E",
+            "yellow": "#C19C00",
+            "blue": "#0037DA",
+            "purple": "#881798",
+            "cyan": "#3A96DD",
+            "white": "#CCCCCC",
+            "brightBlack": "#767676",
+            "brightRed": "#E74856",
+            "brightGreen": "#16C60C",
+            "brightYellow": "#F9F1A5",
+            "brightBlue": "#3B78FF",
+            "brightPurple": "#B4009E",
+            "brightCyan": "#61D6D6",
+            "brightWhite": "#F2F2F2"
+        }"##;
+
+        let colors = parse_windows_terminal(content).unwrap();
+        assert_eq!(colors.background, rgb(0x0c, 0x0c, 0x0c));
+        assert_eq!(colors.cursor, Some(rgb(0xff, 0xff, 0xff)));
+        assert_eq!(colors.red, rgb(0xc5, 0x0f, 0x1f));
+        assert_eq!(colors.magenta, rgb(0x88, 0x17, 0x98));
+        assert_eq!(colors.bright_magenta, rgb(0xb4, 0x00, 0x9e));
+
+        // The same scheme inside a settings file, without a cursor color
+        let settings = format!(r#"{{"schemes": [{}]}}"#, content.replace(r##""cursorColor": "#FFFFFF","##, ""));
+        let colors = parse_windows_terminal(&settings).unwrap();
+        assert_eq!(colors.bright_white, rgb(0xf2, 0xf2, 0xf2));
+        assert_eq!(colors.cursor, None);
+    }
+
+    #[test]
+    fn test_invalid_schemes_are_rejected() {
+        assert!(parse_base16("base00: \"1d1f21\"").is_err());
+        assert!(parse_base16(&BASE16_TOMORROW_NIGHT.replace("cc6666", "cc66")).is_err());
+        assert!(parse_windows_terminal(r##"{"background": "#000000"}"##).is_err());
+        assert!(parse_windows_terminal("not json").is_err());
+    }
+}
@@ -4,11 +4,25 @@
 //! GPU rendering, designed for automation, CI/CD, and server environments.
 //! It's the core component for orchestrating multiple AI agents via redititi.
 
-use crate::terminal::Terminal;
+pub mod benchmark;
+pub mod multiplexer;
+pub mod script;
+
+pub use benchmark::{Benchmark, BenchmarkReport};
+pub use multiplexer::{Multiplexer, TaggedOutput};
+
+use crate::config::ShellConfig;
+use crate::terminal::{Grid, Terminal};
 use crate::server_client::ServerClient;
+use regex::Regex;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tokio::time::{self, Duration};
 use anyhow::Result;
 
+/// How often the server connection is pinged, and how long a `PONG` may take
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
 /// Configuration for headless mode
 pub struct HeadlessConfig {
     /// Server address (e.g., "localhost:6379")
@@ -23,6 +37,12 @@ pub struct HeadlessConfig {
     pub cols: u16,
     /// Terminal rows
     pub rows: u16,
+    /// Directory where `CAPTURE <target> FILE <path>` snapshots are written;
+    /// file captures are refused when unset
+    pub capture_dir: Option<PathBuf>,
+    /// Command script (see [`script`]) to run instead of serving requests;
+    /// the run ends when the script finishes and fails if any step fails
+    pub script: Option<PathBuf>,
 }
 
 impl Default for HeadlessConfig {
@@ -34,6 +54,8 @@ impl Default for HeadlessConfig {
             pane_name: None,
             cols: 80,
             rows: 24,
+            capture_dir: None,
+            script: None,
         }
     }
 }
@@ -62,6 +84,8 @@ impl Default for HeadlessConfig {
 ///         pane_name: Some("my-pane".to_string()),
 ///         cols: 80,
 ///         rows: 24,
+///         capture_dir: None,
+///         script: None,
 ///     };
 ///
 ///     run_headless(config).await
@@ -69,6 +93,10 @@ impl Default for HeadlessConfig {
 /// ```
 pub async fn run_headless(config: HeadlessConfig) -> Result<()> {
     log::info!("Starting headless mode");
+
+    // Parse the script up front so a typo fails before connecting
+    let script = config.script.as_deref().map(script::load_script).transpose()?;
+
     log::info!("Connecting to server at {}", config.server_addr);
 
     // Connect to redititi server
@@ -112,6 +140,14 @@ pub async fn run_headless(config: HeadlessConfig) -> Result<()> {
 
     // Create terminal with server integration
     let mut terminal = Terminal::new_with_server(config.cols, config.rows, client)?;
+    let _keepalive = terminal.spawn_server_keepalive(&config.server_addr, &config.token, KEEPALIVE_INTERVAL);
+
+    if let Some(commands) = script {
+        log::info!("Running script with {} commands", commands.len());
+        script::run_script(&mut terminal, &commands, config.capture_dir.as_deref()).await?;
+        log::info!("Script finished");
+        return Ok(());
+    }
 
     log::info!("Terminal created, entering main loop");
 
@@ -141,6 +177,10 @@ pub async fn run_headless(config: HeadlessConfig) -> Result<()> {
             log::error!("Failed to poll server input: {}", e);
         }
 
+        if let Some(capture_dir) = &config.capture_dir {
+            terminal.poll_capture_requests(capture_dir).await;
+        }
+
         // Log heartbeat every 60 seconds
         if last_log.elapsed() >= Duration::from_secs(60) {
             log::info!("Headless terminal running (frames processed: {})", frame_count);
@@ -152,6 +192,92 @@ pub async fn run_headless(config: HeadlessConfig) -> Result<()> {
     }
 }
 
+/// Configuration for [`run_headless_local`], which needs no server
+#[derive(Default)]
+pub struct LocalHeadlessConfig {
+    /// Terminal columns (0 means 80)
+    pub cols: u16,
+    /// Terminal rows (0 means 24)
+    pub rows: u16,
+    /// Shell or command to run in the PTY
+    pub shell: ShellConfig,
+}
+
+/// A headless terminal driven in-process: write input, pump the PTY and
+/// read the screen, with no redititi server involved
+pub struct LocalHeadless {
+    terminal: Terminal,
+}
+
+/// Start a local headless terminal
+///
+/// Unlike [`run_headless`] nothing runs in the background; output is only
+/// read into the grid when the caller pumps it.
+///
+/// # Example
+/// ```no_run
+/// use titi::headless::{run_headless_local, LocalHeadlessConfig};
+/// use tokio::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     let mut terminal = run_headless_local(LocalHeadlessConfig::default())?;
+///     terminal.write(b"echo hi\n").await?;
+///     terminal.wait_for("^hi$", Duration::from_secs(5)).await?;
+///     println!("{}", terminal.screen());
+///     Ok(())
+/// }
+/// ```
+pub fn run_headless_local(config: LocalHeadlessConfig) -> Result<LocalHeadless> {
+    let cols = if config.cols == 0 { 80 } else { config.cols };
+    let rows = if config.rows == 0 { 24 } else { config.rows };
+
+    log::info!("Starting local headless terminal ({}x{})", cols, rows);
+    let terminal = Terminal::with_shell(cols, rows, &config.shell)?;
+
+    Ok(LocalHeadless { terminal })
+}
+
+impl LocalHeadless {
+    /// Send input to the PTY as if typed
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.terminal.write(data)
+    }
+
+    /// Process all output the PTY has produced so far, returning whether
+    /// there was any
+    pub async fn pump(&mut self) -> Result<bool> {
+        let mut any = false;
+        while let Some(output) = self.terminal.read()? {
+            self.terminal.process_output(&output);
+            any = true;
+        }
+        Ok(any)
+    }
+
+    /// Pump output until the screen matches the regex `pattern` (matched per
+    /// line), failing after `timeout`
+    pub async fn wait_for(&mut self, pattern: &str, timeout: Duration) -> Result<()> {
+        let pattern = Regex::new(&format!("(?m){}", pattern))?;
+        script::wait_for(&mut self.terminal, &pattern, timeout).await
+    }
+
+    /// Visible screen text, one line per row
+    pub fn screen(&self) -> String {
+        self.terminal.grid().lock().unwrap().visible_lines().collect::<Vec<_>>().join("\n")
+    }
+
+    /// Shared grid, for reading cells, cursor or scrollback directly
+    pub fn grid(&self) -> Arc<Mutex<Grid>> {
+        self.terminal.grid()
+    }
+
+    /// The underlying terminal, e.g. to resize it or run a [`script`]
+    pub fn terminal_mut(&mut self) -> &mut Terminal {
+        &mut self.terminal
+    }
+}
+
 /// Builder for HeadlessConfig
 pub struct HeadlessConfigBuilder {
     config: HeadlessConfig,
@@ -190,6 +316,16 @@ impl HeadlessConfigBuilder {
         self
     }
 
+    pub fn capture_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.capture_dir = Some(dir.into());
+        self
+    }
+
+    pub fn script(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.script = Some(path.into());
+        self
+    }
+
     pub fn build(self) -> HeadlessConfig {
         self.config
     }
@@ -213,6 +349,8 @@ mod tests {
             .session_name("test-session")
             .pane_name("test-pane")
             .size(100, 30)
+            .capture_dir("/tmp/titi-captures")
+            .script("/tmp/setup.titi")
             .build();
 
         assert_eq!(config.server_addr, "localhost:6380");
@@ -221,6 +359,8 @@ mod tests {
         assert_eq!(config.pane_name, Some("test-pane".to_string()));
         assert_eq!(config.cols, 100);
         assert_eq!(config.rows, 30);
+        assert_eq!(config.capture_dir, Some(PathBuf::from("/tmp/titi-captures")));
+        assert_eq!(config.script, Some(PathBuf::from("/tmp/setup.titi")));
     }
 
     #[test]
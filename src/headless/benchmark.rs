@@ -0,0 +1,210 @@
+//! Throughput benchmark for the redititi server
+//!
+//! Starts a server, connects a number of clients that each get their own
+//! session and pane, and has every client inject messages into its pane and
+//! read them back from the pane's input queue:
+//!
+//! ```no_run
+//! use titi::headless::Benchmark;
+//!
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let report = Benchmark::new("127.0.0.1:16399").clients(4).messages(1_000).run().await?;
+//!     println!("{:.0} msg/s, p99 {:?}", report.throughput(), report.p99);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::redititi_server::{RateLimits, RedititiTcpServer, TokenAuth};
+use crate::server_client::ServerClient;
+use anyhow::{anyhow, bail, Result};
+use tokio::time::{self, Duration, Instant};
+
+/// How long the server gets to start accepting connections
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A workload to run against a fresh server
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    addr: String,
+    clients: usize,
+    messages: usize,
+    payload_size: usize,
+    timeout: Duration,
+}
+
+/// What a benchmark run measured
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    /// Messages injected and read back, over all clients
+    pub messages: usize,
+    /// Wall time from the first injection to the last read
+    pub elapsed: Duration,
+    /// Round trip (inject until read back) percentiles
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl BenchmarkReport {
+    /// Messages per second
+    pub fn throughput(&self) -> f64 {
+        self.messages as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn from_latencies(mut latencies: Vec<Duration>, elapsed: Duration) -> Self {
+        latencies.sort();
+        let percentile = |p: usize| {
+            let index = (latencies.len() * p).div_ceil(100).saturating_sub(1);
+            latencies.get(index).copied().unwrap_or_default()
+        };
+        Self {
+            messages: latencies.len(),
+            elapsed,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl Benchmark {
+    /// A benchmark serving on `addr`; by default one client sends 1000
+    /// 64-byte messages
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            clients: 1,
+            messages: 1_000,
+            payload_size: 64,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Number of clients running the workload concurrently
+    pub fn clients(mut self, clients: usize) -> Self {
+        self.clients = clients.max(1);
+        self
+    }
+
+    /// Messages each client injects
+    pub fn messages(mut self, messages: usize) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    /// Bytes per injected message
+    pub fn payload_size(mut self, bytes: usize) -> Self {
+        self.payload_size = bytes;
+        self
+    }
+
+    /// How long a single message may take to come back before the run fails
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the workload; the server is shut down again afterwards
+    pub async fn run(&self) -> Result<BenchmarkReport> {
+        let token = format!("benchmark-{:016x}", rand::random::<u64>());
+        let auth = TokenAuth::from_token(token.clone()).map_err(|e| anyhow!("Invalid token: {:?}", e))?;
+        // Rate limits would measure the limiter rather than the server
+        let server = RedititiTcpServer::new(self.addr.clone(), auth).with_rate_limits(RateLimits::unlimited());
+        let shutdown = server.shutdown_handle();
+        let server = tokio::spawn(async move { server.run().await });
+
+        let result = self.run_clients(&token).await;
+        shutdown.shutdown();
+        match server.await {
+            Ok(Err(e)) if result.is_ok() => Err(anyhow!("Server failed: {}", e)),
+            _ => result,
+        }
+    }
+
+    async fn run_clients(&self, token: &str) -> Result<BenchmarkReport> {
+        let mut clients = Vec::with_capacity(self.clients);
+        for index in 0..self.clients {
+            clients.push(self.connect(token, index).await?);
+        }
+
+        let start = Instant::now();
+        let tasks: Vec<_> = clients
+            .into_iter()
+            .enumerate()
+            .map(|(index, client)| tokio::spawn(self.clone().drive(client, index)))
+            .collect();
+
+        let mut latencies = Vec::with_capacity(self.clients * self.messages);
+        for task in tasks {
+            latencies.extend(task.await??);
+        }
+        Ok(BenchmarkReport::from_latencies(latencies, start.elapsed()))
+    }
+
+    /// Connect client `index` and give it its own session and pane,
+    /// retrying while the server is still starting
+    async fn connect(&self, token: &str, index: usize) -> Result<ServerClient> {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        let mut client = loop {
+            match ServerClient::connect(&self.addr).await {
+                Ok(client) => break client,
+                Err(e) if Instant::now() >= deadline => bail!("Failed to connect to {}: {}", self.addr, e),
+                Err(_) => time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+
+        let setup = async {
+            client.authenticate(token).await?;
+            client.create_session(Some(&format!("benchmark-{}", index))).await?;
+            client.create_pane(Some("benchmark")).await?;
+            client.subscribe_input().await
+        };
+        setup.await.map_err(|e| anyhow!("Client {} setup failed: {}", index, e))?;
+        Ok(client)
+    }
+
+    /// Inject each message and wait for it to come back, timing the round trip
+    async fn drive(self, mut client: ServerClient, index: usize) -> Result<Vec<Duration>> {
+        let mut latencies = Vec::with_capacity(self.messages);
+        for n in 0..self.messages {
+            let tag = format!("c{}m{}:", index, n);
+            let message = format!("{}{}", tag, "x".repeat(self.payload_size.saturating_sub(tag.len())));
+
+            let sent = Instant::now();
+            client.inject(&message).await.map_err(|e| anyhow!("Client {}: {}", index, e))?;
+            loop {
+                match client.read_input().await.map_err(|e| anyhow!("Client {}: {}", index, e))? {
+                    Some(received) if received.starts_with(&message) => break,
+                    Some(received) => bail!("Client {} expected {:?}, read {:?}", index, message, received),
+                    None if sent.elapsed() >= self.timeout => {
+                        bail!("Client {} timed out after {:?} waiting for message {}", index, self.timeout, n)
+                    }
+                    None => time::sleep(Duration::from_millis(1)).await,
+                }
+            }
+            latencies.push(sent.elapsed());
+        }
+        Ok(latencies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_percentiles() {
+        let latencies = (1..=100).rev().map(Duration::from_millis).collect();
+        let report = BenchmarkReport::from_latencies(latencies, Duration::from_secs(2));
+
+        assert_eq!(report.messages, 100);
+        assert_eq!(report.p50, Duration::from_millis(50));
+        assert_eq!(report.p95, Duration::from_millis(95));
+        assert_eq!(report.p99, Duration::from_millis(99));
+        assert_eq!(report.max, Duration::from_millis(100));
+        assert_eq!(report.throughput(), 50.0);
+    }
+}
@@ -0,0 +1,220 @@
+//! One controller driving many headless terminals
+//!
+//! A [`Multiplexer`] owns a set of terminals, each with its own shell,
+//! session, pane and server connection, plus a controller connection that
+//! injects into them. Output from every terminal is pumped through one call
+//! and tagged with the name of the terminal it came from:
+//!
+//! ```no_run
+//! use titi::headless::Multiplexer;
+//!
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let mut mux = Multiplexer::connect("127.0.0.1:6379", "token").await?;
+//!     mux.add("build").await?;
+//!     mux.add("test").await?;
+//!     mux.inject_all("echo hi").await?;
+//!     for output in mux.pump().await? {
+//!         print!("[{}] {}", output.source, output.text());
+//!     }
+//!     mux.shutdown().await
+//! }
+//! ```
+
+use crate::server_client::ServerClient;
+use crate::terminal::{CaptureFormat, CaptureMode, Terminal};
+use anyhow::{anyhow, bail, Result};
+use std::borrow::Cow;
+
+/// Output read from one managed terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedOutput {
+    /// Name of the terminal that produced it
+    pub source: String,
+    /// Raw bytes as read from the PTY
+    pub data: Vec<u8>,
+}
+
+impl TaggedOutput {
+    /// The output as text, with invalid UTF-8 replaced
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+}
+
+struct ManagedTerminal {
+    name: String,
+    session_id: String,
+    pane_id: String,
+    terminal: Terminal,
+    /// The shell exited (or its PTY failed); nothing more is read from it
+    exited: bool,
+}
+
+/// A set of named terminals controlled through one server connection
+pub struct Multiplexer {
+    addr: String,
+    token: String,
+    cols: u16,
+    rows: u16,
+    controller: ServerClient,
+    terminals: Vec<ManagedTerminal>,
+}
+
+impl Multiplexer {
+    /// Connect and authenticate the controller
+    pub async fn connect(addr: &str, token: &str) -> Result<Self> {
+        let mut controller = ServerClient::connect(addr).await.map_err(|e| anyhow!(e))?;
+        controller.authenticate(token).await.map_err(|e| anyhow!(e))?;
+
+        Ok(Self {
+            addr: addr.to_string(),
+            token: token.to_string(),
+            cols: 80,
+            rows: 24,
+            controller,
+            terminals: Vec::new(),
+        })
+    }
+
+    /// Size of terminals added from now on (default 80x24)
+    pub fn size(mut self, cols: u16, rows: u16) -> Self {
+        self.cols = cols;
+        self.rows = rows;
+        self
+    }
+
+    /// Start a shell in a new session named `name` and manage it. Returns
+    /// the pane's `session/pane-N` target.
+    pub async fn add(&mut self, name: &str) -> Result<String> {
+        if self.terminals.iter().any(|t| t.name == name) {
+            bail!("Terminal '{}' already exists", name);
+        }
+
+        let mut client = ServerClient::connect(&self.addr).await.map_err(|e| anyhow!(e))?;
+        client.authenticate(&self.token).await.map_err(|e| anyhow!(e))?;
+        let session_id = client.create_session(Some(name)).await.map_err(|e| anyhow!(e))?;
+        let pane_id = client.create_pane(None).await.map_err(|e| anyhow!(e))?;
+        client.subscribe_input().await.map_err(|e| anyhow!(e))?;
+
+        let terminal = Terminal::new_with_server(self.cols, self.rows, client)?;
+        let target = format!("{}/pane-{}", session_id, pane_id);
+        self.terminals.push(ManagedTerminal {
+            name: name.to_string(),
+            session_id,
+            pane_id,
+            terminal,
+            exited: false,
+        });
+        Ok(target)
+    }
+
+    /// Close the session of the terminal named `name` and stop its shell
+    pub async fn remove(&mut self, name: &str) -> Result<()> {
+        let index = self.position(name)?;
+        let managed = self.terminals.remove(index);
+        self.controller.close_session(&managed.session_id).await.map_err(|e| anyhow!(e))
+    }
+
+    /// Names of the managed terminals, in the order they were added
+    pub fn names(&self) -> Vec<&str> {
+        self.terminals.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    /// Inject `command` into every managed terminal
+    pub async fn inject_all(&self, command: &str) -> Result<()> {
+        for managed in &self.terminals {
+            self.inject_into(managed, command).await?;
+        }
+        Ok(())
+    }
+
+    /// Inject `command` into the named terminals. Fails before injecting
+    /// anything if a name is unknown.
+    pub async fn inject(&self, names: &[&str], command: &str) -> Result<()> {
+        let indices = names.iter().map(|name| self.position(name)).collect::<Result<Vec<_>>>()?;
+        for index in indices {
+            self.inject_into(&self.terminals[index], command).await?;
+        }
+        Ok(())
+    }
+
+    /// Deliver pending input to each terminal and read what its shell
+    /// wrote, applying it to the grid and publishing it to the server.
+    /// Terminals with nothing to report are left out. A terminal whose
+    /// shell has exited (its PTY read fails) is marked as exited and skipped
+    /// from then on, while the others keep being pumped.
+    pub async fn pump(&mut self) -> Result<Vec<TaggedOutput>> {
+        let mut outputs = Vec::new();
+        for managed in self.terminals.iter_mut().filter(|t| !t.exited) {
+            managed.terminal.poll_server_input().await?;
+
+            let mut data = Vec::new();
+            loop {
+                match managed.terminal.read() {
+                    Ok(Some(chunk)) => {
+                        managed.terminal.process_output(&chunk);
+                        data.extend_from_slice(&chunk);
+                    }
+                    Ok(None) => {
+                        managed.exited = managed.terminal.has_exited();
+                        break;
+                    }
+                    Err(e) => {
+                        log::info!("Terminal '{}' exited: {}", managed.name, e);
+                        managed.exited = true;
+                        break;
+                    }
+                }
+            }
+            if data.is_empty() {
+                continue;
+            }
+
+            managed.terminal.publish_output_if_needed().await;
+            outputs.push(TaggedOutput { source: managed.name.clone(), data });
+        }
+        Ok(outputs)
+    }
+
+    /// Whether the shell of the terminal named `name` has exited. Its
+    /// screen stays readable until it is removed.
+    pub fn has_exited(&self, name: &str) -> Result<bool> {
+        Ok(self.terminals[self.position(name)?].exited)
+    }
+
+    /// Text on the screen of the terminal named `name`
+    pub fn screen(&self, name: &str) -> Result<String> {
+        let managed = &self.terminals[self.position(name)?];
+        let grid = managed.terminal.grid();
+        let grid = grid.lock().unwrap();
+        Ok(grid.capture(CaptureMode::Visible, CaptureFormat::Text))
+    }
+
+    /// Close every managed session and stop the shells. All terminals are
+    /// removed even if closing one fails; the first error is returned.
+    pub async fn shutdown(mut self) -> Result<()> {
+        let mut result = Ok(());
+        for managed in std::mem::take(&mut self.terminals) {
+            let closed = self.controller.close_session(&managed.session_id).await;
+            if let (Err(e), Ok(())) = (closed, &result) {
+                result = Err(anyhow!(e));
+            }
+        }
+        result
+    }
+
+    async fn inject_into(&self, managed: &ManagedTerminal, command: &str) -> Result<()> {
+        self.controller
+            .inject_command(&managed.session_id, &managed.pane_id, command)
+            .await
+            .map_err(|e| anyhow!("{}: {}", managed.name, e))
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.terminals
+            .iter()
+            .position(|t| t.name == name)
+            .ok_or_else(|| anyhow!("No terminal named '{}'", name))
+    }
+}
@@ -0,0 +1,169 @@
+//! Command scripts for headless terminals
+//!
+//! A script is a text file with one command per line; blank lines and lines
+//! starting with `#` are ignored. Everything after the command name is its
+//! argument, so comments go on their own line:
+//!
+//! ```text
+//! # type a command and press Enter
+//! inject echo hi
+//! # wait until the screen matches a regex
+//! wait_for ^hi$
+//! # pause for a number of milliseconds
+//! sleep 250
+//! # save the screen inside the capture directory
+//! capture out.txt
+//! ```
+
+use crate::terminal::{CaptureFormat, Terminal};
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use std::path::Path;
+use tokio::time::{self, Duration, Instant};
+
+/// How long `wait_for` waits for the screen to match before failing
+pub const WAIT_FOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum ScriptCommand {
+    /// Write the text plus a newline to the terminal, like `INJECT`
+    Inject(String),
+    /// Wait until the visible screen matches the regex (multi-line mode)
+    WaitFor(Regex),
+    Sleep(Duration),
+    /// Write a text snapshot of the screen to this path, relative to the
+    /// capture directory
+    Capture(String),
+}
+
+/// Parse a script, reporting the line number of the first invalid command
+pub fn parse_script(source: &str) -> Result<Vec<ScriptCommand>> {
+    let mut commands = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (command, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arg = arg.trim();
+        let parsed = match command {
+            "inject" => Ok(ScriptCommand::Inject(arg.to_string())),
+            "wait_for" if !arg.is_empty() => Regex::new(&format!("(?m){}", arg))
+                .map(ScriptCommand::WaitFor)
+                .map_err(|e| anyhow!("invalid regex: {}", e)),
+            "sleep" => arg
+                .parse::<u64>()
+                .map(|ms| ScriptCommand::Sleep(Duration::from_millis(ms)))
+                .map_err(|_| anyhow!("sleep takes a number of milliseconds")),
+            "capture" if !arg.is_empty() => Ok(ScriptCommand::Capture(arg.to_string())),
+            "wait_for" | "capture" => Err(anyhow!("{} requires an argument", command)),
+            _ => Err(anyhow!("unknown command '{}'", command)),
+        };
+
+        commands.push(parsed.with_context(|| format!("script line {}", index + 1))?);
+    }
+
+    Ok(commands)
+}
+
+/// Read and parse a script file
+pub fn load_script(path: &Path) -> Result<Vec<ScriptCommand>> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read script {:?}", path))?;
+    parse_script(&source)
+}
+
+/// Run `commands` against `terminal`, failing on the first `wait_for` that
+/// times out or `capture` that can't be written
+pub async fn run_script(terminal: &mut Terminal, commands: &[ScriptCommand], capture_dir: Option<&Path>) -> Result<()> {
+    for command in commands {
+        match command {
+            ScriptCommand::Inject(text) => {
+                log::debug!("Script: inject {}", text);
+                terminal.write(format!("{}\n", text).as_bytes())?;
+            }
+            ScriptCommand::WaitFor(pattern) => {
+                log::debug!("Script: wait_for {}", pattern);
+                wait_for(terminal, pattern, WAIT_FOR_TIMEOUT).await?;
+            }
+            ScriptCommand::Sleep(duration) => time::sleep(*duration).await,
+            ScriptCommand::Capture(path) => {
+                let capture_dir = capture_dir.ok_or_else(|| anyhow!("capture requires a capture directory"))?;
+                let path = terminal.capture_file(capture_dir, path, CaptureFormat::Text)?;
+                log::info!("Script: saved screen capture to {:?}", path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Process terminal output until the screen matches `pattern`
+pub(crate) async fn wait_for(terminal: &mut Terminal, pattern: &Regex, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let screen = terminal.grid().lock().unwrap().visible_lines().collect::<Vec<_>>().join("\n");
+        if pattern.is_match(&screen) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!("Timed out after {:?} waiting for /{}/", timeout, pattern.as_str().trim_start_matches("(?m)"));
+        }
+
+        match terminal.read()? {
+            Some(output) => {
+                terminal.process_output(&output);
+                terminal.publish_output_if_needed().await;
+            }
+            None => time::sleep(Duration::from_millis(10)).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let commands = parse_script("# setup\ninject echo hi there\n\nwait_for ^hi\nsleep 50\ncapture out.txt\n").unwrap();
+
+        assert_eq!(commands.len(), 4);
+        assert!(matches!(&commands[0], ScriptCommand::Inject(text) if text == "echo hi there"));
+        assert!(matches!(&commands[1], ScriptCommand::WaitFor(re) if re.is_match("x\nhi")));
+        assert!(matches!(commands[2], ScriptCommand::Sleep(d) if d == Duration::from_millis(50)));
+        assert!(matches!(&commands[3], ScriptCommand::Capture(path) if path == "out.txt"));
+    }
+
+    #[test]
+    fn test_parse_module_doc_example() {
+        let example: String = include_str!("script.rs")
+            .lines()
+            .map(|line| line.strip_prefix("//!").unwrap_or_default().trim_start())
+            .skip_while(|line| *line != "```text")
+            .skip(1)
+            .take_while(|line| *line != "```")
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        let commands = parse_script(&example).unwrap();
+        assert_eq!(commands.len(), 4);
+        assert!(matches!(&commands[0], ScriptCommand::Inject(text) if text == "echo hi"));
+        assert!(matches!(&commands[1], ScriptCommand::WaitFor(re) if re.is_match("x\nhi")));
+        assert!(matches!(commands[2], ScriptCommand::Sleep(d) if d == Duration::from_millis(250)));
+        assert!(matches!(&commands[3], ScriptCommand::Capture(path) if path == "out.txt"));
+    }
+
+    #[test]
+    fn test_parse_script_reports_line() {
+        let err = parse_script("inject ls\nsleep soon\n").unwrap_err();
+        assert!(format!("{:#}", err).contains("script line 2"));
+
+        assert!(parse_script("wait_for (\n").is_err());
+        assert!(parse_script("capture\n").is_err());
+        assert!(parse_script("launch rockets\n").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Configurable keyboard shortcuts
+//!
+//! Maps key + modifier combinations from the config file to named actions
+//! that the application dispatches before sending input to the PTY.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+/// Named action a shortcut can trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    NewPane,
+    SplitHorizontal,
+    SplitVertical,
+    /// Turn the active pane's split between side by side and stacked
+    RotateSplit,
+    ClosePane,
+    NavigateUp,
+    NavigateDown,
+    NavigateLeft,
+    NavigateRight,
+    FocusNext,
+    FocusPrev,
+    ToggleBroadcast,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    Copy,
+    Paste,
+    ScrollUp,
+    ScrollDown,
+    ScrollToTop,
+    ScrollToBottom,
+    ClearScrollback,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+}
+
+impl Action {
+    /// Whether the action moves through or reads the scrollback, so the
+    /// pane must not jump back to the live screen first
+    pub fn keeps_scroll_position(self) -> bool {
+        matches!(
+            self,
+            Action::ScrollUp | Action::ScrollDown | Action::ScrollToTop | Action::ScrollToBottom | Action::Copy
+        )
+    }
+}
+
+/// A single shortcut as written in the config file
+///
+/// `key` is a single character (`"t"`) or a named key (`"Enter"`, `"ArrowUp"`,
+/// `"PageUp"`, ...). `mods` is a `+`-separated list of `Ctrl`, `Shift`, `Alt`
+/// and `Super`, or empty for no modifiers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub key: String,
+    #[serde(default)]
+    pub mods: String,
+    pub action: Action,
+}
+
+impl KeyBinding {
+    pub fn new(key: &str, mods: &str, action: Action) -> Self {
+        Self {
+            key: key.to_string(),
+            mods: mods.to_string(),
+            action,
+        }
+    }
+}
+
+/// Bindings matching the shortcuts Titi has always shipped with
+pub fn default_bindings() -> Vec<KeyBinding> {
+    vec![
+        KeyBinding::new("Enter", "Ctrl", Action::NewPane),
+        KeyBinding::new("t", "Ctrl", Action::NewPane),
+        KeyBinding::new("h", "Ctrl", Action::SplitHorizontal),
+        KeyBinding::new("v", "Ctrl", Action::SplitVertical),
+        KeyBinding::new("r", "Ctrl+Shift", Action::RotateSplit),
+        KeyBinding::new("w", "Ctrl", Action::ClosePane),
+        KeyBinding::new("ArrowUp", "Ctrl", Action::NavigateUp),
+        KeyBinding::new("ArrowDown", "Ctrl", Action::NavigateDown),
+        KeyBinding::new("ArrowLeft", "Ctrl", Action::NavigateLeft),
+        KeyBinding::new("ArrowRight", "Ctrl", Action::NavigateRight),
+        KeyBinding::new("Tab", "Ctrl", Action::FocusNext),
+        KeyBinding::new("Tab", "Ctrl+Shift", Action::FocusPrev),
+        KeyBinding::new("b", "Ctrl+Shift", Action::ToggleBroadcast),
+        KeyBinding::new("t", "Ctrl+Shift", Action::NewTab),
+        KeyBinding::new("w", "Ctrl+Shift", Action::CloseTab),
+        KeyBinding::new("PageDown", "Ctrl", Action::NextTab),
+        KeyBinding::new("PageUp", "Ctrl", Action::PrevTab),
+        KeyBinding::new("PageUp", "", Action::ScrollUp),
+        KeyBinding::new("PageDown", "", Action::ScrollDown),
+        KeyBinding::new("Home", "Shift", Action::ScrollToTop),
+        KeyBinding::new("End", "Shift", Action::ScrollToBottom),
+        KeyBinding::new("k", "Ctrl+Shift", Action::ClearScrollback),
+        KeyBinding::new("=", "Ctrl", Action::ZoomIn),
+        KeyBinding::new("+", "Ctrl+Shift", Action::ZoomIn),
+        KeyBinding::new("-", "Ctrl", Action::ZoomOut),
+        KeyBinding::new("0", "Ctrl", Action::ZoomReset),
+        KeyBinding::new("c", "Ctrl+Shift", Action::Copy),
+        KeyBinding::new("v", "Ctrl+Shift", Action::Paste),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum KeyName {
+    Character(String),
+    Named(NamedKey),
+}
+
+/// Lookup table resolving key events to actions
+pub struct KeyBindings {
+    table: HashMap<(KeyName, ModifiersState), Action>,
+}
+
+impl KeyBindings {
+    /// Build the lookup table, skipping (and logging) entries that don't parse
+    pub fn new(bindings: &[KeyBinding]) -> Self {
+        let mut table = HashMap::new();
+
+        for binding in bindings {
+            let key = match Self::parse_key(&binding.key) {
+                Some(key) => key,
+                None => {
+                    log::warn!("Ignoring keybinding with unknown key '{}'", binding.key);
+                    continue;
+                }
+            };
+            let mods = match Self::parse_mods(&binding.mods) {
+                Some(mods) => mods,
+                None => {
+                    log::warn!("Ignoring keybinding with unknown modifiers '{}'", binding.mods);
+                    continue;
+                }
+            };
+            table.insert((key, mods), binding.action);
+        }
+
+        Self { table }
+    }
+
+    /// Resolve a pressed key and the current modifiers to an action
+    ///
+    /// Modifiers must match exactly, so `Ctrl+V` and `Ctrl+Shift+V` can be
+    /// bound to different actions.
+    pub fn resolve(&self, key: &Key, modifiers: ModifiersState) -> Option<Action> {
+        let name = match key {
+            Key::Character(c) => KeyName::Character(c.to_lowercase()),
+            Key::Named(named) => KeyName::Named(*named),
+            _ => return None,
+        };
+
+        let mods = modifiers
+            & (ModifiersState::CONTROL
+                | ModifiersState::SHIFT
+                | ModifiersState::ALT
+                | ModifiersState::SUPER);
+
+        self.table.get(&(name, mods)).copied()
+    }
+
+    fn parse_key(key: &str) -> Option<KeyName> {
+        let mut chars = key.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(KeyName::Character(c.to_lowercase().collect()));
+        }
+
+        let named = match key.to_lowercase().as_str() {
+            "enter" | "return" => NamedKey::Enter,
+            "tab" => NamedKey::Tab,
+            "space" => NamedKey::Space,
+            "backspace" => NamedKey::Backspace,
+            "delete" => NamedKey::Delete,
+            "insert" => NamedKey::Insert,
+            "escape" | "esc" => NamedKey::Escape,
+            "arrowup" | "up" => NamedKey::ArrowUp,
+            "arrowdown" | "down" => NamedKey::ArrowDown,
+            "arrowleft" | "left" => NamedKey::ArrowLeft,
+            "arrowright" | "right" => NamedKey::ArrowRight,
+            "home" => NamedKey::Home,
+            "end" => NamedKey::End,
+            "pageup" => NamedKey::PageUp,
+            "pagedown" => NamedKey::PageDown,
+            "f1" => NamedKey::F1,
+            "f2" => NamedKey::F2,
+            "f3" => NamedKey::F3,
+            "f4" => NamedKey::F4,
+            "f5" => NamedKey::F5,
+            "f6" => NamedKey::F6,
+            "f7" => NamedKey::F7,
+            "f8" => NamedKey::F8,
+            "f9" => NamedKey::F9,
+            "f10" => NamedKey::F10,
+            "f11" => NamedKey::F11,
+            "f12" => NamedKey::F12,
+            _ => return None,
+        };
+
+        Some(KeyName::Named(named))
+    }
+
+    fn parse_mods(mods: &str) -> Option<ModifiersState> {
+        let mut state = ModifiersState::empty();
+
+        for part in mods.split('+').map(str::trim).filter(|p| !p.is_empty()) {
+            state |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => ModifiersState::CONTROL,
+                "shift" => ModifiersState::SHIFT,
+                "alt" | "option" => ModifiersState::ALT,
+                "super" | "cmd" | "command" | "logo" => ModifiersState::SUPER,
+                _ => return None,
+            };
+        }
+
+        Some(state)
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::new(&default_bindings())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings_match_builtin_shortcuts() {
+        let bindings = KeyBindings::default();
+
+        assert_eq!(
+            bindings.resolve(&Key::Character("t".into()), ModifiersState::CONTROL),
+            Some(Action::NewPane)
+        );
+        assert_eq!(
+            bindings.resolve(&Key::Character("v".into()), ModifiersState::CONTROL),
+            Some(Action::SplitVertical)
+        );
+        assert_eq!(
+            bindings.resolve(
+                &Key::Character("V".into()),
+                ModifiersState::CONTROL | ModifiersState::SHIFT
+            ),
+            Some(Action::Paste)
+        );
+        assert_eq!(
+            bindings.resolve(&Key::Named(NamedKey::PageUp), ModifiersState::empty()),
+            Some(Action::ScrollUp)
+        );
+        assert_eq!(
+            bindings.resolve(
+                &Key::Named(NamedKey::Tab),
+                ModifiersState::CONTROL | ModifiersState::SHIFT
+            ),
+            Some(Action::FocusPrev)
+        );
+
+        // Unbound keys fall through to the PTY
+        assert_eq!(
+            bindings.resolve(&Key::Character("t".into()), ModifiersState::empty()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_only_scrollback_actions_keep_scroll_position() {
+        assert!(Action::ScrollUp.keeps_scroll_position());
+        assert!(Action::Copy.keeps_scroll_position());
+        assert!(!Action::NewTab.keeps_scroll_position());
+        assert!(!Action::Paste.keeps_scroll_position());
+    }
+
+    #[test]
+    fn test_custom_binding_from_config() {
+        let toml = r#"
+            [[keybindings]]
+            key = "n"
+            mods = "Alt+Shift"
+            action = "NewPane"
+
+            [[keybindings]]
+            key = "Up"
+            mods = "Super"
+            action = "NavigateUp"
+        "#;
+
+        #[derive(Deserialize)]
+        struct Section {
+            keybindings: Vec<KeyBinding>,
+        }
+
+        let section: Section = toml::from_str(toml).unwrap();
+        let bindings = KeyBindings::new(&section.keybindings);
+
+        assert_eq!(
+            bindings.resolve(
+                &Key::Character("N".into()),
+                ModifiersState::ALT | ModifiersState::SHIFT
+            ),
+            Some(Action::NewPane)
+        );
+        assert_eq!(
+            bindings.resolve(&Key::Named(NamedKey::ArrowUp), ModifiersState::SUPER),
+            Some(Action::NavigateUp)
+        );
+        // Defaults are replaced, not merged
+        assert_eq!(
+            bindings.resolve(&Key::Character("t".into()), ModifiersState::CONTROL),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid_bindings_are_skipped() {
+        let bindings = KeyBindings::new(&[
+            KeyBinding::new("NoSuchKey", "Ctrl", Action::Copy),
+            KeyBinding::new("c", "Hyper", Action::Copy),
+            KeyBinding::new("c", "Ctrl", Action::Copy),
+        ]);
+
+        assert_eq!(
+            bindings.resolve(&Key::Character("c".into()), ModifiersState::CONTROL),
+            Some(Action::Copy)
+        );
+    }
+}
@@ -2,10 +2,12 @@ pub mod terminal;
 pub mod renderer;
 pub mod ui;
 pub mod config;
+pub mod keybindings;
 pub mod metrics;
 pub mod redititi_server;
 pub mod server_client;
 pub mod headless;
+pub mod logging;
 
 pub use config::Config;
 pub use metrics::METRICS;
@@ -0,0 +1,18 @@
+//! Logger setup shared by the `titi` and `redititi` binaries
+//!
+//! Logs go to `env_logger` (filtered by `RUST_LOG`) as always. Setting
+//! `TITI_TRACE` to a `tracing-subscriber` filter such as `titi=debug`
+//! installs a tracing subscriber instead, whose lines also name the
+//! connection, session and pane each message was logged for.
+
+/// Environment variable holding the tracing filter
+pub const TRACE_FILTER_ENV: &str = "TITI_TRACE";
+
+/// Install the logger; call once at startup
+pub fn init() {
+    match tracing_subscriber::EnvFilter::try_from_env(TRACE_FILTER_ENV) {
+        // Also picks up `log` records from code outside any span
+        Ok(filter) => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        Err(_) => env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init(),
+    }
+}
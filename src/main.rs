@@ -1,16 +1,52 @@
 use arboard::Clipboard;
 use clap::Parser;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use titi::{renderer::Renderer, ui::PaneManager, Config};
+use titi::keybindings::{Action, KeyBindings};
+use titi::config::ConfigWatcher;
+use titi::renderer::TextRenderer;
+use titi::terminal::keys::{self, KeyModifiers};
+use titi::terminal::mouse::{self, MouseAction};
+use titi::terminal::paste;
+use titi::terminal::Selection;
+use titi::{renderer::Renderer, ui::{CursorBlink, PaneId, PaneManager, SplitDirection, DIVIDER_GRAB_DISTANCE}, Config};
 use winit::{
     application::ApplicationHandler,
-    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{Key, ModifiersState, NamedKey},
-    window::{Window, WindowId},
+    window::{CursorIcon, Window, WindowId},
 };
 
+/// Open `url` with the platform's default handler, in the background
+fn open_url(url: &str) {
+    let url = if url.starts_with("www.") {
+        format!("https://{}", url)
+    } else {
+        url.to_string()
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    // Not `cmd /C start`: cmd would treat `&` in the URL as a command separator
+    #[cfg(target_os = "windows")]
+    let mut command = std::process::Command::new("explorer.exe");
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    match command.arg(&url).spawn() {
+        Ok(mut child) => {
+            log::info!("Opening {}", url);
+            // Reap the opener so it doesn't linger as a zombie
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+        }
+        Err(e) => log::warn!("Failed to open {}: {}", url, e),
+    }
+}
+
 /// Command-line arguments
 #[derive(Parser, Debug)]
 #[command(name = "titi")]
@@ -43,8 +79,67 @@ struct Args {
     /// Terminal rows (default: 24)
     #[arg(long, default_value = "24")]
     rows: u16,
+
+    /// Command to run instead of the shell, split into words like a shell
+    /// would (e.g. "htop -d 5" or "bash -c 'make && make test'")
+    #[arg(long)]
+    command: Option<String>,
+
+    /// Working directory for new panes
+    #[arg(long)]
+    cwd: Option<std::path::PathBuf>,
+
+    /// Directory where headless screen captures may be written
+    #[arg(long)]
+    capture_dir: Option<std::path::PathBuf>,
+
+    /// Command script to run in headless mode (inject, wait_for, sleep, capture)
+    #[arg(long)]
+    script: Option<std::path::PathBuf>,
+
+    /// Serve Prometheus metrics on this address (e.g. 127.0.0.1:9898)
+    #[cfg(feature = "metrics-http")]
+    #[arg(long)]
+    metrics_addr: Option<String>,
 }
 
+impl Args {
+    /// Apply `--command` and `--cwd` on top of the configured shell
+    fn apply_shell_overrides(&self, shell: &mut titi::config::ShellConfig) -> anyhow::Result<()> {
+        if let Some(command) = &self.command {
+            let words = shell_words::split(command)
+                .map_err(|e| anyhow::anyhow!("Invalid --command {:?}: {}", command, e))?;
+            let mut parts = words.into_iter();
+            if let Some(program) = parts.next() {
+                shell.program = Some(program);
+                shell.args = parts.collect();
+                shell.login_shell = false;
+            }
+        }
+
+        if let Some(cwd) = &self.cwd {
+            shell.working_directory = Some(cwd.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Points added or removed per zoom step
+const ZOOM_STEP: f32 = 1.0;
+
+/// Target frame time (~60 FPS)
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+/// Slowest redraw pacing when rendering can't keep up
+const MAX_FRAME_TIME: Duration = Duration::from_millis(100);
+
+/// How often an idle window checks the config watcher for reloads
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Longest gap between clicks on a cell that still counts as a double or
+/// triple click
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
 struct App {
     window: Option<Arc<Window>>,
     renderer: Option<Renderer>,
@@ -53,24 +148,86 @@ struct App {
     modifiers: ModifiersState,
     last_frame: Instant,
     cursor_position: (f64, f64),
+    /// Split divider being dragged with the left mouse button
+    dragged_divider: Option<titi::ui::Divider>,
+    /// Split divider under the mouse (or being dragged), highlighted as
+    /// resizable
+    hovered_divider: Option<titi::ui::Divider>,
+    /// Button held down while the active pane's application tracks the
+    /// mouse, and the last cell reported to it
+    reported_button: Option<mouse::MouseButton>,
+    last_reported_cell: Option<(usize, usize)>,
     clipboard: Option<Clipboard>,
+    keybindings: KeyBindings,
+    config_watcher: Option<ConfigWatcher>,
+    cursor_blink: CursorBlink,
+    /// Time and cell of the last left click, and how many rapid clicks on
+    /// that cell led up to it
+    last_click: Option<(Instant, (usize, usize))>,
+    click_count: u32,
+    /// URLs found in each pane's grid, dropped when the grid changes
+    urls: HashMap<PaneId, Vec<(Selection, String)>>,
+    /// URL under the mouse pointer, underlined in its pane
+    hovered_url: Option<(PaneId, Selection)>,
+    /// Clipboard text waiting for the user to confirm pasting it
+    pending_paste: Option<String>,
 }
 
 impl App {
     fn new(config: Config) -> Self {
         let clipboard = Clipboard::new().ok();
+        let keybindings = KeyBindings::new(&config.keybindings);
+        let mut pane_manager = PaneManager::with_shell(config.shell.clone());
+        pane_manager.set_scrollback_lines(config.scrollback_lines);
+        pane_manager.set_parse_budget(config.max_parse_per_frame);
+        let cursor_blink = CursorBlink::new(Duration::from_millis(config.blink_interval_ms), Instant::now());
         Self {
             window: None,
             renderer: None,
-            pane_manager: PaneManager::new(),
+            pane_manager,
             config,
             modifiers: ModifiersState::default(),
             last_frame: Instant::now(),
             cursor_position: (0.0, 0.0),
+            dragged_divider: None,
+            hovered_divider: None,
+            reported_button: None,
+            last_reported_cell: None,
             clipboard,
+            keybindings,
+            config_watcher: None,
+            cursor_blink,
+            last_click: None,
+            click_count: 0,
+            urls: HashMap::new(),
+            hovered_url: None,
+            pending_paste: None,
         }
     }
 
+    /// Apply a config reloaded from disk. The shell only affects panes
+    /// created from now on; existing terminals keep running.
+    fn apply_config(&mut self, mut config: Config) {
+        // Keep the launch-time shell (it may carry --command/--cwd overrides)
+        config.shell = self.config.shell.clone();
+
+        self.keybindings = KeyBindings::new(&config.keybindings);
+        self.pane_manager.set_scrollback_lines(config.scrollback_lines);
+        self.pane_manager.set_parse_budget(config.max_parse_per_frame);
+        self.cursor_blink
+            .set_interval(Duration::from_millis(config.blink_interval_ms), Instant::now());
+
+        if let Some(renderer) = &mut self.renderer {
+            if renderer.apply_config(&config) {
+                self.config = config;
+                self.resize_panes_to_window();
+                return;
+            }
+        }
+
+        self.config = config;
+    }
+
     async fn initialize_renderer(&mut self) -> anyhow::Result<()> {
         if let Some(window) = &self.window {
             let renderer = Renderer::new(window.clone(), &self.config).await?;
@@ -84,23 +241,39 @@ impl App {
             return;
         }
 
+        // A pending paste confirmation takes all keys until answered
+        if self.pending_paste.is_some() {
+            match event.logical_key {
+                Key::Named(NamedKey::Enter) => self.resolve_paste(true),
+                Key::Named(NamedKey::Escape) => self.resolve_paste(false),
+                _ => {}
+            }
+            return;
+        }
+
         // Handle keyboard shortcuts
-        match &event.logical_key {
-            Key::Named(NamedKey::Enter) if self.modifiers.control_key() => {
-                // Ctrl+Enter: Create new pane
-                if let Some(renderer) = &self.renderer {
-                    let (cell_width, cell_height) = renderer.cell_dimensions();
-                    let window_size = self.window.as_ref().unwrap().inner_size();
-                    let cols = (window_size.width as f32 / cell_width) as u16;
-                    let rows = (window_size.height as f32 / cell_height) as u16;
+        if let Some(action) = self.keybindings.resolve(&event.logical_key, self.modifiers) {
+            // Like typing, any shortcut but scrolling returns to the live screen
+            if !action.keeps_scroll_position() {
+                self.perform_action(Action::ScrollToBottom);
+            }
+            self.perform_action(action);
+            return;
+        }
 
-                    if let Err(e) = self.pane_manager.create_pane(cols, rows) {
-                        log::error!("Failed to create pane: {}", e);
-                    }
+        // Send input to active pane
+        if let Some(text) = self.key_to_bytes(&event) {
+            if self.pane_manager.active_pane().is_some() {
+                if let Err(e) = self.pane_manager.write_input(&text) {
+                    log::error!("Failed to write to terminal: {}", e);
                 }
             }
-            Key::Character(c) if c == "t" && self.modifiers.control_key() => {
-                // Ctrl+T: New terminal (same as Ctrl+Enter)
+        }
+    }
+
+    fn perform_action(&mut self, action: Action) {
+        match action {
+            Action::NewPane => {
                 if let Some(renderer) = &self.renderer {
                     let (cell_width, cell_height) = renderer.cell_dimensions();
                     let window_size = self.window.as_ref().unwrap().inner_size();
@@ -112,8 +285,7 @@ impl App {
                     }
                 }
             }
-            Key::Character(c) if c == "h" && self.modifiers.control_key() => {
-                // Ctrl+H: Split horizontal
+            Action::SplitHorizontal => {
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     if let Some(renderer) = &self.renderer {
                         let (cell_width, cell_height) = renderer.cell_dimensions();
@@ -132,8 +304,7 @@ impl App {
                     }
                 }
             }
-            Key::Character(c) if c == "v" && self.modifiers.control_key() => {
-                // Ctrl+V: Split vertical
+            Action::SplitVertical => {
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     if let Some(renderer) = &self.renderer {
                         let (cell_width, cell_height) = renderer.cell_dimensions();
@@ -152,33 +323,49 @@ impl App {
                     }
                 }
             }
-            Key::Character(c) if c == "w" && self.modifiers.control_key() => {
-                // Ctrl+W: Close active pane
-                if let Some(pane_id) = self.pane_manager.active_pane() {
-                    self.pane_manager.close_pane(pane_id);
+            Action::NewTab => {
+                if let Some(renderer) = &self.renderer {
+                    let (cell_width, cell_height) = renderer.cell_dimensions();
+                    let window_size = self.window.as_ref().unwrap().inner_size();
+                    let cols = (window_size.width as f32 / cell_width) as u16;
+                    let rows = (window_size.height as f32 / cell_height) as u16;
+
+                    if let Err(e) = self.pane_manager.new_tab(cols.max(1), rows.max(1)) {
+                        log::error!("Failed to open tab: {}", e);
+                    }
                 }
             }
-            Key::Named(NamedKey::ArrowUp) if self.modifiers.control_key() => {
-                // Ctrl+ArrowUp: Navigate to pane above
-                self.pane_manager.navigate_up();
+            Action::CloseTab => {
+                let index = self.pane_manager.active_tab();
+                self.pane_manager.close_tab(index);
             }
-            Key::Named(NamedKey::ArrowDown) if self.modifiers.control_key() => {
-                // Ctrl+ArrowDown: Navigate to pane below
-                self.pane_manager.navigate_down();
+            Action::NextTab => self.pane_manager.next_tab(),
+            Action::PrevTab => self.pane_manager.prev_tab(),
+            Action::ClosePane => {
+                if let Some(pane_id) = self.pane_manager.active_pane() {
+                    self.pane_manager.close_pane(pane_id);
+                }
             }
-            Key::Named(NamedKey::ArrowLeft) if self.modifiers.control_key() => {
-                // Ctrl+ArrowLeft: Navigate to pane on left
-                self.pane_manager.navigate_left();
+            Action::NavigateUp => self.pane_manager.navigate_up(),
+            Action::NavigateDown => self.pane_manager.navigate_down(),
+            Action::NavigateLeft => self.pane_manager.navigate_left(),
+            Action::NavigateRight => self.pane_manager.navigate_right(),
+            Action::FocusNext => self.pane_manager.focus_next(),
+            Action::FocusPrev => self.pane_manager.focus_prev(),
+            Action::RotateSplit => {
+                if !self.pane_manager.rotate_active_split() {
+                    log::debug!("Active pane isn't split, nothing to rotate");
+                }
             }
-            Key::Named(NamedKey::ArrowRight) if self.modifiers.control_key() => {
-                // Ctrl+ArrowRight: Navigate to pane on right
-                self.pane_manager.navigate_right();
+            Action::ToggleBroadcast => {
+                let broadcast = !self.pane_manager.broadcast();
+                self.pane_manager.set_broadcast(broadcast);
+                log::info!("Broadcast input {}", if broadcast { "on" } else { "off" });
             }
-            Key::Named(NamedKey::PageUp) => {
-                // PageUp: Scroll back in history
+            Action::ScrollUp => {
+                // Scroll back in history by half a screen
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
-                        // Scroll back by half a screen
                         let (_, rows) = {
                             let grid = pane.terminal.grid();
                             let g = grid.lock().unwrap();
@@ -188,11 +375,10 @@ impl App {
                     }
                 }
             }
-            Key::Named(NamedKey::PageDown) => {
-                // PageDown: Scroll forward in history
+            Action::ScrollDown => {
+                // Scroll forward in history by half a screen
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
-                        // Scroll forward by half a screen
                         let (_, rows) = {
                             let grid = pane.terminal.grid();
                             let g = grid.lock().unwrap();
@@ -202,8 +388,7 @@ impl App {
                     }
                 }
             }
-            Key::Named(NamedKey::Home) if self.modifiers.shift_key() => {
-                // Shift+Home: Scroll to top of history
+            Action::ScrollToTop => {
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
                         let scrollback_len = {
@@ -215,16 +400,35 @@ impl App {
                     }
                 }
             }
-            Key::Named(NamedKey::End) if self.modifiers.shift_key() => {
-                // Shift+End: Scroll to bottom (current)
+            Action::ScrollToBottom => {
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
                         pane.terminal.scroll_to_bottom();
                     }
                 }
             }
-            Key::Character(c) if c == "c" && self.modifiers.control_key() && self.modifiers.shift_key() => {
-                // Ctrl+Shift+C: Copy visible text from active pane
+            Action::ClearScrollback => {
+                if let Some(pane_id) = self.pane_manager.active_pane() {
+                    if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
+                        pane.terminal.clear_scrollback();
+                    }
+                }
+            }
+            Action::ZoomIn | Action::ZoomOut | Action::ZoomReset => {
+                if let Some(renderer) = &mut self.renderer {
+                    let changed = match action {
+                        Action::ZoomIn => renderer.zoom(ZOOM_STEP),
+                        Action::ZoomOut => renderer.zoom(-ZOOM_STEP),
+                        _ => renderer.reset_zoom(),
+                    };
+                    if changed {
+                        log::info!("Font size: {}", renderer.font_size());
+                        self.resize_panes_to_window();
+                    }
+                }
+            }
+            Action::Copy => {
+                // Copy visible text from active pane
                 if let Some(pane_id) = self.pane_manager.active_pane() {
                     let text = self.get_visible_text(pane_id);
                     if let Some(clipboard) = &mut self.clipboard {
@@ -234,19 +438,19 @@ impl App {
                     }
                 }
             }
-            Key::Character(c) if c == "v" && self.modifiers.control_key() && self.modifiers.shift_key() => {
-                // Ctrl+Shift+V: Paste from clipboard
-                if let Some(pane_id) = self.pane_manager.active_pane() {
+            Action::Paste => {
+                if self.pane_manager.active_pane().is_some() {
                     if let Some(clipboard) = &mut self.clipboard {
                         match clipboard.get_text() {
                             Ok(text) => {
-                                if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
-                                    // Scroll to bottom on paste
-                                    pane.terminal.scroll_to_bottom();
-
-                                    if let Err(e) = pane.terminal.write(text.as_bytes()) {
-                                        log::error!("Failed to write pasted text: {}", e);
+                                if self.pane_manager.paste_needs_confirmation(&text, self.config.paste_confirm_threshold) {
+                                    if let Some(renderer) = &mut self.renderer {
+                                        renderer.set_overlay(Some(paste::paste_confirmation_prompt(&text)));
                                     }
+                                    self.pending_paste = Some(text);
+                                    self.pane_manager.request_redraw();
+                                } else if let Err(e) = self.pane_manager.paste_input(&text) {
+                                    log::error!("Failed to write pasted text: {}", e);
                                 }
                             }
                             Err(e) => {
@@ -256,102 +460,359 @@ impl App {
                     }
                 }
             }
-            _ => {
-                // Send input to active pane
-                if let Some(text) = self.key_to_bytes(&event) {
-                    if let Some(pane_id) = self.pane_manager.active_pane() {
-                        if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
-                            // Scroll to bottom on any input
-                            pane.terminal.scroll_to_bottom();
+        }
+    }
 
-                            if let Err(e) = pane.terminal.write(&text) {
-                                log::error!("Failed to write to terminal: {}", e);
-                            }
-                        }
-                    }
-                }
+    /// Paste the text waiting for confirmation if `accepted`, else drop it,
+    /// and take the prompt down
+    fn resolve_paste(&mut self, accepted: bool) {
+        let Some(text) = self.pending_paste.take() else {
+            return;
+        };
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_overlay(None);
+        }
+        if accepted {
+            if let Err(e) = self.pane_manager.paste_input(&text) {
+                log::error!("Failed to write pasted text: {}", e);
             }
         }
+        self.pane_manager.request_redraw();
+    }
+
+    /// Resize every pane's terminal to fit its layout bounds at the current
+    /// cell size
+    fn resize_panes_to_window(&mut self) {
+        let (Some(renderer), Some(window)) = (&self.renderer, &self.window) else {
+            return;
+        };
+
+        let (cell_width, cell_height) = renderer.cell_dimensions();
+        let window_size = window.inner_size();
+        self.pane_manager.set_cell_size(cell_width, cell_height);
+        self.pane_manager.set_pane_header_height(renderer.title_bar_height());
+        self.pane_manager.set_padding(self.config.window.padding);
+        self.pane_manager
+            .set_viewport_size(window_size.width as f32, window_size.height as f32);
+        self.pane_manager.fit_panes_to_viewport();
     }
 
     fn key_to_bytes(&self, event: &KeyEvent) -> Option<Vec<u8>> {
-        match &event.logical_key {
-            Key::Named(NamedKey::Enter) => Some(b"\r".to_vec()),
-            Key::Named(NamedKey::Tab) => Some(b"\t".to_vec()),
-            Key::Named(NamedKey::Backspace) => Some(b"\x7f".to_vec()),
-            Key::Named(NamedKey::ArrowUp) => Some(b"\x1b[A".to_vec()),
-            Key::Named(NamedKey::ArrowDown) => Some(b"\x1b[B".to_vec()),
-            Key::Named(NamedKey::ArrowRight) => Some(b"\x1b[C".to_vec()),
-            Key::Named(NamedKey::ArrowLeft) => Some(b"\x1b[D".to_vec()),
-            Key::Named(NamedKey::Home) => Some(b"\x1b[H".to_vec()),
-            Key::Named(NamedKey::End) => Some(b"\x1b[F".to_vec()),
-            Key::Named(NamedKey::PageUp) => Some(b"\x1b[5~".to_vec()),
-            Key::Named(NamedKey::PageDown) => Some(b"\x1b[6~".to_vec()),
-            Key::Named(NamedKey::Delete) => Some(b"\x1b[3~".to_vec()),
-            Key::Named(NamedKey::Escape) => Some(b"\x1b".to_vec()),
-            Key::Character(c) => {
-                let mut bytes = c.to_string().into_bytes();
-
-                // Handle Ctrl+ combinations
-                if self.modifiers.control_key() && bytes.len() == 1 {
-                    let byte = bytes[0];
-                    if byte >= b'a' && byte <= b'z' {
-                        // Ctrl+a = 1, Ctrl+b = 2, etc.
-                        bytes[0] = byte - b'a' + 1;
-                    } else if byte >= b'A' && byte <= b'Z' {
-                        bytes[0] = byte - b'A' + 1;
+        let mods = KeyModifiers {
+            ctrl: self.modifiers.control_key(),
+            ..KeyModifiers::NONE
+        };
+
+        let name = match &event.logical_key {
+            Key::Named(NamedKey::Enter) => "Enter",
+            Key::Named(NamedKey::Tab) => "Tab",
+            Key::Named(NamedKey::Space) => "Space",
+            Key::Named(NamedKey::Backspace) => "Backspace",
+            Key::Named(NamedKey::ArrowUp) => "Up",
+            Key::Named(NamedKey::ArrowDown) => "Down",
+            Key::Named(NamedKey::ArrowRight) => "Right",
+            Key::Named(NamedKey::ArrowLeft) => "Left",
+            Key::Named(NamedKey::Home) => "Home",
+            Key::Named(NamedKey::End) => "End",
+            Key::Named(NamedKey::Insert) => "Insert",
+            Key::Named(NamedKey::PageUp) => "PageUp",
+            Key::Named(NamedKey::PageDown) => "PageDown",
+            Key::Named(NamedKey::Delete) => "Delete",
+            Key::Named(NamedKey::Escape) => "Escape",
+            Key::Named(NamedKey::F1) => "F1",
+            Key::Named(NamedKey::F2) => "F2",
+            Key::Named(NamedKey::F3) => "F3",
+            Key::Named(NamedKey::F4) => "F4",
+            Key::Named(NamedKey::F5) => "F5",
+            Key::Named(NamedKey::F6) => "F6",
+            Key::Named(NamedKey::F7) => "F7",
+            Key::Named(NamedKey::F8) => "F8",
+            Key::Named(NamedKey::F9) => "F9",
+            Key::Named(NamedKey::F10) => "F10",
+            Key::Named(NamedKey::F11) => "F11",
+            Key::Named(NamedKey::F12) => "F12",
+            // Composed text (dead keys, IME) goes through as is
+            Key::Character(c) if c.chars().count() > 1 => return Some(c.as_bytes().to_vec()),
+            Key::Character(c) => c.as_str(),
+            _ => return None,
+        };
+
+        keys::key_sequence(name, mods)
+    }
+
+    /// Find the pane under the mouse pointer
+    fn pane_at_cursor(&self) -> Option<titi::ui::PaneId> {
+        let window_size = self.window.as_ref()?.inner_size();
+        let pane_bounds = self.pane_manager.layout().calculate_bounds(
+            window_size.width as f32,
+            window_size.height as f32,
+        );
+
+        let (cursor_x, cursor_y) = self.cursor_position;
+        pane_bounds.iter().find_map(|(pane_id, (x, y, width, height))| {
+            let inside = cursor_x >= *x as f64
+                && cursor_x < (*x + *width) as f64
+                && cursor_y >= *y as f64
+                && cursor_y < (*y + *height) as f64;
+            inside.then_some(*pane_id)
+        })
+    }
+
+    fn handle_mouse_input(&mut self, state: ElementState, button: MouseButton) {
+        let reported = match button {
+            MouseButton::Left => Some(mouse::MouseButton::Left),
+            MouseButton::Middle => Some(mouse::MouseButton::Middle),
+            MouseButton::Right => Some(mouse::MouseButton::Right),
+            _ => None,
+        };
+
+        match state {
+            ElementState::Pressed => {
+                // A press on a divider starts resizing the split
+                let (cursor_x, cursor_y) = self.cursor_position;
+                if button == MouseButton::Left {
+                    if let Some(divider) = self.pane_manager.divider_at(cursor_x as f32, cursor_y as f32) {
+                        self.dragged_divider = Some(divider);
+                        return;
+                    }
+                }
+
+                // Ctrl+click opens a URL instead of passing the click on
+                if button == MouseButton::Left && self.modifiers.control_key() {
+                    if let Some((_, _, url)) = self.url_at_cursor() {
+                        open_url(&url);
+                        return;
+                    }
+                }
+
+                // Applications tracking the mouse get clicks on their pane
+                if let (Some(button), Some(cell)) = (reported, self.active_pane_cell()) {
+                    if self.cursor_over_active_pane() && self.report_mouse(button, MouseAction::Press, cell) {
+                        self.reported_button = Some(button);
+                        self.last_reported_cell = Some(cell);
+                        return;
                     }
                 }
 
-                Some(bytes)
+                // Handle left mouse click - focus pane at cursor position
+                if button == MouseButton::Left {
+                    if let Some(pane_id) = self.pane_at_cursor() {
+                        self.pane_manager.set_active_pane(pane_id);
+                    }
+                    self.select_on_click();
+                }
+            }
+            ElementState::Released => {
+                if let Some(held) = self.reported_button.filter(|held| Some(*held) == reported) {
+                    self.reported_button = None;
+                    if let Some(cell) = self.active_pane_cell() {
+                        self.report_mouse(held, MouseAction::Release, cell);
+                    }
+                    return;
+                }
+
+                if button == MouseButton::Left && self.dragged_divider.take().is_some() {
+                    self.update_hovered_divider();
+                }
             }
-            _ => None,
         }
     }
 
-    fn poll_terminals(&mut self) {
-        let pane_ids: Vec<_> = self.pane_manager.panes().keys().copied().collect();
+    /// URL in the active pane under the mouse pointer. Detection runs once
+    /// per grid change; later lookups reuse the result.
+    fn url_at_cursor(&mut self) -> Option<(PaneId, Selection, String)> {
+        if !self.cursor_over_active_pane() {
+            return None;
+        }
+        let (col, row) = self.active_pane_cell()?;
+        let pane_id = self.pane_manager.active_pane()?;
+        let pane = self.pane_manager.get_pane(pane_id)?;
 
-        for pane_id in pane_ids {
-            if let Some(pane) = self.pane_manager.get_pane_mut(pane_id) {
-                match pane.terminal.read() {
-                    Ok(Some(data)) => {
-                        pane.terminal.process_output(&data);
-                    }
-                    Ok(None) => {}
-                    Err(e) => {
-                        log::error!("Error reading from terminal: {}", e);
-                    }
+        let urls = self
+            .urls
+            .entry(pane_id)
+            .or_insert_with(|| pane.terminal.grid().lock().unwrap().detect_urls());
+        urls.iter()
+            .find(|(span, _)| span.contains(col.saturating_sub(1), row.saturating_sub(1)))
+            .map(|(span, url)| (pane_id, *span, url.clone()))
+    }
+
+    /// Underline the URL under the mouse pointer, if it changed
+    fn update_hovered_url(&mut self) {
+        let hovered = self.url_at_cursor().map(|(pane_id, span, _)| (pane_id, span));
+        if hovered == self.hovered_url {
+            return;
+        }
+
+        if let Some(pane) = self.hovered_url.and_then(|(pane_id, _)| self.pane_manager.get_pane(pane_id)) {
+            pane.terminal.grid().lock().unwrap().set_hovered_url(None);
+        }
+        if let Some((pane_id, span)) = hovered {
+            if let Some(pane) = self.pane_manager.get_pane(pane_id) {
+                pane.terminal.grid().lock().unwrap().set_hovered_url(Some(span));
+            }
+        }
+        self.hovered_url = hovered;
+        self.pane_manager.request_redraw();
+    }
+
+    /// Highlight the divider under the mouse, or the one being dragged, and
+    /// show a resize pointer over it
+    fn update_hovered_divider(&mut self) {
+        let (x, y) = (self.cursor_position.0 as f32, self.cursor_position.1 as f32);
+        let hovered = self.dragged_divider.clone().or_else(|| self.pane_manager.divider_at(x, y));
+        if hovered == self.hovered_divider {
+            return;
+        }
+
+        if let Some(window) = &self.window {
+            window.set_cursor(match hovered.as_ref().map(|divider| divider.direction) {
+                Some(SplitDirection::Horizontal) => CursorIcon::ColResize,
+                Some(SplitDirection::Vertical) => CursorIcon::RowResize,
+                None => CursorIcon::Default,
+            });
+        }
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_divider_highlight(hovered.as_ref().map(|divider| divider.grab_rect(DIVIDER_GRAB_DISTANCE)));
+        }
+        self.hovered_divider = hovered;
+        self.pane_manager.request_redraw();
+    }
+
+    /// Count rapid left clicks on the same cell: a double click selects the
+    /// word under the pointer, a triple click its line, and a single click
+    /// clears the selection
+    fn select_on_click(&mut self) {
+        let Some(cell) = self.active_pane_cell() else {
+            return;
+        };
+        let now = Instant::now();
+        let repeated = self
+            .last_click
+            .is_some_and(|(at, last)| last == cell && now.duration_since(at) <= MULTI_CLICK_INTERVAL);
+        self.click_count = if repeated { self.click_count % 3 + 1 } else { 1 };
+        self.last_click = Some((now, cell));
+
+        let Some(pane) = self.pane_manager.active_pane().and_then(|id| self.pane_manager.get_pane(id)) else {
+            return;
+        };
+        let grid = pane.terminal.grid();
+        let mut grid = grid.lock().unwrap();
+        let (col, row) = (cell.0.saturating_sub(1), cell.1.saturating_sub(1));
+        let selection = match self.click_count {
+            2 => grid.word_at(col, row, &self.config.word_separators),
+            3 => grid.line_span(row),
+            _ => None,
+        };
+        grid.set_selection(selection);
+    }
+
+    /// Report wheel motion to the active pane's application, one event per
+    /// line scrolled
+    fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y / renderer.cell_dimensions().1 as f64,
+        };
+        if lines == 0.0 || !self.cursor_over_active_pane() {
+            return;
+        }
+
+        let button = if lines > 0.0 {
+            mouse::MouseButton::WheelUp
+        } else {
+            mouse::MouseButton::WheelDown
+        };
+        let notches = (lines.abs().round() as usize).max(1);
+        if self.send_alternate_scroll(button, notches) {
+            return;
+        }
+        if let Some(cell) = self.active_pane_cell() {
+            for _ in 0..notches {
+                if !self.report_mouse(button, MouseAction::Press, cell) {
+                    break;
                 }
             }
         }
     }
 
+    fn cursor_over_active_pane(&self) -> bool {
+        self.pane_at_cursor().is_some() && self.pane_at_cursor() == self.pane_manager.active_pane()
+    }
+
+    /// 1-based cell of the active pane under the mouse pointer, clamped to
+    /// the pane's terminal so drags past its edge still map to a cell
+    fn active_pane_cell(&self) -> Option<(usize, usize)> {
+        let renderer = self.renderer.as_ref()?;
+        let pane_id = self.pane_manager.active_pane()?;
+        let window_size = self.window.as_ref()?.inner_size();
+        let pane_bounds = self.pane_manager.layout().calculate_bounds(
+            window_size.width as f32,
+            window_size.height as f32,
+        );
+        let (x, y, width, height) = *pane_bounds.get(&pane_id)?;
+        let (viewport_x, viewport_y, _, _) = TextRenderer::pane_content_viewport(
+            (x as u32, y as u32, width as u32, height as u32),
+            renderer.title_bar_height() as u32,
+        );
+
+        let (cell_width, cell_height) = renderer.cell_dimensions();
+        let (cols, rows) = self.pane_manager.get_pane(pane_id)?.terminal.grid().lock().unwrap().size();
+        let (cursor_x, cursor_y) = self.cursor_position;
+        let padding = self.config.window.padding;
+        let text_x = viewport_x as f64 + padding.left as f64;
+        let text_y = viewport_y as f64 + padding.top as f64;
+        let col = ((cursor_x - text_x) / cell_width as f64).max(0.0) as usize + 1;
+        let row = ((cursor_y - text_y) / cell_height as f64).max(0.0) as usize + 1;
+        Some((col.min(cols), row.min(rows)))
+    }
+
+    /// Send a mouse event to the active pane if its application tracks the
+    /// mouse. Returns false when the event should be handled locally.
+    fn report_mouse(&mut self, button: mouse::MouseButton, action: MouseAction, (col, row): (usize, usize)) -> bool {
+        let Some(pane_id) = self.pane_manager.active_pane() else {
+            return false;
+        };
+        let Some(pane) = self.pane_manager.get_pane_mut(pane_id) else {
+            return false;
+        };
+        let Some(report) = pane.terminal.mouse_report(button, action, col, row) else {
+            return false;
+        };
+
+        if let Err(e) = pane.terminal.write(&report) {
+            log::error!("Failed to write mouse report: {}", e);
+        }
+        true
+    }
+
+    /// Send the wheel to the active pane as arrow keys if its application
+    /// asked for alternate scroll mode; returns whether anything was sent
+    fn send_alternate_scroll(&mut self, button: mouse::MouseButton, lines: usize) -> bool {
+        let Some(pane_id) = self.pane_manager.active_pane() else {
+            return false;
+        };
+        let Some(pane) = self.pane_manager.get_pane_mut(pane_id) else {
+            return false;
+        };
+        let Some(keys) = pane.terminal.alternate_scroll_keys(button, lines) else {
+            return false;
+        };
+
+        if let Err(e) = pane.terminal.write(&keys) {
+            log::error!("Failed to write alternate scroll keys: {}", e);
+        }
+        true
+    }
+
     fn get_visible_text(&self, pane_id: titi::ui::PaneId) -> String {
         if let Some(pane) = self.pane_manager.get_pane(pane_id) {
             let grid = pane.terminal.grid();
             let g = grid.lock().unwrap();
-            let (cols, rows) = g.size();
-            let mut text = String::new();
-
-            for row in 0..rows {
-                let mut line = String::new();
-                for col in 0..cols {
-                    if let Some(cell) = g.get_cell(col, row) {
-                        line.push(cell.c);
-                    }
-                }
-                // Trim trailing spaces from each line
-                let trimmed = line.trim_end();
-                if !trimmed.is_empty() || row < rows - 1 {
-                    text.push_str(trimmed);
-                    if row < rows - 1 {
-                        text.push('\n');
-                    }
-                }
-            }
-
-            text
+            g.visible_lines().collect::<Vec<_>>().join("\n")
         } else {
             String::new()
         }
@@ -366,7 +827,10 @@ impl ApplicationHandler for App {
                 .with_inner_size(winit::dpi::LogicalSize::new(
                     self.config.window.width,
                     self.config.window.height,
-                ));
+                ))
+                // Only a request; the renderer falls back to opaque if the
+                // platform can't honor it
+                .with_transparent(self.config.window.opacity < 1.0);
 
             match event_loop.create_window(window_attrs) {
                 Ok(window) => {
@@ -384,11 +848,28 @@ impl ApplicationHandler for App {
                             if let Some(renderer) = &self.renderer {
                                 let (cell_width, cell_height) = renderer.cell_dimensions();
                                 let window_size = window.inner_size();
-                                let cols = (window_size.width as f32 / cell_width) as u16;
-                                let rows = (window_size.height as f32 / cell_height) as u16;
+                                let padding = self.config.window.padding;
+                                let text_width = window_size.width as f32 - padding.left - padding.right;
+                                let text_height = window_size.height as f32
+                                    - renderer.title_bar_height()
+                                    - padding.top
+                                    - padding.bottom;
+                                let cols = (text_width / cell_width) as u16;
+                                let rows = (text_height / cell_height) as u16;
+                                self.pane_manager
+                                    .set_viewport_size(window_size.width as f32, window_size.height as f32);
+                                self.pane_manager.set_cell_size(cell_width, cell_height);
+                                self.pane_manager.set_pane_header_height(renderer.title_bar_height());
+                                self.pane_manager.set_padding(padding);
 
+                                // A shell that fails to start leaves a pane showing why,
+                                // so only an error here is fatal
                                 match self.pane_manager.create_pane(cols.max(80), rows.max(24)) {
-                                    Ok(_) => log::info!("Initial pane created successfully"),
+                                    Ok(_) => {
+                                        log::info!("Initial pane created successfully");
+                                        // Undo the 80x24 minimum on a smaller window
+                                        self.pane_manager.fit_panes_to_viewport();
+                                    }
                                     Err(e) => {
                                         log::error!("Failed to create initial pane: {}", e);
                                         event_loop.exit();
@@ -412,46 +893,80 @@ impl ApplicationHandler for App {
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        // Input, focus and resizes may change what's on screen; pointer
+        // motion only does in the cases handled below
+        if !matches!(event, WindowEvent::RedrawRequested | WindowEvent::CursorMoved { .. }) {
+            self.pane_manager.request_redraw();
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
             }
             WindowEvent::Resized(physical_size) => {
+                self.pane_manager
+                    .set_viewport_size(physical_size.width as f32, physical_size.height as f32);
                 if let Some(renderer) = &mut self.renderer {
                     renderer.resize(physical_size);
                 }
             }
             WindowEvent::KeyboardInput { event, .. } => {
+                // Keep the cursor visible while typing
+                self.cursor_blink.reset(Instant::now());
                 self.handle_key(event);
+
+                // Closing the last pane of the last tab ends the session
+                if self.pane_manager.tabs().is_empty() {
+                    event_loop.exit();
+                }
+            }
+            WindowEvent::Focused(focused) => {
+                if let Err(e) = self.pane_manager.set_window_focused(focused) {
+                    log::error!("Failed to report focus change: {}", e);
+                }
             }
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 self.modifiers = new_modifiers.state();
             }
             WindowEvent::CursorMoved { position, .. } => {
                 self.cursor_position = (position.x, position.y);
-            }
-            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
-                // Handle left mouse click - focus pane at cursor position
-                if let Some(window) = &self.window {
-                    let window_size = window.inner_size();
-                    let pane_bounds = self.pane_manager.layout().calculate_bounds(
-                        window_size.width as f32,
-                        window_size.height as f32,
-                    );
-
-                    // Find which pane was clicked
-                    for (pane_id, (x, y, width, height)) in pane_bounds.iter() {
-                        let (cursor_x, cursor_y) = self.cursor_position;
-                        if cursor_x >= *x as f64
-                            && cursor_x < (*x + *width) as f64
-                            && cursor_y >= *y as f64
-                            && cursor_y < (*y + *height) as f64
-                        {
-                            self.pane_manager.set_active_pane(*pane_id);
-                            break;
+
+                if let Some(divider) = &mut self.dragged_divider {
+                    let (x, y) = (position.x as f32, position.y as f32);
+                    self.pane_manager.drag_divider(divider, x, y);
+                    divider.ratio = divider.ratio_at(x, y);
+                    self.update_hovered_divider();
+                    self.pane_manager.request_redraw();
+                    return;
+                }
+                self.update_hovered_divider();
+
+                if let Some(button) = self.reported_button {
+                    if let Some(cell) = self.active_pane_cell() {
+                        if self.last_reported_cell != Some(cell) {
+                            self.last_reported_cell = Some(cell);
+                            self.report_mouse(button, MouseAction::Drag, cell);
                         }
                     }
+                    return;
                 }
+
+                if self.config.focus_follows_mouse {
+                    if let Some(pane_id) = self.pane_at_cursor() {
+                        if self.pane_manager.active_pane() != Some(pane_id) {
+                            self.pane_manager.set_active_pane(pane_id);
+                            self.pane_manager.request_redraw();
+                        }
+                    }
+                }
+
+                self.update_hovered_url();
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.handle_mouse_input(state, button);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.handle_mouse_wheel(delta);
             }
             WindowEvent::RedrawRequested => {
                 // Skip rendering if renderer is not initialized yet
@@ -459,27 +974,87 @@ impl ApplicationHandler for App {
                     return;
                 }
 
-                // Poll terminals for output
-                self.poll_terminals();
+                // Pick up output that arrived since about_to_wait
+                self.pane_manager.poll_output();
+
+                // Changed grids need their URLs found again
+                let panes = self.pane_manager.panes();
+                self.urls.retain(|pane_id, _| {
+                    panes.get(pane_id).is_some_and(|pane| {
+                        let grid = pane.terminal.grid();
+                        let grid = grid.lock().unwrap();
+                        !grid.is_all_dirty() && grid.dirty_cells().is_empty()
+                    })
+                });
+                if self.hovered_url.is_some_and(|(pane_id, _)| !self.urls.contains_key(&pane_id)) {
+                    self.update_hovered_url();
+                }
 
                 // Render all panes
+                let frozen = self.pane_manager.is_frozen();
                 if let Some(renderer) = &mut self.renderer {
+                    renderer.set_cursor_visible(self.cursor_blink.visible());
                     if let Err(e) = renderer.render_panes(&self.pane_manager) {
-                        log::error!("Render error: {}", e);
-                        // Don't exit on render errors - they might be transient
-                        // (e.g., window minimized, GPU context lost temporarily)
+                        if matches!(e.downcast_ref(), Some(wgpu::SurfaceError::OutOfMemory)) {
+                            log::error!("Render error, giving up: {}", e);
+                            event_loop.exit();
+                        } else {
+                            // Don't exit on other render errors - they might be transient
+                            // (e.g., window minimized; lost surfaces were already reconfigured)
+                            log::error!("Render error: {}", e);
+                        }
                     }
                 }
+                // Changes held back by a synchronized update stay dirty, to
+                // be drawn once it ends
+                if !frozen {
+                    self.pane_manager.mark_drawn();
+                }
             }
             _ => {}
         }
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-        // Limit frame rate to ~60 FPS using WaitUntil instead of sleep
+        if let Some(config) = self.config_watcher.as_ref().and_then(|w| w.try_recv()) {
+            self.apply_config(config);
+            self.pane_manager.request_redraw();
+        }
+
+        // Output wakes the loop through the PTY waker; with nothing new to
+        // show, sleep until the next event instead of rendering
         let now = Instant::now();
+        if self.pane_manager.poll_output() {
+            // Output restarts the "on" phase like typing does
+            if self.cursor_blink.reset(now) {
+                self.pane_manager.request_redraw();
+            }
+        }
+        if self.cursor_blink.update(now) {
+            self.pane_manager.request_redraw();
+        }
+
+        // Frames held back by a synchronized update wouldn't be drawn, so
+        // sleep until it times out unless output ends it sooner
+        let frozen_until = self.pane_manager.frozen_until();
+        if frozen_until.is_some() || !self.pane_manager.needs_redraw() {
+            let config_check = self.config_watcher.as_ref().map(|_| now + CONFIG_CHECK_INTERVAL);
+            let wake_at = [self.cursor_blink.next_toggle(), config_check, frozen_until]
+                .into_iter()
+                .flatten()
+                .min();
+            event_loop.set_control_flow(match wake_at {
+                Some(at) => ControlFlow::WaitUntil(at),
+                None => ControlFlow::Wait,
+            });
+            return;
+        }
+
+        // Limit frame rate to ~60 FPS using WaitUntil instead of sleep, backing
+        // off when frames consistently take longer than that to render
         let elapsed = now - self.last_frame;
-        let target_frame_time = Duration::from_millis(16); // ~60 FPS
+        let target_frame_time = titi::metrics::p99_frame_time()
+            .map_or(FRAME_TIME, |p99| p99.clamp(FRAME_TIME, MAX_FRAME_TIME));
 
         if elapsed >= target_frame_time {
             self.last_frame = now;
@@ -495,7 +1070,7 @@ impl ApplicationHandler for App {
 }
 
 fn main() -> anyhow::Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    titi::logging::init();
 
     let args = Args::parse();
 
@@ -508,13 +1083,30 @@ fn main() -> anyhow::Result<()> {
     // Normal GUI mode
     log::info!("Starting Titi Terminal Emulator");
 
-    let config = Config::load().unwrap_or_default();
+    let mut config = Config::load().unwrap_or_default();
+    args.apply_shell_overrides(&mut config.shell)?;
 
     let event_loop = EventLoop::new()?;
     // Use Wait mode instead of Poll to avoid busy-waiting
     event_loop.set_control_flow(ControlFlow::Wait);
 
+    #[cfg(feature = "metrics-http")]
+    if let Some(addr) = &args.metrics_addr {
+        titi::metrics::serve_prometheus(addr)?;
+    }
+
     let mut app = App::new(config);
+
+    // PTY reader threads wake the event loop when output arrives
+    let proxy = event_loop.create_proxy();
+    app.pane_manager.set_output_waker(Arc::new(move || {
+        let _ = proxy.send_event(());
+    }));
+
+    app.config_watcher = Config::default_path()
+        .ok()
+        .filter(|path| path.exists())
+        .map(Config::watch);
     event_loop.run_app(&mut app)?;
 
     Ok(())
@@ -541,6 +1133,8 @@ async fn run_headless_main(args: Args) -> anyhow::Result<()> {
         pane_name: args.pane,
         cols: args.cols,
         rows: args.rows,
+        capture_dir: args.capture_dir,
+        script: args.script,
     };
 
     // Run headless mode
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -29,12 +31,33 @@ pub struct TerminalMetrics {
     pub total_bytes_written: usize,
 }
 
+/// Render timing for a single pane
+#[derive(Debug, Clone, Copy)]
+pub struct PaneRenderTiming {
+    pub last: Duration,
+    /// Exponential moving average over recent frames
+    pub average: Duration,
+    pub samples: u64,
+}
+
+/// Number of frame samples kept for FPS and percentile calculations
+const FRAME_SAMPLES: usize = 120;
+
 pub struct MetricsCollector {
     memory: Arc<Mutex<MemoryMetrics>>,
     performance: Arc<Mutex<PerformanceMetrics>>,
     terminals: Arc<Mutex<HashMap<String, TerminalMetrics>>>,
     frame_times: Arc<Mutex<Vec<Duration>>>,
+    pane_render_times: Arc<Mutex<HashMap<String, PaneRenderTiming>>>,
     start_time: Instant,
+    frames_rendered: AtomicU64,
+    pty_reads: AtomicU64,
+    bytes_processed: AtomicU64,
+    pty_bytes_written: AtomicU64,
+    bytes_parsed: AtomicU64,
+    pty_buffer_high_water: AtomicU64,
+    output_backlog: AtomicU64,
+    active_sessions: AtomicU64,
 }
 
 impl MetricsCollector {
@@ -53,17 +76,28 @@ impl MetricsCollector {
                 render_time_ms: 0.0,
             })),
             terminals: Arc::new(Mutex::new(HashMap::new())),
-            frame_times: Arc::new(Mutex::new(Vec::with_capacity(60))),
+            frame_times: Arc::new(Mutex::new(Vec::with_capacity(FRAME_SAMPLES))),
+            pane_render_times: Arc::new(Mutex::new(HashMap::new())),
             start_time: Instant::now(),
+            frames_rendered: AtomicU64::new(0),
+            pty_reads: AtomicU64::new(0),
+            bytes_processed: AtomicU64::new(0),
+            pty_bytes_written: AtomicU64::new(0),
+            bytes_parsed: AtomicU64::new(0),
+            pty_buffer_high_water: AtomicU64::new(0),
+            output_backlog: AtomicU64::new(0),
+            active_sessions: AtomicU64::new(0),
         }
     }
 
     pub fn record_frame(&self, duration: Duration) {
+        self.frames_rendered.fetch_add(1, Ordering::Relaxed);
+
         let mut frame_times = self.frame_times.lock().unwrap();
         frame_times.push(duration);
 
-        // Keep only last 60 frames
-        if frame_times.len() > 60 {
+        // Keep only the most recent frames
+        if frame_times.len() > FRAME_SAMPLES {
             frame_times.remove(0);
         }
 
@@ -78,6 +112,41 @@ impl MetricsCollector {
         }
     }
 
+    /// Record how long one pane took to render within a frame
+    pub fn record_pane_render(&self, pane_id: &str, duration: Duration) {
+        let mut panes = self.pane_render_times.lock().unwrap();
+        let timing = panes.entry(pane_id.to_string()).or_insert(PaneRenderTiming {
+            last: duration,
+            average: duration,
+            samples: 0,
+        });
+
+        timing.last = duration;
+        timing.average = timing.average.mul_f64(0.9) + duration.mul_f64(0.1);
+        timing.samples += 1;
+    }
+
+    /// Duration of the most recently recorded frame
+    pub fn last_frame_time(&self) -> Option<Duration> {
+        self.frame_times.lock().unwrap().last().copied()
+    }
+
+    /// 99th percentile frame time over the recent sample window
+    pub fn p99_frame_time(&self) -> Option<Duration> {
+        let mut frame_times = self.frame_times.lock().unwrap().clone();
+        if frame_times.is_empty() {
+            return None;
+        }
+
+        frame_times.sort_unstable();
+        let index = (frame_times.len() * 99).div_ceil(100) - 1;
+        Some(frame_times[index])
+    }
+
+    pub fn get_pane_render_times(&self) -> HashMap<String, PaneRenderTiming> {
+        self.pane_render_times.lock().unwrap().clone()
+    }
+
     pub fn record_parse_time(&self, duration: Duration) {
         let mut perf = self.performance.lock().unwrap();
         perf.parse_time_ms = duration.as_secs_f64() * 1000.0;
@@ -88,6 +157,68 @@ impl MetricsCollector {
         perf.render_time_ms = duration.as_secs_f64() * 1000.0;
     }
 
+    /// Count one read of `bytes` bytes of PTY output
+    pub fn record_pty_read(&self, bytes: usize) {
+        self.pty_reads.fetch_add(1, Ordering::Relaxed);
+        self.bytes_processed.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count `bytes` of input written to a PTY
+    pub fn record_pty_write(&self, bytes: usize) {
+        self.pty_bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count `bytes` fed to a terminal parser
+    pub fn record_parsed(&self, bytes: usize) {
+        self.bytes_parsed.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Total bytes read from PTYs
+    pub fn pty_bytes_read(&self) -> u64 {
+        self.bytes_processed.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes written to PTYs
+    pub fn pty_bytes_written(&self) -> u64 {
+        self.pty_bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes fed to terminal parsers
+    pub fn bytes_parsed(&self) -> u64 {
+        self.bytes_parsed.load(Ordering::Relaxed)
+    }
+
+    /// Bytes parsed per second, averaged since the collector started
+    pub fn parse_rate(&self) -> f64 {
+        let uptime = self.start_time.elapsed().as_secs_f64();
+        if uptime > 0.0 {
+            self.bytes_parsed() as f64 / uptime
+        } else {
+            0.0
+        }
+    }
+
+    /// Note `bytes` of PTY output waiting to be parsed, keeping the largest
+    /// backlog seen
+    pub fn record_pty_buffered(&self, bytes: usize) {
+        self.pty_buffer_high_water.fetch_max(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Note `bytes` of output read from PTYs but held back for a later frame
+    /// by the parse budget, over all panes
+    pub fn set_output_backlog(&self, bytes: usize) {
+        self.output_backlog.store(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Output waiting for a later frame, as of the last poll
+    pub fn output_backlog(&self) -> u64 {
+        self.output_backlog.load(Ordering::Relaxed)
+    }
+
+    pub fn set_active_sessions(&self, count: usize) {
+        self.active_sessions.store(count as u64, Ordering::Relaxed);
+    }
+
     pub fn update_memory(&self, grid_bytes: usize, atlas_bytes: usize) {
         let mut mem = self.memory.lock().unwrap();
         mem.grid_memory_bytes = grid_bytes;
@@ -132,6 +263,8 @@ impl MetricsCollector {
     }
 
     pub fn unregister_terminal(&self, pane_id: &str) {
+        self.pane_render_times.lock().unwrap().remove(pane_id);
+
         let mut terminals = self.terminals.lock().unwrap();
 
         if let Some(metrics) = terminals.remove(pane_id) {
@@ -177,6 +310,53 @@ impl MetricsCollector {
         self.terminals.lock().unwrap().clone()
     }
 
+    /// Serialize the current counters and gauges in the Prometheus text
+    /// exposition format
+    pub fn render_prometheus(&self) -> String {
+        let perf = self.get_performance_metrics();
+        let mem = self.get_memory_metrics();
+        let active_panes = self.terminals.lock().unwrap().len();
+        let mut out = String::new();
+
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        metric("titi_uptime_seconds", "gauge", "Seconds since the metrics collector started",
+            self.start_time.elapsed().as_secs_f64());
+        metric("titi_frames_rendered_total", "counter", "Frames rendered",
+            self.frames_rendered.load(Ordering::Relaxed) as f64);
+        metric("titi_frame_time_ms", "gauge", "Average frame time over recent frames",
+            perf.frame_time_ms);
+        metric("titi_fps", "gauge", "Frames per second over recent frames", perf.fps);
+        metric("titi_parse_time_ms", "gauge", "Last parse time", perf.parse_time_ms);
+        metric("titi_render_time_ms", "gauge", "Last render time", perf.render_time_ms);
+        metric("titi_pty_reads_total", "counter", "PTY reads fed to the parser",
+            self.pty_reads.load(Ordering::Relaxed) as f64);
+        metric("titi_bytes_processed_total", "counter", "Bytes read from PTYs",
+            self.pty_bytes_read() as f64);
+        metric("titi_pty_bytes_written_total", "counter", "Bytes of input written to PTYs",
+            self.pty_bytes_written() as f64);
+        metric("titi_bytes_parsed_total", "counter", "Bytes fed to terminal parsers",
+            self.bytes_parsed() as f64);
+        metric("titi_parse_rate_bytes_per_second", "gauge", "Bytes parsed per second since start",
+            self.parse_rate());
+        metric("titi_pty_buffer_high_water_bytes", "gauge", "Largest PTY output backlog waiting to be parsed",
+            self.pty_buffer_high_water.load(Ordering::Relaxed) as f64);
+        metric("titi_output_backlog_bytes", "gauge", "Output read but held back for a later frame",
+            self.output_backlog() as f64);
+        metric("titi_active_panes", "gauge", "Registered terminal panes", active_panes as f64);
+        metric("titi_active_sessions", "gauge", "Server sessions",
+            self.active_sessions.load(Ordering::Relaxed) as f64);
+        metric("titi_grid_memory_bytes", "gauge", "Grid memory", mem.grid_memory_bytes as f64);
+        metric("titi_atlas_memory_bytes", "gauge", "Glyph atlas memory", mem.atlas_memory_bytes as f64);
+        metric("titi_peak_memory_bytes", "gauge", "Peak grid + atlas memory", mem.peak_memory_bytes as f64);
+
+        out
+    }
+
     pub fn print_summary(&self) {
         let mem = self.memory.lock().unwrap();
         let perf = self.performance.lock().unwrap();
@@ -247,6 +427,100 @@ lazy_static::lazy_static! {
     pub static ref METRICS: MetricsCollector = MetricsCollector::new();
 }
 
+/// Times a frame and each pane rendered within it
+///
+/// The frame itself is always counted and timed; with the `render-timing`
+/// feature disabled the per-pane breakdown is skipped.
+pub struct FrameTimer {
+    start: Instant,
+    #[cfg(feature = "render-timing")]
+    panes: Vec<(usize, Duration)>,
+}
+
+impl FrameTimer {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            #[cfg(feature = "render-timing")]
+            panes: Vec::new(),
+        }
+    }
+
+    /// Run `f` as the render work for `pane_id`, timing it
+    pub fn time_pane<T>(&mut self, pane_id: usize, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "render-timing")]
+        {
+            let start = Instant::now();
+            let result = f();
+            self.panes.push((pane_id, start.elapsed()));
+            result
+        }
+
+        #[cfg(not(feature = "render-timing"))]
+        {
+            let _ = pane_id;
+            f()
+        }
+    }
+
+    /// Record the frame total and per-pane samples into `metrics`
+    pub fn finish(self, metrics: &MetricsCollector) {
+        metrics.record_frame(self.start.elapsed());
+
+        #[cfg(feature = "render-timing")]
+        for (pane_id, duration) in self.panes {
+            metrics.record_pane_render(&pane_id.to_string(), duration);
+        }
+    }
+}
+
+/// Duration of the last frame recorded in the global `METRICS`
+pub fn last_frame_time() -> Option<Duration> {
+    METRICS.last_frame_time()
+}
+
+/// 99th percentile frame time recorded in the global `METRICS`
+pub fn p99_frame_time() -> Option<Duration> {
+    METRICS.p99_frame_time()
+}
+
+/// Render the global `METRICS` in Prometheus text format
+pub fn render_prometheus() -> String {
+    METRICS.render_prometheus()
+}
+
+/// Serve `render_prometheus()` over plain HTTP on `addr` from a background
+/// thread, for scraping by Prometheus
+#[cfg(feature = "metrics-http")]
+pub fn serve_prometheus(addr: &str) -> std::io::Result<std::thread::JoinHandle<()>> {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind(addr)?;
+    log::info!("Serving Prometheus metrics on http://{}/metrics", addr);
+
+    Ok(std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+
+            // The request itself doesn't matter; every path serves metrics
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+
+            let body = render_prometheus();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()) {
+                log::debug!("Failed to write metrics response: {}", e);
+            }
+        }
+    }))
+}
+
 // Convenience macros
 #[macro_export]
 macro_rules! record_frame {
@@ -268,3 +542,67 @@ macro_rules! record_render_time {
         $crate::metrics::METRICS.record_render_time($duration);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_after_activity() {
+        let metrics = MetricsCollector::new();
+        metrics.register_terminal("pane-1".to_string(), 80, 24);
+        metrics.record_frame(Duration::from_millis(16));
+        metrics.record_pty_read(128);
+        metrics.record_pty_read(64);
+        metrics.record_pty_buffered(4096);
+        metrics.record_pty_buffered(1024);
+        metrics.record_pty_write(5);
+        metrics.record_parsed(192);
+        metrics.set_active_sessions(3);
+
+        let output = metrics.render_prometheus();
+
+        assert!(output.contains("# TYPE titi_frames_rendered_total counter"));
+        assert!(output.contains("titi_frames_rendered_total 1\n"));
+        assert!(output.contains("titi_pty_reads_total 2\n"));
+        assert!(output.contains("titi_bytes_processed_total 192\n"));
+        assert!(output.contains("titi_pty_bytes_written_total 5\n"));
+        assert!(output.contains("titi_bytes_parsed_total 192\n"));
+        assert!(output.contains("# TYPE titi_parse_rate_bytes_per_second gauge"));
+        assert!(output.contains("titi_pty_buffer_high_water_bytes 4096\n"));
+        assert!(output.contains("titi_active_panes 1\n"));
+        assert!(output.contains("titi_active_sessions 3\n"));
+        assert!(output.contains("titi_frame_time_ms"));
+    }
+
+    #[test]
+    fn test_frame_timer_counts_frames() {
+        let metrics = MetricsCollector::new();
+
+        let mut timer = FrameTimer::start();
+        timer.time_pane(1, || ());
+        timer.finish(&metrics);
+
+        assert!(metrics.last_frame_time().is_some());
+        assert!(metrics.render_prometheus().contains("titi_frames_rendered_total 1\n"));
+    }
+
+    #[test]
+    fn test_render_prometheus_lines_are_well_formed() {
+        let metrics = MetricsCollector::new();
+        let output = metrics.render_prometheus();
+
+        for line in output.lines() {
+            if line.starts_with('#') {
+                assert!(line.starts_with("# HELP ") || line.starts_with("# TYPE "), "{}", line);
+            } else {
+                let mut parts = line.split(' ');
+                let name = parts.next().unwrap();
+                let value = parts.next().unwrap();
+                assert!(name.starts_with("titi_"), "{}", line);
+                assert!(value.parse::<f64>().is_ok(), "{}", line);
+                assert!(parts.next().is_none(), "{}", line);
+            }
+        }
+    }
+}
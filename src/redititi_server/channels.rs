@@ -3,50 +3,290 @@
 //! Manages publish/subscribe channels for terminal communication.
 
 use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
 pub type ConnectionId = u64;
 
+/// Default cap on messages queued per channel
+pub const DEFAULT_MAX_QUEUE_LEN: usize = 10_000;
+
+/// Metadata about a connected client
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: ConnectionId,
+    pub addr: String,
+    pub authenticated: bool,
+    /// Set by DETACH; the connection's sessions are kept when it closes
+    pub detached: bool,
+    /// Unix timestamp (seconds) of when the client connected
+    pub connected_at: u64,
+    pub connected_since: Instant,
+}
+
 #[derive(Debug, Clone)]
 pub struct Message {
     pub channel: String,
     pub content: String,
+    pub published_at: Instant,
 }
 
 struct Channel {
     _name: String,
     subscribers: Vec<ConnectionId>,
-    queue: VecDeque<Message>, // FIFO queue for messages
+    /// Newest message first, like a Redis list fed by LPUSH: messages go in
+    /// at the front and RPOP takes the oldest from the back
+    queue: VecDeque<Message>,
+    max_len: usize,
+    ttl: Option<Duration>,
 }
 
 impl Channel {
-    fn new(name: String) -> Self {
+    fn new(name: String, max_len: usize, ttl: Option<Duration>) -> Self {
         Self {
             _name: name,
             subscribers: Vec::new(),
             queue: VecDeque::new(),
+            max_len,
+            ttl,
+        }
+    }
+
+    /// Queue a message, dropping the oldest ones past `max_len`
+    fn push(&mut self, message: Message) {
+        self.queue.push_front(message);
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.queue.len() > self.max_len {
+            self.queue.pop_back();
+        }
+    }
+
+    /// Drop messages older than the TTL, returning how many were removed
+    fn expire(&mut self, now: Instant) -> usize {
+        let Some(ttl) = self.ttl else {
+            return 0;
+        };
+
+        let before = self.queue.len();
+        // Messages are queued in publish order, so expired ones are at the back
+        while self
+            .queue
+            .back()
+            .is_some_and(|m| now.duration_since(m.published_at) >= ttl)
+        {
+            self.queue.pop_back();
         }
+        before - self.queue.len()
     }
 }
 
 pub struct ChannelManager {
     channels: Arc<RwLock<HashMap<String, Channel>>>,
+    /// Pattern subscriptions by (pattern, subscriber), each with its own
+    /// queue of matching messages
+    patterns: Arc<RwLock<HashMap<(String, ConnectionId), Channel>>>,
+    clients: Arc<RwLock<HashMap<ConnectionId, ClientInfo>>>,
+    default_max_len: AtomicUsize,
+    default_ttl: Mutex<Option<Duration>>,
 }
 
 impl ChannelManager {
     pub fn new() -> Self {
         Self {
             channels: Arc::new(RwLock::new(HashMap::new())),
+            patterns: Arc::new(RwLock::new(HashMap::new())),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            default_max_len: AtomicUsize::new(DEFAULT_MAX_QUEUE_LEN),
+            default_ttl: Mutex::new(None),
         }
     }
 
+    /// Set the queue cap for channels created from now on
+    pub fn set_default_max_len(&self, max_len: usize) {
+        self.default_max_len.store(max_len, Ordering::Relaxed);
+    }
+
+    /// Set the message TTL for channels created from now on (`None` keeps
+    /// messages until popped or pushed out by the cap)
+    pub fn set_default_ttl(&self, ttl: Option<Duration>) {
+        *self.default_ttl.lock().unwrap() = ttl;
+    }
+
+    fn new_channel(&self, name: &str) -> Channel {
+        Channel::new(
+            name.to_string(),
+            self.default_max_len.load(Ordering::Relaxed),
+            *self.default_ttl.lock().unwrap(),
+        )
+    }
+
+    /// Cap a channel's queue at `max_len` messages, dropping the oldest
+    /// immediately if it is already longer
+    pub async fn set_max_len(&self, channel_name: &str, max_len: usize) {
+        let mut channels = self.channels.write().await;
+        let channel = channels
+            .entry(channel_name.to_string())
+            .or_insert_with(|| self.new_channel(channel_name));
+
+        channel.max_len = max_len;
+        channel.truncate();
+    }
+
+    /// Set how long messages on a channel live before the sweep drops them
+    pub async fn set_ttl(&self, channel_name: &str, ttl: Option<Duration>) {
+        let mut channels = self.channels.write().await;
+        let channel = channels
+            .entry(channel_name.to_string())
+            .or_insert_with(|| self.new_channel(channel_name));
+
+        channel.ttl = ttl;
+    }
+
+    /// Drop expired messages from every channel and pattern queue, returning
+    /// how many were removed
+    pub async fn expire_messages(&self) -> usize {
+        let now = Instant::now();
+        let mut expired = 0;
+
+        for channel in self.channels.write().await.values_mut() {
+            expired += channel.expire(now);
+        }
+        for entry in self.patterns.write().await.values_mut() {
+            expired += entry.expire(now);
+        }
+
+        expired
+    }
+
+    /// Snapshot pending messages of every channel queue, oldest first
+    ///
+    /// Pattern queues aren't included: they belong to connections, which
+    /// don't survive a restart.
+    pub async fn queue_snapshot(&self) -> HashMap<String, Vec<String>> {
+        let channels = self.channels.read().await;
+        channels
+            .iter()
+            .filter(|(_, channel)| !channel.queue.is_empty())
+            .map(|(name, channel)| {
+                let messages = channel.queue.iter().rev().map(|m| m.content.clone()).collect();
+                (name.clone(), messages)
+            })
+            .collect()
+    }
+
+    /// Requeue messages from a snapshot taken by `queue_snapshot`
+    pub async fn restore_queues(&self, snapshot: HashMap<String, Vec<String>>) {
+        let mut channels = self.channels.write().await;
+        for (name, messages) in snapshot {
+            let channel = channels
+                .entry(name.clone())
+                .or_insert_with(|| self.new_channel(&name));
+
+            for content in messages {
+                channel.push(Message {
+                    channel: name.clone(),
+                    content,
+                    published_at: Instant::now(),
+                });
+            }
+        }
+    }
+
+    /// Run `expire_messages` every `interval` until the manager is dropped
+    pub fn spawn_expiry_sweep(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+
+                let expired = manager.expire_messages().await;
+                if expired > 0 {
+                    log::debug!("Expired {} queued messages", expired);
+                }
+            }
+        })
+    }
+
+    /// Track a newly accepted connection
+    pub async fn register_client(&self, conn_id: ConnectionId, addr: String) {
+        let connected_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.clients.write().await.insert(
+            conn_id,
+            ClientInfo {
+                id: conn_id,
+                addr,
+                authenticated: false,
+                detached: false,
+                connected_at,
+                connected_since: Instant::now(),
+            },
+        );
+    }
+
+    /// Mark a connection as authenticated
+    pub async fn set_authenticated(&self, conn_id: ConnectionId) {
+        if let Some(client) = self.clients.write().await.get_mut(&conn_id) {
+            client.authenticated = true;
+        }
+    }
+
+    /// Mark a connection as detached so its sessions outlive it
+    pub async fn set_detached(&self, conn_id: ConnectionId) {
+        if let Some(client) = self.clients.write().await.get_mut(&conn_id) {
+            client.detached = true;
+        }
+    }
+
+    /// Forget a connection and drop all its subscriptions
+    pub async fn unregister_client(&self, conn_id: ConnectionId) {
+        self.unsubscribe_all(conn_id).await;
+        self.clients.write().await.remove(&conn_id);
+    }
+
+    /// Get metadata for a single connection
+    pub async fn client_info(&self, conn_id: ConnectionId) -> Option<ClientInfo> {
+        self.clients.read().await.get(&conn_id).cloned()
+    }
+
+    /// List all connected clients, ordered by connection id
+    pub async fn list_clients(&self) -> Vec<ClientInfo> {
+        let mut clients: Vec<ClientInfo> = self.clients.read().await.values().cloned().collect();
+        clients.sort_by_key(|c| c.id);
+        clients
+    }
+
+    /// Get the channels a connection is subscribed to, sorted by name
+    pub async fn subscriptions(&self, conn_id: ConnectionId) -> Vec<String> {
+        let channels = self.channels.read().await;
+        let mut names: Vec<String> = channels
+            .iter()
+            .filter(|(_, channel)| channel.subscribers.contains(&conn_id))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Subscribe a connection to a channel
     pub async fn subscribe(&self, channel_name: &str, conn_id: ConnectionId) {
         let mut channels = self.channels.write().await;
         let channel = channels
             .entry(channel_name.to_string())
-            .or_insert_with(|| Channel::new(channel_name.to_string()));
+            .or_insert_with(|| self.new_channel(channel_name));
 
         if !channel.subscribers.contains(&conn_id) {
             channel.subscribers.push(conn_id);
@@ -66,8 +306,54 @@ impl ChannelManager {
         }
     }
 
+    /// Subscribe a connection to every channel matching a glob pattern
+    ///
+    /// `*` matches any run of characters (including `/`) and `?` matches a
+    /// single character, so `session-1/*/output` covers all panes of a session.
+    pub async fn psubscribe(&self, pattern: &str, conn_id: ConnectionId) {
+        let mut patterns = self.patterns.write().await;
+        patterns.entry((pattern.to_string(), conn_id)).or_insert_with(|| {
+            let mut entry = self.new_channel(pattern);
+            entry.subscribers.push(conn_id);
+            entry
+        });
+    }
+
+    /// Remove a connection's pattern subscription
+    pub async fn punsubscribe(&self, pattern: &str, conn_id: ConnectionId) {
+        // Pattern queues only exist for their subscribers
+        self.patterns.write().await.remove(&(pattern.to_string(), conn_id));
+    }
+
+    /// Pop the next message delivered to a connection's pattern subscription
+    ///
+    /// The returned message's `channel` is the concrete channel it was
+    /// published to. Every subscriber to a pattern gets its own copy.
+    pub async fn pop_pattern_message(&self, pattern: &str, conn_id: ConnectionId) -> Option<Message> {
+        let mut patterns = self.patterns.write().await;
+        patterns
+            .get_mut(&(pattern.to_string(), conn_id))
+            .and_then(|p| p.queue.pop_back())
+    }
+
+    /// Get the patterns a connection is subscribed to, sorted
+    pub async fn pattern_subscriptions(&self, conn_id: ConnectionId) -> Vec<String> {
+        let patterns = self.patterns.read().await;
+        let mut names: Vec<String> = patterns
+            .keys()
+            .filter(|(_, id)| *id == conn_id)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Unsubscribe a connection from all channels
     pub async fn unsubscribe_all(&self, conn_id: ConnectionId) {
+        let mut patterns = self.patterns.write().await;
+        patterns.retain(|(_, id), _| *id != conn_id);
+        drop(patterns);
+
         let mut channels = self.channels.write().await;
         let channel_names: Vec<String> = channels.keys().cloned().collect();
 
@@ -85,31 +371,82 @@ impl ChannelManager {
 
     /// Publish a message to a channel
     pub async fn publish(&self, channel_name: &str, content: String) -> usize {
+        self.deliver(channel_name, content).await.0
+    }
+
+    /// Queue a message at the head of a channel and fan it out to pattern
+    /// subscriptions, returning (subscribers reached, new queue length)
+    async fn deliver(&self, channel_name: &str, content: String) -> (usize, usize) {
         let mut channels = self.channels.write().await;
         let channel = channels
             .entry(channel_name.to_string())
-            .or_insert_with(|| Channel::new(channel_name.to_string()));
+            .or_insert_with(|| self.new_channel(channel_name));
 
         let message = Message {
             channel: channel_name.to_string(),
             content,
+            published_at: Instant::now(),
         };
 
-        // Add to queue
-        channel.queue.push_back(message);
+        // Deliver a copy to every matching pattern subscription
+        let mut pattern_subscribers = 0;
+        let mut patterns = self.patterns.write().await;
+        for ((pattern, _), entry) in patterns.iter_mut() {
+            if glob_match(pattern, channel_name) {
+                entry.push(message.clone());
+                pattern_subscribers += 1;
+            }
+        }
+
+        // Add to queue, dropping the oldest message past the cap
+        channel.push(message);
 
-        // Return number of subscribers
-        channel.subscribers.len()
+        (channel.subscribers.len() + pattern_subscribers, channel.queue.len())
     }
 
-    /// Pop a message from a channel (FIFO, consume on read)
+    /// Pop the oldest message from a channel (RPOP: FIFO, consume on read)
     pub async fn pop_message(&self, channel_name: &str) -> Option<Message> {
         let mut channels = self.channels.write().await;
-        if let Some(channel) = channels.get_mut(channel_name) {
-            channel.queue.pop_front()
-        } else {
-            None
+        channels.get_mut(channel_name)?.queue.pop_back()
+    }
+
+    /// Push a message onto the head of a channel (LPUSH), delivered like
+    /// `publish`. Returns the new queue length.
+    pub async fn push_message(&self, channel_name: &str, content: String) -> usize {
+        self.deliver(channel_name, content).await.1
+    }
+
+    /// Pop the message at the head of a channel, the most recently queued
+    /// one (LPOP: LIFO, consume on read)
+    pub async fn pop_newest_message(&self, channel_name: &str) -> Option<Message> {
+        let mut channels = self.channels.write().await;
+        channels.get_mut(channel_name)?.queue.pop_front()
+    }
+
+    /// Messages between `start` and `stop` (inclusive) without consuming
+    /// them. Index 0 is the head (newest message), as in a Redis list fed
+    /// by LPUSH; negative indices count from the tail (-1 is the oldest).
+    pub async fn range_messages(&self, channel_name: &str, start: i64, stop: i64) -> Vec<String> {
+        let channels = self.channels.read().await;
+        let Some(channel) = channels.get(channel_name) else {
+            return Vec::new();
+        };
+
+        let len = channel.queue.len() as i64;
+        let resolve = |index: i64| if index < 0 { index + len } else { index };
+        let start = resolve(start).max(0);
+        let stop = resolve(stop).min(len - 1);
+        if start > stop {
+            return Vec::new();
         }
+
+        channel
+            .queue
+            .iter()
+            .skip(start as usize)
+            .take((stop - start + 1) as usize)
+            .map(|m| m.content.clone())
+            .collect()
     }
 
     /// Get all pending messages for a subscriber
@@ -117,8 +454,8 @@ impl ChannelManager {
         let channels = self.channels.read().await;
         if let Some(channel) = channels.get(channel_name) {
             if channel.subscribers.contains(&conn_id) {
-                // Return all messages in queue
-                return channel.queue.iter().cloned().collect();
+                // Return all messages in queue, oldest first
+                return channel.queue.iter().rev().cloned().collect();
             }
         }
         Vec::new()
@@ -133,6 +470,15 @@ impl ChannelManager {
             .unwrap_or(0)
     }
 
+    /// Drop every channel whose name starts with `prefix`, along with its
+    /// queued messages and subscribers. Returns how many were removed.
+    pub async fn remove_channels_with_prefix(&self, prefix: &str) -> usize {
+        let mut channels = self.channels.write().await;
+        let before = channels.len();
+        channels.retain(|name, _| !name.starts_with(prefix));
+        before - channels.len()
+    }
+
     /// List all channels
     pub async fn list_channels(&self) -> Vec<String> {
         let channels = self.channels.read().await;
@@ -149,6 +495,36 @@ impl ChannelManager {
     }
 }
 
+/// Match `text` against a glob pattern supporting `*` and `?`
+///
+/// Greedy with single-star backtracking, so it runs in O(pattern * text)
+/// at worst and linear time for typical channel patterns.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let text = text.as_bytes();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,6 +585,135 @@ mod tests {
         assert_eq!(manager.queue_length("test-channel").await, 2);
     }
 
+    #[tokio::test]
+    async fn test_queue_max_len_drops_oldest() {
+        let manager = ChannelManager::new();
+        manager.set_max_len("test-channel", 3).await;
+
+        for i in 0..5 {
+            manager.publish("test-channel", format!("msg{}", i)).await;
+        }
+
+        assert_eq!(manager.queue_length("test-channel").await, 3);
+        assert_eq!(manager.pop_message("test-channel").await.unwrap().content, "msg2");
+    }
+
+    #[tokio::test]
+    async fn test_queue_snapshot_restore() {
+        let manager = ChannelManager::new();
+        manager.publish("a", "one".to_string()).await;
+        manager.publish("a", "two".to_string()).await;
+        manager.publish("b", "three".to_string()).await;
+
+        let snapshot = manager.queue_snapshot().await;
+
+        let restored = ChannelManager::new();
+        restored.restore_queues(snapshot).await;
+        assert_eq!(restored.queue_length("a").await, 2);
+        assert_eq!(restored.pop_message("a").await.unwrap().content, "one");
+        assert_eq!(restored.pop_message("b").await.unwrap().content, "three");
+    }
+
+    #[tokio::test]
+    async fn test_message_ttl_expiry() {
+        let manager = ChannelManager::new();
+        manager.set_ttl("test-channel", Some(Duration::from_millis(50))).await;
+
+        manager.publish("test-channel", "old".to_string()).await;
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        manager.publish("test-channel", "new".to_string()).await;
+
+        assert_eq!(manager.expire_messages().await, 1);
+        assert_eq!(manager.queue_length("test-channel").await, 1);
+        assert_eq!(manager.pop_message("test-channel").await.unwrap().content, "new");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*/output", "session-1/pane-a/output"));
+        assert!(glob_match("session-1/pane-*/output", "session-1/pane-a/output"));
+        assert!(glob_match("session-?/pane-a/input", "session-1/pane-a/input"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("session-1/pane-*/output", "session-2/pane-a/output"));
+        assert!(!glob_match("*/output", "session-1/pane-a/input"));
+        assert!(!glob_match("session-?/x", "session-10/x"));
+    }
+
+    #[tokio::test]
+    async fn test_pattern_subscription() {
+        let manager = ChannelManager::new();
+
+        manager.psubscribe("session-1/*/output", 1).await;
+        let count = manager.publish("session-1/pane-a/output", "a".to_string()).await;
+        assert_eq!(count, 1);
+        manager.publish("session-2/pane-a/output", "other".to_string()).await;
+        manager.publish("session-1/pane-b/output", "b".to_string()).await;
+
+        let msg = manager.pop_pattern_message("session-1/*/output", 1).await.unwrap();
+        assert_eq!((msg.channel.as_str(), msg.content.as_str()), ("session-1/pane-a/output", "a"));
+        let msg = manager.pop_pattern_message("session-1/*/output", 1).await.unwrap();
+        assert_eq!((msg.channel.as_str(), msg.content.as_str()), ("session-1/pane-b/output", "b"));
+        assert!(manager.pop_pattern_message("session-1/*/output", 1).await.is_none());
+
+        // Exact-channel queues are unaffected
+        assert_eq!(manager.queue_length("session-1/pane-a/output").await, 1);
+
+        manager.punsubscribe("session-1/*/output", 1).await;
+        assert!(manager.pattern_subscriptions(1).await.is_empty());
+        manager.publish("session-1/pane-a/output", "late".to_string()).await;
+        assert!(manager.pop_pattern_message("session-1/*/output", 1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pattern_subscribers_each_get_a_copy() {
+        let manager = ChannelManager::new();
+
+        manager.psubscribe("*/output", 1).await;
+        manager.psubscribe("*/output", 2).await;
+        assert_eq!(manager.publish("s1/pane-a/output", "a".to_string()).await, 2);
+
+        // One subscriber reading doesn't take the message from the other
+        assert_eq!(manager.pop_pattern_message("*/output", 1).await.unwrap().content, "a");
+        assert!(manager.pop_pattern_message("*/output", 1).await.is_none());
+        assert_eq!(manager.pop_pattern_message("*/output", 2).await.unwrap().content, "a");
+
+        // Nor does unsubscribing one of them
+        manager.publish("s1/pane-a/output", "b".to_string()).await;
+        manager.unsubscribe_all(1).await;
+        assert!(manager.pop_pattern_message("*/output", 1).await.is_none());
+        assert_eq!(manager.pop_pattern_message("*/output", 2).await.unwrap().content, "b");
+        assert_eq!(manager.pattern_subscriptions(2).await, vec!["*/output"]);
+    }
+
+    #[tokio::test]
+    async fn test_client_tracking() {
+        let manager = ChannelManager::new();
+
+        manager.register_client(1, "127.0.0.1:5000".to_string()).await;
+        assert!(!manager.client_info(1).await.unwrap().authenticated);
+
+        manager.set_authenticated(1).await;
+        manager.subscribe("channel1", 1).await;
+        assert!(manager.client_info(1).await.unwrap().authenticated);
+        assert_eq!(manager.subscriptions(1).await, vec!["channel1"]);
+
+        manager.unregister_client(1).await;
+        assert!(manager.client_info(1).await.is_none());
+        assert_eq!(manager.get_subscribers("channel1").await.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_remove_channels_with_prefix() {
+        let manager = ChannelManager::new();
+
+        manager.publish("s1/pane-1/output", "a".to_string()).await;
+        manager.publish("s1/pane-2/output", "b".to_string()).await;
+        manager.publish("s10/pane-1/output", "c".to_string()).await;
+
+        assert_eq!(manager.remove_channels_with_prefix("s1/").await, 2);
+        assert_eq!(manager.list_channels().await, vec!["s10/pane-1/output"]);
+    }
+
     #[tokio::test]
     async fn test_unsubscribe_all() {
         let manager = ChannelManager::new();
@@ -2,12 +2,19 @@
 //!
 //! Implements handlers for all Redis-like commands.
 
-use super::channels::{ChannelManager, ConnectionId};
-use super::protocol::Response;
-use super::registry::Registry;
+use super::channels::{ChannelManager, ClientInfo, ConnectionId};
+use super::protocol::{decode_payload, display_message, raw_input_message, Response, DISPLAY_MARKER, RAW_INPUT_MARKER};
+use super::registry::{unix_now, Registry};
+use crate::terminal::keys;
+use crate::terminal::CaptureMode;
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tracing::Instrument;
+
+/// Channel on which the id of every session reaped for being idle is published
+pub const SESSION_CLOSED_CHANNEL: &str = "session-closed";
 
 pub struct CommandHandler {
     registry: Arc<RwLock<Registry>>,
@@ -19,16 +26,38 @@ impl CommandHandler {
         Self { registry, channels }
     }
 
+    /// Run one command, inside a span tagged with the connection and, when
+    /// the command names one, the session and pane it acts on
     pub async fn handle_command(
         &self,
         command: &str,
         args: Vec<String>,
         conn_id: ConnectionId,
     ) -> Response {
+        let args = self.resolve_names(command, args).await;
+        let (session, pane) = command_target(command, &args);
+        let span = tracing::debug_span!("command", name = command, conn_id, session, pane);
+        async {
+            let response = self.dispatch(command, args, conn_id).await;
+            match &response {
+                Response::Error(e) => tracing::debug!(error = %e, "Command failed"),
+                _ => tracing::trace!("Command handled"),
+            }
+            response
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn dispatch(&self, command: &str, args: Vec<String>, conn_id: ConnectionId) -> Response {
         match command {
             // Session management
             "LIST" if args.get(0).map(|s| s.as_str()) == Some("SESSIONS") => {
-                self.handle_list_sessions().await
+                if args.get(1).map(|s| s.as_str()) == Some("VERBOSE") {
+                    self.handle_list_sessions_verbose().await
+                } else {
+                    self.handle_list_sessions().await
+                }
             }
             "LIST" if args.get(0).map(|s| s.as_str()) == Some("PANES") => {
                 if let Some(session_id) = args.get(1) {
@@ -37,15 +66,28 @@ impl CommandHandler {
                     Response::Error("LIST PANES requires session_id".to_string())
                 }
             }
+            "LIST" if args.first().map(|s| s.as_str()) == Some("CLIENTS") => {
+                self.handle_list_clients(conn_id).await
+            }
+            "CLIENT" if args.first().map(|s| s.as_str()) == Some("INFO") => {
+                match args.get(1).map(|id| id.parse::<ConnectionId>()) {
+                    Some(Ok(target)) => self.handle_client_info(target, conn_id).await,
+                    Some(Err(_)) => Response::Error("Invalid connection id".to_string()),
+                    None => Response::Error("CLIENT INFO requires conn_id".to_string()),
+                }
+            }
             "CREATE" if args.get(0).map(|s| s.as_str()) == Some("SESSION") => {
-                let name = args.get(1).map(|s| s.to_string());
-                let pane_name = args.get(2).map(|s| s.to_string());
-                self.handle_create_session(name, pane_name).await
+                // A trailing PERSISTENT keeps the session after its creator disconnects
+                let persistent = args.len() > 1 && args.last().map(|s| s.as_str()) == Some("PERSISTENT");
+                let names = if persistent { &args[1..args.len() - 1] } else { &args[1..] };
+                let name = names.first().map(|s| s.to_string());
+                let pane_name = names.get(1).map(|s| s.to_string());
+                self.handle_create_session(name, pane_name, persistent, conn_id).await
             }
             "CREATE" if args.get(0).map(|s| s.as_str()) == Some("PANE") => {
                 if let Some(session_id) = args.get(1) {
                     let name = args.get(2).map(|s| s.to_string());
-                    self.handle_create_pane(session_id, name).await
+                    self.handle_create_pane(session_id, name, conn_id).await
                 } else {
                     Response::Error("CREATE PANE requires session_id".to_string())
                 }
@@ -64,6 +106,34 @@ impl CommandHandler {
                     Response::Error("CLOSE SESSION requires session_id".to_string())
                 }
             }
+            "RENAME" if args.first().map(|s| s.as_str()) == Some("SESSION") => {
+                if let (Some(session_id), Some(name)) = (args.get(1), args.get(2)) {
+                    self.handle_rename_session(session_id, name).await
+                } else {
+                    Response::Error("RENAME SESSION requires session_id and name".to_string())
+                }
+            }
+            "RENAME" if args.first().map(|s| s.as_str()) == Some("PANE") => {
+                if let (Some(session_id), Some(pane_id), Some(name)) = (args.get(1), args.get(2), args.get(3)) {
+                    self.handle_rename_pane(session_id, pane_id, name).await
+                } else {
+                    Response::Error("RENAME PANE requires session_id, pane_id and name".to_string())
+                }
+            }
+            "KILL" if args.first().map(|s| s.as_str()) == Some("SESSION") => {
+                if let Some(pattern) = args.get(1) {
+                    self.handle_kill_sessions(pattern, conn_id).await
+                } else {
+                    Response::Error("KILL SESSION requires a pattern".to_string())
+                }
+            }
+            "KILL" if args.first().map(|s| s.as_str()) == Some("PANE") => {
+                if let (Some(session_id), Some(pattern)) = (args.get(1), args.get(2)) {
+                    self.handle_kill_panes(session_id, pattern, conn_id).await
+                } else {
+                    Response::Error("KILL PANE requires session_id and pattern".to_string())
+                }
+            }
 
             // Channel operations
             "SUBSCRIBE" => {
@@ -80,6 +150,20 @@ impl CommandHandler {
                     Response::Error("UNSUBSCRIBE requires channel name".to_string())
                 }
             }
+            "PSUBSCRIBE" => {
+                if let Some(pattern) = args.first() {
+                    self.handle_psubscribe(pattern, conn_id).await
+                } else {
+                    Response::Error("PSUBSCRIBE requires pattern".to_string())
+                }
+            }
+            "PUNSUBSCRIBE" => {
+                if let Some(pattern) = args.first() {
+                    self.handle_punsubscribe(pattern, conn_id).await
+                } else {
+                    Response::Error("PUNSUBSCRIBE requires pattern".to_string())
+                }
+            }
             "PUBLISH" => {
                 if let (Some(channel), Some(_message)) = (args.get(0), args.get(1)) {
                     let content = args[1..].join(" ");
@@ -90,6 +174,13 @@ impl CommandHandler {
             }
 
             // Command injection
+            "INJECTRAW" => {
+                if let (Some(target), Some(format), Some(payload)) = (args.first(), args.get(1), args.get(2)) {
+                    self.handle_inject_raw(target, format, payload).await
+                } else {
+                    Response::Error("INJECTRAW requires target, HEX or BASE64, and payload".to_string())
+                }
+            }
             "INJECT" => {
                 if let (Some(target), Some(_command)) = (args.get(0), args.get(1)) {
                     let cmd = args[1..].join(" ");
@@ -105,18 +196,85 @@ impl CommandHandler {
                     Response::Error("INJECT requires target and command".to_string())
                 }
             }
+            "WRITE" => {
+                if let (Some(target), Some(_text)) = (args.first(), args.get(1)) {
+                    let text = args[1..].join(" ");
+                    self.handle_write(target, &text).await
+                } else {
+                    Response::Error("WRITE requires target and text".to_string())
+                }
+            }
+            "KEY" => {
+                if let (Some(target), Some(key)) = (args.first(), args.get(1)) {
+                    self.handle_key(target, key).await
+                } else {
+                    Response::Error("KEY requires target and key name".to_string())
+                }
+            }
 
             // Screen capture
+            "CAPTURE" if args.get(1).map(|s| s.as_str()) == Some("FILE") => {
+                if let (Some(target), Some(path)) = (args.first(), args.get(2)) {
+                    let format = args.get(3).map(|s| s.to_uppercase()).unwrap_or_else(|| "TEXT".to_string());
+                    if format == "TEXT" || format == "ANSI" {
+                        self.handle_capture_file(target, path, &format).await
+                    } else {
+                        Response::Error(format!("Unknown capture format: {}", format))
+                    }
+                } else {
+                    Response::Error("CAPTURE FILE requires target and path".to_string())
+                }
+            }
             "CAPTURE" => {
-                if let Some(target) = args.get(0) {
-                    let mode = args.get(1).map(|s| s.as_str()).unwrap_or("FULL");
-                    self.handle_capture(target, mode).await
+                if let Some(target) = args.first() {
+                    // CAPTURE target [mode [args...]] [TEXT|ANSI]
+                    let mut rest: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+                    let format = match rest.last().map(|s| s.to_uppercase()) {
+                        Some(format) if format == "TEXT" || format == "ANSI" => {
+                            rest.pop();
+                            format
+                        }
+                        _ => "TEXT".to_string(),
+                    };
+                    if rest.is_empty() {
+                        rest.push("FULL");
+                    }
+
+                    match CaptureMode::parse(&rest) {
+                        Ok(mode) => self.handle_capture(target, mode, &format).await,
+                        Err(e) => Response::Error(format!("Invalid CAPTURE: {}", e)),
+                    }
                 } else {
                     Response::Error("CAPTURE requires target".to_string())
                 }
             }
 
-            // Queue operations
+            // Queue operations. A channel is a Redis list: PUBLISH and LPUSH
+            // push onto the head, RPOP takes the oldest message from the
+            // tail, LPOP the newest from the head, and LRANGE indexes from
+            // the head.
+            "LPUSH" => {
+                if let (Some(channel), Some(_message)) = (args.first(), args.get(1)) {
+                    let content = args[1..].join(" ");
+                    self.handle_lpush(channel, content).await
+                } else {
+                    Response::Error("LPUSH requires channel and message".to_string())
+                }
+            }
+            "LPOP" => {
+                if let Some(channel) = args.first() {
+                    self.handle_lpop(channel).await
+                } else {
+                    Response::Error("LPOP requires channel name".to_string())
+                }
+            }
+            "LRANGE" => {
+                match (args.first(), args.get(1).map(|n| n.parse::<i64>()), args.get(2).map(|n| n.parse::<i64>())) {
+                    (Some(channel), Some(Ok(start)), Some(Ok(stop))) => self.handle_lrange(channel, start, stop).await,
+                    (Some(_), Some(_), Some(_)) => Response::Error("Invalid LRANGE index".to_string()),
+                    _ => Response::Error("LRANGE requires channel, start and stop".to_string()),
+                }
+            }
             "LLEN" => {
                 if let Some(channel) = args.get(0) {
                     self.handle_llen(channel).await
@@ -132,16 +290,140 @@ impl CommandHandler {
                 }
             }
 
+            "SETMAXLEN" => {
+                match (args.first(), args.get(1).map(|n| n.parse::<usize>())) {
+                    (Some(_), Some(Ok(0))) => Response::Error("Max length must be at least 1".to_string()),
+                    (Some(channel), Some(Ok(max_len))) => self.handle_setmaxlen(channel, max_len).await,
+                    (Some(_), Some(Err(_))) => Response::Error("Invalid max length".to_string()),
+                    _ => Response::Error("SETMAXLEN requires channel and length".to_string()),
+                }
+            }
+            "SETTTL" => {
+                match (args.first(), args.get(1).map(|n| n.parse::<u64>())) {
+                    (Some(channel), Some(Ok(secs))) => self.handle_setttl(channel, secs).await,
+                    (Some(_), Some(Err(_))) => Response::Error("Invalid TTL".to_string()),
+                    _ => Response::Error("SETTTL requires channel and seconds".to_string()),
+                }
+            }
+            "DETACH" => self.handle_detach(conn_id).await,
+            "PING" => Response::Pong,
+            "PRPOP" => {
+                if let Some(pattern) = args.first() {
+                    self.handle_prpop(pattern, conn_id).await
+                } else {
+                    Response::Error("PRPOP requires pattern".to_string())
+                }
+            }
+
             _ => Response::Error(format!("Unknown command: {}", command)),
         }
     }
 
+    /// Remove the non-persistent sessions idle for longer than `timeout`,
+    /// tear down their channels and announce each id on `session-closed`
+    pub async fn reap_idle_sessions(&self, timeout: Duration) -> Vec<String> {
+        let removed = self.registry.write().await.remove_idle(timeout);
+        for session_id in &removed {
+            tracing::info!("Reaping session '{}' after {:?} idle", session_id, timeout);
+            self.channels
+                .remove_channels_with_prefix(&format!("{}/", session_id))
+                .await;
+            self.channels
+                .publish(SESSION_CLOSED_CHANNEL, session_id.clone())
+                .await;
+        }
+        removed
+    }
+
+    /// Run `reap_idle_sessions` every `interval` until the handler is dropped
+    pub fn spawn_idle_reaper(self: &Arc<Self>, timeout: Duration, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let handler = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(handler) = handler.upgrade() else {
+                    break;
+                };
+                handler.reap_idle_sessions(timeout).await;
+            }
+        })
+    }
+
+    async fn handle_detach(&self, conn_id: ConnectionId) -> Response {
+        self.channels.set_detached(conn_id).await;
+        Response::Ok
+    }
+
+    /// Clean up after a closed connection: unless it sent DETACH, remove the
+    /// non-persistent sessions and panes it created and tear down their
+    /// channels, then forget the client
+    pub async fn disconnect(&self, conn_id: ConnectionId) {
+        let detached = self
+            .channels
+            .client_info(conn_id)
+            .await
+            .is_some_and(|client| client.detached);
+
+        if !detached {
+            let removed = self.registry.write().await.remove_owned_by(conn_id);
+            for session_id in &removed.sessions {
+                self.channels
+                    .remove_channels_with_prefix(&format!("{}/", session_id))
+                    .await;
+            }
+            for (session_id, pane_id) in &removed.panes {
+                self.channels
+                    .remove_channels_with_prefix(&format!("{}/pane-{}/", session_id, pane_id))
+                    .await;
+            }
+        }
+
+        self.channels.unregister_client(conn_id).await;
+    }
+
     async fn handle_list_sessions(&self) -> Response {
         let registry = self.registry.read().await;
         let sessions = registry.list_sessions();
         Response::Array(sessions)
     }
 
+    async fn handle_list_sessions_verbose(&self) -> Response {
+        let registry = self.registry.read().await;
+        let mut ids = registry.list_sessions();
+        ids.sort();
+
+        let sessions: Vec<serde_json::Value> = ids
+            .iter()
+            .filter_map(|id| registry.get_session(id))
+            .map(|session| {
+                json!({
+                    "id": session.id,
+                    "name": session.name,
+                    "pane_count": session.panes.len(),
+                    "panes": session.panes,
+                    "owner": session.owner,
+                    "persistent": session.persistent,
+                    "created_at": session.created_unix,
+                    "last_activity": unix_now().saturating_sub(session.last_activity.elapsed().as_secs()),
+                })
+            })
+            .collect();
+        Response::Json(json!(sessions))
+    }
+
+    /// Record activity on the session (and pane) a target or channel name
+    /// refers to, e.g. `session-1/pane-a/output`
+    async fn touch(&self, path: &str) {
+        let mut parts = path.split('/');
+        let Some(session_id) = parts.next() else {
+            return;
+        };
+        let pane_id = parts.next().map(|pane| pane.strip_prefix("pane-").unwrap_or(pane));
+        self.registry.write().await.touch(session_id, pane_id);
+    }
+
     async fn handle_list_panes(&self, session_id: &str) -> Response {
         let registry = self.registry.read().await;
         match registry.list_panes(session_id) {
@@ -150,12 +432,60 @@ impl CommandHandler {
         }
     }
 
-    async fn handle_create_session(&self, name: Option<String>, pane_name: Option<String>) -> Response {
+    async fn handle_list_clients(&self, conn_id: ConnectionId) -> Response {
+        if !self.is_authenticated(conn_id).await {
+            return Response::Error("LIST CLIENTS requires an authenticated connection".to_string());
+        }
+
+        let mut clients = Vec::new();
+        for client in self.channels.list_clients().await {
+            clients.push(self.client_json(&client).await);
+        }
+        Response::Json(json!(clients))
+    }
+
+    async fn handle_client_info(&self, target: ConnectionId, conn_id: ConnectionId) -> Response {
+        if !self.is_authenticated(conn_id).await {
+            return Response::Error("CLIENT INFO requires an authenticated connection".to_string());
+        }
+
+        match self.channels.client_info(target).await {
+            Some(client) => Response::Json(self.client_json(&client).await),
+            None => Response::Error(format!("Client '{}' not found", target)),
+        }
+    }
+
+    async fn is_authenticated(&self, conn_id: ConnectionId) -> bool {
+        self.channels
+            .client_info(conn_id)
+            .await
+            .is_some_and(|client| client.authenticated)
+    }
+
+    async fn client_json(&self, client: &ClientInfo) -> serde_json::Value {
+        json!({
+            "id": client.id,
+            "addr": client.addr,
+            "authenticated": client.authenticated,
+            "subscriptions": self.channels.subscriptions(client.id).await,
+            "patterns": self.channels.pattern_subscriptions(client.id).await,
+            "connected_at": client.connected_at,
+            "connected_secs": client.connected_since.elapsed().as_secs(),
+        })
+    }
+
+    async fn handle_create_session(
+        &self,
+        name: Option<String>,
+        pane_name: Option<String>,
+        persistent: bool,
+        conn_id: ConnectionId,
+    ) -> Response {
         let mut registry = self.registry.write().await;
-        match registry.create_session(name) {
+        match registry.create_session_with_owner(name, Some(conn_id), persistent) {
             Ok(session_id) => {
                 // Create first pane
-                match registry.create_pane(&session_id, pane_name) {
+                match registry.create_pane_with_owner(&session_id, pane_name, Some(conn_id)) {
                     Ok(pane_id) => {
                         Response::OkWithData(format!("session-id:{} pane-id:{}", session_id, pane_id))
                     }
@@ -166,9 +496,9 @@ impl CommandHandler {
         }
     }
 
-    async fn handle_create_pane(&self, session_id: &str, name: Option<String>) -> Response {
+    async fn handle_create_pane(&self, session_id: &str, name: Option<String>, conn_id: ConnectionId) -> Response {
         let mut registry = self.registry.write().await;
-        match registry.create_pane(session_id, name) {
+        match registry.create_pane_with_owner(session_id, name, Some(conn_id)) {
             Ok(pane_id) => Response::OkWithData(format!("pane-id:{}", pane_id)),
             Err(e) => Response::Error(e),
         }
@@ -190,6 +520,87 @@ impl CommandHandler {
         }
     }
 
+    async fn handle_rename_session(&self, session_id: &str, name: &str) -> Response {
+        match self.registry.write().await.rename_session(session_id, name) {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        }
+    }
+
+    async fn handle_rename_pane(&self, session_id: &str, pane_id: &str, name: &str) -> Response {
+        match self.registry.write().await.rename_pane(session_id, pane_id, name) {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        }
+    }
+
+    /// Replace session and pane names in a command's arguments with the
+    /// ids handlers and channel names use. Channel arguments are already
+    /// ids and unknown names are left for the handler to report.
+    async fn resolve_names(&self, command: &str, mut args: Vec<String>) -> Vec<String> {
+        let registry = self.registry.read().await;
+        let resolve_session = |args: &mut Vec<String>| {
+            if let Some(id) = args.get(1).and_then(|name| registry.resolve_session(name)) {
+                args[1] = id.to_string();
+            }
+        };
+
+        match (command, args.first().map(String::as_str)) {
+            ("LIST", Some("PANES"))
+            | ("CREATE", Some("PANE"))
+            | ("CLOSE", Some("SESSION"))
+            | ("KILL", Some("PANE"))
+            | ("RENAME", Some("SESSION")) => resolve_session(&mut args),
+            ("CLOSE", Some("PANE")) | ("RENAME", Some("PANE")) => {
+                resolve_session(&mut args);
+                if let (Some(session_id), Some(name)) = (args.get(1), args.get(2)) {
+                    if let Some(id) = registry.resolve_pane(session_id, name) {
+                        args[2] = id.to_string();
+                    }
+                }
+            }
+            ("INJECT" | "INJECTRAW" | "WRITE" | "KEY" | "CAPTURE", Some(target)) => args[0] = resolve_target(&registry, target),
+            _ => {}
+        }
+        drop(registry);
+        args
+    }
+
+    /// Remove every session matching a glob pattern and tear down its
+    /// channels. Returns how many sessions were removed.
+    async fn handle_kill_sessions(&self, pattern: &str, conn_id: ConnectionId) -> Response {
+        if !self.is_authenticated(conn_id).await {
+            return Response::Error("KILL SESSION requires an authenticated connection".to_string());
+        }
+
+        let removed = self.registry.write().await.remove_sessions_matching(pattern);
+        for session_id in &removed {
+            self.channels
+                .remove_channels_with_prefix(&format!("{}/", session_id))
+                .await;
+        }
+        Response::Integer(removed.len() as i64)
+    }
+
+    /// Remove every pane of a session matching a glob pattern and tear down
+    /// its channels. Returns how many panes were removed.
+    async fn handle_kill_panes(&self, session_id: &str, pattern: &str, conn_id: ConnectionId) -> Response {
+        if !self.is_authenticated(conn_id).await {
+            return Response::Error("KILL PANE requires an authenticated connection".to_string());
+        }
+
+        let removed = match self.registry.write().await.remove_panes_matching(session_id, pattern) {
+            Ok(removed) => removed,
+            Err(e) => return Response::Error(e),
+        };
+        for pane_id in &removed {
+            self.channels
+                .remove_channels_with_prefix(&format!("{}/pane-{}/", session_id, pane_id))
+                .await;
+        }
+        Response::Integer(removed.len() as i64)
+    }
+
     async fn handle_subscribe(&self, channel: &str, conn_id: ConnectionId) -> Response {
         self.channels.subscribe(channel, conn_id).await;
         Response::Ok
@@ -200,7 +611,21 @@ impl CommandHandler {
         Response::Ok
     }
 
+    async fn handle_psubscribe(&self, pattern: &str, conn_id: ConnectionId) -> Response {
+        self.channels.psubscribe(pattern, conn_id).await;
+        Response::Ok
+    }
+
+    async fn handle_punsubscribe(&self, pattern: &str, conn_id: ConnectionId) -> Response {
+        self.channels.punsubscribe(pattern, conn_id).await;
+        Response::Ok
+    }
+
     async fn handle_publish(&self, channel: &str, content: &str) -> Response {
+        if is_forged_input(channel, content) {
+            return Response::Error(FORGED_INPUT_ERROR.to_string());
+        }
+        self.touch(channel).await;
         let count = self.channels.publish(channel, content.to_string()).await;
         Response::OkWithData(format!("published to {} subscribers", count))
     }
@@ -212,45 +637,206 @@ impl CommandHandler {
             return Response::Error("Invalid target format. Use: session-id/pane-id".to_string());
         }
 
+        self.touch(target).await;
         let channel = format!("{}/input", target);
-        let message = format!("{}\\n", command); // Auto-append newline
+        let message = format!("{}\r", command); // Press Enter after the command
 
         self.channels.publish(&channel, message).await;
         Response::Ok
     }
 
-    async fn handle_capture(&self, target: &str, mode: &str) -> Response {
-        // Request capture from terminal via channel
+    /// Write the decoded payload to a pane's input exactly, with no Enter
+    async fn handle_inject_raw(&self, target: &str, format: &str, payload: &str) -> Response {
+        if target.split('/').count() != 2 {
+            return Response::Error("Invalid target format. Use: session-id/pane-id".to_string());
+        }
+        let bytes = match decode_payload(format, payload) {
+            Ok(bytes) => bytes,
+            Err(e) => return Response::Error(e),
+        };
+
+        self.touch(target).await;
+        let channel = format!("{}/input", target);
+        self.channels.publish(&channel, raw_input_message(&bytes)).await;
+        Response::Ok
+    }
+
+    /// Draw `text` on a pane's screen, escape sequences included, without
+    /// it reaching the shell
+    async fn handle_write(&self, target: &str, text: &str) -> Response {
+        if target.split('/').count() != 2 {
+            return Response::Error("Invalid target format. Use: session-id/pane-id".to_string());
+        }
+
+        self.touch(target).await;
+        let channel = format!("{}/input", target);
+        self.channels.publish(&channel, display_message(text.as_bytes())).await;
+        Response::Ok
+    }
+
+    /// Send a named key or chord (`Enter`, `Up`, `Ctrl+C`) to a pane's input
+    async fn handle_key(&self, target: &str, key: &str) -> Response {
+        if target.split('/').count() != 2 {
+            return Response::Error("Invalid target format. Use: session-id/pane-id".to_string());
+        }
+        let Some(bytes) = keys::chord_sequence(key) else {
+            return Response::Error(format!("Unknown key '{}'", key));
+        };
+
+        self.touch(target).await;
+        let channel = format!("{}/input", target);
+        self.channels
+            .publish(&channel, String::from_utf8_lossy(&bytes).into_owned())
+            .await;
+        Response::Ok
+    }
+
+    async fn handle_capture(&self, target: &str, mode: CaptureMode, format: &str) -> Response {
+        // Request capture from terminal via channel, e.g.
+        // {"mode":"REGION","x":0,"y":2,"width":40,"height":5,"format":"TEXT"}
+        self.touch(target).await;
         let channel = format!("{}/capture-request", target);
-        let request = json!({
-            "mode": mode,
-        });
+        let mut request = serde_json::to_value(mode).unwrap_or_else(|_| json!({}));
+        request["format"] = json!(format);
 
         self.channels.publish(&channel, request.to_string()).await;
 
         // In real implementation, would wait for response on capture-response channel
         // For now, return placeholder
+        let mut response = request;
+        response["session"] = json!(target.split('/').next().unwrap_or(""));
+        response["pane"] = json!(target.split('/').nth(1).unwrap_or(""));
+        response["status"] = json!("requested");
+        Response::Json(response)
+    }
+
+    async fn handle_capture_file(&self, target: &str, path: &str, format: &str) -> Response {
+        // The terminal writes the file; it only accepts paths inside its
+        // configured capture directory
+        self.touch(target).await;
+        let channel = format!("{}/capture-request", target);
+        let request = json!({
+            "mode": "FILE",
+            "path": path,
+            "format": format,
+        });
+
+        self.channels.publish(&channel, request.to_string()).await;
+
         Response::Json(json!({
             "session": target.split('/').next().unwrap_or(""),
             "pane": target.split('/').nth(1).unwrap_or(""),
-            "mode": mode,
+            "mode": "FILE",
+            "path": path,
+            "format": format,
             "status": "requested"
         }))
     }
 
     async fn handle_llen(&self, channel: &str) -> Response {
         let len = self.channels.queue_length(channel).await;
-        Response::String(len.to_string())
+        Response::Integer(len as i64)
     }
 
     async fn handle_rpop(&self, channel: &str) -> Response {
+        self.touch(channel).await;
         match self.channels.pop_message(channel).await {
             Some(msg) => Response::String(msg.content),
-            None => Response::String("(nil)".to_string()),
+            None => Response::Nil,
+        }
+    }
+
+    async fn handle_lpush(&self, channel: &str, content: String) -> Response {
+        if is_forged_input(channel, &content) {
+            return Response::Error(FORGED_INPUT_ERROR.to_string());
+        }
+        self.touch(channel).await;
+        let len = self.channels.push_message(channel, content).await;
+        Response::Integer(len as i64)
+    }
+
+    async fn handle_lpop(&self, channel: &str) -> Response {
+        self.touch(channel).await;
+        match self.channels.pop_newest_message(channel).await {
+            Some(msg) => Response::String(msg.content),
+            None => Response::Nil,
+        }
+    }
+
+    async fn handle_lrange(&self, channel: &str, start: i64, stop: i64) -> Response {
+        Response::Array(self.channels.range_messages(channel, start, stop).await)
+    }
+
+    async fn handle_setmaxlen(&self, channel: &str, max_len: usize) -> Response {
+        self.channels.set_max_len(channel, max_len).await;
+        Response::Ok
+    }
+
+    async fn handle_setttl(&self, channel: &str, secs: u64) -> Response {
+        // 0 disables expiry
+        let ttl = (secs > 0).then(|| Duration::from_secs(secs));
+        self.channels.set_ttl(channel, ttl).await;
+        Response::Ok
+    }
+
+    async fn handle_prpop(&self, pattern: &str, conn_id: ConnectionId) -> Response {
+        match self.channels.pop_pattern_message(pattern, conn_id).await {
+            Some(msg) => Response::Json(json!({
+                "channel": msg.channel,
+                "message": msg.content,
+            })),
+            None => Response::Nil,
         }
     }
 }
 
+const FORGED_INPUT_ERROR: &str = "Input messages may not start with a server marker; use INJECTRAW or WRITE";
+
+/// Whether `content` for `channel` is a pane input message carrying a
+/// marker that only the server writes, see `INJECTRAW` and `WRITE`
+fn is_forged_input(channel: &str, content: &str) -> bool {
+    channel.ends_with("/input")
+        && [RAW_INPUT_MARKER, DISPLAY_MARKER]
+            .iter()
+            .any(|marker| content.starts_with(marker))
+}
+
+/// Session and pane a command acts on, from its `session-id/pane-id[/...]`
+/// target or channel (or the session id of `CREATE PANE` and `LIST PANES`)
+fn command_target<'a>(command: &str, args: &'a [String]) -> (Option<&'a str>, Option<&'a str>) {
+    let first = args.first().map(String::as_str);
+    if matches!((command, first), ("CREATE", Some("PANE")) | ("LIST", Some("PANES"))) {
+        return (args.get(1).map(String::as_str), None);
+    }
+
+    match first.filter(|arg| arg.contains('/')) {
+        Some(target) => {
+            let mut parts = target.split('/');
+            (parts.next(), parts.next())
+        }
+        None => (None, None),
+    }
+}
+
+/// `session[/pane-<pane>]` with names replaced by ids
+fn resolve_target(registry: &Registry, target: &str) -> String {
+    let (session, pane) = match target.split_once('/') {
+        Some((session, pane)) => (session, Some(pane)),
+        None => (target, None),
+    };
+    let Some(session_id) = registry.resolve_session(session) else {
+        return target.to_string();
+    };
+    match pane.map(|pane| (pane, pane.strip_prefix("pane-"))) {
+        Some((_, Some(name))) => {
+            let pane_id = registry.resolve_pane(session_id, name).unwrap_or(name);
+            format!("{}/pane-{}", session_id, pane_id)
+        }
+        Some((pane, None)) => format!("{}/{}", session_id, pane),
+        None => session_id.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -297,6 +883,49 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_disconnect_removes_owned_sessions() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels.clone());
+
+        channels.register_client(1, "127.0.0.1:5001".to_string()).await;
+        for args in [vec!["SESSION", "temp", "p1"], vec!["SESSION", "kept", "p1", "PERSISTENT"]] {
+            let args = args.into_iter().map(String::from).collect();
+            handler.handle_command("CREATE", args, 1).await;
+        }
+        channels.publish("temp/pane-p1/output", "hello".to_string()).await;
+
+        handler.disconnect(1).await;
+
+        assert_eq!(registry.read().await.list_sessions(), vec!["kept"]);
+        assert!(channels.list_channels().await.is_empty());
+        assert!(channels.client_info(1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let handler = CommandHandler::new(Arc::new(RwLock::new(Registry::new())), Arc::new(ChannelManager::new()));
+
+        let response = handler.handle_command("PING", vec![], 1).await;
+        assert_eq!(response.serialize(), "+PONG\n");
+    }
+
+    #[tokio::test]
+    async fn test_detach_keeps_sessions() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels.clone());
+
+        channels.register_client(1, "127.0.0.1:5001".to_string()).await;
+        handler.handle_command("CREATE", vec!["SESSION".to_string(), "temp".to_string()], 1).await;
+        assert!(matches!(handler.handle_command("DETACH", vec![], 1).await, Response::Ok));
+
+        handler.disconnect(1).await;
+
+        assert_eq!(registry.read().await.list_sessions(), vec!["temp"]);
+    }
+
     #[tokio::test]
     async fn test_subscribe_publish() {
         let registry = Arc::new(RwLock::new(Registry::new()));
@@ -320,4 +949,528 @@ mod tests {
             _ => panic!("Expected OkWithData response"),
         }
     }
+
+    #[tokio::test]
+    async fn test_list_sessions_verbose() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels);
+
+        let response = handler
+            .handle_command("CREATE", vec!["SESSION".to_string(), "work".to_string(), "main".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::OkWithData(_)));
+
+        let response = handler
+            .handle_command("LIST", vec!["SESSIONS".to_string(), "VERBOSE".to_string()], 1)
+            .await;
+        let sessions = match response {
+            Response::Json(serde_json::Value::Array(sessions)) => sessions,
+            other => panic!("Expected JSON array response, got {:?}", other),
+        };
+
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session["id"], "work");
+        assert_eq!(session["name"], "work");
+        assert_eq!(session["pane_count"], 1);
+        assert_eq!(session["panes"], json!(["main"]));
+        assert_eq!(session["owner"], 1);
+        assert_eq!(session["persistent"], false);
+        let created_at = session["created_at"].as_u64().unwrap();
+        assert!(created_at > 0);
+        assert!(session["last_activity"].as_u64().unwrap() >= created_at);
+
+        // The plain form is unchanged
+        let response = handler.handle_command("LIST", vec!["SESSIONS".to_string()], 1).await;
+        assert!(matches!(response, Response::Array(ref ids) if ids == &["work".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_are_reaped() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = Arc::new(CommandHandler::new(registry.clone(), channels.clone()));
+
+        {
+            let mut reg = registry.write().await;
+            reg.create_session(Some("idle".to_string())).unwrap();
+            reg.create_session_with_owner(Some("kept".to_string()), None, true).unwrap();
+        }
+        channels.publish("idle/pane-a/output", "hello".to_string()).await;
+
+        let _reaper = handler.spawn_idle_reaper(Duration::from_millis(50), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(registry.read().await.list_sessions(), vec!["kept".to_string()]);
+        assert_eq!(channels.list_channels().await, vec![SESSION_CLOSED_CHANNEL.to_string()]);
+        assert_eq!(
+            channels.pop_message(SESSION_CLOSED_CHANNEL).await.map(|m| m.content),
+            Some("idle".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_activity_defers_reaping() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels);
+
+        registry.write().await.create_session(Some("busy".to_string())).unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        handler
+            .handle_command("PUBLISH", vec!["busy/pane-a/output".to_string(), "x".to_string()], 1)
+            .await;
+
+        assert!(handler.reap_idle_sessions(Duration::from_millis(50)).await.is_empty());
+        assert_eq!(registry.read().await.list_sessions(), vec!["busy".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_key_publishes_sequence() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        for key in ["Up", "ctrl+c", "Enter"] {
+            let response = handler
+                .handle_command("KEY", vec!["s1/pane-p1".to_string(), key.to_string()], 1)
+                .await;
+            assert!(matches!(response, Response::Ok), "got {:?}", response);
+        }
+
+        let mut sent = Vec::new();
+        while let Some(message) = channels.pop_message("s1/pane-p1/input").await {
+            sent.push(message.content);
+        }
+        assert_eq!(sent, vec!["\x1b[A", "\x03", "\r"]);
+
+        let response = handler
+            .handle_command("KEY", vec!["s1/pane-p1".to_string(), "Hyper+x".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_inject_raw_publishes_exact_bytes() {
+        use crate::redititi_server::input_message_bytes;
+
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        for (format, payload) in [("HEX", "03"), ("base64", "G1tB")] {
+            let args = ["s1/pane-p1", format, payload].map(String::from).to_vec();
+            let response = handler.handle_command("INJECTRAW", args, 1).await;
+            assert!(matches!(response, Response::Ok), "got {:?}", response);
+        }
+
+        let mut sent = Vec::new();
+        while let Some(message) = channels.pop_message("s1/pane-p1/input").await {
+            sent.push(input_message_bytes(&message.content));
+        }
+        assert_eq!(sent, vec![b"\x03".to_vec(), b"\x1b[A".to_vec()]);
+
+        let args = ["s1/pane-p1", "HEX", "zz"].map(String::from).to_vec();
+        assert!(matches!(handler.handle_command("INJECTRAW", args, 1).await, Response::Error(_)));
+
+        // Shell text starting with "raw" is typed, not decoded
+        let args = ["s1/pane-p1", "raw", "hex", "03"].map(String::from).to_vec();
+        assert!(matches!(handler.handle_command("INJECT", args, 1).await, Response::Ok));
+        let message = channels.pop_message("s1/pane-p1/input").await.unwrap();
+        assert_eq!(input_message_bytes(&message.content), b"raw hex 03\r".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_publish_rejects_forged_input_markers() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        for command in ["PUBLISH", "LPUSH"] {
+            for content in ["\0raw:03", "\0display:41"] {
+                let args = ["s1/pane-p1/input", content].map(String::from).to_vec();
+                let response = handler.handle_command(command, args, 1).await;
+                assert!(matches!(response, Response::Error(_)), "{} got {:?}", command, response);
+            }
+        }
+        assert!(channels.pop_message("s1/pane-p1/input").await.is_none());
+
+        // Other channels carry whatever they are given
+        let args = ["s1/pane-p1/output", "\0raw:03"].map(String::from).to_vec();
+        assert!(!matches!(handler.handle_command("LPUSH", args, 1).await, Response::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rename_keeps_channels_on_ids() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels.clone());
+        {
+            let mut reg = registry.write().await;
+            reg.create_session(Some("s1".to_string())).unwrap();
+            reg.create_pane("s1", Some("p1".to_string())).unwrap();
+        }
+
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let response = handler.handle_command("RENAME", args(&["SESSION", "s1", "builds"]), 1).await;
+        assert!(matches!(response, Response::Ok), "got {:?}", response);
+        let response = handler.handle_command("RENAME", args(&["PANE", "builds", "p1", "server"]), 1).await;
+        assert!(matches!(response, Response::Ok), "got {:?}", response);
+
+        match handler.handle_command("LIST", args(&["SESSIONS"]), 1).await {
+            Response::Array(sessions) => assert_eq!(sessions, vec!["builds"]),
+            other => panic!("Expected Array response, got {:?}", other),
+        }
+        match handler.handle_command("LIST", args(&["PANES", "builds"]), 1).await {
+            Response::Array(panes) => assert_eq!(panes, vec!["server"]),
+            other => panic!("Expected Array response, got {:?}", other),
+        }
+
+        // Targets may use the new names; messages still go to the id's channel
+        let response = handler.handle_command("INJECT", args(&["builds/pane-server", "ls"]), 1).await;
+        assert!(matches!(response, Response::Ok), "got {:?}", response);
+        assert!(channels.pop_message("s1/pane-p1/input").await.is_some());
+
+        registry.write().await.create_session(Some("other".to_string())).unwrap();
+        let response = handler.handle_command("RENAME", args(&["SESSION", "other", "builds"]), 1).await;
+        assert!(matches!(response, Response::Error(_)), "got {:?}", response);
+    }
+
+    #[tokio::test]
+    async fn test_command_logs_carry_session_and_pane() {
+        #[derive(Clone, Default)]
+        struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let handler = CommandHandler::new(Arc::new(RwLock::new(Registry::new())), Arc::new(ChannelManager::new()));
+        let args = ["agents/pane-7", "ls"].map(String::from).to_vec();
+        handler.handle_command("INJECT", args, 42).await;
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let line = logs.lines().find(|line| line.contains("Command handled")).expect("no command event");
+        assert!(line.contains("command{"), "{}", line);
+        assert!(line.contains("conn_id=42"), "{}", line);
+        assert!(line.contains("session=\"agents\""), "{}", line);
+        assert!(line.contains("pane=\"pane-7\""), "{}", line);
+    }
+
+    #[test]
+    fn test_command_target() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(command_target("INJECT", &args(&["s1/pane-2", "ls"])), (Some("s1"), Some("pane-2")));
+        assert_eq!(command_target("RPOP", &args(&["s1/pane-2/output"])), (Some("s1"), Some("pane-2")));
+        assert_eq!(command_target("CREATE", &args(&["PANE", "s1"])), (Some("s1"), None));
+        assert_eq!(command_target("LIST", &args(&["SESSIONS"])), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_list_clients() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        // Two connected, authenticated clients with different subscriptions
+        for (conn_id, addr) in [(1, "127.0.0.1:5001"), (2, "127.0.0.1:5002")] {
+            channels.register_client(conn_id, addr.to_string()).await;
+            channels.set_authenticated(conn_id).await;
+        }
+        handler.handle_command("SUBSCRIBE", vec!["session-1/output".to_string()], 1).await;
+        handler.handle_command("SUBSCRIBE", vec!["session-1/input".to_string()], 2).await;
+        handler.handle_command("SUBSCRIBE", vec!["session-1/output".to_string()], 2).await;
+
+        let response = handler.handle_command("LIST", vec!["CLIENTS".to_string()], 1).await;
+        let clients = match response {
+            Response::Json(serde_json::Value::Array(clients)) => clients,
+            other => panic!("Expected JSON array response, got {:?}", other),
+        };
+
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0]["id"], 1);
+        assert_eq!(clients[0]["authenticated"], true);
+        assert_eq!(clients[0]["subscriptions"], json!(["session-1/output"]));
+        assert_eq!(clients[1]["id"], 2);
+        assert_eq!(
+            clients[1]["subscriptions"],
+            json!(["session-1/input", "session-1/output"])
+        );
+
+        let response = handler
+            .handle_command("CLIENT", vec!["INFO".to_string(), "2".to_string()], 1)
+            .await;
+        match response {
+            Response::Json(client) => {
+                assert_eq!(client["addr"], "127.0.0.1:5002");
+                assert!(client["connected_at"].as_u64().unwrap() > 0);
+            }
+            other => panic!("Expected JSON response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kill_sessions_by_pattern() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels.clone());
+
+        channels.register_client(1, "127.0.0.1:5001".to_string()).await;
+        channels.set_authenticated(1).await;
+        for name in ["test-1", "test-2", "prod-1"] {
+            registry.write().await.create_session(Some(name.to_string())).unwrap();
+            channels.publish(&format!("{}/pane-a/output", name), "hello".to_string()).await;
+        }
+
+        let response = handler
+            .handle_command("KILL", vec!["SESSION".to_string(), "test-*".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::Integer(2)), "got {:?}", response);
+
+        assert_eq!(registry.read().await.list_sessions(), vec!["prod-1".to_string()]);
+        assert_eq!(channels.list_channels().await, vec!["prod-1/pane-a/output".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_kill_panes_by_pattern() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels.clone());
+
+        channels.register_client(1, "127.0.0.1:5001".to_string()).await;
+        channels.set_authenticated(1).await;
+        {
+            let mut reg = registry.write().await;
+            reg.create_session(Some("work".to_string())).unwrap();
+            for name in ["build-1", "build-2", "editor"] {
+                reg.create_pane("work", Some(name.to_string())).unwrap();
+            }
+        }
+
+        let kill = |pattern: &str| vec!["PANE".to_string(), "work".to_string(), pattern.to_string()];
+        let response = handler.handle_command("KILL", kill("build-?"), 1).await;
+        assert!(matches!(response, Response::Integer(2)), "got {:?}", response);
+        assert_eq!(
+            registry.read().await.list_panes("work"),
+            Some(vec!["editor".to_string()])
+        );
+
+        // Glob characters are the only special ones: regex syntax matches nothing
+        let response = handler.handle_command("KILL", kill(".*"), 1).await;
+        assert!(matches!(response, Response::Integer(0)), "got {:?}", response);
+    }
+
+    #[tokio::test]
+    async fn test_kill_requires_authentication() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry.clone(), channels.clone());
+
+        channels.register_client(1, "127.0.0.1:5001".to_string()).await;
+        registry.write().await.create_session(Some("test-1".to_string())).unwrap();
+
+        let response = handler
+            .handle_command("KILL", vec!["SESSION".to_string(), "*".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::Error(_)));
+        assert_eq!(registry.read().await.list_sessions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_clients_requires_authentication() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        channels.register_client(1, "127.0.0.1:5001".to_string()).await;
+
+        let response = handler.handle_command("LIST", vec!["CLIENTS".to_string()], 1).await;
+        assert!(matches!(response, Response::Error(_)));
+
+        let response = handler
+            .handle_command("CLIENT", vec!["INFO".to_string(), "1".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_setmaxlen_clamps_queue() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels);
+
+        let response = handler
+            .handle_command("SETMAXLEN", vec!["capped".to_string(), "5".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::Ok));
+
+        for i in 0..8 {
+            handler
+                .handle_command("PUBLISH", vec!["capped".to_string(), format!("msg{}", i)], 1)
+                .await;
+        }
+
+        match handler.handle_command("LLEN", vec!["capped".to_string()], 1).await {
+            Response::Integer(len) => assert_eq!(len, 5),
+            other => panic!("Expected Integer response, got {:?}", other),
+        }
+
+        // The three oldest messages were dropped
+        match handler.handle_command("RPOP", vec!["capped".to_string()], 1).await {
+            Response::String(msg) => assert_eq!(msg, "msg3"),
+            other => panic!("Expected String response, got {:?}", other),
+        }
+
+        // A zero cap would drop every message, so it is refused
+        let response = handler
+            .handle_command("SETMAXLEN", vec!["capped".to_string(), "0".to_string()], 1)
+            .await;
+        assert!(matches!(response, Response::Error(_)));
+        handler
+            .handle_command("PUBLISH", vec!["capped".to_string(), "kept".to_string()], 1)
+            .await;
+        match handler.handle_command("LLEN", vec!["capped".to_string()], 1).await {
+            Response::Integer(len) => assert_eq!(len, 5),
+            other => panic!("Expected Integer response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_file_publishes_request() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        let args = ["s1/pane-p1", "FILE", "shot.txt", "ansi"].map(String::from).to_vec();
+        let response = handler.handle_command("CAPTURE", args, 1).await;
+        assert!(matches!(response, Response::Json(_)));
+
+        let request = channels.pop_message("s1/pane-p1/capture-request").await.unwrap();
+        let request: serde_json::Value = serde_json::from_str(&request.content).unwrap();
+        assert_eq!(request["mode"], "FILE");
+        assert_eq!(request["path"], "shot.txt");
+        assert_eq!(request["format"], "ANSI");
+    }
+
+    #[tokio::test]
+    async fn test_lrange_peeks_without_consuming() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels);
+        let args = |extra: &[&str]| {
+            std::iter::once("jobs").chain(extra.iter().copied()).map(String::from).collect::<Vec<_>>()
+        };
+
+        handler.handle_command("PUBLISH", args(&["one"]), 1).await;
+        handler.handle_command("PUBLISH", args(&["two"]), 1).await;
+        match handler.handle_command("LPUSH", args(&["three", "four"]), 1).await {
+            Response::Integer(len) => assert_eq!(len, 3),
+            other => panic!("Expected Integer response, got {:?}", other),
+        }
+
+        let all = vec!["three four".to_string(), "two".to_string(), "one".to_string()];
+        match handler.handle_command("LRANGE", args(&["0", "-1"]), 1).await {
+            Response::Array(messages) => assert_eq!(messages, all),
+            other => panic!("Expected Array response, got {:?}", other),
+        }
+        match handler.handle_command("LRANGE", args(&["-2", "10"]), 1).await {
+            Response::Array(messages) => assert_eq!(messages, all[1..]),
+            other => panic!("Expected Array response, got {:?}", other),
+        }
+        match handler.handle_command("LLEN", args(&[]), 1).await {
+            Response::Integer(len) => assert_eq!(len, 3),
+            other => panic!("Expected Integer response, got {:?}", other),
+        }
+
+        // RPOP still takes the oldest message, LPOP the newest
+        match handler.handle_command("RPOP", args(&[]), 1).await {
+            Response::String(msg) => assert_eq!(msg, "one"),
+            other => panic!("Expected String response, got {:?}", other),
+        }
+        match handler.handle_command("LPOP", args(&[]), 1).await {
+            Response::String(msg) => assert_eq!(msg, "three four"),
+            other => panic!("Expected String response, got {:?}", other),
+        }
+        assert!(matches!(handler.handle_command("LRANGE", args(&["0", "x"]), 1).await, Response::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_lpush_then_rpop_is_fifo() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+        let args = |extra: &[&str]| {
+            std::iter::once("jobs").chain(extra.iter().copied()).map(String::from).collect::<Vec<_>>()
+        };
+
+        channels.psubscribe("jo*", 7).await;
+        for job in ["a", "b", "c"] {
+            handler.handle_command("LPUSH", args(&[job]), 1).await;
+        }
+
+        // Pushed onto the head, so LRANGE lists newest first
+        match handler.handle_command("LRANGE", args(&["0", "-1"]), 1).await {
+            Response::Array(messages) => assert_eq!(messages, vec!["c", "b", "a"]),
+            other => panic!("Expected Array response, got {:?}", other),
+        }
+
+        // RPOP takes them in the order they were pushed, LPOP the newest
+        match handler.handle_command("RPOP", args(&[]), 1).await {
+            Response::String(msg) => assert_eq!(msg, "a"),
+            other => panic!("Expected String response, got {:?}", other),
+        }
+        match handler.handle_command("LPOP", args(&[]), 1).await {
+            Response::String(msg) => assert_eq!(msg, "c"),
+            other => panic!("Expected String response, got {:?}", other),
+        }
+        match handler.handle_command("RPOP", args(&[]), 1).await {
+            Response::String(msg) => assert_eq!(msg, "b"),
+            other => panic!("Expected String response, got {:?}", other),
+        }
+
+        // Pattern subscribers see LPUSHed messages like published ones
+        let mut delivered = Vec::new();
+        while let Some(msg) = channels.pop_pattern_message("jo*", 7).await {
+            delivered.push(msg.content);
+        }
+        assert_eq!(delivered, vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_capture_region_request() {
+        let registry = Arc::new(RwLock::new(Registry::new()));
+        let channels = Arc::new(ChannelManager::new());
+        let handler = CommandHandler::new(registry, channels.clone());
+
+        let args = ["s1/pane-p1", "REGION", "2", "1", "10", "3", "ANSI"].map(String::from).to_vec();
+        let response = handler.handle_command("CAPTURE", args, 1).await;
+        assert!(matches!(response, Response::Json(_)));
+
+        let request = channels.pop_message("s1/pane-p1/capture-request").await.unwrap();
+        let request: serde_json::Value = serde_json::from_str(&request.content).unwrap();
+        assert_eq!(request, json!({"mode": "REGION", "x": 2, "y": 1, "width": 10, "height": 3, "format": "ANSI"}));
+
+        // A bare CAPTURE still asks for everything, as plain text
+        handler.handle_command("CAPTURE", vec!["s1/pane-p1".to_string()], 1).await;
+        let request = channels.pop_message("s1/pane-p1/capture-request").await.unwrap();
+        assert_eq!(request.content, r#"{"format":"TEXT","mode":"FULL"}"#);
+
+        let args = ["s1/pane-p1", "REGION", "2", "1"].map(String::from).to_vec();
+        assert!(matches!(handler.handle_command("CAPTURE", args, 1).await, Response::Error(_)));
+    }
 }
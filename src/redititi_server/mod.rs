@@ -7,12 +7,17 @@ pub mod auth;
 pub mod channels;
 pub mod commands;
 pub mod protocol;
+pub mod rate_limit;
 pub mod registry;
 pub mod redititi_tcp_server;
 
 pub use auth::{TokenAuth, AuthError};
-pub use channels::{ChannelManager, Message};
+pub use channels::{ChannelManager, ClientInfo, Message, DEFAULT_MAX_QUEUE_LEN};
 pub use commands::CommandHandler;
-pub use protocol::{Protocol, Response};
-pub use registry::{Registry, SessionInfo, PaneInfo};
-pub use redititi_tcp_server::RedititiTcpServer;
+pub use protocol::{
+    display_message, display_message_bytes, input_message_bytes, raw_input_message, Encoding, Protocol, Response,
+    DISPLAY_MARKER, RAW_INPUT_MARKER,
+};
+pub use rate_limit::{RateLimit, RateLimits};
+pub use registry::{sequential_ids, IdGenerator, IdKind, Registry, SessionInfo, PaneInfo};
+pub use redititi_tcp_server::{RedititiTcpServer, ShutdownHandle};
@@ -1,8 +1,39 @@
 //! Simple Redis-like protocol parser
 //!
 //! Implements a simplified protocol for command parsing and response serialization.
+//! Clients speaking RESP2 (e.g. `redis-cli`) are also understood; see
+//! [`Protocol::read_resp_command`] and [`Response::serialize_resp`].
 
 use serde_json;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// Largest RESP bulk string accepted in a request
+pub const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Largest RESP bulk string accepted before a connection authenticates,
+/// enough for `AUTH <token>`
+pub const MAX_UNAUTHENTICATED_BULK_LEN: usize = 4096;
+
+/// Arguments preallocated for a RESP request; the `*<count>` header is
+/// client-controlled, so larger requests grow as their bulk strings arrive
+const RESP_PREALLOCATED_ARGS: usize = 64;
+
+/// Starts an input channel message carrying raw bytes (as hex) rather than
+/// text, see `INJECTRAW`
+pub const RAW_INPUT_MARKER: &str = "\0raw:";
+
+/// Starts an input channel message whose bytes (as hex) are drawn on the
+/// pane's screen instead of being written to its shell, see `WRITE`
+pub const DISPLAY_MARKER: &str = "\0display:";
+
+/// Wire format a connection speaks, detected from its first request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Bespoke line protocol (`+OK`, `-ERR`, quoted strings)
+    Line,
+    /// Redis serialization protocol, version 2
+    Resp,
+}
 
 #[derive(Debug, Clone)]
 pub enum Response {
@@ -10,23 +41,67 @@ pub enum Response {
     OkWithData(String),
     Error(String),
     String(String),
+    Integer(i64),
     Array(Vec<String>),
     Json(serde_json::Value),
+    /// Absent value, e.g. popping an empty queue
+    Nil,
+    /// Reply to `PING`
+    Pong,
 }
 
 impl Response {
+    /// Serialize response in the given wire format
+    pub fn encode(&self, encoding: Encoding) -> String {
+        match encoding {
+            Encoding::Line => self.serialize(),
+            Encoding::Resp => self.serialize_resp(),
+        }
+    }
+
+    /// Serialize response as RESP2
+    pub fn serialize_resp(&self) -> String {
+        fn bulk(s: &str) -> String {
+            format!("${}\r\n{}\r\n", s.len(), s)
+        }
+
+        match self {
+            Response::Ok => "+OK\r\n".to_string(),
+            // Simple strings can't contain line breaks
+            Response::OkWithData(data) => format!("+OK {}\r\n", data.replace(['\r', '\n'], " ")),
+            Response::Error(msg) => format!("-ERR {}\r\n", msg.replace(['\r', '\n'], " ")),
+            Response::String(s) => bulk(s),
+            Response::Integer(n) => format!(":{}\r\n", n),
+            Response::Array(arr) => {
+                let items: String = arr.iter().map(|s| bulk(s)).collect();
+                format!("*{}\r\n{}", arr.len(), items)
+            }
+            Response::Json(value) => bulk(&serde_json::to_string(value).unwrap()),
+            Response::Nil => "$-1\r\n".to_string(),
+            Response::Pong => "+PONG\r\n".to_string(),
+        }
+    }
+
     /// Serialize response to wire format
     pub fn serialize(&self) -> String {
         match self {
             Response::Ok => "+OK\n".to_string(),
             Response::OkWithData(data) => format!("+OK {}\n", data),
             Response::Error(msg) => format!("-ERR {}\n", msg),
-            Response::String(s) => format!("\"{}\"\n", s.replace('"', "\\\"")),
+            // A JSON string literal, so quotes, backslashes and line breaks
+            // in the message survive the line-based protocol
+            Response::String(s) => format!("{}\n", serde_json::to_string(s).unwrap()),
+            // Integers keep the quoted form legacy clients already parse
+            Response::Integer(n) => format!("\"{}\"\n", n),
+            // Items are JSON string literals, like `Response::String`
             Response::Array(arr) => {
-                let items: Vec<String> = arr.iter().map(|s| format!("\"{}\"", s)).collect();
+                let items: Vec<String> = arr.iter().map(|s| serde_json::to_string(s).unwrap()).collect();
                 format!("[{}]\n", items.join(", "))
             }
             Response::Json(value) => format!("{}\n", serde_json::to_string(value).unwrap()),
+            // Unquoted, so it can't be mistaken for a message reading "(nil)"
+            Response::Nil => "(nil)\n".to_string(),
+            Response::Pong => "+PONG\n".to_string(),
         }
     }
 }
@@ -52,16 +127,150 @@ impl Protocol {
         Ok((command, args))
     }
 
-    /// Parse a quoted string argument
+    /// Read the rest of a RESP2 request whose first line (`*<count>`) has
+    /// already been consumed, returning the command and arguments. Bulk
+    /// strings longer than `max_bulk_len` are rejected.
+    pub async fn read_resp_command<R: AsyncBufRead + Unpin>(
+        header: &str,
+        reader: &mut R,
+        max_bulk_len: usize,
+    ) -> Result<(String, Vec<String>), String> {
+        let count: usize = header
+            .trim_end()
+            .strip_prefix('*')
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| format!("Invalid RESP array header: {}", header.trim_end()))?;
+
+        let mut parts = Vec::with_capacity(count.min(RESP_PREALLOCATED_ARGS));
+        for _ in 0..count {
+            let mut line = String::new();
+            reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("Failed to read RESP bulk header: {}", e))?;
+
+            let len: usize = line
+                .trim_end()
+                .strip_prefix('$')
+                .and_then(|n| n.parse().ok())
+                .filter(|&n| n <= max_bulk_len)
+                .ok_or_else(|| format!("Invalid RESP bulk header: {}", line.trim_end()))?;
+
+            // Payload plus trailing CRLF, buffered as it arrives rather than
+            // allocated up front from the client's claimed length
+            let mut buf = Vec::new();
+            (&mut *reader)
+                .take(len as u64 + 2)
+                .read_to_end(&mut buf)
+                .await
+                .map_err(|e| format!("Failed to read RESP bulk string: {}", e))?;
+            if buf.len() < len + 2 {
+                return Err("RESP bulk string truncated".to_string());
+            }
+            if !buf.ends_with(b"\r\n") {
+                return Err("RESP bulk string missing CRLF terminator".to_string());
+            }
+            buf.truncate(len);
+
+            parts.push(String::from_utf8(buf).map_err(|_| "RESP argument is not valid UTF-8".to_string())?);
+        }
+
+        let mut parts = parts.into_iter();
+        let command = parts
+            .next()
+            .ok_or_else(|| "Empty command".to_string())?
+            .to_uppercase();
+
+        Ok((command, parts.collect()))
+    }
+
+    /// Decode a quoted string reply (see `Response::serialize`); anything
+    /// that isn't one is returned as is
     pub fn parse_quoted_string(s: &str) -> String {
-        if s.starts_with('"') && s.ends_with('"') {
-            s[1..s.len() - 1].replace("\\\"", "\"")
-        } else {
-            s.to_string()
+        if s.starts_with('"') {
+            if let Ok(decoded) = serde_json::from_str::<String>(s) {
+                return decoded;
+            }
         }
+        s.to_string()
     }
 }
 
+/// Input channel message that writes exactly `bytes` to the pane
+pub fn raw_input_message(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", RAW_INPUT_MARKER, hex)
+}
+
+/// Input channel message that draws `bytes` on the pane's screen as if the
+/// shell had printed them
+pub fn display_message(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", DISPLAY_MARKER, hex)
+}
+
+/// Bytes to draw on the pane's screen if `message` came from
+/// `display_message`; `None` for input meant for the shell
+pub fn display_message_bytes(message: &str) -> Option<Vec<u8>> {
+    message
+        .strip_prefix(DISPLAY_MARKER)
+        .and_then(|hex| decode_payload("HEX", hex).ok())
+}
+
+/// Bytes an input channel message writes to the pane: the decoded payload
+/// of a raw message, otherwise the text itself
+pub fn input_message_bytes(message: &str) -> Vec<u8> {
+    message
+        .strip_prefix(RAW_INPUT_MARKER)
+        .and_then(|hex| decode_payload("HEX", hex).ok())
+        .unwrap_or_else(|| message.as_bytes().to_vec())
+}
+
+/// Decode a `HEX` or `BASE64` (standard alphabet, padding optional) payload
+pub fn decode_payload(format: &str, payload: &str) -> Result<Vec<u8>, String> {
+    match format.to_uppercase().as_str() {
+        "HEX" => {
+            if !payload.len().is_multiple_of(2) || !payload.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(format!("Invalid hex payload: {}", payload));
+            }
+            Ok((0..payload.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&payload[i..i + 2], 16).unwrap())
+                .collect())
+        }
+        "BASE64" => decode_base64(payload).ok_or_else(|| format!("Invalid base64 payload: {}", payload)),
+        other => Err(format!("Unknown payload format: {} (use HEX or BASE64)", other)),
+    }
+}
+
+fn decode_base64(payload: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let digits = payload.trim_end_matches('=').as_bytes();
+    if digits.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let mut group = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            group |= value(c)? << (18 - 6 * i);
+        }
+        bytes.extend_from_slice(&group.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,4 +317,101 @@ mod tests {
         let response = Response::Array(arr);
         assert_eq!(response.serialize(), "[\"session1\", \"session2\"]\n");
     }
+
+    #[test]
+    fn test_resp_serialization() {
+        let arr = Response::Array(vec!["session1".to_string(), "pane-é".to_string()]);
+        assert_eq!(arr.serialize_resp(), "*2\r\n$8\r\nsession1\r\n$7\r\npane-é\r\n");
+
+        assert_eq!(Response::Ok.serialize_resp(), "+OK\r\n");
+        assert_eq!(Response::Error("failed".to_string()).serialize_resp(), "-ERR failed\r\n");
+        assert_eq!(Response::Integer(42).serialize_resp(), ":42\r\n");
+        assert_eq!(Response::Nil.serialize_resp(), "$-1\r\n");
+        assert_eq!(Response::String("hi".to_string()).serialize_resp(), "$2\r\nhi\r\n");
+        assert_eq!(Response::Pong.serialize_resp(), "+PONG\r\n");
+
+        // Legacy encoding is unchanged
+        assert_eq!(Response::Integer(42).encode(Encoding::Line), "\"42\"\n");
+        assert_eq!(Response::Nil.encode(Encoding::Line), "(nil)\n");
+    }
+
+    #[test]
+    fn test_line_strings_round_trip() {
+        for message in ["hello", "say \"hi\"", "C:\\dir\\", "ends in \\\"", "two\nlines\r", "\0raw:03", "\x1b[31m", "(nil)"] {
+            let line = Response::String(message.to_string()).serialize();
+            assert_eq!(line.matches('\n').count(), 1, "{:?} spans lines: {:?}", message, line);
+            assert_eq!(Protocol::parse_quoted_string(line.trim_end()), message);
+        }
+
+        // Array items are escaped the same way
+        let line = Response::Array(vec!["say \"hi\"".to_string(), "two\nlines".to_string()]).serialize();
+        assert_eq!(line, "[\"say \\\"hi\\\"\", \"two\\nlines\"]\n");
+        let items: Vec<String> = serde_json::from_str(line.trim_end()).unwrap();
+        assert_eq!(items, vec!["say \"hi\"", "two\nlines"]);
+
+        // A message reading "(nil)" is still a string, unlike nil itself
+        assert_ne!(Response::String("(nil)".to_string()).serialize(), Response::Nil.serialize());
+        assert_eq!(Protocol::parse_quoted_string("not quoted"), "not quoted");
+    }
+
+    #[tokio::test]
+    async fn test_resp_command_decoding() {
+        let request = b"*3\r\n$7\r\npublish\r\n$4\r\nchan\r\n$11\r\nhello world\r\n";
+        let mut reader = &request[..];
+
+        let mut header = String::new();
+        reader.read_line(&mut header).await.unwrap();
+        let (cmd, args) = Protocol::read_resp_command(&header, &mut reader, MAX_BULK_LEN).await.unwrap();
+
+        assert_eq!(cmd, "PUBLISH");
+        // Bulk strings keep embedded spaces intact
+        assert_eq!(args, vec!["chan", "hello world"]);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_raw_payloads() {
+        assert_eq!(decode_payload("hex", "031b5b41").unwrap(), vec![0x03, 0x1b, b'[', b'A']);
+        assert_eq!(decode_payload("BASE64", "Axtbqf8=").unwrap(), vec![0x03, 0x1b, b'[', 0xa9, 0xff]);
+        assert_eq!(decode_payload("BASE64", "aGk").unwrap(), b"hi".to_vec());
+        assert!(decode_payload("HEX", "3").is_err());
+        assert!(decode_payload("BASE64", "a?==").is_err());
+        assert!(decode_payload("OCTAL", "03").is_err());
+
+        let message = raw_input_message(&[0x03, b'\n', 0xff]);
+        assert_eq!(input_message_bytes(&message), vec![0x03, b'\n', 0xff]);
+        assert_eq!(input_message_bytes("ls\r"), b"ls\r".to_vec());
+    }
+
+    #[test]
+    fn test_display_message_round_trip() {
+        let message = display_message(b"\x1b[31mhi\x1b[0m");
+        assert_eq!(display_message_bytes(&message), Some(b"\x1b[31mhi\x1b[0m".to_vec()));
+
+        // Shell input isn't mistaken for display text
+        assert_eq!(display_message_bytes("ls\r"), None);
+        assert_eq!(display_message_bytes(&raw_input_message(b"ls")), None);
+    }
+
+    #[tokio::test]
+    async fn test_resp_command_rejects_malformed_input() {
+        let mut reader = &b"$3\r\nabcXY"[..];
+        assert!(Protocol::read_resp_command("*1\r\n", &mut reader, MAX_BULK_LEN).await.is_err());
+        assert!(Protocol::read_resp_command("*x\r\n", &mut &b""[..], MAX_BULK_LEN).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resp_command_limits_client_sizes() {
+        // A huge argument count doesn't preallocate; the request just ends early
+        let mut reader = &b"$4\r\nPING\r\n"[..];
+        assert!(Protocol::read_resp_command("*99999999999\r\n", &mut reader, MAX_BULK_LEN).await.is_err());
+
+        // Bulk strings over the limit are refused before their payload is read
+        let mut reader = &b"$5000\r\n"[..];
+        assert!(Protocol::read_resp_command("*1\r\n", &mut reader, MAX_UNAUTHENTICATED_BULK_LEN).await.is_err());
+
+        // A claimed length longer than what arrives is an error, not a hang
+        let mut reader = &b"$100\r\nshort\r\n"[..];
+        assert!(Protocol::read_resp_command("*1\r\n", &mut reader, MAX_BULK_LEN).await.is_err());
+    }
 }
@@ -0,0 +1,146 @@
+//! Per-connection rate limiting
+//!
+//! Token buckets throttling how fast a single connection can issue commands,
+//! with separate budgets for writes (PUBLISH, LPUSH, INJECT) and everything else.
+
+use std::time::Instant;
+
+/// Token bucket parameters: `burst` commands at once, refilled at `per_second`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// Limits applied to each connection; `None` means unlimited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    /// Commands that add data to the server (PUBLISH, LPUSH, INJECT)
+    pub write: Option<RateLimit>,
+    /// All other commands
+    pub read: Option<RateLimit>,
+}
+
+impl RateLimits {
+    pub fn unlimited() -> Self {
+        Self {
+            write: None,
+            read: None,
+        }
+    }
+}
+
+impl Default for RateLimits {
+    /// Generous enough that well-behaved automation never notices
+    fn default() -> Self {
+        Self {
+            write: Some(RateLimit::new(10_000, 5_000.0)),
+            read: Some(RateLimit::new(20_000, 10_000.0)),
+        }
+    }
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Rate limiter state for one connection
+pub struct RateLimiter {
+    write: Option<TokenBucket>,
+    read: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            write: limits.write.map(TokenBucket::new),
+            read: limits.read.map(TokenBucket::new),
+        }
+    }
+
+    /// Spend a token for `command`, returning false if the connection is
+    /// over its limit
+    pub fn check(&mut self, command: &str) -> bool {
+        let bucket = match command {
+            "PUBLISH" | "LPUSH" | "INJECT" | "INJECTRAW" | "WRITE" | "KEY" => &mut self.write,
+            _ => &mut self.read,
+        };
+
+        match bucket {
+            Some(bucket) => bucket.try_take(Instant::now()),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burst_then_reject() {
+        let mut limiter = RateLimiter::new(RateLimits {
+            write: Some(RateLimit::new(5, 1.0)),
+            read: None,
+        });
+
+        for _ in 0..5 {
+            assert!(limiter.check("PUBLISH"));
+        }
+        assert!(!limiter.check("INJECT"));
+        // Keystrokes reach the PTY too, so they share the write budget
+        assert!(!limiter.check("KEY"));
+
+        // Reads have their own (unlimited) budget
+        assert!(limiter.check("LLEN"));
+    }
+
+    #[test]
+    fn test_bucket_refills_over_time() {
+        let mut bucket = TokenBucket::new(RateLimit::new(2, 10.0));
+        let start = Instant::now();
+
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start));
+
+        // 10 tokens/s: one token back after 100ms, never more than the burst
+        assert!(bucket.try_take(start + Duration::from_millis(100)));
+        assert!(!bucket.try_take(start + Duration::from_millis(100)));
+        assert!(bucket.try_take(start + Duration::from_secs(10)));
+        assert!(bucket.try_take(start + Duration::from_secs(10)));
+        assert!(!bucket.try_take(start + Duration::from_secs(10)));
+    }
+}
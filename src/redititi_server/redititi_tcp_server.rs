@@ -5,22 +5,66 @@
 use super::auth::TokenAuth;
 use super::channels::{ChannelManager, ConnectionId};
 use super::commands::CommandHandler;
-use super::protocol::{Protocol, Response};
-use super::registry::Registry;
+use super::protocol::{Encoding, Protocol, Response, MAX_BULK_LEN, MAX_UNAUTHENTICATED_BULK_LEN};
+use super::rate_limit::{RateLimiter, RateLimits};
+use super::registry::{write_json_atomic, IdGenerator, Registry};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinSet;
+use tracing::Instrument;
 
 static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(1);
 
+/// How often expired channel messages are swept
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often idle sessions are looked for, when an idle timeout is set
+const IDLE_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often pending channel queues are saved when persistence is enabled
+const QUEUE_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Asks a running `RedititiTcpServer` to shut down; obtained from
+/// `RedititiTcpServer::shutdown_handle` before `run` takes the server away
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    signal: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    /// Stop accepting connections, let every connection finish the commands
+    /// it has already received, then close them. `run` returns once all of
+    /// them are gone and pending queues are saved.
+    pub fn shutdown(&self) {
+        self.signal.send_replace(true);
+    }
+}
+
 pub struct RedititiTcpServer {
     addr: String,
     auth: Arc<TokenAuth>,
-    _registry: Arc<RwLock<Registry>>,
+    registry: Arc<RwLock<Registry>>,
     channels: Arc<ChannelManager>,
     command_handler: Arc<CommandHandler>,
+    state_path: Option<PathBuf>,
+    rate_limits: RateLimits,
+    idle_timeout: Option<Duration>,
+    connection_timeout: Option<Duration>,
+    id_generator: Option<IdGenerator>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+/// Per-connection limits handed to each connection task
+struct ConnectionLimits {
+    rate_limiter: RateLimiter,
+    /// Close the connection after this long without a request
+    idle_timeout: Option<Duration>,
 }
 
 impl RedititiTcpServer {
@@ -33,33 +77,186 @@ impl RedititiTcpServer {
         Self {
             addr,
             auth,
-            _registry: registry,
+            registry,
             channels,
             command_handler,
+            state_path: None,
+            rate_limits: RateLimits::default(),
+            idle_timeout: None,
+            connection_timeout: None,
+            id_generator: None,
+            shutdown: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Cap every channel queue at `max_len` messages (oldest dropped first)
+    pub fn with_max_queue_len(self, max_len: usize) -> Self {
+        self.channels.set_default_max_len(max_len);
+        self
+    }
+
+    /// Expire queued messages older than `ttl`
+    pub fn with_message_ttl(self, ttl: Duration) -> Self {
+        self.channels.set_default_ttl(Some(ttl));
+        self
+    }
+
+    /// Throttle each connection with the given token buckets
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Remove non-persistent sessions that see no commands for `timeout`
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Close connections that send nothing for `timeout`, so half-open
+    /// connections from crashed peers don't linger. Clients stay connected
+    /// by sending `PING` more often than that.
+    pub fn with_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = Some(timeout);
+        self
+    }
+
+    /// Persist sessions and panes to `path` (and pending channel queues next
+    /// to it), restoring them when the server starts
+    pub fn with_persistence(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_path = Some(path.into());
+        self
+    }
+
+    /// Name unnamed sessions and panes with `generator` instead of random
+    /// memorable names, e.g. `sequential_ids()` for reproducible tests
+    pub fn with_id_generator(mut self, generator: IdGenerator) -> Self {
+        self.id_generator = Some(generator);
+        self
+    }
+
+    /// Handle for shutting the server down gracefully from another task
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            signal: self.shutdown.clone(),
         }
     }
 
+    /// Shut down gracefully; see `ShutdownHandle::shutdown`
+    pub fn shutdown(&self) {
+        self.shutdown_handle().shutdown();
+    }
+
     pub async fn run(&self) -> Result<(), std::io::Error> {
+        let _saver = match &self.state_path {
+            Some(path) => Some(self.restore_state(path).await?),
+            None => None,
+        };
+        if let Some(generator) = &self.id_generator {
+            self.registry.write().await.set_id_generator(generator.clone());
+        }
+
         let listener = TcpListener::bind(&self.addr).await?;
-        log::info!("Server listening on {}", self.addr);
-        log::info!("Token: {}", self.auth.token());
-        log::info!("Token file: {:?}", self.auth.token_path());
+        let _sweep = self.channels.spawn_expiry_sweep(EXPIRY_SWEEP_INTERVAL);
+        let _reaper = self.idle_timeout.map(|timeout| {
+            self.command_handler
+                .spawn_idle_reaper(timeout, timeout.min(IDLE_SWEEP_INTERVAL))
+        });
+        tracing::info!("Server listening on {}", self.addr);
+        tracing::info!("Token: {}", self.auth.token());
+        tracing::info!("Token file: {:?}", self.auth.token_path());
 
+        let mut shutdown = self.shutdown.subscribe();
+        let mut connections = JoinSet::new();
         loop {
-            let (socket, addr) = listener.accept().await?;
-            log::debug!("New connection from: {}", addr);
+            let (socket, addr) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = shutdown.wait_for(|&stop| stop) => break,
+            };
+            tracing::debug!("New connection from: {}", addr);
+
+            // Forget connections that already ended
+            while connections.try_join_next().is_some() {}
 
             let conn_id = NEXT_CONN_ID.fetch_add(1, Ordering::SeqCst);
+            self.channels.register_client(conn_id, addr.to_string()).await;
             let auth = self.auth.clone();
             let command_handler = self.command_handler.clone();
             let channels = self.channels.clone();
+            let limits = ConnectionLimits {
+                rate_limiter: RateLimiter::new(self.rate_limits),
+                idle_timeout: self.connection_timeout,
+            };
+            let shutdown = self.shutdown.subscribe();
+
+            // Everything logged for this connection carries its id and address
+            let span = tracing::info_span!("connection", conn_id, %addr);
+            connections.spawn(
+                async move {
+                    if let Err(e) = Self::handle_connection(socket, conn_id, auth, command_handler.clone(), channels, limits, shutdown).await {
+                        tracing::error!("Connection {} error: {}", conn_id, e);
+                    }
 
-            tokio::spawn(async move {
-                if let Err(e) = Self::handle_connection(socket, conn_id, auth, command_handler, channels).await {
-                    log::error!("Connection {} error: {}", conn_id, e);
+                    // Cleanup runs however the connection ended: drop the
+                    // client's subscriptions and, unless it detached, the
+                    // sessions and panes it created
+                    command_handler.disconnect(conn_id).await;
+                    tracing::debug!("Connection {} cleaned up", conn_id);
                 }
-            });
+                .instrument(span),
+            );
+        }
+
+        drop(listener);
+        tracing::info!("Shutting down, draining {} connections", connections.len());
+        while connections.join_next().await.is_some() {}
+
+        if let Some(path) = &self.state_path {
+            let queue_path = path.with_extension("queues.json");
+            if let Err(e) = write_json_atomic(&queue_path, &self.channels.queue_snapshot().await) {
+                tracing::error!("Failed to save channel queues to {:?}: {}", queue_path, e);
+            }
+        }
+        tracing::info!("Server stopped");
+        Ok(())
+    }
+
+    /// Reload the registry and channel queues from disk, then start saving
+    /// queues periodically
+    async fn restore_state(&self, path: &Path) -> Result<tokio::task::JoinHandle<()>, std::io::Error> {
+        let registry = Registry::with_persistence(path)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        *self.registry.write().await = registry;
+
+        let queue_path = path.with_extension("queues.json");
+        if queue_path.exists() {
+            let content = std::fs::read_to_string(&queue_path)?;
+            match serde_json::from_str::<HashMap<String, Vec<String>>>(&content) {
+                Ok(queues) => self.channels.restore_queues(queues).await,
+                Err(e) => tracing::error!("Ignoring invalid queue file {:?}: {}", queue_path, e),
+            }
         }
+
+        let channels = Arc::downgrade(&self.channels);
+        Ok(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(QUEUE_SAVE_INTERVAL);
+            let mut last_saved = None;
+            loop {
+                ticker.tick().await;
+                let Some(channels) = channels.upgrade() else {
+                    break;
+                };
+
+                let snapshot = channels.queue_snapshot().await;
+                if last_saved.as_ref() == Some(&snapshot) {
+                    continue;
+                }
+                match write_json_atomic(&queue_path, &snapshot) {
+                    Ok(()) => last_saved = Some(snapshot),
+                    Err(e) => tracing::error!("Failed to save channel queues to {:?}: {}", queue_path, e),
+                }
+            }
+        }))
     }
 
     async fn handle_connection(
@@ -68,29 +265,75 @@ impl RedititiTcpServer {
         auth: Arc<TokenAuth>,
         command_handler: Arc<CommandHandler>,
         channels: Arc<ChannelManager>,
+        limits: ConnectionLimits,
+        mut shutdown: watch::Receiver<bool>,
     ) -> Result<(), std::io::Error> {
-        let (reader, mut writer) = socket.into_split();
+        let ConnectionLimits {
+            mut rate_limiter,
+            idle_timeout,
+        } = limits;
+        let (reader, writer) = socket.into_split();
         let mut reader = BufReader::new(reader);
+        // Clients may pipeline commands without waiting for replies. Those
+        // are answered in order, and replies are only flushed once every
+        // command already received has been handled.
+        let mut writer = BufWriter::new(writer);
         let mut authenticated = false;
         let mut auth_attempts = 0;
+        let mut conn_encoding = None;
         const MAX_AUTH_ATTEMPTS: u32 = 3;
 
         loop {
+            if reader.buffer().is_empty() {
+                writer.flush().await?;
+            }
+
+            // On shutdown, commands already received are still answered
+            let drained = reader.buffer().is_empty();
             let mut line = String::new();
-            let bytes_read = reader.read_line(&mut line).await?;
+            let bytes_read = tokio::select! {
+                biased;
+                _ = shutdown.wait_for(|&stop| stop), if drained => {
+                    tracing::debug!("Connection {} closed by server shutdown", conn_id);
+                    break;
+                }
+                read = reader.read_line(&mut line) => read?,
+                _ = Self::idle(idle_timeout) => {
+                    tracing::info!("Connection {} idle for {:?}, closing", conn_id, idle_timeout.unwrap_or_default());
+                    break;
+                }
+            };
 
             if bytes_read == 0 {
                 // Connection closed
-                log::debug!("Connection {} closed", conn_id);
+                tracing::debug!("Connection {} closed", conn_id);
                 break;
             }
 
+            // The first request decides which protocol this connection speaks
+            let is_resp = line.starts_with('*');
+            let encoding = *conn_encoding.get_or_insert(if is_resp {
+                Encoding::Resp
+            } else {
+                Encoding::Line
+            });
+
             // Parse command
-            let (command, args) = match Protocol::parse_command(&line) {
+            let parsed = if is_resp {
+                let max_bulk_len = if authenticated { MAX_BULK_LEN } else { MAX_UNAUTHENTICATED_BULK_LEN };
+                Protocol::read_resp_command(&line, &mut reader, max_bulk_len).await
+            } else {
+                Protocol::parse_command(&line)
+            };
+            let (command, args) = match parsed {
                 Ok(parsed) => parsed,
                 Err(e) => {
                     let response = Response::Error(e);
-                    writer.write_all(response.serialize().as_bytes()).await?;
+                    writer.write_all(response.encode(encoding).as_bytes()).await?;
+                    if is_resp {
+                        // A malformed RESP frame leaves the stream out of sync
+                        break;
+                    }
                     continue;
                 }
             };
@@ -101,40 +344,59 @@ impl RedititiTcpServer {
                     if let Some(token) = args.get(0) {
                         if auth.validate(token) {
                             authenticated = true;
+                            channels.set_authenticated(conn_id).await;
                             let response = Response::Ok;
-                            writer.write_all(response.serialize().as_bytes()).await?;
-                            log::info!("Connection {} authenticated", conn_id);
+                            writer.write_all(response.encode(encoding).as_bytes()).await?;
+                            tracing::info!("Connection {} authenticated", conn_id);
                         } else {
                             auth_attempts += 1;
                             let response = Response::Error("Invalid token".to_string());
-                            writer.write_all(response.serialize().as_bytes()).await?;
+                            writer.write_all(response.encode(encoding).as_bytes()).await?;
 
                             if auth_attempts >= MAX_AUTH_ATTEMPTS {
-                                log::warn!("Connection {} exceeded auth attempts", conn_id);
+                                tracing::warn!("Connection {} exceeded auth attempts", conn_id);
                                 break;
                             }
                         }
                     } else {
                         let response = Response::Error("AUTH requires token".to_string());
-                        writer.write_all(response.serialize().as_bytes()).await?;
+                        writer.write_all(response.encode(encoding).as_bytes()).await?;
                     }
                 } else {
                     let response = Response::Error("Not authenticated. Use AUTH command first".to_string());
-                    writer.write_all(response.serialize().as_bytes()).await?;
+                    writer.write_all(response.encode(encoding).as_bytes()).await?;
                 }
                 continue;
             }
 
             // Handle authenticated commands
+            if !rate_limiter.check(&command) {
+                tracing::debug!("Connection {} rate limited on {}", conn_id, command);
+                let response = Response::Error("rate limited".to_string());
+                writer.write_all(response.encode(encoding).as_bytes()).await?;
+                continue;
+            }
+
+            let detach = command == "DETACH";
             let response = command_handler.handle_command(&command, args, conn_id).await;
-            writer.write_all(response.serialize().as_bytes()).await?;
+            writer.write_all(response.encode(encoding).as_bytes()).await?;
+
+            if detach {
+                tracing::debug!("Connection {} detached", conn_id);
+                break;
+            }
         }
 
-        // Cleanup: unsubscribe from all channels
-        channels.unsubscribe_all(conn_id).await;
-        log::debug!("Connection {} cleaned up", conn_id);
+        // Flush outstanding replies and close our side politely
+        writer.shutdown().await
+    }
 
-        Ok(())
+    /// Completes after `timeout`, or never without one
+    async fn idle(timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
     }
 }
 
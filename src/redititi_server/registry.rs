@@ -1,28 +1,156 @@
 //! Session and pane registry
 //!
 //! Manages the registry of active sessions and panes with random name generation.
+//! Optionally persists itself to a JSON file so sessions survive a restart.
 
+use super::channels::{glob_match, ConnectionId};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
 pub struct SessionInfo {
+    /// Stable identifier; channel names are built from it
     pub id: String,
+    /// Name shown in listings, the id until the session is renamed
+    pub name: String,
     pub created_at: Instant,
+    /// Unix timestamp (seconds) of when the session was created
+    pub created_unix: u64,
+    /// Last command or message that targeted the session or one of its
+    /// panes; idle sessions are reaped by `remove_idle`
+    pub last_activity: Instant,
     pub panes: Vec<String>,
+    /// Connection that created the session; it is removed when that
+    /// connection closes unless `persistent`
+    pub owner: Option<ConnectionId>,
+    pub persistent: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct PaneInfo {
+    /// As on `SessionInfo`
     pub id: String,
+    pub name: String,
     pub session_id: String,
     pub terminal_connected: bool,
+    pub owner: Option<ConnectionId>,
+    /// As on `SessionInfo`
+    pub created_unix: u64,
+    pub last_activity: Instant,
+}
+
+/// What an id is being generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    Session,
+    Pane,
+}
+
+/// Source of ids for sessions and panes created without a name. Ids that
+/// are already taken are skipped and another one is asked for, so a
+/// generator must not return the same id forever.
+pub type IdGenerator = Arc<dyn Fn(IdKind) -> String + Send + Sync>;
+
+/// Generator numbering sessions `session-1`, `session-2`, ... and panes
+/// `1`, `2`, ... in creation order, for ids that tests can predict
+pub fn sequential_ids() -> IdGenerator {
+    let sessions = AtomicUsize::new(0);
+    let panes = AtomicUsize::new(0);
+    Arc::new(move |kind| match kind {
+        IdKind::Session => format!("session-{}", sessions.fetch_add(1, Ordering::Relaxed) + 1),
+        IdKind::Pane => (panes.fetch_add(1, Ordering::Relaxed) + 1).to_string(),
+    })
+}
+
+/// Sessions and panes removed by `Registry::remove_owned_by`
+#[derive(Debug, Default, PartialEq)]
+pub struct RemovedEntries {
+    pub sessions: Vec<String>,
+    /// (session_id, pane_id) of panes removed from sessions that remain
+    pub panes: Vec<(String, String)>,
+}
+
+/// On-disk form of the registry
+///
+/// Connection ids don't survive a restart, so owners aren't saved. Sessions
+/// and panes that would have been removed along with their connection are
+/// left out; everything else comes back unowned.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistrySnapshot {
+    sessions: Vec<SessionSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionSnapshot {
+    id: String,
+    panes: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    persistent: bool,
+    /// Name, if the session was renamed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Names of renamed panes, by pane id
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pane_names: HashMap<String, String>,
+}
+
+/// Writes registry snapshots to disk on its own thread, so callers holding
+/// the registry lock never wait on the filesystem. Pending writes are
+/// flushed when it is dropped.
+struct Persister {
+    tx: Option<mpsc::Sender<RegistrySnapshot>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Persister {
+    fn spawn(path: PathBuf) -> Self {
+        let (tx, rx) = mpsc::channel::<RegistrySnapshot>();
+        let thread = std::thread::spawn(move || {
+            while let Ok(mut snapshot) = rx.recv() {
+                // Only the latest of a burst of changes needs writing
+                while let Ok(newer) = rx.try_recv() {
+                    snapshot = newer;
+                }
+                if let Err(e) = write_json_atomic(&path, &snapshot) {
+                    log::error!("Failed to persist registry to {:?}: {}", path, e);
+                }
+            }
+        });
+
+        Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, snapshot: RegistrySnapshot) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(snapshot);
+        }
+    }
+}
+
+impl Drop for Persister {
+    fn drop(&mut self) {
+        self.tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
 }
 
 pub struct Registry {
     sessions: HashMap<String, SessionInfo>,
     panes: HashMap<(String, String), PaneInfo>, // (session_id, pane_id) -> PaneInfo
+    persister: Option<Persister>,
+    /// Ids for unnamed sessions and panes; memorable random names if unset
+    id_generator: Option<IdGenerator>,
 }
 
 impl Registry {
@@ -30,24 +158,115 @@ impl Registry {
         Self {
             sessions: HashMap::new(),
             panes: HashMap::new(),
+            persister: None,
+            id_generator: None,
+        }
+    }
+
+    /// Use `generator` for the ids of sessions and panes created without a
+    /// name, e.g. `sequential_ids()` in tests
+    pub fn with_id_generator(mut self, generator: IdGenerator) -> Self {
+        self.set_id_generator(generator);
+        self
+    }
+
+    pub fn set_id_generator(&mut self, generator: IdGenerator) {
+        self.id_generator = Some(generator);
+    }
+
+    /// A candidate id for an unnamed session or pane
+    fn next_id(&self, kind: IdKind) -> String {
+        match &self.id_generator {
+            Some(generator) => generator(kind),
+            None => Self::generate_memorable_name(),
+        }
+    }
+
+    /// Create a registry backed by `path`, reloading any sessions saved there
+    ///
+    /// Every change is written back to the file in the background. Restored
+    /// sessions get a fresh `created_at`, keep `persistent`, have no owner,
+    /// and their panes start disconnected.
+    pub fn with_persistence(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let mut registry = Self::new();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read registry file {:?}: {}", path, e))?;
+            let snapshot: RegistrySnapshot = serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid registry file {:?}: {}", path, e))?;
+
+            for session in snapshot.sessions {
+                registry.create_session_with_owner(Some(session.id.clone()), None, session.persistent)?;
+                for pane in session.panes {
+                    registry.create_pane(&session.id, Some(pane))?;
+                }
+                if let Some(name) = session.name {
+                    registry.rename_session(&session.id, &name)?;
+                }
+                for (pane, name) in session.pane_names {
+                    registry.rename_pane(&session.id, &pane, &name)?;
+                }
+            }
+            log::info!("Restored {} sessions from {:?}", registry.sessions.len(), path);
         }
+
+        registry.persister = Some(Persister::spawn(path));
+        Ok(registry)
+    }
+
+    /// Queue the registry for writing to its backing file, if persistence
+    /// is enabled
+    fn persist(&self) {
+        let Some(persister) = &self.persister else {
+            return;
+        };
+
+        // What `remove_owned_by` would reap once its connection is gone
+        let dies_with_owner = |owner: Option<ConnectionId>, persistent: bool| owner.is_some() && !persistent;
+        let mut sessions: Vec<SessionSnapshot> = self
+            .sessions
+            .values()
+            .filter(|s| !dies_with_owner(s.owner, s.persistent))
+            .map(|s| {
+                let panes: Vec<&PaneInfo> = self
+                    .panes_of(&s.id)
+                    .filter(|p| !dies_with_owner(p.owner, s.persistent))
+                    .collect();
+                SessionSnapshot {
+                    id: s.id.clone(),
+                    panes: s.panes.iter().filter(|id| panes.iter().any(|p| &p.id == *id)).cloned().collect(),
+                    persistent: s.persistent,
+                    name: (s.name != s.id).then(|| s.name.clone()),
+                    pane_names: panes
+                        .iter()
+                        .filter(|p| p.name != p.id)
+                        .map(|p| (p.id.clone(), p.name.clone()))
+                        .collect(),
+                }
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+
+        persister.send(RegistrySnapshot { sessions });
     }
 
-    /// Generate a unique random session name
+    /// Generate a unique session name
     pub fn generate_session_name(&self) -> String {
         loop {
-            let name = Self::generate_memorable_name();
-            if !self.sessions.contains_key(&name) {
+            let name = self.next_id(IdKind::Session);
+            if !self.session_name_taken(&name) {
                 return name;
             }
         }
     }
 
-    /// Generate a unique random pane name within a session
+    /// Generate a unique pane name within a session
     pub fn generate_pane_name(&self, session_id: &str) -> String {
         loop {
-            let name = Self::generate_memorable_name();
-            if !self.panes.contains_key(&(session_id.to_string(), name.clone())) {
+            let name = self.next_id(IdKind::Pane);
+            if !self.pane_name_taken(session_id, &name) {
                 return name;
             }
         }
@@ -55,9 +274,19 @@ impl Registry {
 
     /// Create a new session
     pub fn create_session(&mut self, name: Option<String>) -> Result<String, String> {
+        self.create_session_with_owner(name, None, false)
+    }
+
+    /// Create a new session owned by a connection
+    pub fn create_session_with_owner(
+        &mut self,
+        name: Option<String>,
+        owner: Option<ConnectionId>,
+        persistent: bool,
+    ) -> Result<String, String> {
         let session_id = name.unwrap_or_else(|| self.generate_session_name());
 
-        if self.sessions.contains_key(&session_id) {
+        if self.session_name_taken(&session_id) {
             return Err(format!("Session '{}' already exists", session_id));
         }
 
@@ -65,10 +294,17 @@ impl Registry {
             session_id.clone(),
             SessionInfo {
                 id: session_id.clone(),
+                name: session_id.clone(),
                 created_at: Instant::now(),
+                created_unix: unix_now(),
+                last_activity: Instant::now(),
                 panes: Vec::new(),
+                owner,
+                persistent,
             },
         );
+        crate::metrics::METRICS.set_active_sessions(self.sessions.len());
+        self.persist();
 
         Ok(session_id)
     }
@@ -78,6 +314,16 @@ impl Registry {
         &mut self,
         session_id: &str,
         name: Option<String>,
+    ) -> Result<String, String> {
+        self.create_pane_with_owner(session_id, name, None)
+    }
+
+    /// Create a new pane in a session, owned by a connection
+    pub fn create_pane_with_owner(
+        &mut self,
+        session_id: &str,
+        name: Option<String>,
+        owner: Option<ConnectionId>,
     ) -> Result<String, String> {
         if !self.sessions.contains_key(session_id) {
             return Err(format!("Session '{}' not found", session_id));
@@ -85,36 +331,110 @@ impl Registry {
 
         let pane_id = name.unwrap_or_else(|| self.generate_pane_name(session_id));
 
-        let key = (session_id.to_string(), pane_id.clone());
-        if self.panes.contains_key(&key) {
+        if self.pane_name_taken(session_id, &pane_id) {
             return Err(format!("Pane '{}' already exists in session '{}'", pane_id, session_id));
         }
 
         self.panes.insert(
-            key,
+            (session_id.to_string(), pane_id.clone()),
             PaneInfo {
                 id: pane_id.clone(),
+                name: pane_id.clone(),
                 session_id: session_id.to_string(),
                 terminal_connected: false,
+                owner,
+                created_unix: unix_now(),
+                last_activity: Instant::now(),
             },
         );
 
         // Add pane to session's pane list
         if let Some(session) = self.sessions.get_mut(session_id) {
             session.panes.push(pane_id.clone());
+            session.last_activity = Instant::now();
         }
+        self.persist();
 
         Ok(pane_id)
     }
 
-    /// List all sessions
+    /// Rename a session. Its id, and so its channel names, stay the same.
+    pub fn rename_session(&mut self, session_id: &str, name: &str) -> Result<(), String> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(format!("Session '{}' not found", session_id));
+        }
+        if self.sessions.values().any(|s| s.id != session_id && (s.id == name || s.name == name)) {
+            return Err(format!("Session '{}' already exists", name));
+        }
+
+        let session = self.sessions.get_mut(session_id).unwrap();
+        session.name = name.to_string();
+        session.last_activity = Instant::now();
+        self.persist();
+        Ok(())
+    }
+
+    /// Rename a pane within its session, keeping its id
+    pub fn rename_pane(&mut self, session_id: &str, pane_id: &str, name: &str) -> Result<(), String> {
+        let key = (session_id.to_string(), pane_id.to_string());
+        if !self.panes.contains_key(&key) {
+            return Err(format!("Pane '{}' not found in session '{}'", pane_id, session_id));
+        }
+        if self.panes_of(session_id).any(|p| p.id != pane_id && (p.id == name || p.name == name)) {
+            return Err(format!("Pane '{}' already exists in session '{}'", name, session_id));
+        }
+
+        self.panes.get_mut(&key).unwrap().name = name.to_string();
+        self.touch(session_id, Some(pane_id));
+        self.persist();
+        Ok(())
+    }
+
+    /// Id of the session with id or name `name`; ids win over names
+    pub fn resolve_session(&self, name: &str) -> Option<&str> {
+        match self.sessions.get(name) {
+            Some(session) => Some(&session.id),
+            None => self.sessions.values().find(|s| s.name == name).map(|s| s.id.as_str()),
+        }
+    }
+
+    /// Id of the pane with id or name `name` in a session
+    pub fn resolve_pane(&self, session_id: &str, name: &str) -> Option<&str> {
+        match self.get_pane(session_id, name) {
+            Some(pane) => Some(&pane.id),
+            None => self.panes_of(session_id).find(|p| p.name == name).map(|p| p.id.as_str()),
+        }
+    }
+
+    /// Whether `name` is the id or name of a session
+    fn session_name_taken(&self, name: &str) -> bool {
+        self.resolve_session(name).is_some()
+    }
+
+    /// Whether `name` is the id or name of a pane in the session
+    fn pane_name_taken(&self, session_id: &str, name: &str) -> bool {
+        self.resolve_pane(session_id, name).is_some()
+    }
+
+    fn panes_of(&self, session_id: &str) -> impl Iterator<Item = &PaneInfo> {
+        let session_id = session_id.to_string();
+        self.panes.values().filter(move |p| p.session_id == session_id)
+    }
+
+    /// Names of all sessions
     pub fn list_sessions(&self) -> Vec<String> {
-        self.sessions.keys().cloned().collect()
+        self.sessions.values().map(|s| s.name.clone()).collect()
     }
 
-    /// List all panes in a session
+    /// Names of the panes in a session, in creation order
     pub fn list_panes(&self, session_id: &str) -> Option<Vec<String>> {
-        self.sessions.get(session_id).map(|s| s.panes.clone())
+        let session = self.sessions.get(session_id)?;
+        let names = session
+            .panes
+            .iter()
+            .map(|id| self.get_pane(session_id, id).map_or_else(|| id.clone(), |p| p.name.clone()))
+            .collect();
+        Some(names)
     }
 
     /// Get session info
@@ -122,6 +442,20 @@ impl Registry {
         self.sessions.get(session_id)
     }
 
+    /// Record activity on a session and, if given, one of its panes.
+    /// Unknown ids are ignored.
+    pub fn touch(&mut self, session_id: &str, pane_id: Option<&str>) {
+        let now = Instant::now();
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            session.last_activity = now;
+        }
+        if let Some(pane_id) = pane_id {
+            if let Some(pane) = self.panes.get_mut(&(session_id.to_string(), pane_id.to_string())) {
+                pane.last_activity = now;
+            }
+        }
+    }
+
     /// Get pane info
     pub fn get_pane(&self, session_id: &str, pane_id: &str) -> Option<&PaneInfo> {
         self.panes.get(&(session_id.to_string(), pane_id.to_string()))
@@ -140,11 +474,48 @@ impl Registry {
         // Remove pane from session's pane list
         if let Some(session) = self.sessions.get_mut(session_id) {
             session.panes.retain(|p| p != pane_id);
+            session.last_activity = Instant::now();
         }
+        self.persist();
 
         Ok(())
     }
 
+    /// Remove the non-persistent sessions and panes created by a connection
+    pub fn remove_owned_by(&mut self, conn_id: ConnectionId) -> RemovedEntries {
+        let mut removed = RemovedEntries::default();
+
+        let mut sessions: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|s| s.owner == Some(conn_id) && !s.persistent)
+            .map(|s| s.id.clone())
+            .collect();
+        sessions.sort();
+        for session_id in sessions {
+            if self.remove_session(&session_id).is_ok() {
+                removed.sessions.push(session_id);
+            }
+        }
+
+        // Panes the connection added to sessions it didn't own
+        let mut panes: Vec<(String, String)> = self
+            .panes
+            .iter()
+            .filter(|(_, p)| p.owner == Some(conn_id))
+            .filter(|((session_id, _), _)| self.sessions.get(session_id).is_some_and(|s| !s.persistent))
+            .map(|(key, _)| key.clone())
+            .collect();
+        panes.sort();
+        for (session_id, pane_id) in panes {
+            if self.remove_pane(&session_id, &pane_id).is_ok() {
+                removed.panes.push((session_id, pane_id));
+            }
+        }
+
+        removed
+    }
+
     /// Remove a session and all its panes
     pub fn remove_session(&mut self, session_id: &str) -> Result<(), String> {
         if !self.sessions.contains_key(session_id) {
@@ -159,10 +530,56 @@ impl Registry {
 
         // Remove session
         self.sessions.remove(session_id);
+        crate::metrics::METRICS.set_active_sessions(self.sessions.len());
+        self.persist();
 
         Ok(())
     }
 
+    /// Remove every session whose id or name matches a glob pattern (`*` and
+    /// `?`, anchored at both ends). Returns the removed session ids, sorted.
+    pub fn remove_sessions_matching(&mut self, pattern: &str) -> Vec<String> {
+        let mut matching: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|s| glob_match(pattern, &s.id) || glob_match(pattern, &s.name))
+            .map(|s| s.id.clone())
+            .collect();
+        matching.sort();
+        matching.retain(|id| self.remove_session(id).is_ok());
+        matching
+    }
+
+    /// Remove every pane of a session whose id or name matches a glob
+    /// pattern. Returns the removed pane ids, sorted.
+    pub fn remove_panes_matching(&mut self, session_id: &str, pattern: &str) -> Result<Vec<String>, String> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(format!("Session '{}' not found", session_id));
+        }
+        let mut matching: Vec<String> = self
+            .panes_of(session_id)
+            .filter(|p| glob_match(pattern, &p.id) || glob_match(pattern, &p.name))
+            .map(|p| p.id.clone())
+            .collect();
+        matching.sort();
+        matching.retain(|id| self.remove_pane(session_id, id).is_ok());
+        Ok(matching)
+    }
+
+    /// Remove the non-persistent sessions with no activity for longer than
+    /// `timeout`. Returns the removed session ids, sorted.
+    pub fn remove_idle(&mut self, timeout: Duration) -> Vec<String> {
+        let mut idle: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|s| !s.persistent && s.last_activity.elapsed() > timeout)
+            .map(|s| s.id.clone())
+            .collect();
+        idle.sort();
+        idle.retain(|id| self.remove_session(id).is_ok());
+        idle
+    }
+
     /// Generate a memorable name (adjective-color+digit, max 10 chars)
     fn generate_memorable_name() -> String {
         const ADJECTIVES: &[&str] = &[
@@ -185,6 +602,23 @@ impl Registry {
     }
 }
 
+/// Serialize `value` to `path` via a temporary file, so a crash mid-write
+/// never leaves a truncated file behind
+pub(crate) fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("tmp");
+
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,6 +670,19 @@ mod tests {
         assert!(pane_id.len() <= 15, "Pane name too long: {}", pane_id);
     }
 
+    #[test]
+    fn test_sequential_id_generator() {
+        let mut registry = Registry::new().with_id_generator(sequential_ids());
+
+        assert_eq!(registry.create_session(None).unwrap(), "session-1");
+        assert_eq!(registry.create_pane("session-1", None).unwrap(), "1");
+        assert_eq!(registry.create_session(None).unwrap(), "session-2");
+
+        // Ids already taken by name are skipped
+        registry.create_session(Some("session-3".to_string())).unwrap();
+        assert_eq!(registry.create_session(None).unwrap(), "session-4");
+    }
+
     #[test]
     fn test_remove_pane() {
         let mut registry = Registry::new();
@@ -261,4 +708,127 @@ mod tests {
         assert!(registry.list_sessions().is_empty());
         assert!(registry.get_pane(&session_id, "pane1").is_none());
     }
+
+    #[test]
+    fn test_rename_session() {
+        let mut registry = Registry::new();
+        registry.create_session(Some("scratch".to_string())).unwrap();
+        registry.create_pane("scratch", Some("pane1".to_string())).unwrap();
+        registry.create_session(Some("other".to_string())).unwrap();
+
+        registry.rename_session("scratch", "builds").unwrap();
+
+        let mut sessions = registry.list_sessions();
+        sessions.sort();
+        assert_eq!(sessions, vec!["builds", "other"]);
+        // The id, which channel names are built from, is unchanged
+        assert_eq!(registry.resolve_session("builds"), Some("scratch"));
+        assert_eq!(registry.get_session("scratch").unwrap().name, "builds");
+
+        // Names can't collide with another session's name or id
+        assert!(registry.rename_session("scratch", "other").is_err());
+        assert!(registry.create_session(Some("builds".to_string())).is_err());
+        assert!(registry.rename_session("missing", "x").is_err());
+    }
+
+    #[test]
+    fn test_rename_pane() {
+        let mut registry = Registry::new();
+        registry.create_session(Some("work".to_string())).unwrap();
+        registry.create_pane("work", Some("pane1".to_string())).unwrap();
+        registry.create_pane("work", Some("pane2".to_string())).unwrap();
+
+        registry.rename_pane("work", "pane1", "server").unwrap();
+
+        assert_eq!(registry.list_panes("work").unwrap(), vec!["server", "pane2"]);
+        assert_eq!(registry.resolve_pane("work", "server"), Some("pane1"));
+        assert!(registry.rename_pane("work", "pane2", "server").is_err());
+        assert!(registry.rename_pane("work", "pane3", "logs").is_err());
+    }
+
+    #[test]
+    fn test_remove_owned_by() {
+        let mut registry = Registry::new();
+
+        registry.create_session_with_owner(Some("owned".to_string()), Some(1), false).unwrap();
+        registry.create_session_with_owner(Some("kept".to_string()), Some(1), true).unwrap();
+        registry.create_session_with_owner(Some("other".to_string()), Some(2), false).unwrap();
+        registry.create_pane_with_owner("other", Some("guest".to_string()), Some(1)).unwrap();
+        registry.create_pane_with_owner("other", Some("host".to_string()), Some(2)).unwrap();
+        registry.create_pane_with_owner("kept", Some("pane1".to_string()), Some(1)).unwrap();
+
+        let removed = registry.remove_owned_by(1);
+
+        assert_eq!(removed.sessions, vec!["owned"]);
+        assert_eq!(removed.panes, vec![("other".to_string(), "guest".to_string())]);
+        let mut sessions = registry.list_sessions();
+        sessions.sort();
+        assert_eq!(sessions, vec!["kept", "other"]);
+        assert_eq!(registry.list_panes("other").unwrap(), vec!["host"]);
+        assert_eq!(registry.list_panes("kept").unwrap(), vec!["pane1"]);
+    }
+
+    #[test]
+    fn test_persisted_sessions_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("titi-registry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("registry.json");
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let mut registry = Registry::with_persistence(&path).unwrap();
+            registry.create_session(Some("alpha".to_string())).unwrap();
+            registry.create_pane("alpha", Some("pane1".to_string())).unwrap();
+            registry.create_pane("alpha", Some("pane2".to_string())).unwrap();
+            registry.create_session(Some("beta".to_string())).unwrap();
+            registry.create_session(Some("gamma".to_string())).unwrap();
+            registry.remove_session("gamma").unwrap();
+            registry.remove_pane("alpha", "pane2").unwrap();
+            registry.rename_session("beta", "renamed").unwrap();
+        }
+
+        let registry = Registry::with_persistence(&path).unwrap();
+        let mut sessions = registry.list_sessions();
+        sessions.sort();
+        assert_eq!(sessions, vec!["alpha", "renamed"]);
+        assert_eq!(registry.resolve_session("renamed"), Some("beta"));
+        assert_eq!(registry.list_panes("alpha").unwrap(), vec!["pane1"]);
+        assert!(registry.get_pane("alpha", "pane1").is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_persisted_sessions_keep_lifetime() {
+        let dir = std::env::temp_dir().join(format!("titi-registry-owned-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("registry.json");
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let mut registry = Registry::with_persistence(&path).unwrap();
+            registry.create_session_with_owner(Some("kept".to_string()), Some(1), true).unwrap();
+            registry.create_pane_with_owner("kept", Some("pane1".to_string()), Some(1)).unwrap();
+            registry.create_session_with_owner(Some("owned".to_string()), Some(1), false).unwrap();
+            registry.create_session(Some("shared".to_string())).unwrap();
+            registry.create_pane_with_owner("shared", Some("guest".to_string()), Some(2)).unwrap();
+        }
+
+        let mut registry = Registry::with_persistence(&path).unwrap();
+        let mut sessions = registry.list_sessions();
+        sessions.sort();
+        // Whatever belonged to a connection that no longer exists is gone
+        assert_eq!(sessions, vec!["kept", "shared"]);
+        assert!(registry.list_panes("shared").unwrap().is_empty());
+
+        let kept = registry.get_session("kept").unwrap();
+        assert!(kept.persistent);
+        assert_eq!(kept.owner, None);
+        assert_eq!(registry.list_panes("kept").unwrap(), vec!["pane1"]);
+        // Idle expiry still spares the persistent session
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(registry.remove_idle(Duration::from_millis(1)), vec!["shared"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
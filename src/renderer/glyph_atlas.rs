@@ -18,6 +18,9 @@ pub struct GlyphInfo {
     pub advance: f32,
 }
 
+/// Side of the opaque block reserved at the atlas origin for solid quads
+const SOLID_BLOCK: u32 = 2;
+
 pub struct GlyphAtlas {
     texture: Texture,
     atlas_width: u32,
@@ -25,6 +28,7 @@ pub struct GlyphAtlas {
     current_x: u32,
     current_y: u32,
     row_height: u32,
+    solid_uploaded: bool,
     glyph_cache: HashMap<GlyphKey, GlyphInfo>,
     font_system: FontSystem,
     swash_cache: SwashCache,
@@ -55,9 +59,10 @@ impl GlyphAtlas {
             texture,
             atlas_width,
             atlas_height,
-            current_x: 0,
+            current_x: SOLID_BLOCK,
             current_y: 0,
-            row_height: 0,
+            row_height: SOLID_BLOCK,
+            solid_uploaded: false,
             glyph_cache: HashMap::new(),
             font_system: FontSystem::new(),
             swash_cache: SwashCache::new(),
@@ -239,6 +244,60 @@ impl GlyphAtlas {
         Some((rasterized_data, (max_width, max_height, glyph_advance)))
     }
 
+    /// Switch to a new font size, dropping every cached glyph
+    ///
+    /// The texture is reused (so existing bind groups stay valid); glyphs are
+    /// re-rasterized into it on demand.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size;
+        self.glyph_cache.clear();
+        // The solid block at the origin stays in place
+        self.current_x = SOLID_BLOCK;
+        self.current_y = 0;
+        self.row_height = SOLID_BLOCK;
+    }
+
+    /// Texture coordinates of a fully opaque texel, for untextured quads
+    /// (backgrounds, decorations, borders). Uploads it on first use.
+    pub fn solid_tex_coords(&mut self, queue: &Queue) -> [f32; 2] {
+        if !self.solid_uploaded {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &[255; (SOLID_BLOCK * SOLID_BLOCK) as usize],
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(SOLID_BLOCK),
+                    rows_per_image: Some(SOLID_BLOCK),
+                },
+                Extent3d {
+                    width: SOLID_BLOCK,
+                    height: SOLID_BLOCK,
+                    depth_or_array_layers: 1,
+                },
+            );
+            self.solid_uploaded = true;
+        }
+
+        // Center of the block, so linear filtering only sees opaque texels
+        [
+            (SOLID_BLOCK / 2) as f32 / self.atlas_width as f32,
+            (SOLID_BLOCK / 2) as f32 / self.atlas_height as f32,
+        ]
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    pub fn cached_glyph_count(&self) -> usize {
+        self.glyph_cache.len()
+    }
+
     pub fn texture(&self) -> &Texture {
         &self.texture
     }
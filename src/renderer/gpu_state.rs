@@ -1,8 +1,12 @@
 use std::sync::Arc;
 use winit::window::Window;
 
+/// Texture format of offscreen render targets
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
 pub struct GpuState {
-    pub surface: wgpu::Surface<'static>,
+    /// Window surface; `None` when rendering offscreen
+    pub surface: Option<wgpu::Surface<'static>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -10,7 +14,10 @@ pub struct GpuState {
 }
 
 impl GpuState {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
+    /// Set up `window`'s surface. With `transparent` the surface composites
+    /// its alpha with what's behind the window, if the platform supports
+    /// that; otherwise it stays opaque (see `is_transparent`).
+    pub async fn new(window: Arc<Window>, transparent: bool) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -55,15 +62,18 @@ impl GpuState {
             width: size.width,
             height: size.height,
             present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: surface_caps.alpha_modes[0],
+            alpha_mode: choose_alpha_mode(&surface_caps.alpha_modes, transparent),
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
 
+        if transparent && config.alpha_mode == wgpu::CompositeAlphaMode::Opaque {
+            log::warn!("Window transparency isn't supported here, drawing an opaque background");
+        }
         surface.configure(&device, &config);
 
         Ok(Self {
-            surface,
+            surface: Some(surface),
             device,
             queue,
             config,
@@ -71,10 +81,114 @@ impl GpuState {
         })
     }
 
+    /// Create a device without a window for rendering into textures of
+    /// `width` x `height`. Any backend will do, including software ones
+    /// (Lavapipe, llvmpipe, WARP) on machines without a GPU or display.
+    pub async fn new_offscreen(width: u32, height: u32) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let mut adapter = None;
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::LowPower,
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter.ok_or_else(|| anyhow::anyhow!("Failed to find an adapter for offscreen rendering"))?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await?;
+
+        // Not applied to any surface; records the target's format and size.
+        // Texture targets keep whatever alpha is drawn, unpremultiplied.
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: OFFSCREEN_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::PostMultiplied,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        Ok(Self {
+            surface: None,
+            device,
+            queue,
+            config,
+            size: winit::dpi::PhysicalSize::new(width, height),
+        })
+    }
+
+    /// Whether the alpha of drawn frames shows through to what's behind them
+    pub fn is_transparent(&self) -> bool {
+        matches!(
+            self.config.alpha_mode,
+            wgpu::CompositeAlphaMode::PreMultiplied | wgpu::CompositeAlphaMode::PostMultiplied
+        )
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.config.width = new_size.width;
         self.config.height = new_size.height;
-        self.surface.configure(&self.device, &self.config);
+        self.reconfigure();
+    }
+
+    /// Apply the current configuration to the window surface again, e.g.
+    /// after it was lost
+    pub fn reconfigure(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+}
+
+/// Alpha mode for a surface supporting `supported` modes: one that blends
+/// with the desktop when `transparent` is asked for and available, opaque
+/// otherwise
+pub fn choose_alpha_mode(supported: &[wgpu::CompositeAlphaMode], transparent: bool) -> wgpu::CompositeAlphaMode {
+    use wgpu::CompositeAlphaMode::{Opaque, PostMultiplied, PreMultiplied};
+
+    let preferred: &[_] = if transparent { &[PreMultiplied, PostMultiplied, Opaque] } else { &[Opaque] };
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(supported[0])
+}
+
+/// Run `acquire`; if the surface was lost or went out of date (display
+/// sleep, driver reset), `reconfigure` it and try exactly once more. Other
+/// errors, including running out of memory, are returned untouched.
+pub fn retry_after_reconfigure<T>(
+    mut acquire: impl FnMut() -> Result<T, wgpu::SurfaceError>,
+    reconfigure: impl FnOnce(),
+) -> Result<T, wgpu::SurfaceError> {
+    match acquire() {
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+            reconfigure();
+            acquire()
+        }
+        result => result,
     }
 }
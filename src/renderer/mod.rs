@@ -3,10 +3,11 @@ mod gpu_state;
 pub mod glyph_atlas;
 pub mod vertex;
 
-pub use text_renderer::TextRenderer;
-pub use gpu_state::GpuState;
+pub use text_renderer::{TextRenderer, MAX_FONT_SIZE, MIN_FONT_SIZE};
+pub use gpu_state::{choose_alpha_mode, retry_after_reconfigure, GpuState, OFFSCREEN_FORMAT};
 pub use glyph_atlas::GlyphAtlas;
 
+use crate::metrics::{FrameTimer, METRICS};
 use crate::terminal::Grid;
 use crate::ui::PaneManager;
 use crate::Config;
@@ -15,6 +16,17 @@ use std::sync::{Arc, Mutex};
 pub struct Renderer {
     gpu_state: GpuState,
     text_renderer: TextRenderer,
+    base_font_size: f32,
+    background: [f32; 4],
+    /// Background opacity in effect; 1.0 unless the target is transparent
+    opacity: f32,
+    pane_title_bars: bool,
+    /// Lines shown in a box over the panes, e.g. a paste confirmation
+    overlay: Option<Vec<String>>,
+    /// Grab area of the split divider under the mouse
+    divider_highlight: Option<[f32; 4]>,
+    /// Texture frames are drawn into when there's no window
+    offscreen: Option<wgpu::Texture>,
 }
 
 impl Renderer {
@@ -22,20 +34,158 @@ impl Renderer {
         window: Arc<winit::window::Window>,
         config: &Config,
     ) -> anyhow::Result<Self> {
-        let gpu_state = GpuState::new(window).await?;
+        let gpu_state = GpuState::new(window, config.window.opacity < 1.0).await?;
         let text_renderer = TextRenderer::new(&gpu_state, config)?;
 
-        Ok(Self {
+        let mut renderer = Self {
             gpu_state,
             text_renderer,
+            base_font_size: config.font.size,
+            background: config.colors.background,
+            opacity: 1.0,
+            pane_title_bars: config.pane_title_bars,
+            overlay: None,
+            divider_highlight: None,
+            offscreen: None,
+        };
+        renderer.set_opacity(config.window.opacity);
+        Ok(renderer)
+    }
+
+    /// Renderer drawing into a `width` x `height` texture instead of a
+    /// window, for tests and headless captures. Frames are read back with
+    /// `read_pixels`.
+    pub async fn new_offscreen(width: u32, height: u32, config: &Config) -> anyhow::Result<Self> {
+        let gpu_state = GpuState::new_offscreen(width, height).await?;
+        let text_renderer = TextRenderer::new(&gpu_state, config)?;
+        let offscreen = Self::create_offscreen_target(&gpu_state);
+
+        let mut renderer = Self {
+            gpu_state,
+            text_renderer,
+            base_font_size: config.font.size,
+            background: config.colors.background,
+            opacity: 1.0,
+            pane_title_bars: config.pane_title_bars,
+            overlay: None,
+            divider_highlight: None,
+            offscreen: Some(offscreen),
+        };
+        renderer.set_opacity(config.window.opacity);
+        Ok(renderer)
+    }
+
+    fn create_offscreen_target(gpu_state: &GpuState) -> wgpu::Texture {
+        gpu_state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: gpu_state.size.width,
+                height: gpu_state.size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OFFSCREEN_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
         })
     }
 
-    pub fn render(&mut self, grid: &Arc<Mutex<Grid>>) -> anyhow::Result<()> {
-        let output = self.gpu_state.surface.get_current_texture()?;
+    /// View of the texture to draw the next frame into, plus the surface
+    /// texture to present afterwards when drawing to a window. Fails with
+    /// the `wgpu::SurfaceError` when no texture can be had even after
+    /// reconfiguring the surface.
+    fn acquire_frame(&self) -> anyhow::Result<(Option<wgpu::SurfaceTexture>, wgpu::TextureView)> {
+        if let Some(texture) = &self.offscreen {
+            return Ok((None, texture.create_view(&wgpu::TextureViewDescriptor::default())));
+        }
+
+        let surface = self
+            .gpu_state
+            .surface
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Renderer has neither a surface nor an offscreen target"))?;
+        let output = retry_after_reconfigure(
+            || surface.get_current_texture(),
+            || {
+                log::warn!("Window surface lost or outdated, reconfiguring it");
+                self.gpu_state.reconfigure();
+            },
+        )?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        Ok((Some(output), view))
+    }
+
+    /// Render `grid` offscreen and return the frame's pixels (see
+    /// `read_pixels`)
+    pub fn render_to_buffer(&mut self, grid: &Arc<Mutex<Grid>>) -> anyhow::Result<Vec<u8>> {
+        self.render(grid)?;
+        self.read_pixels()
+    }
+
+    /// Copy the offscreen target back from the GPU as tightly packed RGBA
+    /// rows, top row first. Fails for renderers drawing to a window.
+    pub fn read_pixels(&self) -> anyhow::Result<Vec<u8>> {
+        let texture = self
+            .offscreen
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Only offscreen renderers can read back frames"))?;
+        let (width, height) = (texture.width(), texture.height());
+
+        // Buffer rows must be padded to the copy alignment
+        let row_bytes = width * 4;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.gpu_state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: padded_row_bytes as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .gpu_state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.gpu_state.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.gpu_state.device.poll(wgpu::Maintain::Wait);
+        rx.recv()??;
+
+        let data = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        for row in data.chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        drop(data);
+        buffer.unmap();
+
+        Ok(pixels)
+    }
+
+    pub fn render(&mut self, grid: &Arc<Mutex<Grid>>) -> anyhow::Result<()> {
+        let (output, view) = self.acquire_frame()?;
 
         let mut encoder = self
             .gpu_state
@@ -51,12 +201,7 @@ impl Renderer {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.169,
-                            b: 0.212,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear_color()),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -70,7 +215,9 @@ impl Renderer {
         self.text_renderer.render(&self.gpu_state, &mut encoder, &view, grid)?;
 
         self.gpu_state.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
 
         Ok(())
     }
@@ -78,6 +225,9 @@ impl Renderer {
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.gpu_state.resize(new_size);
+            if self.offscreen.is_some() {
+                self.offscreen = Some(Self::create_offscreen_target(&self.gpu_state));
+            }
         }
     }
 
@@ -85,11 +235,108 @@ impl Renderer {
         self.text_renderer.cell_dimensions()
     }
 
+    pub fn font_size(&self) -> f32 {
+        self.text_renderer.font_size()
+    }
+
+    /// Height of the title bar above each pane: one text line, or 0 when
+    /// title bars are off
+    pub fn title_bar_height(&self) -> f32 {
+        if self.pane_title_bars {
+            self.text_renderer.cell_dimensions().1
+        } else {
+            0.0
+        }
+    }
+
+    /// Grow or shrink the font by `delta` points. Returns `true` if the cell
+    /// size changed and panes need resizing.
+    pub fn zoom(&mut self, delta: f32) -> bool {
+        let font_size = self.text_renderer.font_size() + delta;
+        self.text_renderer.set_font_size(font_size)
+    }
+
+    /// Restore the font size from the config
+    pub fn reset_zoom(&mut self) -> bool {
+        self.text_renderer.set_font_size(self.base_font_size)
+    }
+
+    /// Show or hide the cursor in the frames that follow, for blinking
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.text_renderer.set_cursor_visible(visible);
+    }
+
+    /// Show `lines` in a box over the panes in the frames that follow, or
+    /// stop showing it with `None`
+    pub fn set_overlay(&mut self, lines: Option<Vec<String>>) {
+        self.overlay = lines;
+    }
+
+    /// Highlight the divider grab area `rect` in the frames that follow, or
+    /// stop highlighting with `None`
+    pub fn set_divider_highlight(&mut self, rect: Option<[f32; 4]>) {
+        self.divider_highlight = rect;
+    }
+
+    /// Background opacity in effect (1.0 when drawing opaquely)
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Draw the background and cell backgrounds with `opacity`, clamped to
+    /// 0.0..=1.0; stays 1.0 when the target can't be transparent
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = if self.gpu_state.is_transparent() { opacity.clamp(0.0, 1.0) } else { 1.0 };
+        self.text_renderer.set_background_opacity(self.opacity);
+    }
+
+    /// Apply a reloaded config: palette, bold brightening, cursor shape and
+    /// colors, scroll indicator, background, opacity, padding, title bars and font size. Returns `true` if the
+    /// cell size, padding or title bars changed and panes need resizing.
+    pub fn apply_config(&mut self, config: &Config) -> bool {
+        self.background = config.colors.background;
+        self.set_opacity(config.window.opacity);
+        self.text_renderer.set_colors(config.colors.clone());
+        self.text_renderer.set_bold_is_bright(config.bold_is_bright);
+        self.text_renderer.set_cursor_shape(config.cursor_shape);
+        self.text_renderer.set_cursor_reverse(config.cursor_reverse);
+        self.text_renderer.set_scroll_indicator(config.scroll_indicator);
+
+        let layout_changed =
+            self.pane_title_bars != config.pane_title_bars || self.text_renderer.padding() != config.window.padding;
+        self.pane_title_bars = config.pane_title_bars;
+        self.text_renderer.set_padding(config.window.padding);
+
+        if (config.font.size - self.base_font_size).abs() < f32::EPSILON {
+            return layout_changed;
+        }
+        self.base_font_size = config.font.size;
+        self.text_renderer.set_font_size(config.font.size) || layout_changed
+    }
+
+    /// Color frames are cleared to: the background at the configured
+    /// opacity, premultiplied if the surface expects that
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.background;
+        let a = a * self.opacity;
+        let scale = if self.gpu_state.config.alpha_mode == wgpu::CompositeAlphaMode::PreMultiplied { a } else { 1.0 };
+        wgpu::Color {
+            r: (r * scale) as f64,
+            g: (g * scale) as f64,
+            b: (b * scale) as f64,
+            a: a as f64,
+        }
+    }
+
+    /// Draw the current tab's panes and present the frame. Nothing is drawn
+    /// while a pane is partway through a synchronized update, so a
+    /// half-drawn screen is never shown.
     pub fn render_panes(&mut self, pane_manager: &PaneManager) -> anyhow::Result<()> {
-        let output = self.gpu_state.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        if pane_manager.is_frozen() {
+            return Ok(());
+        }
+        let mut timer = FrameTimer::start();
+        let (output, view) = self.acquire_frame()?;
 
         let mut encoder = self
             .gpu_state
@@ -106,12 +353,7 @@ impl Renderer {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.169,
-                            b: 0.212,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear_color()),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -129,19 +371,37 @@ impl Renderer {
         // Render each pane with borders
         let active_pane = pane_manager.active_pane();
 
-        for (pane_id, (x, y, width, height)) in pane_bounds.iter() {
-            if let Some(pane) = pane_manager.get_pane(*pane_id) {
+        let title_bar_height = self.title_bar_height() as u32;
+
+        // In creation order, so overlapping borders always stack the same way
+        for (pane_id, pane) in pane_manager.ordered_panes() {
+            if let Some((x, y, width, height)) = pane_bounds.get(pane_id) {
                 let grid = pane.terminal.grid();
                 let is_active = active_pane == Some(*pane_id);
+                let bounds = (*x as u32, *y as u32, *width as u32, *height as u32);
 
                 // Render pane with viewport
-                self.text_renderer.render_with_viewport(
-                    &self.gpu_state,
-                    &mut encoder,
-                    &view,
-                    &grid,
-                    (*x as u32, *y as u32, *width as u32, *height as u32),
-                )?;
+                timer.time_pane(pane_id.0, || {
+                    self.text_renderer.render_with_viewport(
+                        &self.gpu_state,
+                        &mut encoder,
+                        &view,
+                        &grid,
+                        TextRenderer::pane_content_viewport(bounds, title_bar_height),
+                    )
+                })?;
+
+                if title_bar_height > 0 {
+                    let title = grid.lock().unwrap().title().unwrap_or(&pane.title).to_string();
+                    self.text_renderer.render_title_bar(
+                        &self.gpu_state,
+                        &mut encoder,
+                        &view,
+                        (bounds.0, bounds.1, bounds.2, title_bar_height.min(bounds.3)),
+                        &title,
+                        is_active,
+                    )?;
+                }
 
                 // Render pane border
                 self.text_renderer.render_pane_border(
@@ -150,12 +410,43 @@ impl Renderer {
                     &view,
                     (*x as u32, *y as u32, *width as u32, *height as u32),
                     is_active,
+                    pane_manager.broadcast(),
                 )?;
             }
         }
 
         self.gpu_state.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+
+        // Highlight and overlay each in a submission of their own, so their
+        // vertices don't replace the panes' before they are drawn
+        if let Some(rect) = self.divider_highlight {
+            let mut encoder = self
+                .gpu_state
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Divider Highlight Encoder"),
+                });
+            self.text_renderer
+                .render_divider_highlight(&self.gpu_state, &mut encoder, &view, rect)?;
+            self.gpu_state.queue.submit(std::iter::once(encoder.finish()));
+        }
+        if let Some(lines) = &self.overlay {
+            let mut encoder = self
+                .gpu_state
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Overlay Encoder"),
+                });
+            let window = (0, 0, self.gpu_state.size.width, self.gpu_state.size.height);
+            self.text_renderer
+                .render_overlay(&self.gpu_state, &mut encoder, &view, window, lines)?;
+            self.gpu_state.queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        if let Some(output) = output {
+            output.present();
+        }
+        timer.finish(&METRICS);
 
         Ok(())
     }
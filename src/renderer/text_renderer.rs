@@ -1,18 +1,47 @@
-use super::{GpuState, glyph_atlas::GlyphAtlas};
-use crate::terminal::{Color, Grid};
+use super::{GpuState, glyph_atlas::{GlyphAtlas, GlyphInfo}};
+use crate::terminal::{CellStyle, Color, CursorShape, Grid, LineSize};
 use crate::renderer::vertex::{Vertex, Uniforms};
+use crate::config::{ColorScheme, Padding, ScrollIndicator};
 use crate::Config;
 use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, SwashCache};
 use std::sync::{Arc, Mutex};
 use wgpu::util::DeviceExt;
 
+/// Smallest font size reachable by zooming out
+pub const MIN_FONT_SIZE: f32 = 6.0;
+/// Largest font size reachable by zooming in
+pub const MAX_FONT_SIZE: f32 = 72.0;
+/// Opacity of the foreground-colored tint over selected cells
+const SELECTION_ALPHA: f32 = 0.35;
+/// Border of the active pane, also used to highlight a hovered divider
+const ACTIVE_BORDER_COLOR: [f32; 4] = [0.0, 0.6, 0.8, 1.0];
+/// Width in pixels of the scrollbar shown while scrolled back
+const SCROLLBAR_WIDTH: f32 = 6.0;
+/// Shortest the scrollbar thumb gets, however long the history
+const SCROLLBAR_MIN_THUMB: f32 = 8.0;
+
 pub struct TextRenderer {
     _font_system: FontSystem,
     _swash_cache: SwashCache,
     cell_width: f32,
     cell_height: f32,
-    _font_size: f32,
+    font_size: f32,
+    colors: ColorScheme,
+    /// Alpha scale for cell backgrounds on transparent windows
+    background_opacity: f32,
+    bold_is_bright: bool,
+    /// Shape used unless the application picked one with DECSCUSR
+    cursor_shape: CursorShape,
+    /// Swap the colors of the cell under the cursor instead of using
+    /// `colors.cursor`
+    cursor_reverse: bool,
+    padding: Padding,
+    scroll_indicator: ScrollIndicator,
+    /// Off during the hidden phase of a blinking cursor
+    cursor_visible: bool,
     glyph_atlas: GlyphAtlas,
+    /// Atlas coordinates of an opaque texel, for solid quads
+    solid_uv: [f32; 2],
     render_pipeline: wgpu::RenderPipeline,
     _uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
@@ -27,7 +56,7 @@ impl TextRenderer {
         let mut font_system = FontSystem::new();
         let swash_cache = SwashCache::new();
 
-        let font_size = config.font.size;
+        let font_size = config.font.size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
 
         // Measure cell dimensions using a monospace character
         let metrics = Metrics::new(font_size, font_size * 1.2);
@@ -36,8 +65,7 @@ impl TextRenderer {
         buffer.set_text(&mut font_system, "M", Attrs::new(), cosmic_text::Shaping::Advanced);
 
         // Calculate cell dimensions
-        let cell_width = font_size * 0.6; // Approximation for monospace
-        let cell_height = font_size * 1.2;
+        let (cell_width, cell_height) = Self::cell_size_for_font(font_size);
 
         // Create glyph atlas
         let glyph_atlas = GlyphAtlas::new(&gpu_state.device, font_size);
@@ -188,7 +216,9 @@ impl TextRenderer {
                         topology: wgpu::PrimitiveTopology::TriangleList,
                         strip_index_format: None,
                         front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: Some(wgpu::Face::Back),
+                        // Quads are wound clockwise on screen; there are no
+                        // back faces to skip in 2D
+                        cull_mode: None,
                         polygon_mode: wgpu::PolygonMode::Fill,
                         unclipped_depth: false,
                         conservative: false,
@@ -226,8 +256,17 @@ impl TextRenderer {
             _swash_cache: swash_cache,
             cell_width,
             cell_height,
-            _font_size: font_size,
+            font_size,
+            colors: config.colors.clone(),
+            background_opacity: 1.0,
+            bold_is_bright: config.bold_is_bright,
+            cursor_shape: config.cursor_shape,
+            cursor_reverse: config.cursor_reverse,
+            padding: config.window.padding,
+            scroll_indicator: config.scroll_indicator,
+            cursor_visible: true,
             glyph_atlas,
+            solid_uv: [0.0, 0.0],
             render_pipeline,
             _uniform_buffer: uniform_buffer,
             uniform_bind_group,
@@ -245,113 +284,27 @@ impl TextRenderer {
         view: &wgpu::TextureView,
         grid: &Arc<Mutex<Grid>>,
     ) -> anyhow::Result<()> {
+        self.solid_uv = self.glyph_atlas.solid_tex_coords(&gpu_state.queue);
         let grid = grid.lock().unwrap();
         let (cols, rows) = grid.size();
 
         // Generate vertices and indices for all visible characters
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
+        let (origin_x, origin_y) = (self.padding.left, self.padding.top);
 
         for row in 0..rows {
             for col in 0..cols {
-                if let Some(cell) = grid.get_cell(col, row) {
-                    // Skip empty cells
-                    if cell.c == ' ' || cell.c == '\0' {
-                        continue;
-                    }
-
-                    // Get or cache the glyph
-                    let glyph_info = self.glyph_atlas.get_or_cache_glyph(
-                        &gpu_state.queue,
-                        cell.c,
-                        cell.style.bold,
-                        cell.style.italic,
-                    );
-
-                    if let Some(glyph) = glyph_info {
-                        // Calculate screen position
-                        let x = col as f32 * self.cell_width;
-                        let y = row as f32 * self.cell_height;
-
-                        // Convert colors to RGBA
-                        let fg_color = self.color_to_rgba_array(&cell.style.fg);
-                        let bg_color = self.color_to_rgba_array(&cell.style.bg);
-
-                        // Render background if not default
-                        if !matches!(cell.style.bg, Color::Default) {
-                            let base_vertex = vertices.len() as u32;
-
-                            // Background quad (no texture, just solid color)
-                            vertices.extend_from_slice(&[
-                                Vertex {
-                                    position: [x, y],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                                Vertex {
-                                    position: [x + self.cell_width, y],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                                Vertex {
-                                    position: [x + self.cell_width, y + self.cell_height],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                                Vertex {
-                                    position: [x, y + self.cell_height],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                            ]);
-
-                            indices.extend_from_slice(&[
-                                base_vertex, base_vertex + 1, base_vertex + 2,
-                                base_vertex, base_vertex + 2, base_vertex + 3,
-                            ]);
-                        }
-
-                        // Render foreground character
-                        let base_vertex = vertices.len() as u32;
-
-                        // Calculate glyph size in pixels
-                        let (atlas_width, atlas_height) = self.glyph_atlas.atlas_size();
-                        let glyph_width = glyph.width * atlas_width as f32;
-                        let glyph_height = glyph.height * atlas_height as f32;
-
-                        // Character quad with glyph texture
-                        vertices.extend_from_slice(&[
-                            Vertex {
-                                position: [x, y],
-                                tex_coords: [glyph.atlas_x, glyph.atlas_y],
-                                color: fg_color,
-                            },
-                            Vertex {
-                                position: [x + glyph_width, y],
-                                tex_coords: [glyph.atlas_x + glyph.width, glyph.atlas_y],
-                                color: fg_color,
-                            },
-                            Vertex {
-                                position: [x + glyph_width, y + glyph_height],
-                                tex_coords: [glyph.atlas_x + glyph.width, glyph.atlas_y + glyph.height],
-                                color: fg_color,
-                            },
-                            Vertex {
-                                position: [x, y + glyph_height],
-                                tex_coords: [glyph.atlas_x, glyph.atlas_y + glyph.height],
-                                color: fg_color,
-                            },
-                        ]);
-
-                        indices.extend_from_slice(&[
-                            base_vertex, base_vertex + 1, base_vertex + 2,
-                            base_vertex, base_vertex + 2, base_vertex + 3,
-                        ]);
-                    }
-                }
+                self.push_cell(gpu_state, &mut vertices, &mut indices, &grid, (origin_x, origin_y), (col, row));
             }
         }
 
+        self.push_selection(&mut vertices, &mut indices, &grid, (origin_x, origin_y));
+        self.push_hovered_url(&mut vertices, &mut indices, &grid, (origin_x, origin_y));
+        self.push_cursor(gpu_state, &mut vertices, &mut indices, &grid, (origin_x, origin_y));
+        let target = (0, 0, gpu_state.size.width, gpu_state.size.height);
+        self.push_scroll_indicator(gpu_state, &mut vertices, &mut indices, &grid, target);
+
         drop(grid);
 
         // If no vertices to render, early return
@@ -360,6 +313,7 @@ impl TextRenderer {
         }
 
         // Update vertex and index buffers
+        self.ensure_buffer_capacity(gpu_state, vertices.len(), indices.len());
         gpu_state.queue.write_buffer(
             &self.vertex_buffer,
             0,
@@ -403,6 +357,32 @@ impl TextRenderer {
         (self.cell_width, self.cell_height)
     }
 
+    /// Cell size in pixels for a font size
+    pub fn cell_size_for_font(font_size: f32) -> (f32, f32) {
+        (font_size * 0.6, font_size * 1.2) // Width is an approximation for monospace
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    /// Change the font size, recomputing cell dimensions and invalidating
+    /// every glyph cached at the old size
+    ///
+    /// The size is clamped to `MIN_FONT_SIZE..=MAX_FONT_SIZE`. Returns `true`
+    /// if the size actually changed.
+    pub fn set_font_size(&mut self, font_size: f32) -> bool {
+        let font_size = font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        if (font_size - self.font_size).abs() < f32::EPSILON {
+            return false;
+        }
+
+        self.font_size = font_size;
+        (self.cell_width, self.cell_height) = Self::cell_size_for_font(font_size);
+        self.glyph_atlas.set_font_size(font_size);
+        true
+    }
+
     pub fn render_with_viewport(
         &mut self,
         gpu_state: &GpuState,
@@ -413,111 +393,29 @@ impl TextRenderer {
     ) -> anyhow::Result<()> {
         let (viewport_x, viewport_y, viewport_width, viewport_height) = viewport;
 
+        self.solid_uv = self.glyph_atlas.solid_tex_coords(&gpu_state.queue);
         let grid = grid.lock().unwrap();
         let (cols, rows) = grid.size();
 
         // Generate vertices and indices for all visible characters
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
+        // Text starts inside the padding; the pane's viewport and scissor
+        // still cover its whole area
+        let origin_x = viewport_x as f32 + self.padding.left;
+        let origin_y = viewport_y as f32 + self.padding.top;
 
         for row in 0..rows {
             for col in 0..cols {
-                if let Some(cell) = grid.get_cell(col, row) {
-                    // Skip empty cells
-                    if cell.c == ' ' || cell.c == '\0' {
-                        continue;
-                    }
-
-                    // Get or cache the glyph
-                    let glyph_info = self.glyph_atlas.get_or_cache_glyph(
-                        &gpu_state.queue,
-                        cell.c,
-                        cell.style.bold,
-                        cell.style.italic,
-                    );
-
-                    if let Some(glyph) = glyph_info {
-                        // Calculate position relative to viewport
-                        let x = viewport_x as f32 + col as f32 * self.cell_width;
-                        let y = viewport_y as f32 + row as f32 * self.cell_height;
-
-                        // Convert colors to RGBA
-                        let fg_color = self.color_to_rgba_array(&cell.style.fg);
-                        let bg_color = self.color_to_rgba_array(&cell.style.bg);
-
-                        // Render background if not default
-                        if !matches!(cell.style.bg, Color::Default) {
-                            let base_vertex = vertices.len() as u32;
-
-                            vertices.extend_from_slice(&[
-                                Vertex {
-                                    position: [x, y],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                                Vertex {
-                                    position: [x + self.cell_width, y],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                                Vertex {
-                                    position: [x + self.cell_width, y + self.cell_height],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                                Vertex {
-                                    position: [x, y + self.cell_height],
-                                    tex_coords: [0.0, 0.0],
-                                    color: bg_color,
-                                },
-                            ]);
-
-                            indices.extend_from_slice(&[
-                                base_vertex, base_vertex + 1, base_vertex + 2,
-                                base_vertex, base_vertex + 2, base_vertex + 3,
-                            ]);
-                        }
-
-                        // Render foreground character
-                        let base_vertex = vertices.len() as u32;
-
-                        // Calculate glyph size in pixels
-                        let (atlas_width, atlas_height) = self.glyph_atlas.atlas_size();
-                        let glyph_width = glyph.width * atlas_width as f32;
-                        let glyph_height = glyph.height * atlas_height as f32;
-
-                        vertices.extend_from_slice(&[
-                            Vertex {
-                                position: [x, y],
-                                tex_coords: [glyph.atlas_x, glyph.atlas_y],
-                                color: fg_color,
-                            },
-                            Vertex {
-                                position: [x + glyph_width, y],
-                                tex_coords: [glyph.atlas_x + glyph.width, glyph.atlas_y],
-                                color: fg_color,
-                            },
-                            Vertex {
-                                position: [x + glyph_width, y + glyph_height],
-                                tex_coords: [glyph.atlas_x + glyph.width, glyph.atlas_y + glyph.height],
-                                color: fg_color,
-                            },
-                            Vertex {
-                                position: [x, y + glyph_height],
-                                tex_coords: [glyph.atlas_x, glyph.atlas_y + glyph.height],
-                                color: fg_color,
-                            },
-                        ]);
-
-                        indices.extend_from_slice(&[
-                            base_vertex, base_vertex + 1, base_vertex + 2,
-                            base_vertex, base_vertex + 2, base_vertex + 3,
-                        ]);
-                    }
-                }
+                self.push_cell(gpu_state, &mut vertices, &mut indices, &grid, (origin_x, origin_y), (col, row));
             }
         }
 
+        self.push_selection(&mut vertices, &mut indices, &grid, (origin_x, origin_y));
+        self.push_hovered_url(&mut vertices, &mut indices, &grid, (origin_x, origin_y));
+        self.push_cursor(gpu_state, &mut vertices, &mut indices, &grid, (origin_x, origin_y));
+        self.push_scroll_indicator(gpu_state, &mut vertices, &mut indices, &grid, viewport);
+
         drop(grid);
 
         // If no vertices to render, early return
@@ -526,6 +424,7 @@ impl TextRenderer {
         }
 
         // Update vertex and index buffers
+        self.ensure_buffer_capacity(gpu_state, vertices.len(), indices.len());
         gpu_state.queue.write_buffer(
             &self.vertex_buffer,
             0,
@@ -576,21 +475,208 @@ impl TextRenderer {
         Ok(())
     }
 
-    pub fn render_pane_border(
+    /// Part of a pane's bounds left for its terminal below a title bar of
+    /// `title_bar_height` pixels
+    pub fn pane_content_viewport(bounds: (u32, u32, u32, u32), title_bar_height: u32) -> (u32, u32, u32, u32) {
+        let (x, y, width, height) = bounds;
+        let bar = title_bar_height.min(height);
+        (x, y + bar, width, height - bar)
+    }
+
+    /// Draw a one-line title bar, highlighted for the active pane. The title
+    /// is cut off at the bar's right edge.
+    pub fn render_title_bar(
         &mut self,
         gpu_state: &GpuState,
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         viewport: (u32, u32, u32, u32),
+        title: &str,
         is_active: bool,
     ) -> anyhow::Result<()> {
         let (x, y, width, height) = viewport;
+        let (x, y, width, height) = (x as f32, y as f32, width as f32, height as f32);
+        self.solid_uv = self.glyph_atlas.solid_tex_coords(&gpu_state.queue);
 
-        // Border color: bright for active pane, dim for inactive
-        let border_color = if is_active {
-            [0.0, 0.6, 0.8, 1.0] // Cyan blue for active
+        let (bar_color, text_color) = if is_active {
+            ([0.0, 0.35, 0.5, 1.0], [1.0, 1.0, 1.0, 1.0])
         } else {
-            [0.2, 0.2, 0.2, 1.0] // Dark gray for inactive
+            ([0.15, 0.15, 0.15, 1.0], [0.6, 0.6, 0.6, 1.0])
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        Self::push_rect(&mut vertices, &mut indices, [x, y, width, height], bar_color, self.solid_uv);
+
+        // Half a cell of padding on the left
+        let mut glyph_x = x + self.cell_width / 2.0;
+        for c in title.chars() {
+            if glyph_x + self.cell_width > x + width {
+                break;
+            }
+            if !c.is_whitespace() && !c.is_control() {
+                if let Some(glyph) = self.glyph_atlas.get_or_cache_glyph(&gpu_state.queue, c, is_active, false) {
+                    self.push_glyph(&mut vertices, &mut indices, &glyph, glyph_x, y, text_color);
+                }
+            }
+            glyph_x += self.cell_width;
+        }
+
+        self.ensure_buffer_capacity(gpu_state, vertices.len(), indices.len());
+        gpu_state.queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        gpu_state.queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+        self.num_indices = indices.len() as u32;
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Title Bar Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+
+        Ok(())
+    }
+
+    /// Draw `lines` in a box centered in `viewport` (the whole window),
+    /// above everything else, e.g. to ask for confirmation. Lines are cut
+    /// off at the viewport's edges.
+    pub fn render_overlay(
+        &mut self,
+        gpu_state: &GpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        viewport: (u32, u32, u32, u32),
+        lines: &[String],
+    ) -> anyhow::Result<()> {
+        let (x, y, width, height) = viewport;
+        let (x, y, width, height) = (x as f32, y as f32, width as f32, height as f32);
+        self.solid_uv = self.glyph_atlas.solid_tex_coords(&gpu_state.queue);
+
+        // A cell of padding left and right, half a cell above and below
+        let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        let box_width = ((longest + 2) as f32 * self.cell_width).min(width);
+        let box_height = ((lines.len() + 1) as f32 * self.cell_height).min(height);
+        let box_x = x + (width - box_width) / 2.0;
+        let box_y = y + (height - box_height) / 2.0;
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        Self::push_rect(&mut vertices, &mut indices, [box_x, box_y, box_width, box_height], self.colors.foreground, self.solid_uv);
+
+        for (row, line) in lines.iter().enumerate() {
+            let glyph_y = box_y + (row as f32 + 0.5) * self.cell_height;
+            if glyph_y + self.cell_height > box_y + box_height {
+                break;
+            }
+            let mut glyph_x = box_x + self.cell_width;
+            for c in line.chars() {
+                if glyph_x + self.cell_width > box_x + box_width {
+                    break;
+                }
+                if !c.is_whitespace() && !c.is_control() {
+                    if let Some(glyph) = self.glyph_atlas.get_or_cache_glyph(&gpu_state.queue, c, false, false) {
+                        self.push_glyph(&mut vertices, &mut indices, &glyph, glyph_x, glyph_y, self.colors.background);
+                    }
+                }
+                glyph_x += self.cell_width;
+            }
+        }
+
+        self.draw_over(gpu_state, encoder, view, "Overlay Render Pass", &vertices, &indices);
+        Ok(())
+    }
+
+    /// Draw the divider under the mouse as resizable, over `rect` (its
+    /// grab area) in the active border color
+    pub fn render_divider_highlight(
+        &mut self,
+        gpu_state: &GpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        rect: [f32; 4],
+    ) -> anyhow::Result<()> {
+        self.solid_uv = self.glyph_atlas.solid_tex_coords(&gpu_state.queue);
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        Self::push_rect(&mut vertices, &mut indices, rect, ACTIVE_BORDER_COLOR, self.solid_uv);
+
+        self.draw_over(gpu_state, encoder, view, "Divider Highlight Render Pass", &vertices, &indices);
+        Ok(())
+    }
+
+    /// Draw `vertices` on top of what `view` already shows
+    fn draw_over(
+        &mut self,
+        gpu_state: &GpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) {
+        self.ensure_buffer_capacity(gpu_state, vertices.len(), indices.len());
+        gpu_state.queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        gpu_state.queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(indices));
+        self.num_indices = indices.len() as u32;
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+
+    pub fn render_pane_border(
+        &mut self,
+        gpu_state: &GpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        viewport: (u32, u32, u32, u32),
+        is_active: bool,
+        broadcast: bool,
+    ) -> anyhow::Result<()> {
+        let (x, y, width, height) = viewport;
+        let solid = self.glyph_atlas.solid_tex_coords(&gpu_state.queue);
+
+        // Border color: bright for active pane, dim for inactive; amber
+        // while input is broadcast to every pane
+        let border_color = match (is_active, broadcast) {
+            (true, false) => ACTIVE_BORDER_COLOR, // Cyan blue for active
+            (false, false) => [0.2, 0.2, 0.2, 1.0], // Dark gray for inactive
+            (true, true) => [1.0, 0.6, 0.0, 1.0], // Amber for active
+            (false, true) => [0.5, 0.3, 0.0, 1.0], // Dim amber for inactive
         };
 
         // Border width in pixels
@@ -610,22 +696,22 @@ impl TextRenderer {
         vertices.extend_from_slice(&[
             Vertex {
                 position: [x, y],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width, y],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width, y + border_width],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x, y + border_width],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
         ]);
@@ -639,22 +725,22 @@ impl TextRenderer {
         vertices.extend_from_slice(&[
             Vertex {
                 position: [x + width - border_width, y],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width, y],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width, y + height],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width - border_width, y + height],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
         ]);
@@ -668,22 +754,22 @@ impl TextRenderer {
         vertices.extend_from_slice(&[
             Vertex {
                 position: [x, y + height - border_width],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width, y + height - border_width],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + width, y + height],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x, y + height],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
         ]);
@@ -697,22 +783,22 @@ impl TextRenderer {
         vertices.extend_from_slice(&[
             Vertex {
                 position: [x, y],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + border_width, y],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x + border_width, y + height],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
             Vertex {
                 position: [x, y + height],
-                tex_coords: [0.0, 0.0],
+                tex_coords: solid,
                 color: border_color,
             },
         ]);
@@ -761,25 +847,507 @@ impl TextRenderer {
         Ok(())
     }
 
-    fn color_to_rgba_array(&self, color: &Color) -> [f32; 4] {
+    /// Grow the vertex and index buffers to hold at least the given counts
+    fn ensure_buffer_capacity(&mut self, gpu_state: &GpuState, vertices: usize, indices: usize) {
+        let vertex_bytes = (std::mem::size_of::<Vertex>() * vertices) as u64;
+        if vertex_bytes > self.vertex_buffer.size() {
+            self.vertex_buffer = gpu_state.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Vertex Buffer"),
+                size: vertex_bytes.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        }
+
+        let index_bytes = (std::mem::size_of::<u32>() * indices) as u64;
+        if index_bytes > self.index_buffer.size() {
+            self.index_buffer = gpu_state.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Index Buffer"),
+                size: index_bytes.next_power_of_two(),
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        }
+    }
+
+    /// Append a textured quad drawing `glyph` with its top-left at (`x`, `y`)
+    fn push_glyph(&self, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, glyph: &GlyphInfo, x: f32, y: f32, color: [f32; 4]) {
+        let rect = [x, y, self.cell_width, self.cell_height];
+        self.push_cell_glyph(vertices, indices, glyph, rect, LineSize::Single, color);
+    }
+
+    /// Append the background, glyph and decorations of the cell at
+    /// (`col`, `row`) of `grid`, whose top-left cell is drawn at `origin`
+    fn push_cell(
+        &mut self,
+        gpu_state: &GpuState,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+        grid: &Grid,
+        origin: (f32, f32),
+        (col, row): (usize, usize),
+    ) {
+        let (Some(cell), Some(rect)) = (grid.get_cell(col, row), self.cell_rect(grid, origin, col, row)) else {
+            return;
+        };
+        let size = grid.line_size(row);
+        let fg_color = Self::fg_color_to_rgba(&self.colors, &cell.style.fg, cell.style.bold, self.bold_is_bright);
+
+        if !matches!(cell.style.bg, Color::Default) {
+            let mut bg_color = Self::color_to_rgba(&self.colors, &cell.style.bg);
+            bg_color[3] *= self.background_opacity;
+            Self::push_rect(vertices, indices, rect, bg_color, self.solid_uv);
+        }
+
+        // Empty cells only need their background and decorations, if any
+        if cell.c != ' ' && cell.c != '\0' {
+            let glyph = self.glyph_atlas.get_or_cache_glyph(&gpu_state.queue, cell.c, cell.style.bold, cell.style.italic);
+            if let Some(glyph) = glyph {
+                self.push_cell_glyph(vertices, indices, &glyph, rect, size, fg_color);
+            }
+        }
+
+        self.push_decorations(vertices, indices, &cell.style, rect, size, fg_color);
+    }
+
+    /// Quad (`[x, y, width, height]`) of the cell at (`col`, `row`) of
+    /// `grid`, whose top-left cell is drawn at `origin`: twice as wide on
+    /// double-size lines, whose right half isn't shown (`None`)
+    fn cell_rect(&self, grid: &Grid, origin: (f32, f32), col: usize, row: usize) -> Option<[f32; 4]> {
+        let (cols, _) = grid.size();
+        let y = origin.1 + row as f32 * self.cell_height;
+        if !grid.line_size(row).is_double_width() {
+            return Some([origin.0 + col as f32 * self.cell_width, y, self.cell_width, self.cell_height]);
+        }
+
+        (col * 2 < cols).then(|| [origin.0 + (col * 2) as f32 * self.cell_width, y, 2.0 * self.cell_width, self.cell_height])
+    }
+
+    /// Area a cell's contents are laid out in before clipping to `rect`: a
+    /// double-height line shows the top or bottom half of a cell twice as tall
+    fn layout_rect(&self, rect: [f32; 4], size: LineSize) -> [f32; 4] {
+        let [x, y, width, height] = rect;
+        match size {
+            LineSize::DoubleHeightTop => [x, y, width, 2.0 * height],
+            LineSize::DoubleHeightBottom => [x, y - height, width, 2.0 * height],
+            _ => rect,
+        }
+    }
+
+    /// Append `glyph` for the cell at `rect` on a line of `size`, stretched
+    /// to the cell's layout and clipped to the half shown on this row
+    fn push_cell_glyph(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+        glyph: &GlyphInfo,
+        rect: [f32; 4],
+        size: LineSize,
+        color: [f32; 4],
+    ) {
+        let [x, top, ..] = self.layout_rect(rect, size);
+        let scale_x = rect[2] / self.cell_width;
+        let scale_y = if size.is_double_height() { 2.0 } else { 1.0 };
+
+        let (atlas_width, atlas_height) = self.glyph_atlas.atlas_size();
+        let glyph_width = glyph.width * atlas_width as f32 * scale_x;
+        let glyph_height = glyph.height * atlas_height as f32 * scale_y;
+
+        // A double-height line shows only the part of the glyph within this
+        // row, with matching texels
+        let (y0, y1) = if size.is_double_height() {
+            (top.max(rect[1]), (top + glyph_height).min(rect[1] + rect[3]))
+        } else {
+            (top, top + glyph_height)
+        };
+        if y1 <= y0 || glyph_height <= 0.0 {
+            return;
+        }
+        let v0 = glyph.atlas_y + (y0 - top) / glyph_height * glyph.height;
+        let v1 = glyph.atlas_y + (y1 - top) / glyph_height * glyph.height;
+
+        let base_vertex = vertices.len() as u32;
+        vertices.extend_from_slice(&[
+            Vertex {
+                position: [x, y0],
+                tex_coords: [glyph.atlas_x, v0],
+                color,
+            },
+            Vertex {
+                position: [x + glyph_width, y0],
+                tex_coords: [glyph.atlas_x + glyph.width, v0],
+                color,
+            },
+            Vertex {
+                position: [x + glyph_width, y1],
+                tex_coords: [glyph.atlas_x + glyph.width, v1],
+                color,
+            },
+            Vertex {
+                position: [x, y1],
+                tex_coords: [glyph.atlas_x, v1],
+                color,
+            },
+        ]);
+
+        indices.extend_from_slice(&[
+            base_vertex, base_vertex + 1, base_vertex + 2,
+            base_vertex, base_vertex + 2, base_vertex + 3,
+        ]);
+    }
+
+    /// Tint the selected cells of `grid`, whose top-left cell is drawn at
+    /// `origin`, with a translucent foreground color
+    fn push_selection(&self, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, grid: &Grid, origin: (f32, f32)) {
+        let Some(selection) = grid.selection() else {
+            return;
+        };
+        let (cols, rows) = grid.size();
+        let [r, g, b, _] = self.colors.foreground;
+        let color = [r, g, b, SELECTION_ALPHA];
+
+        for row in selection.start.1..=selection.end.1.min(rows.saturating_sub(1)) {
+            for col in (0..cols).filter(|&col| selection.contains(col, row)) {
+                if let Some(rect) = self.cell_rect(grid, origin, col, row) {
+                    Self::push_rect(vertices, indices, rect, color, self.solid_uv);
+                }
+            }
+        }
+    }
+
+    /// Underline the URL under the mouse pointer in `grid`, whose top-left
+    /// cell is drawn at `origin`
+    fn push_hovered_url(&self, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, grid: &Grid, origin: (f32, f32)) {
+        let Some(url) = grid.hovered_url() else {
+            return;
+        };
+        let (cols, rows) = grid.size();
+
+        for row in url.start.1..=url.end.1.min(rows.saturating_sub(1)) {
+            for col in (0..cols).filter(|&col| url.contains(col, row)) {
+                let Some(cell) = grid.get_cell(col, row).filter(|cell| !cell.style.underline) else {
+                    continue;
+                };
+                let style = CellStyle { underline: true, ..CellStyle::default() };
+                let color = Self::fg_color_to_rgba(&self.colors, &cell.style.fg, cell.style.bold, self.bold_is_bright);
+                if let Some(rect) = self.cell_rect(grid, origin, col, row) {
+                    self.push_decorations(vertices, indices, &style, rect, grid.line_size(row), color);
+                }
+            }
+        }
+    }
+
+    /// Append the cursor of `grid`, whose top-left cell is drawn at `origin`.
+    /// A block cursor redraws its glyph over the block; nothing is drawn
+    /// while scrolled back or in the off phase of a blink.
+    fn push_cursor(
+        &mut self,
+        gpu_state: &GpuState,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+        grid: &Grid,
+        origin: (f32, f32),
+    ) {
+        let (cols, rows) = grid.size();
+        let (cursor_x, cursor_y) = grid.cursor_pos();
+        if !self.cursor_visible || !grid.cursor_visible() || !grid.is_at_bottom() || cols == 0 || cursor_y >= rows {
+            return;
+        }
+
+        let size = grid.line_size(cursor_y);
+        let Some(cell_rect) = self.cell_rect(grid, origin, cursor_x, cursor_y) else {
+            return;
+        };
+        let [x, y, width, height] = cell_rect;
+        let shape = grid.cursor_shape().unwrap_or(self.cursor_shape);
+
+        let cell = grid.get_cell(cursor_x, cursor_y).copied().unwrap_or_default();
+        let (cursor_color, glyph_color) =
+            Self::cursor_colors(&self.colors, self.cursor_reverse, &cell.style, self.bold_is_bright);
+        let rect = Self::cursor_rect(shape, x, y, width, height);
+        Self::push_rect(vertices, indices, rect, cursor_color, self.solid_uv);
+
+        if shape == CursorShape::Block && cell.c != ' ' && cell.c != '\0' {
+            let glyph = self.glyph_atlas.get_or_cache_glyph(&gpu_state.queue, cell.c, cell.style.bold, cell.style.italic);
+            if let Some(glyph) = glyph {
+                self.push_cell_glyph(vertices, indices, &glyph, cell_rect, size, glyph_color);
+            }
+        }
+    }
+
+    /// Append the scroll indicator for `grid`, drawn over the pane at
+    /// `viewport`, while it is scrolled back
+    fn push_scroll_indicator(
+        &mut self,
+        gpu_state: &GpuState,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+        grid: &Grid,
+        viewport: (u32, u32, u32, u32),
+    ) {
+        let (scroll_offset, scrollback_len) = (grid.scroll_offset(), grid.scrollback_len());
+        let rects = Self::scroll_indicator_rects(
+            self.scroll_indicator,
+            viewport,
+            (self.cell_width, self.cell_height),
+            scroll_offset,
+            scrollback_len,
+            grid.size().1,
+        );
+        let [r, g, b, _] = self.colors.foreground;
+
+        match (self.scroll_indicator, rects.as_slice()) {
+            (ScrollIndicator::Scrollbar, [track, thumb]) => {
+                Self::push_rect(vertices, indices, *track, [r, g, b, 0.15], self.solid_uv);
+                Self::push_rect(vertices, indices, *thumb, [r, g, b, 0.5], self.solid_uv);
+            }
+            (ScrollIndicator::Position, [backdrop]) => {
+                Self::push_rect(vertices, indices, *backdrop, self.colors.foreground, self.solid_uv);
+                let mut glyph_x = backdrop[0] + self.cell_width / 2.0;
+                for c in Self::scroll_position_label(scroll_offset, scrollback_len).chars() {
+                    if let Some(glyph) = self.glyph_atlas.get_or_cache_glyph(&gpu_state.queue, c, false, false) {
+                        self.push_glyph(vertices, indices, &glyph, glyph_x, backdrop[1], self.colors.background);
+                    }
+                    glyph_x += self.cell_width;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Quads (`[x, y, width, height]`) of the scroll indicator for a pane at
+    /// `viewport` showing `rows` lines, `scroll_offset` lines back into a
+    /// history of `scrollback_len`. Nothing on the live screen.
+    ///
+    /// A scrollbar is a track along the right edge then a thumb spanning the
+    /// visible lines; a position label is one backdrop in the top-right
+    /// corner fitting `scroll_position_label` in cells of `cell_size`.
+    pub fn scroll_indicator_rects(
+        style: ScrollIndicator,
+        viewport: (u32, u32, u32, u32),
+        cell_size: (f32, f32),
+        scroll_offset: usize,
+        scrollback_len: usize,
+        rows: usize,
+    ) -> Vec<[f32; 4]> {
+        if scroll_offset == 0 || scrollback_len == 0 {
+            return Vec::new();
+        }
+        let (x, y, width, height) = viewport;
+        let (x, y, width, height) = (x as f32, y as f32, width as f32, height as f32);
+
+        match style {
+            ScrollIndicator::Scrollbar => {
+                let bar_width = SCROLLBAR_WIDTH.min(width);
+                let track = [x + width - bar_width, y, bar_width, height];
+
+                let total = (scrollback_len + rows) as f32;
+                let thumb_height = (height * rows as f32 / total).max(SCROLLBAR_MIN_THUMB).min(height);
+                let first_visible = scrollback_len.saturating_sub(scroll_offset) as f32;
+                let thumb_y = y + (height - thumb_height) * first_visible / scrollback_len as f32;
+                vec![track, [track[0], thumb_y, bar_width, thumb_height]]
+            }
+            ScrollIndicator::Position => {
+                let (cell_width, cell_height) = cell_size;
+                let label_cells = Self::scroll_position_label(scroll_offset, scrollback_len).chars().count();
+                let label_width = ((label_cells + 1) as f32 * cell_width).min(width);
+                vec![[x + width - label_width, y, label_width, cell_height.min(height)]]
+            }
+            ScrollIndicator::Off => Vec::new(),
+        }
+    }
+
+    /// Text of the `Position` scroll indicator: lines scrolled back out of
+    /// the history's length
+    pub fn scroll_position_label(scroll_offset: usize, scrollback_len: usize) -> String {
+        format!("{}/{}", scroll_offset, scrollback_len)
+    }
+
+    /// Colors of the cursor and of the glyph redrawn over a block cursor, for
+    /// a cell of `style`: the cell's foreground and background swapped when
+    /// `reverse`, else `colors.cursor` (or the foreground) with the glyph in
+    /// the cell's background so it stays legible
+    pub fn cursor_colors(
+        colors: &ColorScheme,
+        reverse: bool,
+        style: &CellStyle,
+        bold_is_bright: bool,
+    ) -> ([f32; 4], [f32; 4]) {
+        let background = match style.bg {
+            Color::Default => colors.background,
+            bg => Self::color_to_rgba(colors, &bg),
+        };
+        if reverse {
+            (Self::fg_color_to_rgba(colors, &style.fg, style.bold, bold_is_bright), background)
+        } else {
+            (colors.cursor.unwrap_or(colors.foreground), background)
+        }
+    }
+
+    /// Quad (`[x, y, width, height]`) for a cursor of `shape` in the cell at
+    /// (`x`, `y`): the whole cell, a line along its bottom, or a bar on its
+    /// left edge
+    pub fn cursor_rect(shape: CursorShape, x: f32, y: f32, cell_width: f32, cell_height: f32) -> [f32; 4] {
+        match shape {
+            CursorShape::Block => [x, y, cell_width, cell_height],
+            CursorShape::Underline => {
+                let thickness = (cell_height / 8.0).round().max(1.0);
+                [x, y + cell_height - thickness, cell_width, thickness]
+            }
+            CursorShape::Bar => [x, y, (cell_width / 8.0).round().max(1.0), cell_height],
+        }
+    }
+
+    /// Append the underline, strikethrough and overline quads for the cell
+    /// at `rect` on a line of `size`, clipped to the half shown on this row
+    fn push_decorations(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+        style: &CellStyle,
+        rect: [f32; 4],
+        size: LineSize,
+        color: [f32; 4],
+    ) {
+        let [x, y, width, height] = self.layout_rect(rect, size);
+        for [dx, dy, dwidth, dheight] in Self::decoration_rects(style, x, y, width, height) {
+            let top = dy.max(rect[1]);
+            let bottom = (dy + dheight).min(rect[1] + rect[3]);
+            if bottom > top {
+                Self::push_rect(vertices, indices, [dx, top, dwidth, bottom - top], color, self.solid_uv);
+            }
+        }
+    }
+
+    /// Line quads (`[x, y, width, height]`) for a cell's text decorations:
+    /// overline at the top, strikethrough through the middle and underline
+    /// near the bottom. Framed and encircled aren't drawn yet.
+    pub fn decoration_rects(style: &CellStyle, x: f32, y: f32, cell_width: f32, cell_height: f32) -> Vec<[f32; 4]> {
+        let thickness = (cell_height / 16.0).round().max(1.0);
+        let mut rects = Vec::new();
+
+        if style.overline {
+            rects.push([x, y, cell_width, thickness]);
+        }
+        if style.strikethrough {
+            rects.push([x, y + ((cell_height - thickness) / 2.0).round(), cell_width, thickness]);
+        }
+        if style.underline {
+            rects.push([x, y + cell_height - 2.0 * thickness, cell_width, thickness]);
+        }
+
+        rects
+    }
+
+    /// Append a solid quad covering `[x, y, width, height]`, sampling the
+    /// atlas at the opaque texel `uv`
+    fn push_rect(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, rect: [f32; 4], color: [f32; 4], uv: [f32; 2]) {
+        let [x, y, width, height] = rect;
+        let base_vertex = vertices.len() as u32;
+
+        // Solid quad
+        vertices.extend_from_slice(&[
+            Vertex {
+                position: [x, y],
+                tex_coords: uv,
+                color,
+            },
+            Vertex {
+                position: [x + width, y],
+                tex_coords: uv,
+                color,
+            },
+            Vertex {
+                position: [x + width, y + height],
+                tex_coords: uv,
+                color,
+            },
+            Vertex {
+                position: [x, y + height],
+                tex_coords: uv,
+                color,
+            },
+        ]);
+
+        indices.extend_from_slice(&[
+            base_vertex, base_vertex + 1, base_vertex + 2,
+            base_vertex, base_vertex + 2, base_vertex + 3,
+        ]);
+    }
+
+    /// Replace the palette used for named colors
+    pub fn set_colors(&mut self, colors: ColorScheme) {
+        self.colors = colors;
+    }
+
+    /// Scale the alpha of cell backgrounds, leaving text opaque
+    pub fn set_background_opacity(&mut self, opacity: f32) {
+        self.background_opacity = opacity;
+    }
+
+    /// Set the cursor shape used until an application picks one
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+    }
+
+    /// Toggle drawing the cursor in the swapped colors of its cell
+    pub fn set_cursor_reverse(&mut self, reverse: bool) {
+        self.cursor_reverse = reverse;
+    }
+
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    /// Set the space left between a pane's edges and its text
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.padding = padding;
+    }
+
+    /// Set how panes scrolled back into history are marked
+    pub fn set_scroll_indicator(&mut self, style: ScrollIndicator) {
+        self.scroll_indicator = style;
+    }
+
+    /// Show or hide the cursor, for blinking
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+
+    /// Toggle drawing bold text in the bright palette variants
+    pub fn set_bold_is_bright(&mut self, bold_is_bright: bool) {
+        self.bold_is_bright = bold_is_bright;
+    }
+
+    /// RGBA for a cell's foreground, promoting the 8 standard colors to their
+    /// bright variants for bold text when `bold_is_bright` is set
+    pub fn fg_color_to_rgba(colors: &ColorScheme, color: &Color, bold: bool, bold_is_bright: bool) -> [f32; 4] {
+        if bold && bold_is_bright {
+            Self::color_to_rgba(colors, &color.brightened())
+        } else {
+            Self::color_to_rgba(colors, color)
+        }
+    }
+
+    fn color_to_rgba(colors: &ColorScheme, color: &Color) -> [f32; 4] {
         match color {
-            Color::Black => [0.0, 0.0, 0.0, 1.0],
-            Color::Red => [0.8, 0.0, 0.0, 1.0],
-            Color::Green => [0.0, 0.8, 0.0, 1.0],
-            Color::Yellow => [0.8, 0.8, 0.0, 1.0],
-            Color::Blue => [0.0, 0.0, 0.8, 1.0],
-            Color::Magenta => [0.8, 0.0, 0.8, 1.0],
-            Color::Cyan => [0.0, 0.8, 0.8, 1.0],
-            Color::White => [0.8, 0.8, 0.8, 1.0],
-            Color::BrightBlack => [0.5, 0.5, 0.5, 1.0],
-            Color::BrightRed => [1.0, 0.0, 0.0, 1.0],
-            Color::BrightGreen => [0.0, 1.0, 0.0, 1.0],
-            Color::BrightYellow => [1.0, 1.0, 0.0, 1.0],
-            Color::BrightBlue => [0.0, 0.0, 1.0, 1.0],
-            Color::BrightMagenta => [1.0, 0.0, 1.0, 1.0],
-            Color::BrightCyan => [0.0, 1.0, 1.0, 1.0],
-            Color::BrightWhite => [1.0, 1.0, 1.0, 1.0],
-            Color::Default => [0.9, 0.9, 0.9, 1.0],
+            Color::Black => colors.black,
+            Color::Red => colors.red,
+            Color::Green => colors.green,
+            Color::Yellow => colors.yellow,
+            Color::Blue => colors.blue,
+            Color::Magenta => colors.magenta,
+            Color::Cyan => colors.cyan,
+            Color::White => colors.white,
+            Color::BrightBlack => colors.bright_black,
+            Color::BrightRed => colors.bright_red,
+            Color::BrightGreen => colors.bright_green,
+            Color::BrightYellow => colors.bright_yellow,
+            Color::BrightBlue => colors.bright_blue,
+            Color::BrightMagenta => colors.bright_magenta,
+            Color::BrightCyan => colors.bright_cyan,
+            Color::BrightWhite => colors.bright_white,
+            Color::Default => colors.foreground,
             Color::Rgb(r, g, b) => [
                 *r as f32 / 255.0,
                 *g as f32 / 255.0,
@@ -3,10 +3,20 @@
 //! This module provides a client for Titi terminals to connect to the redititi
 //! automation server, enabling command injection and screen capture.
 
+use crate::redititi_server::Protocol;
+use crate::terminal::{CellChange, Grid, TerminalParser};
+use regex::Regex;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// Size of the screen `run_command` replays output onto; big enough for any
+/// pane, since published output positions the cursor absolutely
+const RUN_COMMAND_SCREEN: (usize, usize) = (512, 256);
 
 /// Client for connecting to redititi server
 pub struct ServerClient {
@@ -15,6 +25,8 @@ pub struct ServerClient {
     session_id: String,
     pane_id: String,
     authenticated: bool,
+    /// Channels subscribed to, restored by `reconnect`
+    subscriptions: HashSet<String>,
 }
 
 impl ServerClient {
@@ -33,9 +45,85 @@ impl ServerClient {
             session_id: String::new(),
             pane_id: String::new(),
             authenticated: false,
+            subscriptions: HashSet::new(),
         })
     }
 
+    /// Replace a dropped connection: connect to `addr` again, authenticate
+    /// with `token` and restore every channel subscription. Session and pane
+    /// ids are kept, so this is meant for persistent sessions.
+    pub async fn reconnect(&mut self, addr: &str, token: &str) -> Result<(), String> {
+        let fresh = Self::connect(addr).await?;
+        self.reader = fresh.reader;
+        self.writer = fresh.writer;
+        self.authenticated = false;
+
+        self.authenticate(token).await?;
+        self.resubscribe().await
+    }
+
+    /// Check the connection is alive: send `PING` and wait for `PONG`.
+    /// Replies to earlier fire-and-forget calls still in the way are skipped.
+    pub async fn ping(&self) -> Result<(), String> {
+        self.send_command("PING").await?;
+
+        let mut reader = self.reader.write().await;
+        loop {
+            let mut line = String::new();
+            let bytes_read = reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("Failed to read response: {}", e))?;
+            if bytes_read == 0 {
+                return Err("Connection closed".to_string());
+            }
+            if line.trim() == "+PONG" {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Ping the server every `interval`, reconnecting to `addr` with `token`
+    /// when no `PONG` arrives within `interval`. The task ends once `client`
+    /// is dropped.
+    pub fn spawn_keepalive(client: &Arc<RwLock<Self>>, addr: String, token: String, interval: Duration) -> JoinHandle<()> {
+        let client = Arc::downgrade(client);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(client) = client.upgrade() else {
+                    break;
+                };
+                let mut client = client.write().await;
+
+                match tokio::time::timeout(interval, client.ping()).await {
+                    Ok(Ok(())) => continue,
+                    Ok(Err(e)) => log::warn!("Keep-alive ping failed: {}", e),
+                    Err(_) => log::warn!("No PONG within {:?}", interval),
+                }
+
+                log::info!("Reconnecting to {}", addr);
+                match tokio::time::timeout(interval, client.reconnect(&addr, &token)).await {
+                    Ok(Ok(())) => log::info!("Reconnected to {}", addr),
+                    Ok(Err(e)) => log::warn!("Reconnect failed: {}", e),
+                    Err(_) => log::warn!("Reconnect to {} timed out", addr),
+                }
+            }
+        })
+    }
+
+    /// Send `SUBSCRIBE` again for every channel this client subscribed to
+    pub async fn resubscribe(&mut self) -> Result<(), String> {
+        let channels: Vec<String> = self.subscriptions.iter().cloned().collect();
+        for channel in channels {
+            self.subscribe(&channel).await?;
+        }
+        Ok(())
+    }
+
     /// Authenticate with token
     pub async fn authenticate(&mut self, token: &str) -> Result<(), String> {
         self.send_command(&format!("AUTH {}", token)).await?;
@@ -51,15 +139,27 @@ impl ServerClient {
 
     /// Create or join session
     pub async fn create_session(&mut self, name: Option<&str>) -> Result<String, String> {
+        self.create_session_with(name, false).await
+    }
+
+    /// Create a session that outlives this client's connection
+    pub async fn create_persistent_session(&mut self, name: Option<&str>) -> Result<String, String> {
+        self.create_session_with(name, true).await
+    }
+
+    async fn create_session_with(&mut self, name: Option<&str>, persistent: bool) -> Result<String, String> {
         if !self.authenticated {
             return Err("Not authenticated".to_string());
         }
 
-        let cmd = if let Some(n) = name {
+        let mut cmd = if let Some(n) = name {
             format!("CREATE SESSION {}", n)
         } else {
             "CREATE SESSION".to_string()
         };
+        if persistent {
+            cmd.push_str(" PERSISTENT");
+        }
 
         self.send_command(&cmd).await?;
         let response = self.read_response().await?;
@@ -112,23 +212,58 @@ impl ServerClient {
         }
     }
 
-    /// Subscribe to input channel
-    pub async fn subscribe_input(&mut self) -> Result<(), String> {
+    /// Subscribe to a channel
+    pub async fn subscribe(&mut self, channel: &str) -> Result<(), String> {
         if !self.authenticated {
             return Err("Not authenticated".to_string());
         }
 
-        let channel = format!("{}/pane-{}/input", self.session_id, self.pane_id);
         self.send_command(&format!("SUBSCRIBE {}", channel)).await?;
         let response = self.read_response().await?;
 
         if response.starts_with("+OK") {
+            self.subscriptions.insert(channel.to_string());
+            Ok(())
+        } else {
+            Err(format!("Failed to subscribe to {}: {}", channel, response))
+        }
+    }
+
+    /// Unsubscribe from a channel; a channel never subscribed to is left alone
+    pub async fn unsubscribe(&mut self, channel: &str) -> Result<(), String> {
+        if !self.subscriptions.contains(channel) {
+            return Ok(());
+        }
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        self.send_command(&format!("UNSUBSCRIBE {}", channel)).await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("+OK") {
+            self.subscriptions.remove(channel);
             Ok(())
         } else {
-            Err(format!("Failed to subscribe to input: {}", response))
+            Err(format!("Failed to unsubscribe from {}: {}", channel, response))
         }
     }
 
+    /// Whether this client is subscribed to `channel`
+    pub fn is_subscribed(&self, channel: &str) -> bool {
+        self.subscriptions.contains(channel)
+    }
+
+    /// Subscribe to input channel
+    pub async fn subscribe_input(&mut self) -> Result<(), String> {
+        self.subscribe(&self.pane_channel("input")).await
+    }
+
+    /// Stop receiving this pane's input
+    pub async fn unsubscribe_input(&mut self) -> Result<(), String> {
+        self.unsubscribe(&self.pane_channel("input")).await
+    }
+
     /// Publish output to channel
     pub async fn publish_output(&self, data: &str) -> Result<(), String> {
         if !self.authenticated {
@@ -156,23 +291,18 @@ impl ServerClient {
         Ok(())
     }
 
-    /// Read message from input channel (non-blocking)
+    /// Read message from input channel (non-blocking); nothing is read
+    /// unless subscribed to it
     pub async fn read_input(&mut self) -> Result<Option<String>, String> {
         if !self.authenticated {
             return Err("Not authenticated".to_string());
         }
 
-        let channel = format!("{}/pane-{}/input", self.session_id, self.pane_id);
-        self.send_command(&format!("RPOP {}", channel)).await?;
-        let response = self.read_response().await?;
-
-        if response.starts_with("-ERR") {
-            Ok(None)  // Queue empty
-        } else if let Some(msg) = response.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
-            Ok(Some(msg.to_string()))
-        } else {
-            Ok(None)
+        let channel = self.pane_channel("input");
+        if !self.is_subscribed(&channel) {
+            return Ok(None);
         }
+        self.rpop(&channel).await
     }
 
     /// Inject command into a terminal (for external clients controlling terminals)
@@ -200,39 +330,263 @@ impl ServerClient {
         self.inject_command(&session_id, &pane_id, command).await
     }
 
+    /// Write `bytes` to `target`'s (`session-id/pane-N`) terminal exactly as
+    /// given, e.g. `b"\x03"` for Ctrl+C; unlike `inject_command` no Enter
+    /// is added
+    pub async fn inject_raw(&self, target: &str, bytes: &[u8]) -> Result<(), String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        self.send_command(&format!("INJECTRAW {} HEX {}", target, hex)).await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("+OK") {
+            Ok(())
+        } else {
+            Err(format!("Failed to inject bytes: {}", response))
+        }
+    }
+
+    /// Draw `text` on `target`'s (`session-id/pane-N`) screen, escape
+    /// sequences included, without sending it to the shell. The text must
+    /// fit on one protocol line.
+    pub async fn write_to_screen(&self, target: &str, text: &str) -> Result<(), String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+        if text.contains(['\r', '\n']) {
+            return Err("Screen text can't contain line breaks".to_string());
+        }
+
+        self.send_command(&format!("WRITE {} {}", target, text)).await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("+OK") {
+            Ok(())
+        } else {
+            Err(format!("Failed to write to screen: {}", response))
+        }
+    }
+
+    /// Inject `command` into `target` (`session-id/pane-N`), then replay the
+    /// pane's output channel onto a local screen until one of its lines
+    /// matches the `wait_for` regex. Returns the screen text at that point.
+    ///
+    /// Output already queued before the call is replayed too, so drain the
+    /// channel first if an earlier match must not count.
+    pub async fn run_command(&self, target: &str, command: &str, wait_for: &str, timeout: Duration) -> Result<String, String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+        let pattern = Regex::new(wait_for).map_err(|e| format!("Invalid pattern: {}", e))?;
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        self.send_command(&format!("INJECT {} {}", target, command)).await?;
+        let response = self.read_response().await?;
+        if !response.starts_with("+OK") {
+            return Err(format!("Failed to inject command: {}", response));
+        }
+
+        // Output is published as the escape sequences redrawing changed
+        // cells, so the pane's lines only exist once they are replayed
+        let screen = Arc::new(Mutex::new(Grid::new(RUN_COMMAND_SCREEN.0, RUN_COMMAND_SCREEN.1)));
+        let mut parser = TerminalParser::new(screen.clone());
+        let channel = format!("{}/output", target);
+        loop {
+            let payload = self.rpop(&channel).await?;
+            if let Some(payload) = &payload {
+                parser.parse(&CellChange::decode(payload));
+            }
+
+            let lines: Vec<String> = screen.lock().unwrap().visible_lines().collect();
+            if lines.iter().any(|line| pattern.is_match(line)) {
+                return Ok(lines.join("\n").trim_end().to_string());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(format!("Timed out after {:?} waiting for /{}/", timeout, wait_for));
+            }
+            if payload.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+
     /// Subscribe to output channel to read terminal output
     pub async fn subscribe_output(&mut self) -> Result<(), String> {
+        self.subscribe(&self.pane_channel("output")).await
+    }
+
+    /// Stop receiving this pane's output
+    pub async fn unsubscribe_output(&mut self) -> Result<(), String> {
+        self.unsubscribe(&self.pane_channel("output")).await
+    }
+
+    /// Read message from output channel (non-blocking); nothing is read
+    /// unless subscribed to it
+    pub async fn read_output(&mut self) -> Result<Option<String>, String> {
         if !self.authenticated {
             return Err("Not authenticated".to_string());
         }
 
-        let channel = format!("{}/pane-{}/output", self.session_id, self.pane_id);
-        self.send_command(&format!("SUBSCRIBE {}", channel)).await?;
+        let channel = self.pane_channel("output");
+        if !self.is_subscribed(&channel) {
+            return Ok(None);
+        }
+        self.rpop(&channel).await
+    }
+
+    /// Subscribe to every channel matching a glob pattern (e.g. `*/output`)
+    pub async fn psubscribe(&self, pattern: &str) -> Result<(), String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        self.send_command(&format!("PSUBSCRIBE {}", pattern)).await?;
         let response = self.read_response().await?;
 
         if response.starts_with("+OK") {
             Ok(())
         } else {
-            Err(format!("Failed to subscribe to output: {}", response))
+            Err(format!("Failed to subscribe to pattern: {}", response))
         }
     }
 
-    /// Read message from output channel (non-blocking)
-    pub async fn read_output(&mut self) -> Result<Option<String>, String> {
+    /// Remove a pattern subscription
+    pub async fn punsubscribe(&self, pattern: &str) -> Result<(), String> {
         if !self.authenticated {
             return Err("Not authenticated".to_string());
         }
 
-        let channel = format!("{}/pane-{}/output", self.session_id, self.pane_id);
-        self.send_command(&format!("RPOP {}", channel)).await?;
+        self.send_command(&format!("PUNSUBSCRIBE {}", pattern)).await?;
         let response = self.read_response().await?;
 
-        if response.starts_with("-ERR") {
-            Ok(None)  // Queue empty
-        } else if let Some(msg) = response.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
-            Ok(Some(msg.to_string()))
+        if response.starts_with("+OK") {
+            Ok(())
         } else {
-            Ok(None)
+            Err(format!("Failed to unsubscribe from pattern: {}", response))
+        }
+    }
+
+    /// Read the next message delivered to a pattern subscription (non-blocking)
+    ///
+    /// Returns the concrete channel the message was published to and its content.
+    pub async fn read_pattern(&self, pattern: &str) -> Result<Option<(String, String)>, String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        self.send_command(&format!("PRPOP {}", pattern)).await?;
+        let response = self.read_response().await?;
+
+        if !response.starts_with('{') {
+            return Ok(None); // Queue empty
+        }
+
+        let value: serde_json::Value = serde_json::from_str(&response)
+            .map_err(|e| format!("Invalid pattern message: {}", e))?;
+        match (value["channel"].as_str(), value["message"].as_str()) {
+            (Some(channel), Some(message)) => Ok(Some((channel.to_string(), message.to_string()))),
+            _ => Err(format!("Invalid pattern message: {}", response)),
+        }
+    }
+
+    /// Ask a terminal to save a snapshot of its screen to `path`
+    ///
+    /// `path` is resolved inside the terminal's capture directory; `ansi`
+    /// keeps colors and attributes as escape sequences.
+    pub async fn capture_to_file(&self, session_id: &str, pane_id: &str, path: &str, ansi: bool) -> Result<(), String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        let target = format!("{}/pane-{}", session_id, pane_id);
+        let format = if ansi { "ANSI" } else { "TEXT" };
+        self.send_command(&format!("CAPTURE {} FILE {} {}", target, path, format)).await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with('{') {
+            Ok(())
+        } else {
+            Err(format!("Failed to request capture: {}", response))
+        }
+    }
+
+    /// Read the next capture request for this client's pane (non-blocking)
+    pub async fn read_capture_request(&self) -> Result<Option<String>, String> {
+        let session_id = self.session_id.clone();
+        let pane_id = self.pane_id.clone();
+        self.read_from_channel(&session_id, &pane_id, "capture-request").await
+    }
+
+    /// List the sessions known to the server
+    pub async fn list_sessions(&self) -> Result<Vec<String>, String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        self.send_command("LIST SESSIONS").await?;
+        let response = self.read_response().await?;
+
+        serde_json::from_str(&response).map_err(|_| format!("Failed to list sessions: {}", response))
+    }
+
+    /// Rename a session; its id and channels stay the same
+    pub async fn rename_session(&self, session_id: &str, name: &str) -> Result<(), String> {
+        self.rename(&format!("RENAME SESSION {} {}", session_id, name)).await
+    }
+
+    /// Rename a pane of a session, keeping its id
+    pub async fn rename_pane(&self, session_id: &str, pane_id: &str, name: &str) -> Result<(), String> {
+        self.rename(&format!("RENAME PANE {} {} {}", session_id, pane_id, name)).await
+    }
+
+    /// Close a session along with its panes and channels
+    pub async fn close_session(&self, session_id: &str) -> Result<(), String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        self.send_command(&format!("CLOSE SESSION {}", session_id)).await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("+OK") {
+            Ok(())
+        } else {
+            Err(format!("Failed to close session: {}", response))
+        }
+    }
+
+    async fn rename(&self, cmd: &str) -> Result<(), String> {
+        if !self.authenticated {
+            return Err("Not authenticated".to_string());
+        }
+
+        self.send_command(cmd).await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("+OK") {
+            Ok(())
+        } else {
+            Err(format!("Failed to rename: {}", response))
+        }
+    }
+
+    /// Disconnect without removing the sessions and panes this client
+    /// created. The server closes the connection after replying.
+    pub async fn detach(&mut self) -> Result<(), String> {
+        self.send_command("DETACH").await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("+OK") {
+            self.authenticated = false;
+            Ok(())
+        } else {
+            Err(format!("Failed to detach: {}", response))
         }
     }
 
@@ -258,29 +612,81 @@ impl ServerClient {
         }
 
         let channel = format!("{}/pane-{}/{}", session_id, pane_id, channel_type);
+        self.rpop(&channel).await
+    }
+
+    /// Send several commands in one write and read their responses, in
+    /// order. Responses are read while the batch is still being written, so
+    /// large batches can't stall on full socket buffers.
+    ///
+    /// Responses to earlier fire-and-forget calls (e.g. `publish_output`)
+    /// that were never read come first and would be misattributed.
+    pub async fn pipeline(&self, commands: &[&str]) -> Result<Vec<String>, String> {
+        let (_, responses) = tokio::try_join!(self.send_batch(commands), self.read_responses(commands.len()))?;
+        Ok(responses)
+    }
+
+    /// Send several commands in one write without waiting for responses;
+    /// read them afterwards with `read_responses`
+    pub async fn send_batch(&self, commands: &[&str]) -> Result<(), String> {
+        let mut batch = String::new();
+        for cmd in commands {
+            batch.push_str(cmd);
+            batch.push('\n');
+        }
+
+        let mut writer = self.writer.write().await;
+        writer
+            .write_all(batch.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send batch: {}", e))
+    }
+
+    /// Read the next `count` responses
+    pub async fn read_responses(&self, count: usize) -> Result<Vec<String>, String> {
+        let mut reader = self.reader.write().await;
+        let mut responses = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut line = String::new();
+            let bytes_read = reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("Failed to read response: {}", e))?;
+            if bytes_read == 0 {
+                return Err(format!("Connection closed after {} of {} responses", responses.len(), count));
+            }
+            responses.push(line.trim().to_string());
+        }
+        Ok(responses)
+    }
+
+    // Helper methods
+    /// Pop the oldest message queued on `channel`, if any
+    async fn rpop(&self, channel: &str) -> Result<Option<String>, String> {
         self.send_command(&format!("RPOP {}", channel)).await?;
         let response = self.read_response().await?;
 
-        if response.starts_with("-ERR") {
-            Ok(None)  // Queue empty
-        } else if let Some(msg) = response.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
-            Ok(Some(msg.to_string()))
+        // Messages come back quoted; an empty queue answers the unquoted
+        // nil reply (or an error for unknown channels)
+        if response.starts_with('"') {
+            Ok(Some(Protocol::parse_quoted_string(&response)))
         } else {
             Ok(None)
         }
     }
 
-    // Helper methods
+    fn pane_channel(&self, channel_type: &str) -> String {
+        format!("{}/pane-{}/{}", self.session_id, self.pane_id, channel_type)
+    }
+
     async fn send_command(&self, cmd: &str) -> Result<(), String> {
+        // One write per command: a separate newline write would sit behind
+        // Nagle's algorithm until the server's delayed ACK
         let mut writer = self.writer.write().await;
         writer
-            .write_all(cmd.as_bytes())
+            .write_all(format!("{}\n", cmd).as_bytes())
             .await
             .map_err(|e| format!("Failed to send command: {}", e))?;
-        writer
-            .write_all(b"\n")
-            .await
-            .map_err(|e| format!("Failed to send newline: {}", e))?;
         Ok(())
     }
 
@@ -1,7 +1,13 @@
+use super::mouse::MouseTracking;
 use super::Color;
-use std::collections::{HashSet, VecDeque};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CellStyle {
     pub fg: Color,
     pub bg: Color,
@@ -10,6 +16,11 @@ pub struct CellStyle {
     pub underline: bool,
     pub strikethrough: bool,
     pub inverse: bool,
+    pub overline: bool,
+    /// SGR 51; stored but not drawn yet
+    pub framed: bool,
+    /// SGR 52; stored but not drawn yet
+    pub encircled: bool,
 }
 
 impl Default for CellStyle {
@@ -22,11 +33,14 @@ impl Default for CellStyle {
             underline: false,
             strikethrough: false,
             inverse: false,
+            overline: false,
+            framed: false,
+            encircled: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cell {
     pub c: char,
     pub style: CellStyle,
@@ -41,14 +55,277 @@ impl Default for Cell {
     }
 }
 
+/// How the cursor is drawn (DECSCUSR); blinking variants are drawn steady
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+}
+
+/// Size of a line's glyphs (DECSWL, DECDWL, DECDHL). Every size but
+/// `Single` doubles the width of the line's cells, so only the left half
+/// of its columns is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineSize {
+    #[default]
+    Single,
+    DoubleWidth,
+    /// Upper half of double-height text, repeated on the next line as
+    /// `DoubleHeightBottom`
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    pub fn is_double_width(self) -> bool {
+        self != LineSize::Single
+    }
+
+    pub fn is_double_height(self) -> bool {
+        matches!(self, LineSize::DoubleHeightTop | LineSize::DoubleHeightBottom)
+    }
+}
+
+/// One cell rewritten since the last `Grid::take_changes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellChange {
+    pub row: usize,
+    pub col: usize,
+    pub c: char,
+    pub style: CellStyle,
+}
+
+impl CellChange {
+    /// Serialize changes as the escape sequences that redraw them: a cursor
+    /// position per run of adjacent cells, minimal SGR transitions, and the
+    /// characters. `%` and whitespace are percent-encoded so the payload
+    /// survives the whitespace-separated server protocol.
+    pub fn encode(changes: &[CellChange]) -> String {
+        let mut ansi = String::new();
+        let mut style = CellStyle::default();
+        let mut next: Option<(usize, usize)> = None;
+        for change in changes {
+            if next != Some((change.row, change.col)) {
+                ansi.push_str(&format!("\x1b[{};{}H", change.row + 1, change.col + 1));
+            }
+            if change.style != style {
+                ansi.push_str(&Grid::sgr(&style, &change.style));
+                style = change.style;
+            }
+            ansi.push(change.c);
+            next = Some((change.row, change.col + 1));
+        }
+        if style != CellStyle::default() {
+            ansi.push_str("\x1b[0m");
+        }
+
+        let mut encoded = String::with_capacity(ansi.len());
+        for c in ansi.chars() {
+            if c == '%' || c.is_whitespace() {
+                let mut utf8 = [0; 4];
+                for byte in c.encode_utf8(&mut utf8).bytes() {
+                    encoded.push_str(&format!("%{:02X}", byte));
+                }
+            } else {
+                encoded.push(c);
+            }
+        }
+        encoded
+    }
+
+    /// Undo the percent-encoding of `encode`, giving bytes to feed a
+    /// `TerminalParser`
+    pub fn decode(payload: &str) -> Vec<u8> {
+        let bytes = payload.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = bytes
+                .get(i + 1..i + 3)
+                .filter(|_| bytes[i] == b'%')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        decoded
+    }
+}
+
+/// Output format for `Grid::capture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// Plain text, trailing whitespace trimmed
+    Text,
+    /// Text with SGR escape sequences preserving colors and attributes
+    Ansi,
+}
+
+/// Which part of the terminal `Grid::capture` snapshots. Serialized as the
+/// capture request sent to terminals, e.g. `{"mode":"REGION","x":0,...}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "UPPERCASE")]
+pub enum CaptureMode {
+    /// Scrollback history followed by the screen
+    Full,
+    /// The screen
+    Visible,
+    /// The cursor's row
+    Line,
+    /// `width` x `height` cells from column `x`, row `y` of the screen,
+    /// clipped to it
+    Region { x: usize, y: usize, width: usize, height: usize },
+    /// The last `lines` lines of scrollback history
+    Scrollback { lines: usize },
+}
+
+impl CaptureMode {
+    /// Parse the protocol form: `FULL`, `VISIBLE`, `LINE`, `REGION x y w h`
+    /// or `SCROLLBACK n` (keywords are case-insensitive)
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        let number = |i: usize, name: &str| -> Result<usize, String> {
+            let arg = args.get(i).ok_or_else(|| format!("missing {}", name))?;
+            arg.parse().map_err(|_| format!("invalid {}: {}", name, arg))
+        };
+
+        let keyword = args.first().map(|arg| arg.to_uppercase());
+        let (mode, arity) = match keyword.as_deref() {
+            Some("FULL") => (CaptureMode::Full, 1),
+            Some("VISIBLE") => (CaptureMode::Visible, 1),
+            Some("LINE") => (CaptureMode::Line, 1),
+            Some("REGION") => (
+                CaptureMode::Region {
+                    x: number(1, "x")?,
+                    y: number(2, "y")?,
+                    width: number(3, "width")?,
+                    height: number(4, "height")?,
+                },
+                5,
+            ),
+            Some("SCROLLBACK") => (CaptureMode::Scrollback { lines: number(1, "line count")? }, 2),
+            Some(other) => return Err(format!("unknown capture mode: {}", other)),
+            None => return Err("missing capture mode".to_string()),
+        };
+
+        if args.len() > arity {
+            return Err(format!("unexpected argument: {}", args[arity]));
+        }
+        Ok(mode)
+    }
+}
+
+/// Scrollback lines kept by `Grid::new`
+pub const DEFAULT_SCROLLBACK: usize = 10000;
+
+/// Longest a synchronized update (DECSET 2026) holds back frames, in case
+/// the application never ends it
+pub const SYNCHRONIZED_OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Values kept per private mode by XTSAVE (`CSI ? Pm s`); older saves are
+/// dropped beyond this
+pub const SAVED_PRIVATE_MODES_DEPTH: usize = 16;
+
+/// Characters that end a word for double-click selection, unless
+/// configured otherwise
+pub const DEFAULT_WORD_SEPARATORS: &str = " \t,│`|:\"'()[]{}<>";
+
+lazy_static::lazy_static! {
+    /// Plain-text links: a scheme or `www.`, then anything up to whitespace
+    /// or a character that can't appear unescaped in a URL
+    static ref URL_PATTERN: Regex = Regex::new(r#"\b(?:https?://|file://|www\.)[^\s<>"'`{}|\\^]+"#).unwrap();
+}
+
+/// Cells from `start` to `end` inclusive in reading order, as (col, row)
+/// positions on the visible screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Selection {
+    pub fn contains(&self, col: usize, row: usize) -> bool {
+        let (start_col, start_row) = self.start;
+        let (end_col, end_row) = self.end;
+        (start_row..=end_row).contains(&row)
+            && (row != start_row || col >= start_col)
+            && (row != end_row || col <= end_col)
+    }
+}
+
+/// Complete state of a `Grid` from `Grid::snapshot`: screen contents,
+/// cursor, scroll region and modes, and optionally scrollback
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridSnapshot {
+    cols: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+    wrapped: Vec<bool>,
+    line_sizes: Vec<LineSize>,
+    cursor: (usize, usize),
+    #[serde(default)]
+    pending_wrap: bool,
+    saved_cursor: (usize, usize),
+    current_style: CellStyle,
+    cursor_visible: bool,
+    scroll_region: (usize, usize),
+    bracketed_paste: bool,
+    autowrap: bool,
+    title: Option<String>,
+    current_dir: Option<PathBuf>,
+    mouse_tracking: MouseTracking,
+    sgr_mouse: bool,
+    focus_reporting: bool,
+    cursor_shape: Option<CursorShape>,
+    alternate_scroll: bool,
+    /// The normal screen, if the snapshot was taken on the alternate screen
+    primary_screen: Option<Box<GridSnapshot>>,
+    /// `None` unless taken with `Grid::snapshot_with_scrollback`
+    scrollback: Option<Vec<Vec<Cell>>>,
+}
+
+impl GridSnapshot {
+    /// (cols, rows) of the grid the snapshot was taken from
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn cursor_pos(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    /// Whether restoring replaces the scrollback too
+    pub fn has_scrollback(&self) -> bool {
+        self.scrollback.is_some()
+    }
+}
+
 pub struct Grid {
     cells: Vec<Cell>,
     cols: usize,
     rows: usize,
     cursor_x: usize,
     cursor_y: usize,
+    // The last glyph filled the right margin: the cursor stays on the last
+    // column and the next printable starts a new line (deferred wrap)
+    pending_wrap: bool,
     current_style: CellStyle,
-    _cursor_visible: bool,
+    cursor_visible: bool, // DECTCEM (DECSET 25)
     scroll_top: usize,
     scroll_bottom: usize,
     saved_cursor: (usize, usize),
@@ -59,10 +336,37 @@ pub struct Grid {
     // Dirty tracking for performance
     dirty_cells: HashSet<(usize, usize)>, // (col, row) of dirty cells
     all_dirty: bool, // True if entire screen needs redraw
+    bracketed_paste: bool, // DECSET 2004
+    autowrap: bool, // DECAWM (DECSET 7)
+    title: Option<String>, // OSC 0 / OSC 2
+    current_dir: Option<PathBuf>, // OSC 7
+    mouse_tracking: MouseTracking, // DECSET 1000 / 1002
+    sgr_mouse: bool, // DECSET 1006
+    focus_reporting: bool, // DECSET 1004
+    cursor_shape: Option<CursorShape>, // DECSCUSR; None = configured default
+    // wrapped[row] = the row's text continues on the next row (autowrap)
+    wrapped: Vec<bool>,
+    line_sizes: Vec<LineSize>,
+    selection: Option<Selection>,
+    hovered_url: Option<Selection>,
+    // The normal screen while the alternate screen is shown
+    // (DECSET 1049 / 1047 / 47)
+    primary_screen: Option<Box<GridSnapshot>>,
+    alternate_scroll: bool, // DECSET 1007
+    // Start of the synchronized update in progress (DECSET 2026)
+    frozen_since: Option<Instant>,
+    // Private mode values saved by XTSAVE, most recent last
+    saved_private_modes: HashMap<u16, Vec<bool>>,
 }
 
 impl Grid {
     pub fn new(cols: usize, rows: usize) -> Self {
+        Self::with_scrollback(cols, rows, DEFAULT_SCROLLBACK)
+    }
+
+    /// Create a grid keeping at most `max_scrollback` lines of history
+    /// (0 disables scrollback)
+    pub fn with_scrollback(cols: usize, rows: usize, max_scrollback: usize) -> Self {
         let cells = vec![Cell::default(); cols * rows];
         Self {
             cells,
@@ -70,53 +374,112 @@ impl Grid {
             rows,
             cursor_x: 0,
             cursor_y: 0,
+            pending_wrap: false,
             current_style: CellStyle::default(),
-            _cursor_visible: true,
+            cursor_visible: true,
             scroll_top: 0,
             scroll_bottom: rows - 1,
             saved_cursor: (0, 0),
             scrollback: VecDeque::new(),
-            max_scrollback: 10000, // Store up to 10000 lines
+            max_scrollback,
             scroll_offset: 0,
             dirty_cells: HashSet::new(),
             all_dirty: true, // Start with full redraw
+            bracketed_paste: false,
+            autowrap: true,
+            title: None,
+            current_dir: None,
+            mouse_tracking: MouseTracking::Off,
+            sgr_mouse: false,
+            focus_reporting: false,
+            cursor_shape: None,
+            wrapped: vec![false; rows],
+            line_sizes: vec![LineSize::Single; rows],
+            selection: None,
+            hovered_url: None,
+            primary_screen: None,
+            alternate_scroll: false,
+            frozen_since: None,
+            saved_private_modes: HashMap::new(),
         }
     }
 
+    /// Change the size of the screen. On the primary screen the cursor's line
+    /// stays put relative to the text around it: shrinking moves the rows it
+    /// would push off the top into scrollback, and growing pulls lines back
+    /// from scrollback above it. The saved cursor moves with the text and,
+    /// like the cursor, is clamped to the new size; the scroll region is
+    /// reset to the whole screen.
     pub fn resize(&mut self, cols: usize, rows: usize) {
-        let mut new_cells = vec![Cell::default(); cols * rows];
-
-        let min_rows = self.rows.min(rows);
-        let min_cols = self.cols.min(cols);
-
-        for y in 0..min_rows {
-            for x in 0..min_cols {
-                let old_idx = y * self.cols + x;
-                let new_idx = y * cols + x;
-                new_cells[new_idx] = self.cells[old_idx].clone();
+        if self.primary_screen.is_none() {
+            if rows < self.rows {
+                let pushed = (self.cursor_y + 1).saturating_sub(rows);
+                for row in 0..pushed {
+                    let line = self.cells[row * self.cols..(row + 1) * self.cols].to_vec();
+                    self.push_scrollback(line);
+                }
+                self.remove_top_rows(pushed);
+            } else {
+                let pulled = (rows - self.rows).min(self.scrollback.len());
+                let lines: Vec<Vec<Cell>> = (0..pulled).filter_map(|_| self.scrollback.pop_back()).collect();
+                self.insert_top_rows(lines.into_iter().rev());
             }
+            self.scroll_offset = self.scroll_offset.min(self.scrollback.len());
         }
 
-        self.cells = new_cells;
+        self.cells = resize_cells(&self.cells, (self.cols, self.rows), (cols, rows));
+        // Lines don't reflow, so a shrink or grow breaks any wrap
+        self.wrapped = vec![false; rows];
+        self.line_sizes.resize(rows, LineSize::Single);
+        self.selection = None;
+        self.hovered_url = None;
         self.cols = cols;
         self.rows = rows;
         self.cursor_x = self.cursor_x.min(cols - 1);
         self.cursor_y = self.cursor_y.min(rows - 1);
+        self.saved_cursor = clamp_to_screen(self.saved_cursor, cols, rows);
+        if let Some(primary) = &mut self.primary_screen {
+            primary.cursor = clamp_to_screen(primary.cursor, cols, rows);
+            primary.saved_cursor = clamp_to_screen(primary.saved_cursor, cols, rows);
+        }
+        self.pending_wrap = false;
+        self.scroll_top = 0;
         self.scroll_bottom = rows - 1;
 
         // Mark all as dirty after resize
         self.all_dirty = true;
     }
 
+    /// Drop the top `count` rows, moving the rest of the screen up; the grid
+    /// is `count` rows shorter until it is resized
+    fn remove_top_rows(&mut self, count: usize) {
+        self.cells.drain(..count * self.cols);
+        self.line_sizes.drain(..count);
+        self.rows -= count;
+        self.cursor_y -= count;
+        self.saved_cursor.1 = self.saved_cursor.1.saturating_sub(count);
+    }
+
+    /// Put `lines` (cut or padded to the width) above the screen, moving it
+    /// down; the grid grows by one row per line until it is resized
+    fn insert_top_rows(&mut self, lines: impl Iterator<Item = Vec<Cell>>) {
+        let cols = self.cols;
+        let top: Vec<Cell> = lines
+            .flat_map(|mut line| {
+                line.resize(cols, Cell::default());
+                line
+            })
+            .collect();
+        let count = top.len() / cols.max(1);
+        self.cells.splice(..0, top);
+        self.line_sizes.splice(..0, std::iter::repeat_n(LineSize::Single, count));
+        self.rows += count;
+        self.cursor_y += count;
+        self.saved_cursor.1 += count;
+    }
+
     pub fn put_char(&mut self, c: char) {
-        if self.cursor_x >= self.cols {
-            self.cursor_x = 0;
-            self.cursor_y += 1;
-            if self.cursor_y > self.scroll_bottom {
-                self.scroll_up(1);
-                self.cursor_y = self.scroll_bottom;
-            }
-        }
+        self.wrap_if_pending();
 
         let idx = self.cursor_y * self.cols + self.cursor_x;
         if idx < self.cells.len() {
@@ -126,8 +489,35 @@ impl Grid {
             };
             // Mark cell as dirty
             self.dirty_cells.insert((self.cursor_x, self.cursor_y));
+            self.invalidate_selection((self.cursor_x, self.cursor_y), (self.cursor_x, self.cursor_y));
+        }
+        self.advance_cursor();
+    }
+
+    /// Start a new line if the previous glyph filled the right margin. If
+    /// autowrap was turned off since, the glyph overwrites the last column.
+    fn wrap_if_pending(&mut self) {
+        if !std::mem::take(&mut self.pending_wrap) || !self.autowrap {
+            return;
+        }
+
+        self.wrapped[self.cursor_y] = true;
+        self.cursor_x = 0;
+        self.cursor_y += 1;
+        if self.cursor_y > self.scroll_bottom {
+            self.scroll_up(1);
+            self.cursor_y = self.scroll_bottom;
+        }
+    }
+
+    /// Step past a written glyph. At the right margin the cursor stays on
+    /// the last column, leaving a wrap pending if autowrap is on.
+    fn advance_cursor(&mut self) {
+        if self.cursor_x + 1 < self.line_cols(self.cursor_y) {
+            self.cursor_x += 1;
+        } else {
+            self.pending_wrap = self.autowrap;
         }
-        self.cursor_x += 1;
     }
 
     /// Bulk write text without per-character dirty tracking (much faster for large outputs)
@@ -138,14 +528,7 @@ impl Grid {
 
         for c in text.chars() {
             // Check for wrapping before writing
-            if self.cursor_x >= self.cols {
-                self.cursor_x = 0;
-                self.cursor_y += 1;
-                if self.cursor_y > self.scroll_bottom {
-                    self.scroll_up(1);
-                    self.cursor_y = self.scroll_bottom;
-                }
-            }
+            self.wrap_if_pending();
 
             let idx = self.cursor_y * self.cols + self.cursor_x;
             if idx < self.cells.len() {
@@ -155,12 +538,15 @@ impl Grid {
                 };
                 chars_written += 1;
             }
-            self.cursor_x += 1;
+            self.advance_cursor();
         }
 
         // Batch mark affected rows as dirty instead of per-character tracking
+        // A scroll while writing leaves the cursor above where it started,
+        // but then scroll_up has already invalidated the moved rows
         let end_row = self.cursor_y;
-        if end_row - start_row > 5 {
+        self.invalidate_selection((0, start_row.min(end_row)), (self.cols - 1, end_row));
+        if end_row.saturating_sub(start_row) > 5 {
             // If many rows affected, just mark all dirty
             self.all_dirty = true;
         } else {
@@ -183,13 +569,16 @@ impl Grid {
         }
         // In Unix terminals, newline typically includes carriage return
         self.cursor_x = 0;
+        self.pending_wrap = false;
     }
 
     pub fn carriage_return(&mut self) {
         self.cursor_x = 0;
+        self.pending_wrap = false;
     }
 
     pub fn backspace(&mut self) {
+        self.pending_wrap = false;
         if self.cursor_x > 0 {
             self.cursor_x -= 1;
         }
@@ -197,41 +586,114 @@ impl Grid {
 
     pub fn tab(&mut self) {
         let next_tab = ((self.cursor_x / 8) + 1) * 8;
-        self.cursor_x = next_tab.min(self.cols - 1);
+        self.cursor_x = next_tab.min(self.line_cols(self.cursor_y) - 1);
+        self.pending_wrap = false;
     }
 
     pub fn set_cursor(&mut self, x: usize, y: usize) {
-        self.cursor_x = x.min(self.cols - 1);
         self.cursor_y = y.min(self.rows - 1);
+        self.cursor_x = x.min(self.line_cols(self.cursor_y) - 1);
+        self.pending_wrap = false;
     }
 
     pub fn move_cursor(&mut self, dx: i32, dy: i32) {
-        let new_x = (self.cursor_x as i32 + dx).clamp(0, self.cols as i32 - 1) as usize;
         let new_y = (self.cursor_y as i32 + dy).clamp(0, self.rows as i32 - 1) as usize;
+        let new_x = (self.cursor_x as i32 + dx).clamp(0, self.line_cols(new_y) as i32 - 1) as usize;
         self.cursor_x = new_x;
         self.cursor_y = new_y;
+        self.pending_wrap = false;
     }
 
-    pub fn clear_screen(&mut self) {
-        for cell in &mut self.cells {
-            *cell = Cell::default();
+    /// Columns usable on `row`: half the width on double-size lines
+    fn line_cols(&self, row: usize) -> usize {
+        if self.line_sizes.get(row).is_some_and(|size| size.is_double_width()) {
+            (self.cols / 2).max(1)
+        } else {
+            self.cols
+        }
+    }
+
+    /// Size of the glyphs on the displayed `row`; lines in the scrollback
+    /// are always drawn at single size
+    pub fn line_size(&self, row: usize) -> LineSize {
+        if self.scroll_offset > 0 {
+            return LineSize::Single;
+        }
+        self.line_sizes.get(row).copied().unwrap_or_default()
+    }
+
+    /// Set the size of the cursor's line (DECSWL, DECDWL, DECDHL), moving
+    /// the cursor back inside the line's narrower margin if needed
+    pub fn set_line_size(&mut self, size: LineSize) {
+        let row = self.cursor_y;
+        let Some(line_size) = self.line_sizes.get_mut(row) else {
+            return;
+        };
+        if *line_size == size {
+            return;
+        }
+
+        *line_size = size;
+        self.cursor_x = self.cursor_x.min(self.line_cols(row) - 1);
+        self.pending_wrap = false;
+        self.mark_rows_dirty(row..row + 1);
+    }
+
+    /// Cell used to blank erased and scrolled-in areas: a space carrying
+    /// the current background color, with foreground and attributes reset
+    fn blank_cell(&self) -> Cell {
+        Cell {
+            c: ' ',
+            style: CellStyle {
+                bg: self.current_style.bg,
+                ..CellStyle::default()
+            },
         }
+    }
+
+    pub fn clear_screen(&mut self) {
+        let blank = self.blank_cell();
+        self.cells.fill(blank);
+        self.wrapped.fill(false);
+        self.line_sizes.fill(LineSize::Single);
+        self.selection = None;
         // Mark all as dirty
         self.all_dirty = true;
     }
 
     pub fn clear_line(&mut self) {
+        let blank = self.blank_cell();
         let start = self.cursor_y * self.cols;
         let end = start + self.cols;
         for idx in start..end {
             if idx < self.cells.len() {
-                self.cells[idx] = Cell::default();
+                self.cells[idx] = blank;
             }
         }
+        if let Some(wrapped) = self.wrapped.get_mut(self.cursor_y) {
+            *wrapped = false;
+        }
         // Mark entire line as dirty
         for x in 0..self.cols {
             self.dirty_cells.insert((x, self.cursor_y));
         }
+        self.invalidate_selection((0, self.cursor_y), (self.cols - 1, self.cursor_y));
+    }
+
+    /// Blank `n` cells from the cursor rightward without moving the cursor
+    /// or shifting the rest of the line (`CSI n X`)
+    pub fn erase_chars(&mut self, n: usize) {
+        let line_cols = self.line_cols(self.cursor_y);
+        let start = self.cursor_x;
+        let end = (start + n.max(1)).min(line_cols);
+
+        let blank = self.blank_cell();
+        let row = self.cursor_y * self.cols;
+        self.cells[row + start..row + end].fill(blank);
+        for x in start..end {
+            self.dirty_cells.insert((x, self.cursor_y));
+        }
+        self.invalidate_selection((start, self.cursor_y), (end - 1, self.cursor_y));
     }
 
     pub fn scroll_up(&mut self, lines: usize) {
@@ -243,8 +705,9 @@ impl Grid {
             return;
         }
 
-        // Save scrolled lines to scrollback (if scrolling from top)
-        if start_row == 0 {
+        // Save scrolled lines to scrollback (if scrolling from top); the
+        // alternate screen has no history
+        if start_row == 0 && self.max_scrollback > 0 && self.primary_screen.is_none() {
             for i in 0..lines {
                 let mut line = Vec::with_capacity(self.cols);
                 let row_start = i * self.cols;
@@ -255,42 +718,125 @@ impl Grid {
                     line.extend_from_slice(&self.cells[row_start..row_end]);
                 }
 
-                self.scrollback.push_back(line);
-
-                // O(1) pop from front instead of O(n) remove(0)
-                if self.scrollback.len() > self.max_scrollback {
-                    self.scrollback.pop_front();
-                }
+                self.push_scrollback(line);
             }
         }
 
-        // Bulk move rows using copy_within (single operation instead of nested loops!)
-        // This replaces the O(n³) nested loop with O(n) operation
-        let src_start = (start_row + lines) * self.cols;
-        let src_end = end_row * self.cols;
-        let dst_start = start_row * self.cols;
+        self.shift_rows_up(start_row, lines);
 
-        if src_end <= self.cells.len() {
-            // Single memory copy operation - MUCH faster than nested loops
-            self.cells.copy_within(src_start..src_end, dst_start);
+        // Reset scroll offset when new content arrives
+        if self.scroll_offset != 0 {
+            self.scroll_offset = 0;
+            self.selection = None;
+            self.all_dirty = true;
         }
+    }
 
-        // Clear bottom rows (bulk operation)
-        let clear_start = (end_row - lines) * self.cols;
-        let clear_end = end_row * self.cols;
+    /// Append a line to the history, dropping the oldest beyond the limit
+    fn push_scrollback(&mut self, line: Vec<Cell>) {
+        if self.max_scrollback == 0 {
+            return;
+        }
+        self.scrollback.push_back(line);
 
-        if clear_end <= self.cells.len() {
-            // Bulk clear instead of cell-by-cell
-            for cell in &mut self.cells[clear_start..clear_end] {
-                *cell = Cell::default();
-            }
+        // O(1) pop from front instead of O(n) remove(0)
+        if self.scrollback.len() > self.max_scrollback {
+            self.scrollback.pop_front();
         }
+    }
 
-        // Reset scroll offset when new content arrives
-        self.scroll_offset = 0;
+    /// Scroll the scroll region down, inserting blank lines at its top
+    /// (`CSI n T`). Nothing is pulled back from scrollback.
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.shift_rows_down(self.scroll_top, lines);
+    }
 
-        // Mark all as dirty after scroll
-        self.all_dirty = true;
+    /// Insert blank lines at the cursor row, pushing the rows below towards
+    /// the bottom of the scroll region (`CSI n L`)
+    pub fn insert_lines(&mut self, lines: usize) {
+        if (self.scroll_top..=self.scroll_bottom).contains(&self.cursor_y) {
+            self.shift_rows_down(self.cursor_y, lines);
+            self.cursor_x = 0;
+            self.pending_wrap = false;
+        }
+    }
+
+    /// Delete lines at the cursor row, pulling the rows below up and
+    /// blanking the bottom of the scroll region (`CSI n M`)
+    pub fn delete_lines(&mut self, lines: usize) {
+        if (self.scroll_top..=self.scroll_bottom).contains(&self.cursor_y) {
+            self.shift_rows_up(self.cursor_y, lines);
+            self.cursor_x = 0;
+            self.pending_wrap = false;
+        }
+    }
+
+    /// Move rows `top + lines..=scroll_bottom` up to `top`, blanking the
+    /// vacated rows at the bottom of the region
+    fn shift_rows_up(&mut self, top: usize, lines: usize) {
+        let end_row = self.scroll_bottom + 1;
+        let lines = lines.min(end_row.saturating_sub(top));
+        if lines == 0 {
+            return;
+        }
+
+        self.cells.copy_within((top + lines) * self.cols..end_row * self.cols, top * self.cols);
+        let blank = self.blank_cell();
+        self.cells[(end_row - lines) * self.cols..end_row * self.cols].fill(blank);
+        self.wrapped.copy_within(top + lines..end_row, top);
+        self.wrapped[end_row - lines..end_row].fill(false);
+        self.line_sizes.copy_within(top + lines..end_row, top);
+        self.line_sizes[end_row - lines..end_row].fill(LineSize::Single);
+        self.mark_rows_dirty(top..end_row);
+    }
+
+    /// Move rows `top..=scroll_bottom - lines` down by `lines`, blanking the
+    /// vacated rows at `top`
+    fn shift_rows_down(&mut self, top: usize, lines: usize) {
+        let end_row = self.scroll_bottom + 1;
+        let lines = lines.min(end_row.saturating_sub(top));
+        if lines == 0 {
+            return;
+        }
+
+        self.cells.copy_within(top * self.cols..(end_row - lines) * self.cols, (top + lines) * self.cols);
+        let blank = self.blank_cell();
+        self.cells[top * self.cols..(top + lines) * self.cols].fill(blank);
+        self.wrapped.copy_within(top..end_row - lines, top + lines);
+        self.wrapped[top..top + lines].fill(false);
+        self.line_sizes.copy_within(top..end_row - lines, top + lines);
+        self.line_sizes[top..top + lines].fill(LineSize::Single);
+        self.mark_rows_dirty(top..end_row);
+    }
+
+    /// Every cell in `rows` moved or changed; a range covering the whole
+    /// screen falls back to a full redraw
+    fn mark_rows_dirty(&mut self, rows: std::ops::Range<usize>) {
+        if !rows.is_empty() {
+            self.invalidate_selection((0, rows.start), (self.cols - 1, rows.end - 1));
+        }
+        if rows.start == 0 && rows.end >= self.rows {
+            self.all_dirty = true;
+            return;
+        }
+        for row in rows {
+            for x in 0..self.cols {
+                self.dirty_cells.insert((x, row));
+            }
+        }
+    }
+
+    /// Drop the selection if it covers any cell from `start` to `end`
+    /// (reading order, as (col, row)): the text it highlighted is gone, and
+    /// copying would pick up whatever replaced it
+    fn invalidate_selection(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let reading_order = |(col, row): (usize, usize)| (row, col);
+        let overlaps = self.selection.is_some_and(|selection| {
+            reading_order(selection.start) <= reading_order(end) && reading_order(start) <= reading_order(selection.end)
+        });
+        if overlaps {
+            self.set_selection(None);
+        }
     }
 
     pub fn set_style(&mut self, style: CellStyle) {
@@ -325,10 +871,520 @@ impl Grid {
         self.cells.get(idx)
     }
 
+    /// Every visible cell, row by row, with the grid's (cols, rows), for
+    /// callers that read the whole screen under one lock. While scrolled
+    /// back this reflects the scrollback, exactly as `get_cell` does, and is
+    /// copied out; otherwise it borrows the screen.
+    pub fn visible_buffer(&self) -> (Cow<'_, [Cell]>, usize, usize) {
+        if self.scroll_offset == 0 {
+            return (Cow::Borrowed(&self.cells), self.cols, self.rows);
+        }
+
+        let cells = (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| self.get_cell(col, row).copied().unwrap_or_default()))
+            .collect();
+        (Cow::Owned(cells), self.cols, self.rows)
+    }
+
+    /// Text of a visible row (honoring the scrollback offset, like
+    /// `get_cell`), with trailing spaces trimmed
+    pub fn row_text(&self, row: usize) -> String {
+        Self::trimmed_text((0..self.cols).filter_map(|col| self.get_cell(col, row)))
+    }
+
+    /// `row_text` for every visible row, top to bottom
+    pub fn visible_lines(&self) -> impl Iterator<Item = String> + '_ {
+        (0..self.rows).map(move |row| self.row_text(row))
+    }
+
+    /// Characters and styles of a visible row. Unlike `row_text` nothing is
+    /// trimmed, since blank cells can still carry a background color.
+    pub fn styled_row(&self, row: usize) -> Vec<(char, CellStyle)> {
+        (0..self.cols)
+            .filter_map(|col| self.get_cell(col, row))
+            .map(|cell| (cell.c, cell.style))
+            .collect()
+    }
+
+    fn trimmed_text<'a>(cells: impl Iterator<Item = &'a Cell>) -> String {
+        let line: String = cells.map(|cell| cell.c).collect();
+        line.trim_end().to_string()
+    }
+
+    /// Snapshot part of the terminal as text, ignoring any scrollback
+    /// offset. Every line ends with `\n`; for `Full`, `Visible` and
+    /// `Scrollback` trailing blank lines are dropped.
+    pub fn capture(&self, mode: CaptureMode, format: CaptureFormat) -> String {
+        let screen = || self.cells.chunks(self.cols);
+        let history_from = |skip: usize| self.scrollback.iter().skip(skip).map(Vec::as_slice);
+
+        let (rows, columns, trim_blank_lines): (Vec<&[Cell]>, _, _) = match mode {
+            CaptureMode::Full => (history_from(0).chain(screen()).collect(), 0..self.cols, true),
+            CaptureMode::Visible => (screen().collect(), 0..self.cols, true),
+            CaptureMode::Line => (
+                screen().skip(self.cursor_y).take(1).collect(),
+                0..self.cols,
+                false,
+            ),
+            CaptureMode::Region { x, y, width, height } => (
+                // A region entirely right of the screen captures nothing
+                screen().skip(y).take(if x < self.cols { height } else { 0 }).collect(),
+                x.min(self.cols)..x.saturating_add(width).min(self.cols),
+                false,
+            ),
+            CaptureMode::Scrollback { lines } => (
+                history_from(self.scrollback.len().saturating_sub(lines)).collect(),
+                0..self.cols,
+                true,
+            ),
+        };
+
+        let mut lines: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                // Scrollback rows keep the width they were written at
+                let row = &row[columns.start.min(row.len())..columns.end.min(row.len())];
+                match format {
+                    CaptureFormat::Text => Self::trimmed_text(row.iter()),
+                    CaptureFormat::Ansi => Self::capture_ansi_line(row),
+                }
+            })
+            .collect();
+
+        if trim_blank_lines {
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+        }
+
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// `capture` with SGR sequences, reproducing the styled screen when
+    /// printed
+    pub fn capture_ansi(&self, mode: CaptureMode) -> String {
+        self.capture(mode, CaptureFormat::Ansi)
+    }
+
+    /// Write a capture of the screen to `path`
+    pub fn capture_to_file(&self, path: &Path, format: CaptureFormat) -> std::io::Result<()> {
+        std::fs::write(path, self.capture(CaptureMode::Visible, format))
+    }
+
+    fn capture_ansi_line(row: &[Cell]) -> String {
+        // Trailing default-styled blanks carry no information
+        let end = row
+            .iter()
+            .rposition(|cell| cell.c != ' ' || cell.style != CellStyle::default())
+            .map_or(0, |i| i + 1);
+
+        let mut line = String::new();
+        let mut style = CellStyle::default();
+        // Runs of identically styled cells share one sequence
+        for cell in &row[..end] {
+            if cell.style != style {
+                line.push_str(&Self::sgr(&style, &cell.style));
+                style = cell.style;
+            }
+            line.push(cell.c);
+        }
+        if style != CellStyle::default() {
+            line.push_str("\x1b[0m");
+        }
+        line
+    }
+
+    /// Shortest SGR sequence switching from `from` to `style`. Attributes
+    /// are only added incrementally; dropping one or returning a color to
+    /// its default starts over from a reset.
+    fn sgr(from: &CellStyle, style: &CellStyle) -> String {
+        let attributes = |s: &CellStyle| {
+            [
+                (s.bold, "1"),
+                (s.italic, "3"),
+                (s.underline, "4"),
+                (s.inverse, "7"),
+                (s.strikethrough, "9"),
+                (s.framed, "51"),
+                (s.encircled, "52"),
+                (s.overline, "53"),
+            ]
+        };
+
+        let dropped = attributes(from)
+            .iter()
+            .zip(attributes(style))
+            .any(|(&(was, _), (is, _))| was && !is)
+            || (from.fg != Color::Default && style.fg == Color::Default)
+            || (from.bg != Color::Default && style.bg == Color::Default);
+        let (base, mut params) = if dropped {
+            (CellStyle::default(), vec!["0".to_string()])
+        } else {
+            (*from, Vec::new())
+        };
+
+        for (&(was, _), (is, code)) in attributes(&base).iter().zip(attributes(style)) {
+            if is && !was {
+                params.push(code.to_string());
+            }
+        }
+        if style.fg != base.fg {
+            params.extend(Self::sgr_color(&style.fg, 30, 90, 38));
+        }
+        if style.bg != base.bg {
+            params.extend(Self::sgr_color(&style.bg, 40, 100, 48));
+        }
+        format!("\x1b[{}m", params.join(";"))
+    }
+
+    fn sgr_color(color: &Color, base: u8, bright_base: u8, extended: u8) -> Option<String> {
+        let code = match color {
+            Color::Default => return None,
+            Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", extended, r, g, b)),
+            Color::Black => base,
+            Color::Red => base + 1,
+            Color::Green => base + 2,
+            Color::Yellow => base + 3,
+            Color::Blue => base + 4,
+            Color::Magenta => base + 5,
+            Color::Cyan => base + 6,
+            Color::White => base + 7,
+            Color::BrightBlack => bright_base,
+            Color::BrightRed => bright_base + 1,
+            Color::BrightGreen => bright_base + 2,
+            Color::BrightYellow => bright_base + 3,
+            Color::BrightBlue => bright_base + 4,
+            Color::BrightMagenta => bright_base + 5,
+            Color::BrightCyan => bright_base + 6,
+            Color::BrightWhite => bright_base + 7,
+        };
+        Some(code.to_string())
+    }
+
+    /// Whether the application enabled bracketed paste (`CSI ? 2004 h`)
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    pub fn set_bracketed_paste(&mut self, enabled: bool) {
+        self.bracketed_paste = enabled;
+    }
+
+    /// Whether printing past the right margin wraps to the next line
+    /// (DECAWM, on by default)
+    pub fn autowrap(&self) -> bool {
+        self.autowrap
+    }
+
+    pub fn set_autowrap(&mut self, enabled: bool) {
+        self.autowrap = enabled;
+    }
+
+    /// Whether the application wants the cursor shown (`CSI ? 25 h`)
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+
+    /// Mouse events the application wants reported
+    pub fn mouse_tracking(&self) -> MouseTracking {
+        self.mouse_tracking
+    }
+
+    pub fn set_mouse_tracking(&mut self, tracking: MouseTracking) {
+        self.mouse_tracking = tracking;
+    }
+
+    /// Whether mouse reports use the SGR encoding (`CSI ? 1006 h`)
+    pub fn sgr_mouse(&self) -> bool {
+        self.sgr_mouse
+    }
+
+    pub fn set_sgr_mouse(&mut self, enabled: bool) {
+        self.sgr_mouse = enabled;
+    }
+
+    /// Whether focus changes are reported as `CSI I` / `CSI O`
+    /// (`CSI ? 1004 h`)
+    pub fn focus_reporting(&self) -> bool {
+        self.focus_reporting
+    }
+
+    pub fn set_focus_reporting(&mut self, enabled: bool) {
+        self.focus_reporting = enabled;
+    }
+
+    /// Whether the alternate screen is shown (`CSI ? 1049 h`)
+    pub fn alternate_screen(&self) -> bool {
+        self.primary_screen.is_some()
+    }
+
+    /// Switch to a blank alternate screen, keeping the normal screen to
+    /// restore on exit. Does nothing if the alternate screen is already shown.
+    pub fn enter_alternate_screen(&mut self) {
+        if self.primary_screen.is_some() {
+            return;
+        }
+
+        self.primary_screen = Some(Box::new(self.snapshot()));
+        self.selection = None;
+        self.hovered_url = None;
+        self.scroll_offset = 0;
+        self.clear_screen();
+    }
+
+    /// Return to the normal screen as it was before the alternate screen
+    /// was entered
+    pub fn exit_alternate_screen(&mut self) {
+        let Some(primary) = self.primary_screen.take() else {
+            return;
+        };
+
+        self.restore_screen(&primary);
+    }
+
+    /// Capture the screen, cursor, scroll region and modes, to bring back
+    /// later with `restore`. Scrollback is left out.
+    pub fn snapshot(&self) -> GridSnapshot {
+        GridSnapshot {
+            cols: self.cols,
+            rows: self.rows,
+            cells: self.cells.clone(),
+            wrapped: self.wrapped.clone(),
+            line_sizes: self.line_sizes.clone(),
+            cursor: (self.cursor_x, self.cursor_y),
+            pending_wrap: self.pending_wrap,
+            saved_cursor: self.saved_cursor,
+            current_style: self.current_style,
+            cursor_visible: self.cursor_visible,
+            scroll_region: (self.scroll_top, self.scroll_bottom),
+            bracketed_paste: self.bracketed_paste,
+            autowrap: self.autowrap,
+            title: self.title.clone(),
+            current_dir: self.current_dir.clone(),
+            mouse_tracking: self.mouse_tracking,
+            sgr_mouse: self.sgr_mouse,
+            focus_reporting: self.focus_reporting,
+            cursor_shape: self.cursor_shape,
+            alternate_scroll: self.alternate_scroll,
+            primary_screen: self.primary_screen.clone(),
+            scrollback: None,
+        }
+    }
+
+    /// As `snapshot`, including the scrollback
+    pub fn snapshot_with_scrollback(&self) -> GridSnapshot {
+        GridSnapshot {
+            scrollback: Some(self.scrollback.iter().cloned().collect()),
+            ..self.snapshot()
+        }
+    }
+
+    /// Put the grid back in the state `snapshot` captured, at the size it
+    /// had then. The scrollback is only replaced if the snapshot has one.
+    pub fn restore(&mut self, snapshot: &GridSnapshot) {
+        self.cols = snapshot.cols;
+        self.rows = snapshot.rows;
+        self.restore_screen(snapshot);
+        (self.cursor_x, self.cursor_y) = snapshot.cursor;
+        self.pending_wrap = snapshot.pending_wrap;
+        self.saved_cursor = snapshot.saved_cursor;
+        self.current_style = snapshot.current_style;
+        self.cursor_visible = snapshot.cursor_visible;
+        (self.scroll_top, self.scroll_bottom) = snapshot.scroll_region;
+        self.bracketed_paste = snapshot.bracketed_paste;
+        self.autowrap = snapshot.autowrap;
+        self.title = snapshot.title.clone();
+        self.current_dir = snapshot.current_dir.clone();
+        self.mouse_tracking = snapshot.mouse_tracking;
+        self.sgr_mouse = snapshot.sgr_mouse;
+        self.focus_reporting = snapshot.focus_reporting;
+        self.cursor_shape = snapshot.cursor_shape;
+        self.alternate_scroll = snapshot.alternate_scroll;
+        self.primary_screen = snapshot.primary_screen.clone();
+
+        if let Some(scrollback) = &snapshot.scrollback {
+            let skip = scrollback.len().saturating_sub(self.max_scrollback);
+            self.scrollback = scrollback[skip..].iter().cloned().collect();
+        }
+        self.scroll_offset = 0;
+    }
+
+    /// Bring back the screen contents of `snapshot`, keeping the current
+    /// size: a snapshot of another size is cropped or padded, and loses its
+    /// line wrapping
+    fn restore_screen(&mut self, snapshot: &GridSnapshot) {
+        if snapshot.size() == (self.cols, self.rows) {
+            self.cells = snapshot.cells.clone();
+            self.wrapped = snapshot.wrapped.clone();
+            self.line_sizes = snapshot.line_sizes.clone();
+        } else {
+            self.cells = resize_cells(&snapshot.cells, snapshot.size(), (self.cols, self.rows));
+            self.wrapped = vec![false; self.rows];
+            self.line_sizes = snapshot.line_sizes.clone();
+            self.line_sizes.resize(self.rows, LineSize::Single);
+        }
+        self.selection = None;
+        self.hovered_url = None;
+        self.all_dirty = true;
+    }
+
+    /// Whether the wheel sends arrow keys on the alternate screen
+    /// (`CSI ? 1007 h`)
+    pub fn alternate_scroll(&self) -> bool {
+        self.alternate_scroll
+    }
+
+    pub fn set_alternate_scroll(&mut self, enabled: bool) {
+        self.alternate_scroll = enabled;
+    }
+
+    /// Whether the application is partway through a synchronized update
+    /// (`CSI ? 2026 h`), so the screen shouldn't be presented yet. Lapses
+    /// after `SYNCHRONIZED_OUTPUT_TIMEOUT`.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen_until().is_some()
+    }
+
+    /// When the synchronized update in progress times out, or `None` if
+    /// the grid isn't frozen
+    pub fn frozen_until(&self) -> Option<Instant> {
+        self.frozen_since
+            .map(|since| since + SYNCHRONIZED_OUTPUT_TIMEOUT)
+            .filter(|&until| Instant::now() < until)
+    }
+
+    /// Begin or end a synchronized update; output keeps updating the grid
+    /// either way
+    pub fn set_frozen(&mut self, frozen: bool) {
+        if !frozen {
+            self.frozen_since = None;
+        } else if !self.is_frozen() {
+            self.frozen_since = Some(Instant::now());
+        }
+    }
+
+    /// Value of DEC private mode `mode`, or `None` for modes that aren't
+    /// supported
+    pub fn private_mode(&self, mode: u16) -> Option<bool> {
+        let enabled = match mode {
+            7 => self.autowrap,
+            25 => self.cursor_visible,
+            47 | 1047 | 1049 => self.alternate_screen(),
+            1000 => self.mouse_tracking == MouseTracking::Click,
+            1002 => self.mouse_tracking == MouseTracking::Drag,
+            1004 => self.focus_reporting,
+            1006 => self.sgr_mouse,
+            1007 => self.alternate_scroll,
+            2004 => self.bracketed_paste,
+            2026 => self.is_frozen(),
+            _ => return None,
+        };
+        Some(enabled)
+    }
+
+    /// Set or reset DEC private mode `mode` (`CSI ? mode h` / `l`).
+    /// Unsupported modes are ignored.
+    pub fn set_private_mode(&mut self, mode: u16, enabled: bool) {
+        match mode {
+            7 => self.set_autowrap(enabled),
+            25 => self.set_cursor_visible(enabled),
+            47 | 1047 => {
+                if enabled {
+                    self.enter_alternate_screen();
+                } else {
+                    self.exit_alternate_screen();
+                }
+            }
+            // As 1047, saving the cursor on entry and restoring it on exit
+            1049 => {
+                if enabled {
+                    self.save_cursor();
+                    self.enter_alternate_screen();
+                } else {
+                    self.exit_alternate_screen();
+                    self.restore_cursor();
+                }
+            }
+            1000 => self.set_mouse_tracking(if enabled { MouseTracking::Click } else { MouseTracking::Off }),
+            1002 => self.set_mouse_tracking(if enabled { MouseTracking::Drag } else { MouseTracking::Off }),
+            1004 => self.set_focus_reporting(enabled),
+            1006 => self.set_sgr_mouse(enabled),
+            1007 => self.set_alternate_scroll(enabled),
+            2004 => self.set_bracketed_paste(enabled),
+            2026 => self.set_frozen(enabled),
+            _ => {}
+        }
+    }
+
+    /// Save the current value of each of `modes` (XTSAVE, `CSI ? Pm s`)
+    pub fn save_private_modes(&mut self, modes: &[u16]) {
+        for &mode in modes {
+            if let Some(enabled) = self.private_mode(mode) {
+                let saved = self.saved_private_modes.entry(mode).or_default();
+                if saved.len() == SAVED_PRIVATE_MODES_DEPTH {
+                    saved.remove(0);
+                }
+                saved.push(enabled);
+            }
+        }
+    }
+
+    /// Put each of `modes` back to its most recently saved value (XTRESTORE,
+    /// `CSI ? Pm r`). Modes with nothing saved are left as they are.
+    pub fn restore_private_modes(&mut self, modes: &[u16]) {
+        for &mode in modes {
+            let Some(enabled) = self.saved_private_modes.get_mut(&mode).and_then(Vec::pop) else {
+                continue;
+            };
+            // Setting a mode again can have side effects, e.g. 1049 saving the cursor
+            if self.private_mode(mode) != Some(enabled) {
+                self.set_private_mode(mode, enabled);
+            }
+        }
+    }
+
+    /// Cursor shape requested by the application with `CSI Ps SP q`, or
+    /// `None` to use the configured default
+    pub fn cursor_shape(&self) -> Option<CursorShape> {
+        self.cursor_shape
+    }
+
+    pub fn set_cursor_shape(&mut self, shape: Option<CursorShape>) {
+        self.cursor_shape = shape;
+    }
+
+    /// Title set by the application with `OSC 0` or `OSC 2`, if any
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
+    /// Working directory the shell last reported with `OSC 7`, if any
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    pub fn set_current_dir(&mut self, dir: Option<PathBuf>) {
+        self.current_dir = dir;
+    }
+
     pub fn cursor_pos(&self) -> (usize, usize) {
         (self.cursor_x, self.cursor_y)
     }
 
+    /// Whether the last glyph filled the right margin, so the next printable
+    /// starts a new line
+    pub fn wrap_pending(&self) -> bool {
+        self.pending_wrap
+    }
+
     pub fn size(&self) -> (usize, usize) {
         (self.cols, self.rows)
     }
@@ -342,20 +1398,191 @@ impl Grid {
     }
 
     pub fn restore_cursor(&mut self) {
-        (self.cursor_x, self.cursor_y) = self.saved_cursor;
+        (self.cursor_x, self.cursor_y) = clamp_to_screen(self.saved_cursor, self.cols, self.rows);
+        self.pending_wrap = false;
+    }
+
+    /// Limit scrolling to rows `top..=bottom` (0-based). `bottom` is clamped
+    /// to the last row; a region that doesn't span at least two rows is
+    /// ignored, as DECSTBM does. Returns whether the region was applied.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) -> bool {
+        let bottom = bottom.min(self.rows - 1);
+        if top >= bottom {
+            return false;
+        }
+
+        self.scroll_top = top;
+        self.scroll_bottom = bottom;
+        true
+    }
+
+    /// Whether `row`'s text continues on the next row because it wrapped
+    /// at the right margin
+    pub fn is_wrapped(&self, row: usize) -> bool {
+        self.wrapped.get(row).copied().unwrap_or(false)
+    }
+
+    /// Span of the word under (`col`, `row`), bounded by any character in
+    /// `separators`. A separator on its own selects just that cell.
+    pub fn word_at(&self, col: usize, row: usize, separators: &str) -> Option<Selection> {
+        let is_separator = |x: usize| self.get_cell(x, row).is_some_and(|cell| separators.contains(cell.c));
+        if is_separator(col) {
+            return Some(Selection { start: (col, row), end: (col, row) });
+        }
+        self.get_cell(col, row)?;
+
+        let mut start = col;
+        while start > 0 && !is_separator(start - 1) {
+            start -= 1;
+        }
+        let mut end = col;
+        while end + 1 < self.cols && !is_separator(end + 1) {
+            end += 1;
+        }
+        Some(Selection { start: (start, row), end: (end, row) })
+    }
+
+    /// Span of the logical line through `row`: the rows joined by autowrap,
+    /// from the first column of the first to the last column of the last.
+    /// Scrolled-back history has no wrap information, so there it is `row`.
+    pub fn line_span(&self, row: usize) -> Option<Selection> {
+        if row >= self.rows {
+            return None;
+        }
+
+        let (mut first, mut last) = (row, row);
+        if self.scroll_offset == 0 {
+            while first > 0 && self.wrapped[first - 1] {
+                first -= 1;
+            }
+            while last + 1 < self.rows && self.wrapped[last] {
+                last += 1;
+            }
+        }
+        Some(Selection { start: (0, first), end: (self.cols - 1, last) })
     }
 
-    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
-        self.scroll_top = top.min(self.rows - 1);
-        self.scroll_bottom = bottom.min(self.rows - 1);
+    /// Find the URLs on screen, following lines that wrap onto the next row.
+    /// Returns each URL with the cells it covers; `www.` links are returned
+    /// as printed, without a scheme.
+    pub fn detect_urls(&self) -> Vec<(Selection, String)> {
+        let mut urls = Vec::new();
+        let mut row = 0;
+
+        while row < self.rows {
+            // Gather one logical line and the cell behind each character
+            let mut text = String::new();
+            let mut cells = Vec::new();
+            loop {
+                for col in 0..self.cols {
+                    text.push(self.get_cell(col, row).map_or(' ', |cell| cell.c));
+                    cells.push((col, row));
+                }
+                row += 1;
+                if row >= self.rows || self.scroll_offset != 0 || !self.wrapped[row - 1] {
+                    break;
+                }
+            }
+
+            for found in URL_PATTERN.find_iter(&text) {
+                let url = Self::trim_url(found.as_str());
+                let start = text[..found.start()].chars().count();
+                let end = start + url.chars().count() - 1;
+                urls.push((Selection { start: cells[start], end: cells[end] }, url.to_string()));
+            }
+        }
+
+        urls
+    }
+
+    /// Drop punctuation that ends the surrounding sentence rather than the
+    /// URL, keeping a closing paren that matches one inside it
+    fn trim_url(url: &str) -> &str {
+        let mut url = url;
+        while let Some(last) = url.chars().last() {
+            let unbalanced_paren = last == ')' && url.matches('(').count() < url.matches(')').count();
+            if !(matches!(last, '.' | ',' | ';' | ':' | '!' | '?') || unbalanced_paren) {
+                break;
+            }
+            url = &url[..url.len() - 1];
+        }
+        url
+    }
+
+    pub fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    /// Select cells (or clear the selection with `None`), redrawing the
+    /// screen if it changed
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        if self.selection != selection {
+            self.selection = selection;
+            self.all_dirty = true;
+        }
+    }
+
+    /// Cells of the URL under the mouse pointer, drawn underlined
+    pub fn hovered_url(&self) -> Option<Selection> {
+        self.hovered_url
+    }
+
+    pub fn set_hovered_url(&mut self, url: Option<Selection>) {
+        self.hovered_url = url;
+    }
+
+    /// Text of the selected cells, with a newline between rows unless the
+    /// first wrapped onto the second
+    pub fn selected_text(&self) -> Option<String> {
+        let selection = self.selection?;
+        let mut text = String::new();
+        for row in selection.start.1..=selection.end.1.min(self.rows.saturating_sub(1)) {
+            let from = if row == selection.start.1 { selection.start.0 } else { 0 };
+            let to = if row == selection.end.1 { selection.end.0 } else { self.cols - 1 };
+            let cells = (from..=to).filter_map(|col| self.get_cell(col, row));
+            let wrapped = self.scroll_offset == 0 && self.is_wrapped(row);
+            if wrapped {
+                text.extend(cells.map(|cell| cell.c));
+            } else {
+                text.push_str(&Self::trimmed_text(cells));
+                if row != selection.end.1 {
+                    text.push('\n');
+                }
+            }
+        }
+        Some(text)
+    }
+
+    /// Top and bottom rows of the scroll region, inclusive
+    pub fn scroll_region(&self) -> (usize, usize) {
+        (self.scroll_top, self.scroll_bottom)
+    }
+
+    /// DECSTR (`CSI ! p`): restore the modes a program may have changed,
+    /// including leaving the alternate screen, without clearing the screen
+    pub fn soft_reset(&mut self) {
+        self.exit_alternate_screen();
+        self.current_style = CellStyle::default();
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.cursor_visible = true;
+        self.autowrap = true;
+        self.saved_cursor = (0, 0);
+    }
+
+    /// RIS (`ESC c`): return to the power-on state, clearing the screen and
+    /// scrollback but keeping the size and scrollback limit
+    pub fn reset(&mut self) {
+        *self = Self::with_scrollback(self.cols, self.rows, self.max_scrollback);
     }
 
     pub fn scroll_back_up(&mut self, lines: usize) {
         let max_offset = self.scrollback.len();
         let old_offset = self.scroll_offset;
         self.scroll_offset = (self.scroll_offset + lines).min(max_offset);
-        // Mark all dirty if scroll changed
+        // The selection marks screen positions, which now show other lines
         if old_offset != self.scroll_offset {
+            self.selection = None;
             self.all_dirty = true;
         }
     }
@@ -363,8 +1590,9 @@ impl Grid {
     pub fn scroll_back_down(&mut self, lines: usize) {
         let old_offset = self.scroll_offset;
         self.scroll_offset = self.scroll_offset.saturating_sub(lines);
-        // Mark all dirty if scroll changed
+        // The selection marks screen positions, which now show other lines
         if old_offset != self.scroll_offset {
+            self.selection = None;
             self.all_dirty = true;
         }
     }
@@ -372,8 +1600,9 @@ impl Grid {
     pub fn scroll_to_bottom(&mut self) {
         let old_offset = self.scroll_offset;
         self.scroll_offset = 0;
-        // Mark all dirty if scroll changed
+        // The selection marks screen positions, which now show other lines
         if old_offset != self.scroll_offset {
+            self.selection = None;
             self.all_dirty = true;
         }
     }
@@ -386,6 +1615,45 @@ impl Grid {
         self.scrollback.len()
     }
 
+    pub fn max_scrollback(&self) -> usize {
+        self.max_scrollback
+    }
+
+    /// Text of scrollback line `index`, oldest first, trimmed like `row_text`
+    pub fn scrollback_line(&self, index: usize) -> Option<String> {
+        self.scrollback.get(index).map(|line| Self::trimmed_text(line.iter()))
+    }
+
+    /// `scrollback_line` for lines `start..end`, clamped to the history
+    pub fn scrollback_range(&self, start: usize, end: usize) -> Vec<String> {
+        let end = end.min(self.scrollback.len());
+        self.scrollback
+            .range(start.min(end)..end)
+            .map(|line| Self::trimmed_text(line.iter()))
+            .collect()
+    }
+
+    /// Change the scrollback limit, dropping the oldest lines if needed
+    pub fn set_max_scrollback(&mut self, max_scrollback: usize) {
+        self.max_scrollback = max_scrollback;
+        while self.scrollback.len() > max_scrollback {
+            self.scrollback.pop_front();
+        }
+        if self.scroll_offset > self.scrollback.len() {
+            self.scroll_offset = self.scrollback.len();
+            self.all_dirty = true;
+        }
+    }
+
+    /// Discard all scrollback history
+    pub fn clear_scrollback(&mut self) {
+        self.scrollback.clear();
+        if self.scroll_offset != 0 {
+            self.scroll_offset = 0;
+            self.all_dirty = true;
+        }
+    }
+
     pub fn scroll_offset(&self) -> usize {
         self.scroll_offset
     }
@@ -407,4 +1675,52 @@ impl Grid {
     pub fn mark_all_dirty(&mut self) {
         self.all_dirty = true;
     }
+
+    /// Cells changed since the last call, in row-major order, clearing the
+    /// dirty state. After a full invalidation every screen cell is returned.
+    pub fn take_changes(&mut self) -> Vec<CellChange> {
+        let mut positions: Vec<(usize, usize)> = if self.all_dirty {
+            (0..self.rows).flat_map(|row| (0..self.cols).map(move |col| (row, col))).collect()
+        } else {
+            self.dirty_cells
+                .iter()
+                .map(|&(col, row)| (row, col))
+                .filter(|&(row, col)| row < self.rows && col < self.cols)
+                .collect()
+        };
+        positions.sort_unstable();
+        self.clear_dirty();
+
+        positions
+            .into_iter()
+            .map(|(row, col)| {
+                let cell = self.cells[row * self.cols + col];
+                CellChange { row, col, c: cell.c, style: cell.style }
+            })
+            .collect()
+    }
+}
+
+/// Move a (col, row) position onto a screen of `cols` x `rows`
+fn clamp_to_screen((col, row): (usize, usize), cols: usize, rows: usize) -> (usize, usize) {
+    (col.min(cols - 1), row.min(rows - 1))
+}
+
+/// Copy `cells` laid out as `old` (cols, rows) into a blank buffer of size
+/// `new`, keeping the top-left overlap
+fn resize_cells(cells: &[Cell], old: (usize, usize), new: (usize, usize)) -> Vec<Cell> {
+    let (old_cols, old_rows) = old;
+    let (cols, rows) = new;
+    let mut new_cells = vec![Cell::default(); cols * rows];
+
+    let min_rows = old_rows.min(rows);
+    let min_cols = old_cols.min(cols);
+
+    for y in 0..min_rows {
+        let old_start = y * old_cols;
+        let new_start = y * cols;
+        new_cells[new_start..new_start + min_cols].copy_from_slice(&cells[old_start..old_start + min_cols]);
+    }
+
+    new_cells
 }
@@ -0,0 +1,93 @@
+//! Keyboard input: the bytes an application expects for a key press, by key
+//! name, shared by the window and automation (`KEY` on the server)
+
+/// Modifiers that change what a key sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyModifiers {
+    /// Ctrl+letter sends the matching control character
+    pub ctrl: bool,
+    /// Alt prefixes the key's bytes with ESC
+    pub alt: bool,
+}
+
+impl KeyModifiers {
+    pub const NONE: KeyModifiers = KeyModifiers { ctrl: false, alt: false };
+    pub const CTRL: KeyModifiers = KeyModifiers { ctrl: true, alt: false };
+    pub const ALT: KeyModifiers = KeyModifiers { ctrl: false, alt: true };
+}
+
+/// Bytes for the key `name`, or `None` if it isn't a key we know
+///
+/// `name` is a single character (`a`, `/`) or a case-insensitive key name
+/// (`Enter`, `Tab`, `Up`, `PageDown`, `F5`, ...).
+pub fn key_sequence(name: &str, mods: KeyModifiers) -> Option<Vec<u8>> {
+    let mut chars = name.chars();
+    let mut bytes = match (chars.next(), chars.next()) {
+        (Some(c), None) => {
+            let mut bytes = c.to_string().into_bytes();
+            // Ctrl+a = 1, Ctrl+b = 2, etc.
+            if mods.ctrl && c.is_ascii_alphabetic() {
+                bytes[0] = c.to_ascii_lowercase() as u8 - b'a' + 1;
+            }
+            bytes
+        }
+        _ => named_key_sequence(name)?.to_vec(),
+    };
+
+    if mods.alt {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+/// Bytes for a chord such as `Ctrl+C`, `Alt+f` or `Up`: any number of
+/// `Ctrl`/`Alt` modifiers joined to the key with `+`
+pub fn chord_sequence(chord: &str) -> Option<Vec<u8>> {
+    let mut parts: Vec<&str> = chord.split('+').collect();
+    let key = parts.pop()?;
+
+    let mut mods = KeyModifiers::NONE;
+    for part in parts {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" => mods.ctrl = true,
+            "alt" | "meta" => mods.alt = true,
+            _ => return None,
+        }
+    }
+
+    key_sequence(key, mods)
+}
+
+fn named_key_sequence(name: &str) -> Option<&'static [u8]> {
+    let bytes: &[u8] = match name.to_lowercase().as_str() {
+        "enter" | "return" => b"\r",
+        "tab" => b"\t",
+        "space" => b" ",
+        "backspace" => b"\x7f",
+        "escape" | "esc" => b"\x1b",
+        "up" | "arrowup" => b"\x1b[A",
+        "down" | "arrowdown" => b"\x1b[B",
+        "right" | "arrowright" => b"\x1b[C",
+        "left" | "arrowleft" => b"\x1b[D",
+        "home" => b"\x1b[H",
+        "end" => b"\x1b[F",
+        "insert" => b"\x1b[2~",
+        "delete" => b"\x1b[3~",
+        "pageup" => b"\x1b[5~",
+        "pagedown" => b"\x1b[6~",
+        "f1" => b"\x1bOP",
+        "f2" => b"\x1bOQ",
+        "f3" => b"\x1bOR",
+        "f4" => b"\x1bOS",
+        "f5" => b"\x1b[15~",
+        "f6" => b"\x1b[17~",
+        "f7" => b"\x1b[18~",
+        "f8" => b"\x1b[19~",
+        "f9" => b"\x1b[20~",
+        "f10" => b"\x1b[21~",
+        "f11" => b"\x1b[23~",
+        "f12" => b"\x1b[24~",
+        _ => return None,
+    };
+    Some(bytes)
+}
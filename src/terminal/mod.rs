@@ -1,17 +1,24 @@
 mod pty;
 mod parser;
 mod grid;
+pub mod paste;
+pub mod mouse;
+pub mod keys;
 
-pub use pty::Pty;
-pub use parser::TerminalParser;
-pub use grid::{Cell, Grid, CellStyle};
+pub use pty::{OutputWaker, Pty, PTY_BUFFER_CAPACITY};
+pub use parser::{secondary_device_attributes, TerminalParser, PRIMARY_DEVICE_ATTRIBUTES};
+pub use grid::{CaptureFormat, CaptureMode, Cell, CellChange, Grid, GridSnapshot, CellStyle, CursorShape, LineSize, Selection, DEFAULT_SCROLLBACK, DEFAULT_WORD_SEPARATORS, SYNCHRONIZED_OUTPUT_TIMEOUT};
 
 use crossbeam_channel::{Receiver, Sender};
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use crate::config::ShellConfig;
 use crate::server_client::ServerClient;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
+use tracing::Instrument;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Color {
     Black,
     Red,
@@ -33,38 +40,79 @@ pub enum Color {
     Rgb(u8, u8, u8),
 }
 
-#[derive(Debug, Clone)]
+impl Color {
+    /// Bright variant of one of the 8 standard colors; every other color
+    /// (bright, Default, RGB) is returned unchanged
+    pub fn brightened(self) -> Color {
+        match self {
+            Color::Black => Color::BrightBlack,
+            Color::Red => Color::BrightRed,
+            Color::Green => Color::BrightGreen,
+            Color::Yellow => Color::BrightYellow,
+            Color::Blue => Color::BrightBlue,
+            Color::Magenta => Color::BrightMagenta,
+            Color::Cyan => Color::BrightCyan,
+            Color::White => Color::BrightWhite,
+            other => other,
+        }
+    }
+}
+
+/// Something that happened to a terminal, delivered to every receiver from
+/// `Terminal::subscribe`
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TerminalEvent {
+    /// Output from the child, after it was applied to the grid
     Output(Vec<u8>),
+    /// The PTY and grid were resized to (cols, rows)
     Resize(u16, u16),
+    /// The child exited and all of its output has been read
     Exit,
 }
 
+/// Output parsed per pane each frame unless configured otherwise; the most
+/// one `Pty::read` returns, so a single read is parsed whole
+pub const DEFAULT_PARSE_BUDGET: usize = 256 * 1024;
+
 pub struct Terminal {
     pub grid: Arc<Mutex<Grid>>,
-    pty: Pty,
+    // None for a terminal whose process failed to start, see `failed`
+    pty: Option<Pty>,
     parser: TerminalParser,
-    _event_tx: Sender<TerminalEvent>,
-    _event_rx: Receiver<TerminalEvent>,
+    subscribers: Mutex<Vec<Sender<TerminalEvent>>>,
+    exited: bool,
     server_client: Option<Arc<RwLock<ServerClient>>>,
     publish_output: bool,
+    /// Tags server input and output logs with the session and pane
+    server_span: tracing::Span,
+    /// Output handed to `process_output` but not parsed yet, see
+    /// `set_parse_budget`
+    backlog: Vec<u8>,
+    parse_budget: Option<usize>,
 }
 
 impl Terminal {
     pub fn new(cols: u16, rows: u16) -> anyhow::Result<Self> {
-        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        Self::with_shell(cols, rows, &ShellConfig::default())
+    }
+
+    /// Create a new terminal running the configured shell or command
+    pub fn with_shell(cols: u16, rows: u16, shell: &ShellConfig) -> anyhow::Result<Self> {
         let grid = Arc::new(Mutex::new(Grid::new(cols as usize, rows as usize)));
-        let pty = Pty::new(cols, rows)?;
+        let pty = Pty::with_options(cols, rows, shell)?;
         let parser = TerminalParser::new(grid.clone());
 
         Ok(Self {
             grid,
-            pty,
+            pty: Some(pty),
             parser,
-            _event_tx: event_tx,
-            _event_rx: event_rx,
+            subscribers: Mutex::new(Vec::new()),
+            exited: false,
             server_client: None,
             publish_output: false,
+            server_span: tracing::Span::none(),
+            backlog: Vec::new(),
+            parse_budget: None,
         })
     }
 
@@ -74,46 +122,224 @@ impl Terminal {
         rows: u16,
         server_client: ServerClient,
     ) -> anyhow::Result<Self> {
-        let (event_tx, event_rx) = crossbeam_channel::unbounded();
         let grid = Arc::new(Mutex::new(Grid::new(cols as usize, rows as usize)));
         let pty = Pty::new(cols, rows)?;
         let parser = TerminalParser::new(grid.clone());
+        let server_span = tracing::info_span!(
+            "terminal",
+            session = server_client.session_id(),
+            pane = server_client.pane_id()
+        );
 
         Ok(Self {
             grid,
-            pty,
+            pty: Some(pty),
             parser,
-            _event_tx: event_tx,
-            _event_rx: event_rx,
+            subscribers: Mutex::new(Vec::new()),
+            exited: false,
             server_client: Some(Arc::new(RwLock::new(server_client))),
             publish_output: true,
+            server_span,
+            backlog: Vec::new(),
+            parse_budget: None,
         })
     }
 
+    /// A terminal with no process that shows `message` in red, standing in
+    /// for one whose shell or command failed to start. Writes to it fail.
+    pub fn failed(cols: u16, rows: u16, message: &str) -> Self {
+        let grid = Arc::new(Mutex::new(Grid::new(cols as usize, rows as usize)));
+        let mut parser = TerminalParser::new(grid.clone());
+        parser.parse(format!("\x1b[31m{}\x1b[0m", message.replace('\n', "\r\n")).as_bytes());
+
+        Self {
+            grid,
+            pty: None,
+            parser,
+            subscribers: Mutex::new(Vec::new()),
+            exited: false,
+            server_client: None,
+            publish_output: false,
+            server_span: tracing::Span::none(),
+            backlog: Vec::new(),
+            parse_budget: None,
+        }
+    }
+
+    /// The PTY, or an error for a terminal whose process failed to start
+    fn running(pty: &mut Option<Pty>) -> anyhow::Result<&mut Pty> {
+        pty.as_mut().ok_or_else(|| anyhow::anyhow!("Terminal has no running process"))
+    }
+
     pub fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
-        self.pty.write(data)?;
+        Self::running(&mut self.pty)?.write(data)?;
+        Ok(())
+    }
+
+    /// Send the key `name` to the PTY as if typed; see `keys::key_sequence`
+    pub fn send_key(&mut self, name: &str, mods: keys::KeyModifiers) -> anyhow::Result<()> {
+        let bytes = keys::key_sequence(name, mods)
+            .ok_or_else(|| anyhow::anyhow!("Unknown key '{}'", name))?;
+        Self::running(&mut self.pty)?.write(&bytes)?;
+        Ok(())
+    }
+
+    /// Bytes reporting a mouse event at the 1-based cell (`col`, `row`), if
+    /// the application enabled mouse tracking for it
+    pub fn mouse_report(&self, button: mouse::MouseButton, action: mouse::MouseAction, col: usize, row: usize) -> Option<Vec<u8>> {
+        let grid = self.grid.lock().unwrap();
+        mouse::encode_mouse(grid.mouse_tracking(), grid.sgr_mouse(), button, action, col, row)
+    }
+
+    /// Arrow keys to send for `lines` wheel notches, if the application is
+    /// on the alternate screen with alternate scroll mode on and isn't
+    /// tracking the mouse itself
+    pub fn alternate_scroll_keys(&self, button: mouse::MouseButton, lines: usize) -> Option<Vec<u8>> {
+        let grid = self.grid.lock().unwrap();
+        mouse::encode_alternate_scroll(
+            grid.mouse_tracking(),
+            grid.alternate_screen(),
+            grid.alternate_scroll(),
+            button,
+            lines,
+        )
+    }
+
+    /// Bytes reporting that the window gained or lost focus, if the
+    /// application enabled focus reporting
+    pub fn focus_report(&self, focused: bool) -> Option<&'static [u8]> {
+        if !self.grid.lock().unwrap().focus_reporting() {
+            return None;
+        }
+        Some(if focused { b"\x1b[I" } else { b"\x1b[O" })
+    }
+
+    /// Write pasted text to the PTY: sanitized for the current bracketed
+    /// paste mode and split into bounded writes
+    pub fn paste(&mut self, text: &str) -> anyhow::Result<()> {
+        let bracketed = self.grid.lock().unwrap().bracketed_paste();
+        let mut data = paste::sanitize_paste(text, bracketed);
+        if bracketed {
+            data = format!("{}{}{}", paste::BRACKETED_PASTE_START, data, paste::BRACKETED_PASTE_END);
+        }
+
+        let pty = Self::running(&mut self.pty)?;
+        for chunk in paste::paste_chunks(&data, paste::PASTE_CHUNK_SIZE) {
+            pty.write(chunk.as_bytes())?;
+        }
         Ok(())
     }
 
+    /// Resize the PTY and grid, then emit `TerminalEvent::Resize`
     pub fn resize(&mut self, cols: u16, rows: u16) -> anyhow::Result<()> {
-        self.pty.resize(cols, rows)?;
-        let mut grid = self.grid.lock().unwrap();
-        grid.resize(cols as usize, rows as usize);
+        if let Some(pty) = &mut self.pty {
+            pty.resize(cols, rows)?;
+        }
+        self.grid.lock().unwrap().resize(cols as usize, rows as usize);
+        self.emit(TerminalEvent::Resize(cols, rows));
         Ok(())
     }
 
+    /// Receive this terminal's events from now on. Every subscriber gets
+    /// every event, in the order they happened on this terminal; events from
+    /// before subscribing aren't replayed. The channel is unbounded, and it
+    /// is dropped from the terminal once the receiver is.
+    pub fn subscribe(&self) -> Receiver<TerminalEvent> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn emit(&self, event: TerminalEvent) {
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Take the child's pending output (see `Pty::read`). Emits
+    /// `TerminalEvent::Exit` once the child has exited and everything it
+    /// wrote has been read.
     pub fn read(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
-        self.pty.read()
+        let Some(pty) = &mut self.pty else {
+            return Ok(None);
+        };
+        let result = pty.read();
+        if !matches!(result, Ok(Some(_))) && !self.exited && pty.is_finished() {
+            self.exited = true;
+            self.emit(TerminalEvent::Exit);
+        }
+        result
+    }
+
+    /// Whether the child has exited and all of its output has been read
+    pub fn has_exited(&self) -> bool {
+        self.exited
     }
 
+    /// Apply output to the grid, answer any queries in it (device
+    /// attributes, status reports), then emit it as `TerminalEvent::Output`.
+    /// With a parse budget set, only that much is applied now and the rest
+    /// waits for `parse_pending`.
     pub fn process_output(&mut self, data: &[u8]) {
-        self.parser.parse(data);
+        self.backlog.extend_from_slice(data);
+        self.parse_pending();
+    }
+
+    /// Return the parser and grid to a clean state, as RIS (`ESC c`) would:
+    /// the screen and scrollback are cleared, styles, scroll region and
+    /// modes reset and the cursor homed. Unparsed output is dropped; the
+    /// PTY and its process are left alone.
+    pub fn reset(&mut self) {
+        self.backlog.clear();
+        self.parser.reset();
+    }
+
+    /// Apply up to the parse budget of the output still waiting from
+    /// `process_output`, returning whether there was any
+    pub fn parse_pending(&mut self) -> bool {
+        if self.backlog.is_empty() {
+            return false;
+        }
+        let len = self.parse_budget.map_or(self.backlog.len(), |budget| budget.clamp(1, self.backlog.len()));
+        let data: Vec<u8> = if len == self.backlog.len() {
+            std::mem::take(&mut self.backlog)
+        } else {
+            self.backlog.drain(..len).collect()
+        };
+
+        self.parser.parse(&data);
+
+        let responses = self.parser.take_responses();
+        if !responses.is_empty() {
+            if let Err(e) = self.write(&responses) {
+                log::error!("Failed to answer terminal query: {}", e);
+            }
+        }
+        if !self.subscribers.lock().unwrap().is_empty() {
+            self.emit(TerminalEvent::Output(data));
+        }
+        true
+    }
+
+    /// Bytes of output waiting to be parsed
+    pub fn pending_output(&self) -> usize {
+        self.backlog.len()
+    }
+
+    /// Parse at most `bytes` of output per `process_output` or
+    /// `parse_pending` call, so a burst is spread over several frames
+    /// instead of stalling one; `None` parses everything at once
+    pub fn set_parse_budget(&mut self, bytes: Option<usize>) {
+        self.parse_budget = bytes;
     }
 
     pub fn grid(&self) -> Arc<Mutex<Grid>> {
         self.grid.clone()
     }
 
+    /// Working directory the shell last reported with `OSC 7`, if any
+    pub fn current_dir(&self) -> Option<PathBuf> {
+        self.grid.lock().unwrap().current_dir().map(Path::to_path_buf)
+    }
+
     pub fn scroll_back_up(&mut self, lines: usize) {
         let mut grid = self.grid.lock().unwrap();
         grid.scroll_back_up(lines);
@@ -129,95 +355,226 @@ impl Terminal {
         grid.scroll_to_bottom();
     }
 
-    /// Poll for input commands from server and write to PTY
+    /// See `Pty::set_output_waker`
+    pub fn set_output_waker(&self, waker: OutputWaker) {
+        if let Some(pty) = &self.pty {
+            pty.set_output_waker(waker);
+        }
+    }
+
+    /// Text of scrollback line `index`, where 0 is the oldest line kept
+    pub fn scrollback_line(&self, index: usize) -> Option<String> {
+        self.grid.lock().unwrap().scrollback_line(index)
+    }
+
+    /// Scrollback lines `start..end`, oldest first; out of range indices
+    /// are clamped
+    pub fn scrollback_range(&self, start: usize, end: usize) -> Vec<String> {
+        self.grid.lock().unwrap().scrollback_range(start, end)
+    }
+
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        let mut grid = self.grid.lock().unwrap();
+        grid.set_max_scrollback(lines);
+    }
+
+    pub fn clear_scrollback(&mut self) {
+        let mut grid = self.grid.lock().unwrap();
+        grid.clear_scrollback();
+    }
+
+    /// Keep the server connection alive, reconnecting to `addr` when pings
+    /// go unanswered; see `ServerClient::spawn_keepalive`. `None` without a
+    /// server connection.
+    pub fn spawn_server_keepalive(&self, addr: &str, token: &str, interval: std::time::Duration) -> Option<tokio::task::JoinHandle<()>> {
+        let client = self.server_client.as_ref()?;
+        Some(ServerClient::spawn_keepalive(client, addr.to_string(), token.to_string(), interval))
+    }
+
+    /// Poll for input commands from server and write to PTY; text sent
+    /// with `WRITE` is drawn on the screen instead.
     /// Should be called from the main event loop periodically
     pub async fn poll_server_input(&mut self) -> anyhow::Result<()> {
-        if let Some(client) = &self.server_client {
-            let mut client_guard = client.write().await;
+        let span = self.server_span.clone();
+        async {
+            if let Some(client) = &self.server_client {
+                let mut client_guard = client.write().await;
 
-            // Poll for input commands (non-blocking)
-            match client_guard.read_input().await {
-                Ok(Some(cmd)) => {
-                    // Write command to PTY
-                    self.pty.write(cmd.as_bytes())?;
-                }
-                Ok(None) => {
-                    // Queue empty, nothing to do
-                }
-                Err(e) => {
-                    log::warn!("Failed to read server input: {}", e);
+                // Poll for input commands (non-blocking)
+                match client_guard.read_input().await {
+                    Ok(Some(cmd)) => {
+                        if let Some(bytes) = crate::redititi_server::display_message_bytes(&cmd) {
+                            tracing::trace!(bytes = bytes.len(), "Displaying server text");
+                            drop(client_guard);
+                            self.process_output(&bytes);
+                            return Ok(());
+                        }
+
+                        // Write command to PTY
+                        let bytes = crate::redititi_server::input_message_bytes(&cmd);
+                        tracing::trace!(bytes = bytes.len(), "Writing server input");
+                        Self::running(&mut self.pty)?.write(&bytes)?;
+                    }
+                    Ok(None) => {
+                        // Queue empty, nothing to do
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to read server input: {}", e);
+                    }
                 }
             }
+
+            Ok(())
         }
+        .instrument(span)
+        .await
+    }
 
-        Ok(())
+    /// Handle pending `CAPTURE ... FILE` requests from the server, writing
+    /// snapshots inside `capture_dir`
+    pub async fn poll_capture_requests(&self, capture_dir: &Path) {
+        let Some(client) = &self.server_client else {
+            return;
+        };
+
+        let request = match client.read().await.read_capture_request().await {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Failed to read capture request: {}", e);
+                return;
+            }
+        };
+
+        let request: serde_json::Value = match serde_json::from_str(&request) {
+            Ok(request) => request,
+            Err(e) => {
+                log::warn!("Ignoring malformed capture request: {}", e);
+                return;
+            }
+        };
+        if request["mode"] != "FILE" {
+            return;
+        }
+
+        let Some(path) = request["path"].as_str() else {
+            log::warn!("Ignoring capture request without a path");
+            return;
+        };
+        let format = if request["format"] == "ANSI" {
+            CaptureFormat::Ansi
+        } else {
+            CaptureFormat::Text
+        };
+
+        match self.capture_file(capture_dir, path, format) {
+            Ok(path) => log::info!("Saved screen capture to {:?}", path),
+            Err(e) => log::error!("Failed to capture {:?}: {}", path, e),
+        }
     }
 
-    /// Publish output to server if enabled
+    /// Write a snapshot of the screen to `requested`, resolved inside
+    /// `capture_dir`; absolute paths and `..` are rejected
+    pub fn capture_file(&self, capture_dir: &Path, requested: &str, format: CaptureFormat) -> anyhow::Result<PathBuf> {
+        let path = resolve_capture_path(capture_dir, requested)
+            .ok_or_else(|| anyhow::anyhow!("capture path {:?} is outside {:?}", requested, capture_dir))?;
+        self.grid.lock().unwrap().capture_to_file(&path, format)?;
+        Ok(path)
+    }
+
+    /// Publish output to server if enabled: every cell changed since the
+    /// last call, in screen order, as a single `PUBLISH` however many lines
+    /// changed
     pub async fn publish_output_if_needed(&self) {
         if !self.publish_output {
             return;
         }
 
         if let Some(client) = &self.server_client {
-            // Get dirty lines from grid
-            let dirty_lines = self.get_dirty_lines();
+            let changes = self.grid.lock().unwrap().take_changes();
 
-            if !dirty_lines.is_empty() {
-                // Publish each dirty line
+            if !changes.is_empty() {
                 let client_guard = client.read().await;
-                for (line_num, content) in dirty_lines {
-                    let output = format!("L{}: {}", line_num, content);
-                    if let Err(e) = client_guard.publish_output(&output).await {
-                        log::error!("Failed to publish output: {}", e);
-                    }
+                let published = client_guard
+                    .publish_output(&CellChange::encode(&changes))
+                    .instrument(self.server_span.clone())
+                    .await;
+                if let Err(e) = published {
+                    self.server_span.in_scope(|| tracing::error!("Failed to publish output: {}", e));
                 }
             }
         }
     }
+}
 
-    /// Extract dirty lines from grid as strings
-    fn get_dirty_lines(&self) -> Vec<(usize, String)> {
-        let mut grid = self.grid.lock().unwrap();
-        let mut dirty_lines = Vec::new();
-
-        if grid.is_all_dirty() {
-            // Full screen is dirty, get all lines
-            let (cols, rows) = grid.size();
-            for row in 0..rows {
-                let line = self.extract_line_text(&grid, row, cols);
-                dirty_lines.push((row, line));
-            }
-        } else {
-            // Only specific cells are dirty, group by row
-            let dirty_cells = grid.dirty_cells();
-            let mut dirty_rows: std::collections::HashSet<usize> = std::collections::HashSet::new();
+/// Resolve a capture file name requested over the network inside `dir`,
+/// rejecting absolute paths and anything that would escape it
+fn resolve_capture_path(dir: &Path, requested: &str) -> Option<PathBuf> {
+    let requested = Path::new(requested);
+    let is_contained = requested
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
 
-            for &(_, row) in dirty_cells {
-                dirty_rows.insert(row);
-            }
+    (is_contained && requested.components().next().is_some()).then(|| dir.join(requested))
+}
 
-            let (cols, _) = grid.size();
-            for &row in &dirty_rows {
-                let line = self.extract_line_text(&grid, row, cols);
-                dirty_lines.push((row, line));
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Clear dirty flags after extraction
-        grid.clear_dirty();
+    #[test]
+    fn test_resolve_capture_path_stays_in_dir() {
+        let dir = Path::new("/tmp/captures");
 
-        dirty_lines
+        assert_eq!(
+            resolve_capture_path(dir, "run-1/screen.txt"),
+            Some(PathBuf::from("/tmp/captures/run-1/screen.txt"))
+        );
+        assert_eq!(resolve_capture_path(dir, "/etc/passwd"), None);
+        assert_eq!(resolve_capture_path(dir, "../escape.txt"), None);
+        assert_eq!(resolve_capture_path(dir, "a/../../b.txt"), None);
+        assert_eq!(resolve_capture_path(dir, ""), None);
     }
 
-    /// Extract text from a single line
-    fn extract_line_text(&self, grid: &Grid, row: usize, cols: usize) -> String {
-        let mut line = String::new();
-        for col in 0..cols {
-            if let Some(cell) = grid.get_cell(col, row) {
-                line.push(cell.c);
-            }
+    #[test]
+    fn test_scrollback_lines_oldest_first() {
+        let mut terminal = Terminal::failed(20, 3, "");
+        let output: String = (0..6).map(|n| format!("line {}\r\n", n)).collect();
+        terminal.process_output(output.as_bytes());
+
+        // Four lines scrolled off a 3-row screen ending on an empty line
+        assert_eq!(terminal.scrollback_line(0).as_deref(), Some("line 0"));
+        assert_eq!(terminal.scrollback_line(3).as_deref(), Some("line 3"));
+        assert_eq!(terminal.scrollback_line(4), None);
+        assert_eq!(terminal.scrollback_range(1, 3), vec!["line 1", "line 2"]);
+        assert_eq!(terminal.scrollback_range(2, 100), vec!["line 2", "line 3"]);
+        assert!(terminal.scrollback_range(5, 2).is_empty());
+    }
+
+    #[test]
+    fn test_reset_clears_grid_and_parser() {
+        let mut terminal = Terminal::failed(20, 3, "");
+        let output: String = (0..5).map(|n| format!("\x1b[1;31;44mline {}\r\n", n)).collect();
+        terminal.process_output(output.as_bytes());
+        // Leave a sequence half parsed
+        terminal.process_output(b"\x1b[3");
+
+        terminal.reset();
+
+        {
+            let grid = terminal.grid();
+            let grid = grid.lock().unwrap();
+            assert!(grid.cells().iter().all(|cell| *cell == Cell::default()));
+            assert_eq!(grid.scrollback_len(), 0);
+            assert_eq!(grid.cursor_pos(), (0, 0));
+            assert_eq!(grid.get_current_style(), CellStyle::default());
         }
-        line.trim_end().to_string()
+
+        // The half-parsed sequence is gone, so this prints as plain text
+        terminal.process_output(b"2mX");
+        let grid = terminal.grid();
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.row_text(0), "2mX");
+        assert_eq!(grid.get_cell(2, 0).unwrap().style, CellStyle::default());
     }
 }
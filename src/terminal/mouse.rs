@@ -0,0 +1,97 @@
+//! Mouse reporting: encoding pointer events for applications that enabled
+//! tracking with DECSET 1000/1002, in SGR (1006) or legacy form, and the
+//! wheel-to-arrow-keys translation of alternate scroll mode (1007)
+
+use serde::{Deserialize, Serialize};
+
+/// Which mouse events the application asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// Presses, releases and wheel (DECSET 1000)
+    Click,
+    /// As `Click`, plus motion while a button is held (DECSET 1002)
+    Drag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseAction {
+    Press,
+    Release,
+    /// Motion with the button held
+    Drag,
+}
+
+/// Largest coordinate the legacy encoding can carry in one byte
+const LEGACY_MAX_COORD: usize = 223;
+
+/// Encode an event at the 1-based cell (`col`, `row`), or `None` if
+/// `tracking` doesn't cover it
+pub fn encode_mouse(
+    tracking: MouseTracking,
+    sgr: bool,
+    button: MouseButton,
+    action: MouseAction,
+    col: usize,
+    row: usize,
+) -> Option<Vec<u8>> {
+    match (tracking, action) {
+        (MouseTracking::Off, _) | (MouseTracking::Click, MouseAction::Drag) => return None,
+        _ => {}
+    }
+
+    let mut code: u8 = match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+        MouseButton::WheelUp => 64,
+        MouseButton::WheelDown => 65,
+    };
+    if action == MouseAction::Drag {
+        code += 32;
+    }
+
+    if sgr {
+        let final_byte = if action == MouseAction::Release { 'm' } else { 'M' };
+        return Some(format!("\x1b[<{};{};{}{}", code, col, row, final_byte).into_bytes());
+    }
+
+    // Legacy encoding can't say which button was released
+    if action == MouseAction::Release {
+        code = 3;
+    }
+    let coord = |value: usize| (value.clamp(1, LEGACY_MAX_COORD) + 32) as u8;
+    Some(vec![0x1b, b'[', b'M', code + 32, coord(col), coord(row)])
+}
+
+/// Arrow keys standing in for `lines` wheel notches on the alternate screen
+/// with alternate scroll mode on, or `None` when the wheel should be reported
+/// or scroll the scrollback instead
+pub fn encode_alternate_scroll(
+    tracking: MouseTracking,
+    alternate_screen: bool,
+    alternate_scroll: bool,
+    button: MouseButton,
+    lines: usize,
+) -> Option<Vec<u8>> {
+    if tracking != MouseTracking::Off || !alternate_screen || !alternate_scroll {
+        return None;
+    }
+
+    let arrow: &[u8] = match button {
+        MouseButton::WheelUp => b"\x1b[A",
+        MouseButton::WheelDown => b"\x1b[B",
+        _ => return None,
+    };
+    Some(arrow.repeat(lines))
+}
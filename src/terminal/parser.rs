@@ -1,4 +1,6 @@
-use super::{Color, Grid};
+use super::{Color, CursorShape, Grid, LineSize};
+use crate::metrics::METRICS;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use vte::{Params, Perform};
 
@@ -6,24 +8,46 @@ pub struct TerminalParser {
     grid: Arc<Mutex<Grid>>,
     performer: TerminalPerformer,
     vte_parser: vte::Parser,  // Keep parser state across calls
+    c1_controls: C1Controls,
 }
 
 impl TerminalParser {
     pub fn new(grid: Arc<Mutex<Grid>>) -> Self {
         let performer = TerminalPerformer {
             grid: grid.clone(),
+            last_char: None,
+            responses: Vec::new(),
         };
         Self {
             grid,
             performer,
             vte_parser: vte::Parser::new(),
+            c1_controls: C1Controls::default(),
         }
     }
 
+    /// Forget any half-parsed sequence and pending replies, and return the
+    /// grid to its power-on state as RIS does
+    pub fn reset(&mut self) {
+        self.vte_parser = vte::Parser::new();
+        self.c1_controls = C1Controls::default();
+        self.performer.last_char = None;
+        self.performer.responses.clear();
+        self.grid.lock().unwrap().reset();
+    }
+
+    /// Replies to queries (device attributes, status reports) found by
+    /// `parse` since the last call, to be written back to the application
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.performer.responses)
+    }
+
     pub fn parse(&mut self, data: &[u8]) {
+        METRICS.record_parsed(data.len());
+
         // Fast path: if data has no escape sequences, process as plain text
         // This dramatically improves throughput for large file output (cat, tail, etc.)
-        if !data.contains(&b'\x1b') {
+        if !data.contains(&b'\x1b') && self.c1_controls.is_idle() && !C1Controls::has_utf8_c1(data) {
             // Plain text - no ANSI codes, use optimized bulk processing
             if let Ok(text) = std::str::from_utf8(data) {
                 let mut grid = self.grid.lock().unwrap();
@@ -50,28 +74,138 @@ impl TerminalParser {
                     }
                 }
 
+                // Keep REP working when the repeat arrives in a later chunk
+                self.performer.last_char = text.chars().last().filter(|c| !c.is_control());
+
                 return;
             }
         }
 
         // Slow path: has escape sequences, use full ANSI parser
-        for byte in data {
-            self.vte_parser.advance(&mut self.performer, *byte);
+        let (vte_parser, performer) = (&mut self.vte_parser, &mut self.performer);
+        for &byte in data {
+            self.c1_controls.translate(byte, |b| vte_parser.advance(performer, b));
+        }
+    }
+}
+
+/// Path of a `file://host/path` URL with its percent-escapes decoded; the
+/// host is ignored
+fn file_url_path(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Rewrites 8-bit C1 controls (0x9B for CSI, 0x9D for OSC, 0x9C for ST, ...)
+/// as their 7-bit `ESC Fe` forms, the only ones vte recognizes. Both raw C1
+/// bytes and C1 characters encoded as UTF-8 (`C2 9B`) are rewritten; bytes
+/// continuing a UTF-8 character are left alone.
+#[derive(Default)]
+struct C1Controls {
+    /// Continuation bytes still expected for the current UTF-8 character
+    utf8_remaining: u8,
+    /// A 0xC2 lead byte held back until the next byte shows whether it
+    /// starts a C1 control
+    pending_c2: bool,
+}
+
+impl C1Controls {
+    fn is_idle(&self) -> bool {
+        self.utf8_remaining == 0 && !self.pending_c2
+    }
+
+    /// Whether `data` holds a C1 control encoded as UTF-8
+    fn has_utf8_c1(data: &[u8]) -> bool {
+        data.windows(2).any(|pair| pair[0] == 0xC2 && (0x80..=0x9F).contains(&pair[1]))
+    }
+
+    fn translate(&mut self, byte: u8, mut out: impl FnMut(u8)) {
+        if self.pending_c2 {
+            self.pending_c2 = false;
+            if (0x80..=0x9F).contains(&byte) {
+                out(0x1b);
+                out(byte - 0x40);
+                return;
+            }
+            out(0xC2);
+            self.utf8_remaining = 1;
+        }
+
+        match byte {
+            0x80..=0xBF if self.utf8_remaining > 0 => {
+                self.utf8_remaining -= 1;
+                out(byte);
+            }
+            0x80..=0x9F => {
+                out(0x1b);
+                out(byte - 0x40);
+            }
+            0xC2 => {
+                self.utf8_remaining = 0;
+                self.pending_c2 = true;
+            }
+            _ => {
+                self.utf8_remaining = match byte {
+                    0xC3..=0xDF => 1,
+                    0xE0..=0xEF => 2,
+                    0xF0..=0xF4 => 3,
+                    _ => 0,
+                };
+                out(byte);
+            }
         }
     }
 }
 
+/// Reply to primary device attributes (`CSI c`): a VT220 with 132 columns
+/// and selective erase
+pub const PRIMARY_DEVICE_ATTRIBUTES: &[u8] = b"\x1b[?62;1;6c";
+
+/// Reply to secondary device attributes (`CSI > c`): a VT220 with the crate
+/// version as the firmware version (major * 10000 + minor * 100 + patch)
+pub fn secondary_device_attributes() -> String {
+    let part = |s: &str| s.parse::<u32>().unwrap_or(0);
+    let version = part(env!("CARGO_PKG_VERSION_MAJOR")) * 10000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"));
+    format!("\x1b[>1;{};0c", version)
+}
+
 struct TerminalPerformer {
     grid: Arc<Mutex<Grid>>,
+    /// Last printed character, repeated by REP (CSI b). Cleared when the
+    /// cursor moves so a repeat never resurrects a stale character.
+    last_char: Option<char>,
+    /// Replies owed to the application, see `TerminalParser::take_responses`
+    responses: Vec<u8>,
 }
 
 impl Perform for TerminalPerformer {
     fn print(&mut self, c: char) {
         let mut grid = self.grid.lock().unwrap();
         grid.put_char(c);
+        self.last_char = Some(c);
     }
 
     fn execute(&mut self, byte: u8) {
+        if matches!(byte, b'\n' | b'\r' | b'\t' | b'\x08') {
+            self.last_char = None;
+        }
         let mut grid = self.grid.lock().unwrap();
         match byte {
             b'\n' => grid.newline(),
@@ -94,34 +228,63 @@ impl Perform for TerminalPerformer {
         // End of DCS - not implemented yet
     }
 
-    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {
-        // OSC sequences (Operating System Command) - not implemented yet
-        // These are used for things like setting window title, etc.
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        // OSC sequences (Operating System Command): the title and working
+        // directory are handled
+        match params.first() {
+            // 0 sets icon name and title, 2 just the title
+            Some(&b"0") | Some(&b"2") => {
+                let title = params[1..]
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                let title = (!title.is_empty()).then_some(title);
+                self.grid.lock().unwrap().set_title(title);
+            }
+            // 7 reports the working directory as a file:// URL
+            Some(&b"7") => {
+                let url = params[1..]
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                match file_url_path(&url) {
+                    Some(path) => self.grid.lock().unwrap().set_current_dir(Some(path)),
+                    None => log::debug!("Ignoring OSC 7 with unsupported URL {:?}", url),
+                }
+            }
+            _ => {}
+        }
     }
 
-    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, c: char) {
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, c: char) {
+        if matches!(c, 'A' | 'B' | 'C' | 'D' | 'H' | 'f' | 'u') {
+            self.last_char = None;
+        }
+
         match c {
             'A' => {
                 // Cursor up
-                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1) as i32;
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1) as i32;
                 let mut grid = self.grid.lock().unwrap();
                 grid.move_cursor(0, -n);
             }
             'B' => {
                 // Cursor down
-                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1) as i32;
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1) as i32;
                 let mut grid = self.grid.lock().unwrap();
                 grid.move_cursor(0, n);
             }
             'C' => {
                 // Cursor forward
-                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1) as i32;
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1) as i32;
                 let mut grid = self.grid.lock().unwrap();
                 grid.move_cursor(n, 0);
             }
             'D' => {
                 // Cursor back
-                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1) as i32;
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1) as i32;
                 let mut grid = self.grid.lock().unwrap();
                 grid.move_cursor(-n, 0);
             }
@@ -143,6 +306,16 @@ impl Perform for TerminalPerformer {
                 let mut grid = self.grid.lock().unwrap();
                 grid.set_cursor(x, y);
             }
+            'b' => {
+                // REP - repeat the preceding printed character
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1);
+                if let Some(last) = self.last_char {
+                    let mut grid = self.grid.lock().unwrap();
+                    for _ in 0..n {
+                        grid.put_char(last);
+                    }
+                }
+            }
             'J' => {
                 // Erase in display
                 let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(0);
@@ -157,11 +330,27 @@ impl Perform for TerminalPerformer {
                 let mut grid = self.grid.lock().unwrap();
                 grid.clear_line();
             }
-            'm' => {
+            'X' if intermediates.is_empty() => {
+                // ECH - erase characters from the cursor
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1) as usize;
+                self.grid.lock().unwrap().erase_chars(n);
+            }
+            'S' | 'T' | 'L' | 'M' if intermediates.is_empty() => {
+                // SU / SD scroll the region, IL / DL insert or delete lines at the cursor
+                let n = params.iter().next().and_then(|p| p.first()).copied().unwrap_or(1).max(1) as usize;
+                let mut grid = self.grid.lock().unwrap();
+                match c {
+                    'S' => grid.scroll_up(n),
+                    'T' => grid.scroll_down(n),
+                    'L' => grid.insert_lines(n),
+                    _ => grid.delete_lines(n),
+                }
+            }
+            'm' if intermediates.is_empty() => {
                 // SGR - Select Graphic Rendition
                 self.handle_sgr(params);
             }
-            'r' => {
+            'r' if intermediates.is_empty() => {
                 // Set scroll region
                 let mut iter = params.iter();
                 let top = iter
@@ -171,33 +360,110 @@ impl Perform for TerminalPerformer {
                     .unwrap_or(1)
                     .saturating_sub(1) as usize;
                 let mut grid = self.grid.lock().unwrap();
+                // A missing or 0 bottom means the last row
                 let bottom = iter
                     .next()
                     .and_then(|p| p.first())
                     .copied()
+                    .filter(|&row| row > 0)
                     .unwrap_or(grid.size().1 as u16)
                     .saturating_sub(1) as usize;
-                grid.set_scroll_region(top, bottom);
+                if !grid.set_scroll_region(top, bottom) {
+                    log::debug!("Ignoring invalid scroll region {}..={}", top, bottom);
+                }
+            }
+            'h' | 'l' if intermediates == b"?" => {
+                // DEC private mode set/reset
+                let enabled = c == 'h';
+                let mut grid = self.grid.lock().unwrap();
+                for param in params.iter() {
+                    if let Some(&mode) = param.first() {
+                        grid.set_private_mode(mode, enabled);
+                    }
+                }
+            }
+            // XTSAVE / XTRESTORE: save or restore the listed private modes
+            's' | 'r' if intermediates == b"?" => {
+                let modes: Vec<u16> = params.iter().filter_map(|param| param.first().copied()).collect();
+                let mut grid = self.grid.lock().unwrap();
+                if c == 's' {
+                    grid.save_private_modes(&modes);
+                } else {
+                    grid.restore_private_modes(&modes);
+                }
+            }
+            'q' if intermediates == b" " => {
+                // DECSCUSR: set cursor shape (blinking and steady look the same)
+                let shape = match params.iter().next().and_then(|p| p.first()).copied().unwrap_or(0) {
+                    0 => None,
+                    1 | 2 => Some(CursorShape::Block),
+                    3 | 4 => Some(CursorShape::Underline),
+                    5 | 6 => Some(CursorShape::Bar),
+                    _ => return,
+                };
+                self.grid.lock().unwrap().set_cursor_shape(shape);
+            }
+            'c' if intermediates.is_empty() || intermediates == b">" => {
+                // Primary (CSI c) or secondary (CSI > c) device attributes;
+                // other parameters aren't queries
+                if params.iter().next().and_then(|p| p.first()).copied().unwrap_or(0) != 0 {
+                    return;
+                }
+                if intermediates.is_empty() {
+                    self.responses.extend_from_slice(PRIMARY_DEVICE_ATTRIBUTES);
+                } else {
+                    self.responses.extend_from_slice(secondary_device_attributes().as_bytes());
+                }
+            }
+            'n' if intermediates.is_empty() => {
+                // DSR: device status report
+                match params.iter().next().and_then(|p| p.first()).copied() {
+                    Some(5) => self.responses.extend_from_slice(b"\x1b[0n"),
+                    Some(6) => {
+                        // Cursor position report, 1-based
+                        let (x, y) = self.grid.lock().unwrap().cursor_pos();
+                        self.responses.extend_from_slice(format!("\x1b[{};{}R", y + 1, x + 1).as_bytes());
+                    }
+                    _ => {}
+                }
             }
-            's' => {
+            's' if intermediates.is_empty() => {
                 // Save cursor position
                 let mut grid = self.grid.lock().unwrap();
                 grid.save_cursor();
             }
-            'u' => {
+            'u' if intermediates.is_empty() => {
                 // Restore cursor position
                 let mut grid = self.grid.lock().unwrap();
                 grid.restore_cursor();
             }
+            'p' if intermediates == b"!" => {
+                // DECSTR - soft terminal reset
+                self.grid.lock().unwrap().soft_reset();
+            }
             _ => {
                 // Unhandled CSI sequence
-                log::debug!("Unhandled CSI: {}", c);
+                log::debug!("Unhandled CSI: {:?} {}", String::from_utf8_lossy(intermediates), c);
             }
         }
     }
 
-    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {
-        // ESC sequences - not fully implemented
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        match (intermediates, byte) {
+            (b"", b'c') => {
+                // RIS - full reset
+                self.last_char = None;
+                self.grid.lock().unwrap().reset();
+            }
+            (b"#", b'3') => self.grid.lock().unwrap().set_line_size(LineSize::DoubleHeightTop),
+            (b"#", b'4') => self.grid.lock().unwrap().set_line_size(LineSize::DoubleHeightBottom),
+            (b"#", b'5') => self.grid.lock().unwrap().set_line_size(LineSize::Single),
+            (b"#", b'6') => self.grid.lock().unwrap().set_line_size(LineSize::DoubleWidth),
+            _ => {
+                // Other ESC sequences - not implemented
+                log::debug!("Unhandled ESC: {:?} {}", String::from_utf8_lossy(intermediates), byte as char);
+            }
+        }
     }
 }
 
@@ -287,6 +553,14 @@ impl TerminalPerformer {
                     }
                 }
                 49 => current_style.bg = Color::Default,
+                51 => current_style.framed = true,
+                52 => current_style.encircled = true,
+                53 => current_style.overline = true,
+                54 => {
+                    current_style.framed = false;
+                    current_style.encircled = false;
+                }
+                55 => current_style.overline = false,
                 90 => current_style.fg = Color::BrightBlack,
                 91 => current_style.fg = Color::BrightRed,
                 92 => current_style.fg = Color::BrightGreen,
@@ -330,15 +604,16 @@ impl TerminalPerformer {
             14 => Color::BrightCyan,
             15 => Color::BrightWhite,
             16..=231 => {
-                // 216 color cube
-                let idx = color - 16;
-                let r = (idx / 36) * 51;
-                let g = ((idx % 36) / 6) * 51;
-                let b = (idx % 6) * 51;
+                // 6x6x6 color cube, using xterm's non-linear channel levels
+                const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+                let idx = (color - 16) as usize;
+                let r = CUBE_LEVELS[idx / 36];
+                let g = CUBE_LEVELS[(idx % 36) / 6];
+                let b = CUBE_LEVELS[idx % 6];
                 Color::Rgb(r, g, b)
             }
             232..=255 => {
-                // Grayscale
+                // Grayscale ramp from 8 to 238 in steps of 10
                 let gray = (color - 232) * 10 + 8;
                 Color::Rgb(gray, gray, gray)
             }
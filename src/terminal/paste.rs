@@ -0,0 +1,88 @@
+//! Clipboard paste handling: control-character filtering and chunking
+
+/// Largest single PTY write for a paste. Matches the canonical-mode line
+/// buffer of most ttys so a long paste isn't truncated or stalled.
+pub const PASTE_CHUNK_SIZE: usize = 4096;
+
+/// Pastes longer than this many bytes ask for confirmation first unless
+/// the application enabled bracketed paste
+pub const DEFAULT_PASTE_CONFIRM_THRESHOLD: usize = 4096;
+
+pub const BRACKETED_PASTE_START: &str = "\x1b[200~";
+pub const BRACKETED_PASTE_END: &str = "\x1b[201~";
+
+/// Strip control characters from pasted text.
+///
+/// Tabs are always kept. Line breaks are kept only when `bracketed` is set,
+/// since the application then receives the paste as one unit instead of
+/// running each line; without it each one (`\r\n` counting as one) becomes
+/// a space, so a paste can't execute commands and its lines don't run
+/// together. ESC is always removed so the text can't end a bracketed paste
+/// early or inject escape sequences.
+pub fn sanitize_paste(text: &str, bracketed: bool) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | '\r' if bracketed => sanitized.push(c),
+            '\n' | '\r' => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                sanitized.push(' ');
+            }
+            '\t' => sanitized.push(c),
+            c if c.is_control() => {}
+            c => sanitized.push(c),
+        }
+    }
+
+    sanitized
+}
+
+/// Whether pasting `text` should be confirmed first: it spans several lines
+/// or is longer than `threshold` bytes, and the application didn't enable
+/// bracketed paste so it would take the text as typed input. A `threshold`
+/// of 0 turns confirmation off.
+pub fn paste_needs_confirmation(text: &str, bracketed: bool, threshold: usize) -> bool {
+    if bracketed || threshold == 0 {
+        return false;
+    }
+    text.contains(['\n', '\r']) || text.len() > threshold
+}
+
+/// Lines of the prompt asking whether to go ahead with pasting `text`
+pub fn paste_confirmation_prompt(text: &str) -> Vec<String> {
+    let lines = text.lines().count().max(1);
+    let mut prompt = vec![format!(
+        "Paste {} line{} ({} bytes)?",
+        lines,
+        if lines == 1 { "" } else { "s" },
+        text.len()
+    )];
+    if lines > 1 {
+        prompt.push("Line breaks become spaces without bracketed paste.".to_string());
+    }
+    prompt.push("Enter: paste   Esc: cancel".to_string());
+    prompt
+}
+
+/// Split `data` into pieces of at most `max_len` bytes without breaking a
+/// UTF-8 character
+pub fn paste_chunks(data: &str, max_len: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max_len.max(1));
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    chunks
+}
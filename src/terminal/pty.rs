@@ -1,14 +1,66 @@
-use portable_pty::{native_pty_system, CommandBuilder, PtyPair, PtySize};
+use crate::config::ShellConfig;
+use crate::metrics::METRICS;
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use std::collections::VecDeque;
 use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
 
+/// Output the reader thread buffers before it stops draining the PTY. Past
+/// this the kernel buffer fills and the child blocks until `read` catches up.
+pub const PTY_BUFFER_CAPACITY: usize = 4 * 1024 * 1024;
+
+/// Most bytes handed out by one `Pty::read`
+const MAX_READ_BATCH: usize = 256 * 1024;
+
+/// Size of each read from the PTY
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Called from the reader thread when output arrives in an empty buffer or
+/// the child's output ends, so a sleeping event loop knows to `read`
+pub type OutputWaker = Arc<dyn Fn() + Send + Sync>;
+
+/// A child process attached to a pseudo-terminal. `native_pty_system` picks
+/// the platform backend: ConPTY on Windows, a Unix98 PTY elsewhere.
+///
+/// A background thread drains the child's output into a bounded buffer, so
+/// the child isn't stalled by how often `read` is called.
 pub struct Pty {
-    pair: PtyPair,
-    reader: Box<dyn Read + Send>,
+    master: Box<dyn MasterPty + Send>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
     writer: Box<dyn Write + Send>,
+    output: Arc<OutputBuffer>,
+}
+
+/// Output shared between the reader thread and `Pty::read`
+#[derive(Default)]
+struct OutputBuffer {
+    state: Mutex<OutputState>,
+    /// Signalled when `read` frees space
+    drained: Condvar,
+}
+
+#[derive(Default)]
+struct OutputState {
+    data: VecDeque<u8>,
+    high_water: usize,
+    /// The reader thread has stopped (EOF or a read error)
+    finished: bool,
+    /// Read error to report once the buffered output is consumed
+    error: Option<std::io::Error>,
+    /// The `Pty` was dropped; the reader thread should stop
+    detached: bool,
+    waker: Option<OutputWaker>,
 }
 
 impl Pty {
     pub fn new(cols: u16, rows: u16) -> anyhow::Result<Self> {
+        Self::with_options(cols, rows, &ShellConfig::default())
+    }
+
+    /// Create a PTY running the program, arguments, environment and working
+    /// directory described by `shell`
+    pub fn with_options(cols: u16, rows: u16, shell: &ShellConfig) -> anyhow::Result<Self> {
         let pty_system = native_pty_system();
 
         let size = PtySize {
@@ -20,46 +72,134 @@ impl Pty {
 
         let pair = pty_system.openpty(size)?;
 
-        // Spawn shell
-        let shell = Self::get_shell();
-        let mut cmd = CommandBuilder::new(&shell.0);
-        for arg in &shell.1 {
-            cmd.arg(arg);
-        }
-
-        pair.slave.spawn_command(cmd)?;
+        // Spawn shell (or the configured command). Our copy of the slave is
+        // closed afterwards so reads fail once the child exits.
+        let cmd = Self::build_command(shell);
+        let child = pair.slave.spawn_command(cmd)?;
+        drop(pair.slave);
+        let killer = child.clone_killer();
 
         let reader = pair.master.try_clone_reader()?;
         let writer = pair.master.take_writer()?;
 
+        let output = Arc::new(OutputBuffer::default());
+        let thread_output = output.clone();
+        thread::Builder::new()
+            .name("pty-reader".to_string())
+            .spawn(move || Self::drain(reader, &thread_output))?;
+
         Ok(Self {
-            pair,
-            reader,
+            master: pair.master,
+            killer,
             writer,
+            output,
         })
     }
 
+    /// Reader thread: move the child's output into `output` until EOF, an
+    /// error, or the `Pty` going away, waiting while the buffer is full
+    fn drain(mut reader: Box<dyn Read + Send>, output: &OutputBuffer) {
+        let mut chunk = vec![0u8; READ_CHUNK];
+
+        loop {
+            let result = reader.read(&mut chunk);
+            let mut state = output.state.lock().unwrap();
+
+            let was_empty = state.data.is_empty();
+            match result {
+                Ok(0) => {
+                    state.finished = true;
+                    Self::wake(state);
+                    return;
+                }
+                Ok(n) => {
+                    while state.data.len() + n > PTY_BUFFER_CAPACITY && !state.detached {
+                        state = output.drained.wait(state).unwrap();
+                    }
+                    if state.detached {
+                        return;
+                    }
+
+                    state.data.extend(&chunk[..n]);
+                    state.high_water = state.high_water.max(state.data.len());
+                    METRICS.record_pty_buffered(state.data.len());
+                    if was_empty {
+                        Self::wake(state);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    state.error = Some(e);
+                    state.finished = true;
+                    Self::wake(state);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Call the waker, if any, without holding the lock
+    fn wake(state: std::sync::MutexGuard<'_, OutputState>) {
+        let waker = state.waker.clone();
+        drop(state);
+        if let Some(waker) = waker {
+            waker();
+        }
+    }
+
+    /// Have the reader thread call `waker` whenever output becomes available.
+    /// It is called right away if output is already waiting.
+    pub fn set_output_waker(&self, waker: OutputWaker) {
+        let mut state = self.output.state.lock().unwrap();
+        state.waker = Some(waker);
+        if !state.data.is_empty() || state.finished {
+            Self::wake(state);
+        }
+    }
+
     pub fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
         self.writer.write_all(data)?;
         self.writer.flush()?;
+        METRICS.record_pty_write(data.len());
         Ok(())
     }
 
+    /// Take the output buffered since the last call, up to a batch limit.
+    /// Never blocks: `None` means nothing is waiting. A read error (on Unix,
+    /// `EIO` once the child exits) is returned once, after the output before
+    /// it.
     pub fn read(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
-        let mut buf = vec![0u8; 8192];
-
-        // Try non-blocking read
-        match self.reader.read(&mut buf) {
-            Ok(0) => Ok(None),
-            Ok(n) => {
-                buf.truncate(n);
-                Ok(Some(buf))
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
-            Err(e) => Err(e.into()),
+        let mut state = self.output.state.lock().unwrap();
+
+        if state.data.is_empty() {
+            return match state.error.take() {
+                Some(e) => Err(e.into()),
+                None => Ok(None),
+            };
         }
+
+        let len = state.data.len().min(MAX_READ_BATCH);
+        let data = state.data.drain(..len).collect();
+        self.output.drained.notify_one();
+        METRICS.record_pty_read(len);
+        Ok(Some(data))
+    }
+
+    /// Whether the child's output has been read to the end
+    pub fn is_finished(&self) -> bool {
+        let state = self.output.state.lock().unwrap();
+        state.finished && state.data.is_empty()
     }
 
+    /// Most output ever buffered at once, waiting for `read`
+    pub fn high_water_mark(&self) -> usize {
+        self.output.state.lock().unwrap().high_water
+    }
+
+    /// Change the terminal size seen by the child. On Unix this is a
+    /// `TIOCSWINSZ` ioctl, after which the kernel sends `SIGWINCH` to the
+    /// foreground process group; on Windows ConPTY's `ResizePseudoConsole`
+    /// notifies the console application.
     pub fn resize(&mut self, cols: u16, rows: u16) -> anyhow::Result<()> {
         let size = PtySize {
             rows,
@@ -67,10 +207,49 @@ impl Pty {
             pixel_width: 0,
             pixel_height: 0,
         };
-        self.pair.master.resize(size)?;
+        self.master.resize(size)?;
         Ok(())
     }
 
+    /// Size as (cols, rows), as the backend reports it (`TIOCGWINSZ` on Unix)
+    pub fn size(&self) -> anyhow::Result<(u16, u16)> {
+        let size = self.master.get_size()?;
+        Ok((size.cols, size.rows))
+    }
+
+    fn build_command(shell: &ShellConfig) -> CommandBuilder {
+        // An explicitly configured program is used as-is; it is the user's
+        // own choice rather than something picked up from the environment
+        let (program, mut args) = match &shell.program {
+            Some(program) => (program.clone(), vec![]),
+            None => Self::get_shell(),
+        };
+
+        if shell.login_shell {
+            args.insert(0, "-l".to_string());
+        }
+        args.extend(shell.args.iter().cloned());
+
+        // CommandBuilder inherits the parent environment; only add to it
+        let mut cmd = CommandBuilder::new(&program);
+        cmd.args(&args);
+        cmd.env("TERM", &shell.term);
+        cmd.env("COLORTERM", "truecolor");
+        for (key, value) in &shell.env {
+            cmd.env(key, value);
+        }
+
+        if let Some(dir) = &shell.working_directory {
+            if dir.is_dir() {
+                cmd.cwd(dir);
+            } else {
+                log::warn!("Working directory {:?} does not exist, using default", dir);
+            }
+        }
+
+        cmd
+    }
+
     fn get_shell() -> (String, Vec<String>) {
         // Try to get shell from environment
         if let Ok(shell) = std::env::var("SHELL") {
@@ -177,3 +356,15 @@ impl Pty {
         }
     }
 }
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        // Hang up on the child; the reader thread holds its own handle on
+        // the PTY, so closing ours no longer does that
+        let _ = self.killer.kill();
+
+        let mut state = self.output.state.lock().unwrap();
+        state.detached = true;
+        self.output.drained.notify_all();
+    }
+}
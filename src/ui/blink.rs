@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+/// Blink phase of the text cursor. Callers pass in the current time, so the
+/// event loop decides when to advance it.
+#[derive(Debug, Clone)]
+pub struct CursorBlink {
+    /// How long each on and off phase lasts; `None` keeps the cursor on
+    interval: Option<Duration>,
+    visible: bool,
+    phase_start: Instant,
+}
+
+impl CursorBlink {
+    /// Start in the "on" phase; a zero interval disables blinking
+    pub fn new(interval: Duration, now: Instant) -> Self {
+        Self {
+            interval: (!interval.is_zero()).then_some(interval),
+            visible: true,
+            phase_start: now,
+        }
+    }
+
+    /// Change the interval (zero disables blinking) and restart the "on"
+    /// phase
+    pub fn set_interval(&mut self, interval: Duration, now: Instant) {
+        self.interval = (!interval.is_zero()).then_some(interval);
+        self.reset(now);
+    }
+
+    /// Whether the cursor is drawn in the current phase
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Show the cursor and restart the "on" phase, e.g. on typing or output.
+    /// Returns whether the cursor was hidden.
+    pub fn reset(&mut self, now: Instant) -> bool {
+        let was_hidden = !self.visible;
+        self.visible = true;
+        self.phase_start = now;
+        was_hidden
+    }
+
+    /// Advance to `now`, returning whether the cursor toggled
+    pub fn update(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+
+        let elapsed = now.saturating_duration_since(self.phase_start).as_nanos();
+        let phases = elapsed / interval.as_nanos();
+        if phases == 0 {
+            return false;
+        }
+
+        // Keep the remainder so a late wakeup doesn't stretch the next phase
+        self.phase_start = now - Duration::from_nanos((elapsed % interval.as_nanos()) as u64);
+        let toggled = phases % 2 == 1;
+        self.visible ^= toggled;
+        toggled
+    }
+
+    /// When the cursor toggles next, if it blinks
+    pub fn next_toggle(&self) -> Option<Instant> {
+        self.interval.map(|interval| self.phase_start + interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn test_blink_toggles_each_interval() {
+        let start = Instant::now();
+        let mut blink = CursorBlink::new(INTERVAL, start);
+        assert!(blink.visible());
+
+        assert!(!blink.update(start + Duration::from_millis(499)));
+        assert!(blink.visible());
+
+        assert!(blink.update(start + Duration::from_millis(510)));
+        assert!(!blink.visible());
+        assert_eq!(blink.next_toggle(), Some(start + Duration::from_millis(1000)));
+
+        assert!(blink.update(start + Duration::from_millis(1000)));
+        assert!(blink.visible());
+
+        // Two whole phases later the cursor is back where it was
+        assert!(!blink.update(start + Duration::from_millis(2000)));
+        assert!(blink.visible());
+    }
+
+    #[test]
+    fn test_reset_shows_cursor_and_restarts_phase() {
+        let start = Instant::now();
+        let mut blink = CursorBlink::new(INTERVAL, start);
+        blink.update(start + INTERVAL);
+        assert!(!blink.visible());
+
+        let typed = start + Duration::from_millis(700);
+        assert!(blink.reset(typed));
+        assert!(blink.visible());
+        assert!(!blink.update(typed + Duration::from_millis(400)));
+        assert!(!blink.reset(typed), "Already visible");
+    }
+
+    #[test]
+    fn test_zero_interval_never_blinks() {
+        let start = Instant::now();
+        let mut blink = CursorBlink::new(Duration::ZERO, start);
+
+        assert!(!blink.update(start + Duration::from_secs(10)));
+        assert!(blink.visible());
+        assert_eq!(blink.next_toggle(), None);
+    }
+}
@@ -1,7 +1,9 @@
 use super::PaneId;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SplitDirection {
     Horizontal,
     Vertical,
@@ -18,12 +20,71 @@ pub enum LayoutNode {
     },
 }
 
+/// What a saved layout remembers about each pane; its content isn't kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneMetadata {
+    pub title: String,
+    #[serde(default)]
+    pub working_directory: Option<PathBuf>,
+}
+
+/// A layout tree that can be written to disk, with pane metadata in place of
+/// pane ids (which are only meaningful within one `PaneManager`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SerializedNode {
+    Pane(PaneMetadata),
+    Split {
+        direction: SplitDirection,
+        ratio: f32,
+        first: Box<SerializedNode>,
+        second: Box<SerializedNode>,
+    },
+}
+
+/// Smallest share of a split either side can be dragged down to
+pub const MIN_SPLIT_RATIO: f32 = 0.05;
+
+/// The divider of one split, as found under the mouse
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divider {
+    /// Route from the root to the split node; `true` means the second child
+    pub path: Vec<bool>,
+    pub direction: SplitDirection,
+    /// Share of the split node taken by its first child
+    pub ratio: f32,
+    /// Bounds of the whole split node, in the same units as the layout
+    pub bounds: (f32, f32, f32, f32),
+}
+
+impl Divider {
+    /// Ratio that puts this divider under the given point
+    pub fn ratio_at(&self, x: f32, y: f32) -> f32 {
+        let (node_x, node_y, width, height) = self.bounds;
+        let ratio = match self.direction {
+            SplitDirection::Horizontal => (x - node_x) / width,
+            SplitDirection::Vertical => (y - node_y) / height,
+        };
+        ratio.clamp(MIN_SPLIT_RATIO, 1.0 - MIN_SPLIT_RATIO)
+    }
+
+    /// Quad (`[x, y, width, height]`) covering the points within `tolerance`
+    /// of the divider, i.e. where `divider_at` finds it
+    pub fn grab_rect(&self, tolerance: f32) -> [f32; 4] {
+        let (node_x, node_y, width, height) = self.bounds;
+        match self.direction {
+            SplitDirection::Horizontal => [node_x + width * self.ratio - tolerance, node_y, 2.0 * tolerance, height],
+            SplitDirection::Vertical => [node_x, node_y + height * self.ratio - tolerance, width, 2.0 * tolerance],
+        }
+    }
+}
+
 pub struct Layout {
     root: Option<LayoutNode>,
 }
 
 impl Layout {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self { root: None }
     }
 
@@ -87,10 +148,165 @@ impl Layout {
         }
     }
 
+    /// Turn the split directly containing `pane_id` between side by side
+    /// and stacked; splits further up are left as they are. Returns false
+    /// if the pane isn't part of a split.
+    pub fn rotate_split(&mut self, pane_id: PaneId) -> bool {
+        self.root.as_mut().is_some_and(|root| Self::rotate_node(root, pane_id))
+    }
+
+    fn rotate_node(node: &mut LayoutNode, target: PaneId) -> bool {
+        let LayoutNode::Split { direction, first, second, .. } = node else {
+            return false;
+        };
+        let is_target = |child: &LayoutNode| matches!(child, LayoutNode::Pane(id) if *id == target);
+
+        if is_target(first) || is_target(second) {
+            *direction = match direction {
+                SplitDirection::Horizontal => SplitDirection::Vertical,
+                SplitDirection::Vertical => SplitDirection::Horizontal,
+            };
+            true
+        } else {
+            Self::rotate_node(first, target) || Self::rotate_node(second, target)
+        }
+    }
+
     pub fn root(&self) -> Option<&LayoutNode> {
         self.root.as_ref()
     }
 
+    /// Pane ids in tree order (first before second at every split)
+    pub fn pane_ids(&self) -> Vec<PaneId> {
+        fn collect(node: &LayoutNode, ids: &mut Vec<PaneId>) {
+            match node {
+                LayoutNode::Pane(id) => ids.push(*id),
+                LayoutNode::Split { first, second, .. } => {
+                    collect(first, ids);
+                    collect(second, ids);
+                }
+            }
+        }
+
+        let mut ids = Vec::new();
+        if let Some(root) = &self.root {
+            collect(root, &mut ids);
+        }
+        ids
+    }
+
+    /// Convert the tree to its saved form, asking `metadata` about each pane
+    pub fn to_serializable(&self, metadata: impl Fn(PaneId) -> PaneMetadata) -> Option<SerializedNode> {
+        fn convert(node: &LayoutNode, metadata: &impl Fn(PaneId) -> PaneMetadata) -> SerializedNode {
+            match node {
+                LayoutNode::Pane(id) => SerializedNode::Pane(metadata(*id)),
+                LayoutNode::Split { direction, ratio, first, second } => SerializedNode::Split {
+                    direction: *direction,
+                    ratio: *ratio,
+                    first: Box::new(convert(first, metadata)),
+                    second: Box::new(convert(second, metadata)),
+                },
+            }
+        }
+
+        self.root.as_ref().map(|root| convert(root, &metadata))
+    }
+
+    /// Rebuild a layout from its saved form, calling `spawn` to create a pane
+    /// for each leaf in tree order
+    pub fn from_serializable(
+        node: &SerializedNode,
+        mut spawn: impl FnMut(&PaneMetadata) -> anyhow::Result<PaneId>,
+    ) -> anyhow::Result<Self> {
+        fn convert(
+            node: &SerializedNode,
+            spawn: &mut impl FnMut(&PaneMetadata) -> anyhow::Result<PaneId>,
+        ) -> anyhow::Result<LayoutNode> {
+            match node {
+                SerializedNode::Pane(metadata) => Ok(LayoutNode::Pane(spawn(metadata)?)),
+                SerializedNode::Split { direction, ratio, first, second } => {
+                    if !(*ratio > 0.0 && *ratio < 1.0) {
+                        anyhow::bail!("Split ratio {} is outside (0, 1)", ratio);
+                    }
+                    Ok(LayoutNode::Split {
+                        direction: *direction,
+                        ratio: *ratio,
+                        first: Box::new(convert(first, spawn)?),
+                        second: Box::new(convert(second, spawn)?),
+                    })
+                }
+            }
+        }
+
+        Ok(Self {
+            root: Some(convert(node, &mut spawn)?),
+        })
+    }
+
+    /// Find the split divider within `tolerance` of the point, with the
+    /// layout drawn at `width` x `height`
+    pub fn divider_at(&self, width: f32, height: f32, x: f32, y: f32, tolerance: f32) -> Option<Divider> {
+        let mut node = self.root.as_ref()?;
+        let mut bounds = (0.0, 0.0, width, height);
+        let mut path = Vec::new();
+
+        while let LayoutNode::Split { direction, ratio, first, second } = node {
+            let (node_x, node_y, node_w, node_h) = bounds;
+            // `along` runs across the divider, `across` runs beside it
+            let (offset, along, across, start, length) = match direction {
+                SplitDirection::Horizontal => (node_x + node_w * ratio, x, y, node_y, node_h),
+                SplitDirection::Vertical => (node_y + node_h * ratio, y, x, node_x, node_w),
+            };
+
+            if (along - offset).abs() <= tolerance && across >= start && across <= start + length {
+                return Some(Divider {
+                    path,
+                    direction: *direction,
+                    ratio: *ratio,
+                    bounds,
+                });
+            }
+
+            // Descend into the side holding the point
+            let in_second = along > offset;
+            bounds = match (direction, in_second) {
+                (SplitDirection::Horizontal, false) => (node_x, node_y, offset - node_x, node_h),
+                (SplitDirection::Horizontal, true) => (offset, node_y, node_x + node_w - offset, node_h),
+                (SplitDirection::Vertical, false) => (node_x, node_y, node_w, offset - node_y),
+                (SplitDirection::Vertical, true) => (node_x, offset, node_w, node_y + node_h - offset),
+            };
+            node = if in_second { second } else { first };
+            path.push(in_second);
+        }
+
+        None
+    }
+
+    /// Set the ratio of the split at `path`, clamped so neither side
+    /// disappears. Returns false if `path` doesn't lead to a split.
+    pub fn set_split_ratio(&mut self, path: &[bool], new_ratio: f32) -> bool {
+        let Some(mut node) = self.root.as_mut() else {
+            return false;
+        };
+
+        for in_second in path {
+            match node {
+                LayoutNode::Split { first, second, .. } => {
+                    node = if *in_second { second } else { first };
+                }
+                LayoutNode::Pane(_) => return false,
+            }
+        }
+
+        match node {
+            LayoutNode::Split { ratio, .. } => {
+                *ratio = new_ratio.clamp(MIN_SPLIT_RATIO, 1.0 - MIN_SPLIT_RATIO);
+                true
+            }
+            LayoutNode::Pane(_) => false,
+        }
+    }
+
     pub fn calculate_bounds(&self, width: f32, height: f32) -> HashMap<PaneId, (f32, f32, f32, f32)> {
         let mut bounds = HashMap::new();
         if let Some(root) = &self.root {
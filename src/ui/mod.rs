@@ -1,42 +1,333 @@
 mod pane;
 mod layout;
+mod tab;
+mod blink;
 
-pub use pane::{Pane, PaneId};
-pub use layout::{Layout, LayoutNode, SplitDirection};
+pub use blink::CursorBlink;
+pub use pane::{Pane, PaneId, PaneSpawnOptions};
+pub use tab::Tab;
+pub use layout::{Divider, Layout, LayoutNode, PaneMetadata, SerializedNode, SplitDirection, MIN_SPLIT_RATIO};
 
-use crate::terminal::Terminal;
+use crate::config::{Padding, ShellConfig};
+use crate::terminal::{paste, OutputWaker, Terminal, DEFAULT_PARSE_BUDGET, DEFAULT_SCROLLBACK};
+use anyhow::Context;
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+/// Layout reported while there are no tabs
+static EMPTY_LAYOUT: Layout = Layout::new();
+
+/// How close (in pixels) a click must land to a divider to grab it
+pub const DIVIDER_GRAB_DISTANCE: f32 = 4.0;
 
 pub struct PaneManager {
     panes: HashMap<PaneId, Pane>,
-    layout: Layout,
-    active_pane: Option<PaneId>,
+    /// Ids of `panes` in creation order
+    pane_order: Vec<PaneId>,
+    tabs: Vec<Tab>,
+    active_tab: usize,
     next_id: usize,
+    shell: ShellConfig,
+    scrollback_lines: usize,
+    /// Bytes of output parsed per pane each poll (0 = no limit)
+    parse_budget: usize,
+    /// Last known window size in pixels, used for directional navigation
+    viewport_size: (f32, f32),
+    /// Cell size in pixels, once the renderer has reported it; needed to
+    /// turn pane bounds into terminal sizes
+    cell_size: Option<(f32, f32)>,
+    /// Pixels at the top of each pane taken by its title bar
+    pane_header_height: f32,
+    /// Space inside each pane around its text
+    padding: Padding,
+    /// Send typed and pasted input to every pane instead of the active one
+    broadcast: bool,
+    /// Something changed on screen besides grid cells (cursor motion,
+    /// focus, layout) since the last `mark_drawn`
+    needs_redraw: bool,
+    /// Given to every pane's terminal, see `set_output_waker`
+    output_waker: Option<OutputWaker>,
+    /// Last focus state reported by the window
+    window_focused: bool,
 }
 
 impl PaneManager {
     pub fn new() -> Self {
+        Self::with_shell(ShellConfig::default())
+    }
+
+    /// Create a pane manager whose panes run the given shell or command
+    pub fn with_shell(shell: ShellConfig) -> Self {
         Self {
             panes: HashMap::new(),
-            layout: Layout::new(),
-            active_pane: None,
+            pane_order: Vec::new(),
+            tabs: vec![Tab::new()],
+            active_tab: 0,
             next_id: 0,
+            shell,
+            scrollback_lines: DEFAULT_SCROLLBACK,
+            parse_budget: DEFAULT_PARSE_BUDGET,
+            viewport_size: (1000.0, 1000.0),
+            cell_size: None,
+            pane_header_height: 0.0,
+            padding: Padding::default(),
+            broadcast: false,
+            needs_redraw: true,
+            output_waker: None,
+            window_focused: true,
+        }
+    }
+
+    /// Call `waker` from a PTY reader thread whenever a pane (existing or
+    /// created later) has new output for `poll_output`
+    pub fn set_output_waker(&mut self, waker: OutputWaker) {
+        for pane in self.panes.values() {
+            pane.terminal.set_output_waker(waker.clone());
+        }
+        self.output_waker = Some(waker);
+    }
+
+    /// Read and process output every pane's PTY has buffered, up to the
+    /// parse budget per pane, returning whether there was any. A pane only
+    /// reads more once what it read before is parsed, so the rest of a burst
+    /// waits in its PTY buffer.
+    pub fn poll_output(&mut self) -> bool {
+        let mut any = false;
+        let mut backlog = 0;
+        for pane in self.panes.values_mut() {
+            if pane.terminal.pending_output() > 0 {
+                any |= pane.terminal.parse_pending();
+            } else {
+                match pane.terminal.read() {
+                    Ok(Some(data)) => {
+                        pane.terminal.process_output(&data);
+                        any = true;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        log::error!("Error reading from terminal: {}", e);
+                    }
+                }
+            }
+            backlog += pane.terminal.pending_output();
+        }
+        crate::metrics::METRICS.set_output_backlog(backlog);
+        self.needs_redraw |= any;
+        any
+    }
+
+    /// Ask for the next frame even though no grid cell changed
+    pub fn request_redraw(&mut self) {
+        self.needs_redraw = true;
+    }
+
+    /// Whether anything changed since the last `mark_drawn`: output was
+    /// processed, a redraw was requested, or a grid is dirty (scrolling,
+    /// resizing)
+    pub fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+            || self.panes.values().any(|pane| {
+                let grid = pane.terminal.grid();
+                let grid = grid.lock().unwrap();
+                grid.is_all_dirty() || !grid.dirty_cells().is_empty()
+            })
+    }
+
+    /// Whether a pane in the current tab is partway through a synchronized
+    /// update (DECSET 2026), so frames should be held back until it ends
+    pub fn is_frozen(&self) -> bool {
+        self.frozen_until().is_some()
+    }
+
+    /// When frames may be drawn again if no frozen pane ends its
+    /// synchronized update first, or `None` if none is frozen
+    pub fn frozen_until(&self) -> Option<Instant> {
+        self.layout()
+            .pane_ids()
+            .iter()
+            .filter_map(|id| self.panes.get(id))
+            .filter_map(|pane| pane.terminal.grid().lock().unwrap().frozen_until())
+            .max()
+    }
+
+    /// Record that a frame showing the current state was rendered
+    pub fn mark_drawn(&mut self) {
+        self.needs_redraw = false;
+        for pane in self.panes.values() {
+            pane.terminal.grid().lock().unwrap().clear_dirty();
+        }
+    }
+
+    /// Set the scrollback limit used for panes created from now on
+    pub fn set_scrollback_lines(&mut self, lines: usize) {
+        self.scrollback_lines = lines;
+    }
+
+    /// Parse at most `bytes` of each pane's output per `poll_output`,
+    /// leaving the rest for later polls (0 = no limit)
+    pub fn set_parse_budget(&mut self, bytes: usize) {
+        self.parse_budget = bytes;
+        let budget = self.terminal_parse_budget();
+        for pane in self.panes.values_mut() {
+            pane.terminal.set_parse_budget(budget);
+        }
+    }
+
+    fn terminal_parse_budget(&self) -> Option<usize> {
+        (self.parse_budget > 0).then_some(self.parse_budget)
+    }
+
+    /// Record the window size so navigation sees the panes as they're drawn
+    pub fn set_viewport_size(&mut self, width: f32, height: f32) {
+        self.viewport_size = (width, height);
+    }
+
+    pub fn set_cell_size(&mut self, width: f32, height: f32) {
+        self.cell_size = Some((width, height));
+    }
+
+    /// Reserve space above each pane's terminal (for a title bar)
+    pub fn set_pane_header_height(&mut self, height: f32) {
+        self.pane_header_height = height;
+    }
+
+    /// Leave space inside each pane around its text
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.padding = padding;
+    }
+
+    /// Resize every pane's terminal to fit its layout bounds in the viewport
+    pub fn fit_panes_to_viewport(&mut self) {
+        for index in 0..self.tabs.len() {
+            self.fit_tab(index);
+        }
+    }
+
+    fn fit_tab(&mut self, index: usize) {
+        let Some((cell_width, cell_height)) = self.cell_size else {
+            return;
+        };
+        let (width, height) = self.viewport_size;
+        let bounds = self.tabs[index].layout.calculate_bounds(width, height);
+
+        for (pane_id, (_, _, width, height)) in bounds {
+            let width = width - self.padding.left - self.padding.right;
+            let height = height - self.pane_header_height - self.padding.top - self.padding.bottom;
+            let cols = ((width / cell_width) as u16).max(1);
+            let rows = ((height / cell_height) as u16).max(1);
+            let Some(pane) = self.panes.get_mut(&pane_id) else {
+                continue;
+            };
+            if pane.terminal.grid().lock().unwrap().size() == (cols as usize, rows as usize) {
+                continue;
+            }
+            if let Err(e) = pane.terminal.resize(cols, rows) {
+                log::error!("Failed to resize pane: {}", e);
+            }
         }
     }
 
+    /// Find the split divider of the current tab under a point in the window
+    pub fn divider_at(&self, x: f32, y: f32) -> Option<Divider> {
+        let (width, height) = self.viewport_size;
+        self.layout().divider_at(width, height, x, y, DIVIDER_GRAB_DISTANCE)
+    }
+
+    /// Move a divider found by `divider_at` to follow the pointer, resizing
+    /// the panes on both sides
+    pub fn drag_divider(&mut self, divider: &Divider, x: f32, y: f32) {
+        let index = self.active_tab;
+        let Some(tab) = self.tabs.get_mut(index) else {
+            return;
+        };
+        if tab.layout.set_split_ratio(&divider.path, divider.ratio_at(x, y)) {
+            self.fit_tab(index);
+        }
+    }
+
+    /// Turn the split holding the active pane between side by side and
+    /// stacked, resizing its panes to fit. Returns false if the active pane
+    /// isn't split.
+    pub fn rotate_active_split(&mut self) -> bool {
+        let index = self.active_tab;
+        let Some(pane_id) = self.active_pane() else {
+            return false;
+        };
+        if !self.tabs[index].layout.rotate_split(pane_id) {
+            return false;
+        }
+        self.fit_tab(index);
+        true
+    }
+
+    /// Start a pane; in a tab with no panes yet it becomes the tab's root
     pub fn create_pane(&mut self, cols: u16, rows: u16) -> anyhow::Result<PaneId> {
+        let options = self.inherited_options(self.active_pane());
+        self.create_pane_with(cols, rows, options)
+    }
+
+    /// Like `create_pane`, but starting the pane with `options` applied on
+    /// top of the configured shell
+    pub fn create_pane_with(&mut self, cols: u16, rows: u16, options: PaneSpawnOptions) -> anyhow::Result<PaneId> {
+        let id = self.spawn_pane(cols, rows, options)?;
+
+        if self.tabs.is_empty() {
+            self.tabs.push(Tab::new());
+            self.active_tab = 0;
+        }
+        let tab = &mut self.tabs[self.active_tab];
+        if tab.active_pane.is_none() {
+            tab.active_pane = Some(id);
+            tab.layout.set_root(id);
+        }
+
+        Ok(id)
+    }
+
+    /// Options for a pane opened from `source`: started in the directory
+    /// `source` last reported if `inherit_working_directory` is on
+    fn inherited_options(&self, source: Option<PaneId>) -> PaneSpawnOptions {
+        let cwd = if self.shell.inherit_working_directory {
+            source
+                .and_then(|id| self.panes.get(&id))
+                .and_then(|pane| pane.terminal.current_dir())
+        } else {
+            None
+        };
+        PaneSpawnOptions { cwd, ..Default::default() }
+    }
+
+    /// Start a terminal and register its pane without placing it in the
+    /// layout. If the shell can't be started the pane is still created,
+    /// showing the error (see `Pane::error`).
+    fn spawn_pane(&mut self, cols: u16, rows: u16, mut options: PaneSpawnOptions) -> anyhow::Result<PaneId> {
         let id = PaneId(self.next_id);
         self.next_id += 1;
 
-        let terminal = Terminal::new(cols, rows)?;
-        let pane = Pane::new(id, terminal);
+        let shell = options.apply(&self.shell);
+        let mut pane = match Terminal::with_shell(cols, rows, &shell) {
+            Ok(mut terminal) => {
+                terminal.set_scrollback_limit(self.scrollback_lines);
+                terminal.set_parse_budget(self.terminal_parse_budget());
+                if let Some(waker) = &self.output_waker {
+                    terminal.set_output_waker(waker.clone());
+                }
+                Pane::new(id, terminal)
+            }
+            Err(e) => {
+                let program = shell.program.as_deref().unwrap_or("shell");
+                let error = format!("failed to start {}: {:#}", program, e);
+                log::error!("Pane {}: {}", id.0, error);
+                Pane::failed(id, cols, rows, error)
+            }
+        };
+        options.cwd = shell.working_directory;
+        pane.spawn_options = options;
 
         self.panes.insert(id, pane);
-
-        if self.active_pane.is_none() {
-            self.active_pane = Some(id);
-            self.layout.set_root(id);
-        }
+        self.pane_order.push(id);
+        crate::metrics::METRICS.register_terminal(id.0.to_string(), cols as usize, rows as usize);
 
         Ok(id)
     }
@@ -48,17 +339,167 @@ impl PaneManager {
         cols: u16,
         rows: u16,
     ) -> anyhow::Result<PaneId> {
-        let new_id = self.create_pane(cols, rows)?;
-        self.layout.split(pane_id, new_id, direction);
+        let options = self.inherited_options(Some(pane_id));
+        let new_id = self.create_pane_with(cols, rows, options)?;
+        if let Some(index) = self.tab_index_of(pane_id) {
+            self.tabs[index].layout.split(pane_id, new_id, direction);
+        }
         Ok(new_id)
     }
 
+    /// Close a pane and grow its neighbors into the space; closing the last
+    /// pane of a tab closes the tab
     pub fn close_pane(&mut self, pane_id: PaneId) {
         self.panes.remove(&pane_id);
-        self.layout.remove(pane_id);
+        self.pane_order.retain(|&id| id != pane_id);
+        crate::metrics::METRICS.unregister_terminal(&pane_id.0.to_string());
+
+        let Some(index) = self.tab_index_of(pane_id) else {
+            return;
+        };
+        let tab = &mut self.tabs[index];
+        tab.layout.remove(pane_id);
+
+        if tab.layout.root().is_none() {
+            self.remove_tab(index);
+            return;
+        }
+        if tab.active_pane == Some(pane_id) {
+            tab.active_pane = tab.layout.pane_ids().first().copied();
+        }
+        self.fit_tab(index);
+    }
+
+    /// Open a tab with one pane and switch to it
+    pub fn new_tab(&mut self, cols: u16, rows: u16) -> anyhow::Result<PaneId> {
+        // Picked before switching, while the previous tab's pane is active
+        let options = self.inherited_options(self.active_pane());
+        let previous = self.active_tab;
+        self.tabs.push(Tab::new());
+        self.active_tab = self.tabs.len() - 1;
 
-        if self.active_pane == Some(pane_id) {
-            self.active_pane = self.panes.keys().next().copied();
+        self.create_pane_with(cols, rows, options).inspect_err(|_| {
+            self.tabs.pop();
+            self.active_tab = previous;
+        })
+    }
+
+    /// Close a tab and every pane in it
+    pub fn close_tab(&mut self, index: usize) {
+        let Some(tab) = self.tabs.get(index) else {
+            return;
+        };
+
+        let pane_ids = tab.layout.pane_ids();
+        if pane_ids.is_empty() {
+            self.remove_tab(index);
+        }
+        // Closing the tab's last pane removes the tab
+        for id in pane_ids {
+            self.close_pane(id);
+        }
+    }
+
+    fn remove_tab(&mut self, index: usize) {
+        self.tabs.remove(index);
+        if self.active_tab > index || self.active_tab >= self.tabs.len() {
+            self.active_tab = self.active_tab.saturating_sub(1);
+        }
+    }
+
+    pub fn next_tab(&mut self) {
+        if !self.tabs.is_empty() {
+            self.active_tab = (self.active_tab + 1) % self.tabs.len();
+        }
+    }
+
+    pub fn prev_tab(&mut self) {
+        if !self.tabs.is_empty() {
+            self.active_tab = (self.active_tab + self.tabs.len() - 1) % self.tabs.len();
+        }
+    }
+
+    /// Index of the tab being shown
+    pub fn active_tab(&self) -> usize {
+        self.active_tab
+    }
+
+    pub fn set_active_tab(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.active_tab = index;
+        }
+    }
+
+    pub fn tabs(&self) -> &[Tab] {
+        &self.tabs
+    }
+
+    fn tab_index_of(&self, pane_id: PaneId) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.contains(pane_id))
+    }
+
+    /// Focus `pane_id` within the current tab
+    fn focus(&mut self, pane_id: PaneId) {
+        if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+            tab.active_pane = Some(pane_id);
+        }
+    }
+
+    /// Write the current tab's pane arrangement (splits, ratios, titles and
+    /// working directories) to `path` as JSON
+    pub fn save_layout(&self, path: &Path) -> anyhow::Result<()> {
+        let tree = self
+            .layout()
+            .to_serializable(|id| {
+                let pane = &self.panes[&id];
+                PaneMetadata {
+                    title: pane.title.clone(),
+                    working_directory: pane.terminal.current_dir().or_else(|| pane.spawn_options.cwd.clone()),
+                }
+            })
+            .ok_or_else(|| anyhow::anyhow!("No panes to save"))?;
+
+        let json = serde_json::to_string_pretty(&tree)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write layout to {:?}", path))
+    }
+
+    /// Replace all tabs and panes with the arrangement saved at `path`,
+    /// starting a fresh shell in each. Panes start at 80x24 until the caller resizes
+    /// them to the window.
+    pub fn restore_layout(&mut self, path: &Path) -> anyhow::Result<()> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read layout from {:?}", path))?;
+        let tree: SerializedNode = serde_json::from_str(&json)
+            .with_context(|| format!("Invalid layout file {:?}", path))?;
+
+        for id in self.pane_order.clone() {
+            self.close_pane(id);
+        }
+
+        let result = Layout::from_serializable(&tree, |metadata| {
+            let options = PaneSpawnOptions {
+                cwd: metadata.working_directory.clone(),
+                ..Default::default()
+            };
+            let id = self.spawn_pane(80, 24, options)?;
+            self.panes.get_mut(&id).unwrap().set_title(metadata.title.clone());
+            Ok(id)
+        });
+
+        match result {
+            Ok(layout) => {
+                let active_pane = layout.pane_ids().first().copied();
+                self.tabs = vec![Tab { layout, active_pane }];
+                self.active_tab = 0;
+                Ok(())
+            }
+            Err(e) => {
+                // Don't leave half a layout's shells running
+                for id in self.pane_order.clone() {
+                    self.close_pane(id);
+                }
+                Err(e)
+            }
         }
     }
 
@@ -70,106 +511,260 @@ impl PaneManager {
         self.panes.get_mut(&id)
     }
 
+    /// Focused pane of the current tab
     pub fn active_pane(&self) -> Option<PaneId> {
-        self.active_pane
+        self.tabs.get(self.active_tab).and_then(|tab| tab.active_pane)
     }
 
+    /// Focus a pane, switching to the tab that holds it
     pub fn set_active_pane(&mut self, id: PaneId) {
         if self.panes.contains_key(&id) {
-            self.active_pane = Some(id);
+            if let Some(index) = self.tab_index_of(id) {
+                self.active_tab = index;
+            }
+            self.focus(id);
+        }
+    }
+
+    /// Write input to a pane's terminal, scrolling it back to the live screen
+    /// first the way typing does
+    pub fn write_to_pane(&mut self, id: PaneId, data: &[u8]) -> anyhow::Result<()> {
+        let pane = self
+            .panes
+            .get_mut(&id)
+            .ok_or_else(|| anyhow::anyhow!("No pane with id {}", id.0))?;
+        pane.terminal.scroll_to_bottom();
+        pane.terminal.write(data)
+    }
+
+    /// Write input to the active pane's terminal
+    pub fn write_to_active(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let id = self.active_pane().ok_or_else(|| anyhow::anyhow!("No active pane"))?;
+        self.write_to_pane(id, data)
+    }
+
+    /// Record the window gaining or losing focus, reporting the change to
+    /// the active pane if its application asked for focus events
+    pub fn set_window_focused(&mut self, focused: bool) -> anyhow::Result<()> {
+        if self.window_focused == focused {
+            return Ok(());
+        }
+        self.window_focused = focused;
+
+        let Some(pane) = self.active_pane().and_then(|id| self.panes.get_mut(&id)) else {
+            return Ok(());
+        };
+        match pane.terminal.focus_report(focused) {
+            Some(report) => pane.terminal.write(report),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        self.broadcast = broadcast;
+    }
+
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Panes that keyboard input goes to: every pane in the current tab
+    /// while broadcasting, otherwise just the active one. Panes whose shell
+    /// failed to start take no input.
+    fn input_targets(&self) -> Vec<PaneId> {
+        let mut ids = if self.broadcast {
+            self.ordered_tab_panes()
+        } else {
+            self.active_pane().into_iter().collect()
+        };
+        ids.retain(|id| self.panes.get(id).is_some_and(|pane| pane.error.is_none()));
+        ids
+    }
+
+    /// Write typed input to the active pane, or to every pane in the tab
+    /// while broadcasting. Every target is tried; the first error is returned.
+    pub fn write_input(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for id in self.input_targets() {
+            let written = self.write_to_pane(id, data);
+            if result.is_ok() {
+                result = written;
+            }
+        }
+        result
+    }
+
+    /// Whether pasting `text` into any pane `paste_input` would write to
+    /// should be confirmed first; see `paste::paste_needs_confirmation`
+    pub fn paste_needs_confirmation(&self, text: &str, threshold: usize) -> bool {
+        self.input_targets().iter().filter_map(|id| self.panes.get(id)).any(|pane| {
+            let bracketed = pane.terminal.grid().lock().unwrap().bracketed_paste();
+            paste::paste_needs_confirmation(text, bracketed, threshold)
+        })
+    }
+
+    /// Paste text into the active pane, or into every pane in the tab while
+    /// broadcasting, honoring each pane's bracketed paste mode
+    pub fn paste_input(&mut self, text: &str) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for id in self.input_targets() {
+            if let Some(pane) = self.panes.get_mut(&id) {
+                pane.terminal.scroll_to_bottom();
+                let pasted = pane.terminal.paste(text);
+                if result.is_ok() {
+                    result = pasted;
+                }
+            }
         }
+        result
+    }
+
+    /// Feed output bytes to the active pane's terminal as if they came from
+    /// its PTY
+    pub fn process_active_output(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let pane = self
+            .active_pane()
+            .and_then(|id| self.panes.get_mut(&id))
+            .ok_or_else(|| anyhow::anyhow!("No active pane"))?;
+        pane.terminal.process_output(data);
+        Ok(())
     }
 
     pub fn panes(&self) -> &HashMap<PaneId, Pane> {
         &self.panes
     }
 
+    /// Every pane, in the order they were created
+    pub fn ordered_panes(&self) -> impl Iterator<Item = (&PaneId, &Pane)> {
+        self.pane_order.iter().filter_map(|id| self.panes.get_key_value(id))
+    }
+
+    /// The current tab's panes in creation order
+    fn ordered_tab_panes(&self) -> Vec<PaneId> {
+        let in_tab = self.layout().pane_ids();
+        self.pane_order.iter().copied().filter(|id| in_tab.contains(id)).collect()
+    }
+
+    /// Pane tree of the current tab
     pub fn layout(&self) -> &Layout {
-        &self.layout
+        self.tabs.get(self.active_tab).map_or(&EMPTY_LAYOUT, |tab| &tab.layout)
     }
 
     pub fn navigate_up(&mut self) {
-        if let Some(current_id) = self.active_pane {
+        if let Some(current_id) = self.active_pane() {
             if let Some(next_id) = self.find_pane_in_direction(current_id, NavigationDirection::Up) {
-                self.active_pane = Some(next_id);
+                self.focus(next_id);
             }
         }
     }
 
     pub fn navigate_down(&mut self) {
-        if let Some(current_id) = self.active_pane {
+        if let Some(current_id) = self.active_pane() {
             if let Some(next_id) = self.find_pane_in_direction(current_id, NavigationDirection::Down) {
-                self.active_pane = Some(next_id);
+                self.focus(next_id);
             }
         }
     }
 
     pub fn navigate_left(&mut self) {
-        if let Some(current_id) = self.active_pane {
+        if let Some(current_id) = self.active_pane() {
             if let Some(next_id) = self.find_pane_in_direction(current_id, NavigationDirection::Left) {
-                self.active_pane = Some(next_id);
+                self.focus(next_id);
             }
         }
     }
 
     pub fn navigate_right(&mut self) {
-        if let Some(current_id) = self.active_pane {
+        if let Some(current_id) = self.active_pane() {
             if let Some(next_id) = self.find_pane_in_direction(current_id, NavigationDirection::Right) {
-                self.active_pane = Some(next_id);
+                self.focus(next_id);
             }
         }
     }
 
-    fn find_pane_in_direction(&self, current_id: PaneId, direction: NavigationDirection) -> Option<PaneId> {
-        // Calculate bounds for all panes (using a default size)
-        let bounds = self.layout.calculate_bounds(1000.0, 1000.0);
+    /// Focus the tab's next pane in creation order, wrapping after the last one
+    pub fn focus_next(&mut self) {
+        self.cycle_focus(1);
+    }
 
-        let current_bounds = bounds.get(&current_id)?;
-        let (curr_x, curr_y, curr_w, curr_h) = *current_bounds;
+    /// Focus the tab's previous pane in creation order, wrapping before the
+    /// first one
+    pub fn focus_prev(&mut self) {
+        self.cycle_focus(-1);
+    }
 
-        // Center of current pane
-        let curr_center_x = curr_x + curr_w / 2.0;
-        let curr_center_y = curr_y + curr_h / 2.0;
+    fn cycle_focus(&mut self, step: isize) {
+        let ids = self.ordered_tab_panes();
+        if ids.is_empty() {
+            return;
+        }
+
+        let next = match self.active_pane().and_then(|id| ids.iter().position(|p| *p == id)) {
+            Some(index) => (index as isize + step).rem_euclid(ids.len() as isize) as usize,
+            None => 0,
+        };
+        self.focus(ids[next]);
+    }
+
+    /// Pick the pane across the edge of `current_id` in `direction`
+    ///
+    /// Candidates must lie entirely on that side and share part of the edge;
+    /// the nearest one wins, then the one sharing the longest stretch of it.
+    fn find_pane_in_direction(&self, current_id: PaneId, direction: NavigationDirection) -> Option<PaneId> {
+        let (width, height) = self.viewport_size;
+        let bounds = self.layout().calculate_bounds(width, height);
 
-        let mut best_pane: Option<PaneId> = None;
-        let mut best_distance = f32::MAX;
+        let (curr_x, curr_y, curr_w, curr_h) = *bounds.get(&current_id)?;
 
-        for (pane_id, bounds) in bounds.iter() {
+        let mut best: Option<(f32, f32, PaneId)> = None;
+
+        for (pane_id, (x, y, w, h)) in bounds.iter() {
             if *pane_id == current_id {
                 continue;
             }
 
-            let (x, y, w, h) = *bounds;
-            let center_x = x + w / 2.0;
-            let center_y = y + h / 2.0;
-
-            // Check if pane is in the correct direction
-            let is_valid = match direction {
-                NavigationDirection::Up => center_y < curr_center_y,
-                NavigationDirection::Down => center_y > curr_center_y,
-                NavigationDirection::Left => center_x < curr_center_x,
-                NavigationDirection::Right => center_x > curr_center_x,
+            // Distance between the facing edges, and how much of the edge
+            // the two panes share
+            let (gap, overlap) = match direction {
+                NavigationDirection::Up => (curr_y - (y + h), span_overlap(curr_x, curr_w, *x, *w)),
+                NavigationDirection::Down => (y - (curr_y + curr_h), span_overlap(curr_x, curr_w, *x, *w)),
+                NavigationDirection::Left => (curr_x - (x + w), span_overlap(curr_y, curr_h, *y, *h)),
+                NavigationDirection::Right => (x - (curr_x + curr_w), span_overlap(curr_y, curr_h, *y, *h)),
             };
 
-            if !is_valid {
+            if gap < -EDGE_EPSILON || overlap <= EDGE_EPSILON {
                 continue;
             }
 
-            // Calculate distance
-            let dx = center_x - curr_center_x;
-            let dy = center_y - curr_center_y;
-            let distance = dx * dx + dy * dy;
-
-            if distance < best_distance {
-                best_distance = distance;
-                best_pane = Some(*pane_id);
+            let better = match best {
+                None => true,
+                Some((best_gap, best_overlap, best_id)) => {
+                    if (gap - best_gap).abs() > EDGE_EPSILON {
+                        gap < best_gap
+                    } else if (overlap - best_overlap).abs() > EDGE_EPSILON {
+                        overlap > best_overlap
+                    } else {
+                        *pane_id < best_id
+                    }
+                }
+            };
+            if better {
+                best = Some((gap, overlap, *pane_id));
             }
         }
 
-        best_pane
+        best.map(|(_, _, id)| id)
     }
 }
 
+/// Slack for float rounding when comparing pane edges
+const EDGE_EPSILON: f32 = 0.01;
+
+/// Length shared by the ranges `[a, a + a_len)` and `[b, b + b_len)`
+fn span_overlap(a: f32, a_len: f32, b: f32, b_len: f32) -> f32 {
+    ((a + a_len).min(b + b_len) - a.max(b)).max(0.0)
+}
+
 #[derive(Debug, Clone, Copy)]
 enum NavigationDirection {
     Up,
@@ -183,3 +778,505 @@ impl Default for PaneManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cat_manager() -> PaneManager {
+        PaneManager::with_shell(ShellConfig {
+            program: Some("/bin/cat".to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn row_text(manager: &PaneManager, id: PaneId, row: usize) -> String {
+        manager.get_pane(id).unwrap().terminal.grid().lock().unwrap().row_text(row)
+    }
+
+    #[test]
+    fn test_frozen_until_tracks_synchronized_update() {
+        let mut manager = cat_manager();
+        let id = manager.create_pane(40, 10).unwrap();
+        assert_eq!(manager.frozen_until(), None);
+
+        let pane = manager.get_pane_mut(id).unwrap();
+        pane.terminal.process_output(b"\x1b[?2026h");
+        let until = manager.frozen_until().expect("Pane should be frozen");
+        assert!(until > Instant::now());
+        assert!(manager.is_frozen());
+
+        let pane = manager.get_pane_mut(id).unwrap();
+        pane.terminal.process_output(b"\x1b[?2026l");
+        assert_eq!(manager.frozen_until(), None);
+    }
+
+    #[test]
+    fn test_write_to_active_reaches_grid() {
+        let mut manager = cat_manager();
+        let id = manager.create_pane(40, 10).unwrap();
+
+        manager.write_to_active(b"hello\n").unwrap();
+
+        // cat echoes the line back through the PTY
+        for _ in 0..200 {
+            if row_text(&manager, id, 0).contains("hello") {
+                break;
+            }
+            let pane = manager.get_pane_mut(id).unwrap();
+            match pane.terminal.read().unwrap() {
+                Some(data) => pane.terminal.process_output(&data),
+                None => std::thread::sleep(std::time::Duration::from_millis(10)),
+            }
+        }
+        assert_eq!(row_text(&manager, id, 0), "hello");
+    }
+
+    #[test]
+    fn test_focus_changes_reported_when_enabled() {
+        let mut manager = cat_manager();
+        let id = manager.create_pane(40, 10).unwrap();
+
+        // Not enabled yet: nothing is written
+        manager.set_window_focused(false).unwrap();
+        manager.set_window_focused(true).unwrap();
+
+        manager.process_active_output(b"\x1b[?1004h").unwrap();
+        manager.set_window_focused(false).unwrap();
+        manager.set_window_focused(true).unwrap();
+        // Repeating the current state reports nothing
+        manager.set_window_focused(true).unwrap();
+        manager.write_to_active(b"\n").unwrap();
+
+        // The tty echoes the escape back as `^[`; the newline follows the
+        // reports, so once it moves the cursor everything has arrived
+        for _ in 0..200 {
+            if manager.get_pane(id).unwrap().terminal.grid().lock().unwrap().cursor_pos().1 > 0 {
+                break;
+            }
+            let pane = manager.get_pane_mut(id).unwrap();
+            match pane.terminal.read().unwrap() {
+                Some(data) => pane.terminal.process_output(&data),
+                None => std::thread::sleep(std::time::Duration::from_millis(10)),
+            }
+        }
+        assert_eq!(row_text(&manager, id, 0), "^[[O^[[I");
+    }
+
+    #[test]
+    fn test_idle_panes_need_no_redraw() {
+        let mut manager = cat_manager();
+        let id = manager.create_pane(40, 10).unwrap();
+        assert!(manager.needs_redraw(), "A new pane has never been drawn");
+        manager.mark_drawn();
+
+        // cat prints nothing until it is sent input
+        for _ in 0..10 {
+            assert!(!manager.poll_output());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(!manager.needs_redraw());
+
+        manager.get_pane_mut(id).unwrap().terminal.resize(50, 12).unwrap();
+        assert!(manager.needs_redraw(), "Resizing dirties the grid");
+        manager.mark_drawn();
+
+        let woken = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = woken.clone();
+        manager.set_output_waker(std::sync::Arc::new(move || flag.store(true, std::sync::atomic::Ordering::SeqCst)));
+        manager.write_to_active(b"x\n").unwrap();
+        for _ in 0..200 {
+            if manager.poll_output() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(manager.needs_redraw(), "Echoed output needs a redraw");
+        assert!(woken.load(std::sync::atomic::Ordering::SeqCst), "The reader thread wakes the event loop");
+    }
+
+    #[test]
+    fn test_process_active_output() {
+        let mut manager = cat_manager();
+        assert!(manager.process_active_output(b"early").is_err());
+        assert!(manager.write_to_active(b"early").is_err());
+
+        let id = manager.create_pane(40, 10).unwrap();
+        manager.process_active_output(b"abc").unwrap();
+        assert_eq!(row_text(&manager, id, 0), "abc");
+
+        assert!(manager.write_to_pane(PaneId(99), b"x").is_err());
+    }
+
+    #[test]
+    fn test_focus_cycles_and_wraps() {
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let second = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+        let third = manager.split_pane(second, SplitDirection::Vertical, 40, 10).unwrap();
+        assert_eq!(manager.active_pane(), Some(first));
+
+        let mut visited = Vec::new();
+        for _ in 0..3 {
+            manager.focus_next();
+            visited.push(manager.active_pane().unwrap());
+        }
+        assert_eq!(visited, vec![second, third, first]);
+
+        manager.focus_prev();
+        assert_eq!(manager.active_pane(), Some(third));
+        manager.set_active_pane(second);
+        manager.focus_prev();
+        assert_eq!(manager.active_pane(), Some(first));
+    }
+
+    #[test]
+    fn test_navigate_right_picks_adjacent_pane() {
+        let mut manager = cat_manager();
+        manager.set_viewport_size(1600.0, 400.0);
+
+        // +-----------------+
+        // | top             |
+        // | upper           |
+        // | lower           |
+        // +-----+-----+-----+
+        // |left | mid |right|
+        // +-----+-----+-----+
+        let top = manager.create_pane(40, 10).unwrap();
+        let left = manager.split_pane(top, SplitDirection::Vertical, 40, 10).unwrap();
+        let upper = manager.split_pane(top, SplitDirection::Vertical, 40, 10).unwrap();
+        let lower = manager.split_pane(upper, SplitDirection::Vertical, 40, 10).unwrap();
+        let right = manager.split_pane(left, SplitDirection::Horizontal, 40, 10).unwrap();
+        let mid = manager.split_pane(left, SplitDirection::Horizontal, 40, 10).unwrap();
+
+        // `lower`'s center is closer to `mid` than `right`'s, but it isn't
+        // beside it
+        manager.set_active_pane(mid);
+        manager.navigate_right();
+        assert_eq!(manager.active_pane(), Some(right));
+
+        manager.navigate_up();
+        assert_eq!(manager.active_pane(), Some(lower));
+        manager.navigate_right();
+        assert_eq!(manager.active_pane(), Some(lower));
+
+        manager.set_active_pane(left);
+        manager.navigate_right();
+        assert_eq!(manager.active_pane(), Some(mid));
+        manager.navigate_left();
+        assert_eq!(manager.active_pane(), Some(left));
+    }
+
+    #[test]
+    fn test_broadcast_writes_to_every_pane() {
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let second = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+        let third = manager.split_pane(second, SplitDirection::Vertical, 40, 10).unwrap();
+
+        manager.set_broadcast(true);
+        assert!(manager.broadcast());
+        manager.write_input(b"sync\n").unwrap();
+
+        for id in [first, second, third] {
+            for _ in 0..200 {
+                if row_text(&manager, id, 0).contains("sync") {
+                    break;
+                }
+                let pane = manager.get_pane_mut(id).unwrap();
+                match pane.terminal.read().unwrap() {
+                    Some(data) => pane.terminal.process_output(&data),
+                    None => std::thread::sleep(std::time::Duration::from_millis(10)),
+                }
+            }
+            assert_eq!(row_text(&manager, id, 0), "sync", "pane {:?} missed the input", id);
+        }
+
+        manager.set_broadcast(false);
+        assert_eq!(manager.input_targets(), vec![first]);
+    }
+
+    #[test]
+    fn test_new_panes_inherit_reported_directory() {
+        let dir = std::env::temp_dir();
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        manager
+            .get_pane_mut(first)
+            .unwrap()
+            .terminal
+            .process_output(format!("\x1b]7;file://host{}\x07", dir.display()).as_bytes());
+
+        let split = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+        assert_eq!(manager.get_pane(split).unwrap().spawn_options.cwd, Some(dir.clone()));
+        let tab = manager.new_tab(40, 10).unwrap();
+        assert_eq!(manager.get_pane(tab).unwrap().spawn_options.cwd, Some(dir));
+
+        let mut manager = PaneManager::with_shell(ShellConfig {
+            inherit_working_directory: false,
+            ..manager.shell.clone()
+        });
+        let first = manager.create_pane(40, 10).unwrap();
+        manager.get_pane_mut(first).unwrap().terminal.process_output(b"\x1b]7;file:///\x07");
+        let split = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+        assert_eq!(manager.get_pane(split).unwrap().spawn_options.cwd, None);
+    }
+
+    #[test]
+    fn test_split_starts_in_source_pane_directory() {
+        let dir = std::env::temp_dir();
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let second = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+        manager
+            .get_pane_mut(second)
+            .unwrap()
+            .terminal
+            .process_output(format!("\x1b]7;file://host{}\x07", dir.display()).as_bytes());
+        manager.set_active_pane(first);
+
+        let split = manager.split_pane(second, SplitDirection::Vertical, 40, 10).unwrap();
+        assert_eq!(manager.get_pane(split).unwrap().spawn_options.cwd, Some(dir));
+
+        let options = PaneSpawnOptions {
+            env: HashMap::from([("TITI_PANE".to_string(), "1".to_string())]),
+            command: vec!["/bin/cat".to_string(), "-u".to_string()],
+            ..Default::default()
+        };
+        let custom = manager.create_pane_with(40, 10, options.clone()).unwrap();
+        assert_eq!(manager.get_pane(custom).unwrap().spawn_options, options);
+        let shell = options.apply(&manager.shell);
+        assert_eq!(shell.program.as_deref(), Some("/bin/cat"));
+        assert_eq!(shell.args, vec!["-u".to_string()]);
+        assert_eq!(shell.env.get("TITI_PANE").map(String::as_str), Some("1"));
+    }
+
+    #[test]
+    fn test_failed_command_leaves_error_pane() {
+        let mut manager = PaneManager::with_shell(ShellConfig {
+            program: Some("/nonexistent/titi-test-shell".to_string()),
+            ..Default::default()
+        });
+
+        let id = manager.create_pane(60, 5).unwrap();
+        assert_eq!(manager.active_pane(), Some(id));
+
+        let pane = manager.get_pane(id).unwrap();
+        let error = pane.error.clone().expect("pane should carry the spawn error");
+        assert!(error.starts_with("failed to start /nonexistent/titi-test-shell: "), "{}", error);
+        assert!(row_text(&manager, id, 0).starts_with("failed to start"));
+
+        // Input is dropped instead of failing, and the pane never exits
+        manager.write_input(b"ignored").unwrap();
+        assert!(!manager.poll_output());
+        assert!(manager.get_pane(id).is_some());
+    }
+
+    #[test]
+    fn test_save_and_restore_layout() {
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let second = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+        manager.split_pane(second, SplitDirection::Vertical, 40, 10).unwrap();
+        manager.get_pane_mut(second).unwrap().set_title("logs".to_string());
+
+        let path = std::env::temp_dir().join(format!("titi-layout-{}.json", std::process::id()));
+        manager.save_layout(&path).unwrap();
+
+        let mut restored = cat_manager();
+        restored.restore_layout(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(restored.panes().len(), 3);
+        assert_eq!(restored.layout().pane_ids().len(), 3);
+        assert_eq!(restored.active_pane(), restored.layout().pane_ids().first().copied());
+
+        let metadata = |manager: &PaneManager| {
+            manager.layout().to_serializable(|id| PaneMetadata {
+                title: manager.get_pane(id).unwrap().title.clone(),
+                working_directory: None,
+            })
+        };
+        assert_eq!(metadata(&restored), metadata(&manager));
+        assert!(matches!(
+            metadata(&restored),
+            Some(SerializedNode::Split { direction: SplitDirection::Horizontal, .. })
+        ));
+    }
+
+    #[test]
+    fn test_tabs_keep_their_own_panes() {
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let split = manager.split_pane(first, SplitDirection::Horizontal, 40, 10).unwrap();
+
+        let second = manager.new_tab(40, 10).unwrap();
+        assert_eq!(manager.tabs().len(), 2);
+        assert_eq!(manager.active_tab(), 1);
+        assert_eq!(manager.active_pane(), Some(second));
+        assert_eq!(manager.layout().pane_ids(), vec![second]);
+
+        manager.next_tab();
+        assert_eq!(manager.active_tab(), 0);
+        assert_eq!(manager.layout().pane_ids(), vec![first, split]);
+        assert_eq!(manager.active_pane(), Some(first));
+
+        manager.prev_tab();
+        assert_eq!(manager.active_tab(), 1);
+        assert_eq!(manager.layout().pane_ids(), vec![second]);
+
+        // Focusing a pane in another tab switches to that tab
+        manager.set_active_pane(split);
+        assert_eq!(manager.active_tab(), 0);
+        assert_eq!(manager.active_pane(), Some(split));
+    }
+
+    #[test]
+    fn test_closing_last_pane_closes_tab() {
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let second = manager.new_tab(40, 10).unwrap();
+
+        manager.close_pane(second);
+        assert_eq!(manager.tabs().len(), 1);
+        assert_eq!(manager.active_pane(), Some(first));
+
+        manager.close_tab(0);
+        assert!(manager.tabs().is_empty());
+        assert!(manager.panes().is_empty());
+        assert_eq!(manager.active_pane(), None);
+        assert!(manager.layout().root().is_none());
+    }
+
+    #[test]
+    fn test_close_pane_resizes_survivor() {
+        let mut manager = cat_manager();
+        manager.set_viewport_size(800.0, 400.0);
+        manager.set_cell_size(10.0, 20.0);
+
+        let first = manager.create_pane(80, 20).unwrap();
+        let second = manager.split_pane(first, SplitDirection::Horizontal, 40, 20).unwrap();
+        let size = |manager: &PaneManager, id| manager.get_pane(id).unwrap().terminal.grid().lock().unwrap().size();
+        assert_eq!(size(&manager, second), (40, 20));
+
+        manager.close_pane(first);
+        assert_eq!(size(&manager, second), (80, 20));
+    }
+
+    #[test]
+    fn test_ordered_panes_follow_creation_order() {
+        let mut manager = cat_manager();
+        let first = manager.create_pane(40, 10).unwrap();
+        let second = manager.split_pane(first, SplitDirection::Horizontal, 20, 10).unwrap();
+        let third = manager.split_pane(first, SplitDirection::Vertical, 20, 5).unwrap();
+
+        manager.close_pane(second);
+        let fourth = manager.split_pane(third, SplitDirection::Horizontal, 10, 5).unwrap();
+
+        let ids: Vec<PaneId> = manager.ordered_panes().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![first, third, fourth]);
+    }
+
+    #[test]
+    fn test_rotate_split() {
+        let mut layout = Layout::new();
+        layout.set_root(PaneId(0));
+        layout.split(PaneId(0), PaneId(1), SplitDirection::Horizontal);
+        assert!(layout.rotate_split(PaneId(1)));
+
+        let bounds = layout.calculate_bounds(800.0, 600.0);
+        assert_eq!(bounds[&PaneId(0)], (0.0, 0.0, 800.0, 300.0));
+        assert_eq!(bounds[&PaneId(1)], (0.0, 300.0, 800.0, 300.0));
+
+        // Only the split directly holding the pane turns
+        layout.split(PaneId(1), PaneId(2), SplitDirection::Horizontal);
+        assert!(layout.rotate_split(PaneId(2)));
+        let bounds = layout.calculate_bounds(800.0, 600.0);
+        assert_eq!(bounds[&PaneId(0)], (0.0, 0.0, 800.0, 300.0));
+        assert_eq!(bounds[&PaneId(1)], (0.0, 300.0, 800.0, 150.0));
+        assert_eq!(bounds[&PaneId(2)], (0.0, 450.0, 800.0, 150.0));
+
+        let mut single = Layout::new();
+        single.set_root(PaneId(0));
+        assert!(!single.rotate_split(PaneId(0)));
+    }
+
+    #[test]
+    fn test_divider_hit_test() {
+        // Left pane | right column split into top and bottom
+        let mut layout = Layout::new();
+        layout.set_root(PaneId(0));
+        layout.split(PaneId(0), PaneId(1), SplitDirection::Horizontal);
+        layout.split(PaneId(1), PaneId(2), SplitDirection::Vertical);
+
+        let divider = layout.divider_at(800.0, 600.0, 402.0, 100.0, 4.0).unwrap();
+        assert_eq!(divider.path, Vec::<bool>::new());
+        assert_eq!(divider.direction, SplitDirection::Horizontal);
+        assert_eq!(divider.ratio_at(600.0, 100.0), 0.75);
+
+        let divider = layout.divider_at(800.0, 600.0, 600.0, 297.0, 4.0).unwrap();
+        assert_eq!(divider.path, vec![true]);
+        assert_eq!(divider.direction, SplitDirection::Vertical);
+        assert_eq!(divider.bounds, (400.0, 0.0, 400.0, 600.0));
+
+        // Inside panes, and level with the right column's divider but in the
+        // left pane
+        assert_eq!(layout.divider_at(800.0, 600.0, 200.0, 100.0, 4.0), None);
+        assert_eq!(layout.divider_at(800.0, 600.0, 200.0, 300.0, 4.0), None);
+
+        assert!(layout.set_split_ratio(&divider.path, divider.ratio_at(600.0, 150.0)));
+        let bounds = layout.calculate_bounds(800.0, 600.0);
+        assert_eq!(bounds[&PaneId(1)], (400.0, 0.0, 400.0, 150.0));
+        assert!(!layout.set_split_ratio(&[false], 0.5));
+    }
+
+    #[test]
+    fn test_divider_hover_highlight() {
+        // Left pane | right column split into top and bottom
+        let mut layout = Layout::new();
+        layout.set_root(PaneId(0));
+        layout.split(PaneId(0), PaneId(1), SplitDirection::Horizontal);
+        layout.split(PaneId(1), PaneId(2), SplitDirection::Vertical);
+        let highlight = |x, y| {
+            layout
+                .divider_at(800.0, 600.0, x, y, DIVIDER_GRAB_DISTANCE)
+                .map(|divider| divider.grab_rect(DIVIDER_GRAB_DISTANCE))
+        };
+
+        // Either side of the vertical line between the columns
+        let column_divider = Some([396.0, 0.0, 8.0, 600.0]);
+        assert_eq!(highlight(397.0, 500.0), column_divider);
+        assert_eq!(highlight(403.0, 20.0), column_divider);
+
+        // Near the line between the right column's panes
+        assert_eq!(highlight(700.0, 302.0), Some([400.0, 296.0, 400.0, 8.0]));
+
+        // Away from every divider
+        assert_eq!(highlight(390.0, 300.0), None);
+        assert_eq!(highlight(600.0, 100.0), None);
+    }
+
+    #[test]
+    fn test_fit_leaves_room_for_pane_header() {
+        let mut manager = cat_manager();
+        manager.set_viewport_size(800.0, 400.0);
+        manager.set_cell_size(10.0, 20.0);
+        manager.set_pane_header_height(20.0);
+
+        let id = manager.create_pane(80, 20).unwrap();
+        manager.fit_panes_to_viewport();
+        assert_eq!(manager.get_pane(id).unwrap().terminal.grid().lock().unwrap().size(), (80, 19));
+    }
+
+    #[test]
+    fn test_fit_leaves_room_for_padding() {
+        let mut manager = cat_manager();
+        manager.set_viewport_size(800.0, 400.0);
+        manager.set_cell_size(10.0, 20.0);
+        manager.set_padding(Padding { top: 10.0, right: 5.0, bottom: 10.0, left: 15.0 });
+
+        let id = manager.create_pane(80, 20).unwrap();
+        manager.fit_panes_to_viewport();
+        assert_eq!(manager.get_pane(id).unwrap().terminal.grid().lock().unwrap().size(), (78, 19));
+    }
+}
@@ -1,12 +1,49 @@
+use crate::config::ShellConfig;
 use crate::terminal::Terminal;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct PaneId(pub usize);
 
+/// Per-pane overrides of the configured shell
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PaneSpawnOptions {
+    /// Directory to start in instead of the configured one
+    pub cwd: Option<PathBuf>,
+    /// Variables set on top of the configured environment
+    pub env: HashMap<String, String>,
+    /// Program and arguments to run instead of the shell; empty runs the shell
+    pub command: Vec<String>,
+}
+
+impl PaneSpawnOptions {
+    /// `shell` with these overrides applied
+    pub fn apply(&self, shell: &ShellConfig) -> ShellConfig {
+        let mut shell = shell.clone();
+        if self.cwd.is_some() {
+            shell.working_directory = self.cwd.clone();
+        }
+        shell.env.extend(self.env.clone());
+        if let Some((program, args)) = self.command.split_first() {
+            shell.program = Some(program.clone());
+            shell.args = args.to_vec();
+            shell.login_shell = false;
+        }
+        shell
+    }
+}
+
 pub struct Pane {
     pub id: PaneId,
     pub terminal: Terminal,
     pub title: String,
+    /// What the pane was started with on top of the configured shell; `cwd`
+    /// is the directory it was started in, if one was chosen
+    pub spawn_options: PaneSpawnOptions,
+    /// Why the pane's shell or command failed to start; its terminal then
+    /// shows this message and takes no input
+    pub error: Option<String>,
 }
 
 impl Pane {
@@ -15,6 +52,16 @@ impl Pane {
             id,
             terminal,
             title: format!("Terminal {}", id.0),
+            spawn_options: PaneSpawnOptions::default(),
+            error: None,
+        }
+    }
+
+    /// A pane whose shell or command failed to start, showing `error`
+    pub fn failed(id: PaneId, cols: u16, rows: u16, error: String) -> Self {
+        Self {
+            error: Some(error.clone()),
+            ..Self::new(id, Terminal::failed(cols, rows, &error))
         }
     }
 
@@ -0,0 +1,34 @@
+use super::{Layout, PaneId};
+
+/// One tab: an independent pane tree and the pane focused within it
+pub struct Tab {
+    pub(super) layout: Layout,
+    pub(super) active_pane: Option<PaneId>,
+}
+
+impl Tab {
+    pub fn new() -> Self {
+        Self {
+            layout: Layout::new(),
+            active_pane: None,
+        }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn active_pane(&self) -> Option<PaneId> {
+        self.active_pane
+    }
+
+    pub fn contains(&self, pane_id: PaneId) -> bool {
+        self.layout.pane_ids().contains(&pane_id)
+    }
+}
+
+impl Default for Tab {
+    fn default() -> Self {
+        Self::new()
+    }
+}
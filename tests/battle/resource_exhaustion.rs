@@ -118,10 +118,8 @@ fn test_maximum_panes() -> Result<usize, String> {
                 created_count += 1;
 
                 // Write some data to each pane to ensure it's functional
-                if let Some(pane) = pane_manager.get_pane_mut(_id) {
-                    let msg = format!("Pane {} initialized\n", i);
-                    let _ = pane.terminal.write(msg.as_bytes());
-                }
+                let msg = format!("Pane {} initialized\n", i);
+                let _ = pane_manager.write_to_pane(_id, msg.as_bytes());
             }
             Err(e) => {
                 // If we hit a limit, that's okay as long as we got a reasonable number
@@ -0,0 +1,25 @@
+//! Headless Benchmark Test
+//!
+//! Runs a tiny benchmark end to end: a fresh server, two clients and a
+//! hundred messages each.
+
+use titi::headless::Benchmark;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_tiny_benchmark_reports_throughput() {
+    let report = Benchmark::new("127.0.0.1:18951")
+        .clients(2)
+        .messages(100)
+        .payload_size(32)
+        .timeout(Duration::from_secs(5))
+        .run()
+        .await
+        .expect("Benchmark failed");
+
+    assert_eq!(report.messages, 200);
+    assert!(report.elapsed > Duration::ZERO);
+    assert!(report.throughput() > 0.0 && report.throughput().is_finite());
+    assert!(report.p50 > Duration::ZERO);
+    assert!(report.p50 <= report.p95 && report.p95 <= report.p99 && report.p99 <= report.max);
+}
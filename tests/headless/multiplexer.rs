@@ -0,0 +1,84 @@
+//! Headless Multiplexer Test
+//!
+//! Drives two shells through one controller: a broadcast command must come
+//! back from both, tagged with the terminal that produced it.
+
+use std::collections::HashMap;
+use titi::headless::Multiplexer;
+use titi::redititi_server::{RedititiTcpServer, TokenAuth};
+use tokio::time::{sleep, Duration, Instant};
+
+#[tokio::test]
+async fn test_broadcast_output_is_tagged_per_terminal() {
+    let addr = "127.0.0.1:17406";
+    let token = format!("test-token-{}", rand::random::<u32>());
+    let server = RedititiTcpServer::new(addr.to_string(), TokenAuth::from_token(token.clone()).unwrap());
+    let handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    let mut mux = Multiplexer::connect(addr, &token).await.expect("Failed to connect controller");
+    mux.add("alpha").await.expect("Failed to add alpha");
+    mux.add("beta").await.expect("Failed to add beta");
+    assert_eq!(mux.names(), vec!["alpha", "beta"]);
+    assert!(mux.add("alpha").await.is_err());
+
+    mux.inject_all("echo hi").await.expect("Failed to broadcast");
+
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let printed_hi = |mux: &Multiplexer, name: &str| {
+        mux.screen(name).unwrap().lines().any(|line| line.trim_end() == "hi")
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !(printed_hi(&mux, "alpha") && printed_hi(&mux, "beta")) {
+        assert!(Instant::now() < deadline, "Timed out waiting for output: {:?}", seen);
+        for output in mux.pump().await.expect("Pump failed") {
+            seen.entry(output.source.clone()).or_default().push_str(&output.text());
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    // Each terminal's output arrives tagged with its own name
+    let mut sources: Vec<_> = seen.keys().cloned().collect();
+    sources.sort();
+    assert_eq!(sources, vec!["alpha", "beta"]);
+    assert!(seen.values().all(|text| text.contains("hi\r\n")), "Missing output: {:?}", seen);
+
+    mux.remove("beta").await.expect("Failed to remove beta");
+    assert_eq!(mux.names(), vec!["alpha"]);
+    mux.shutdown().await.expect("Shutdown failed");
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_pump_keeps_going_when_one_shell_exits() {
+    let addr = "127.0.0.1:17407";
+    let token = format!("test-token-{}", rand::random::<u32>());
+    let server = RedititiTcpServer::new(addr.to_string(), TokenAuth::from_token(token.clone()).unwrap());
+    let handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    let mut mux = Multiplexer::connect(addr, &token).await.expect("Failed to connect controller");
+    mux.add("quitter").await.expect("Failed to add quitter");
+    mux.add("talker").await.expect("Failed to add talker");
+
+    mux.inject(&["talker"], "for i in 1 2 3 4 5 6 7 8; do echo tick$i; sleep 0.2; done")
+        .await
+        .expect("Failed to inject into talker");
+    mux.inject(&["quitter"], "exit").await.expect("Failed to inject into quitter");
+
+    let ticked = |mux: &Multiplexer| mux.screen("talker").unwrap().lines().any(|line| line.trim_end() == "tick8");
+    let deadline = Instant::now() + Duration::from_secs(15);
+    while !(mux.has_exited("quitter").unwrap() && ticked(&mux)) {
+        assert!(Instant::now() < deadline, "Timed out: talker screen {:?}", mux.screen("talker").unwrap());
+        mux.pump().await.expect("Pump failed after a shell exited");
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    assert!(!mux.has_exited("talker").unwrap());
+    mux.shutdown().await.expect("Shutdown failed");
+    handle.abort();
+}
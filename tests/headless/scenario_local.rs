@@ -0,0 +1,52 @@
+//! Local Headless Terminal Test
+//!
+//! Drives a shell through `run_headless_local`, with no redititi server:
+//! write a command, pump the PTY and read the result off the grid.
+
+use titi::config::ShellConfig;
+use titi::headless::{run_headless_local, LocalHeadlessConfig};
+use tokio::time::{sleep, Duration, Instant};
+
+#[tokio::test]
+async fn test_local_headless_echo() {
+    let config = LocalHeadlessConfig {
+        cols: 80,
+        rows: 24,
+        shell: ShellConfig {
+            program: Some("/bin/sh".to_string()),
+            ..Default::default()
+        },
+    };
+    let mut terminal = run_headless_local(config).expect("Failed to spawn shell");
+
+    // Wait for the prompt so the shell has set up the tty before typing
+    terminal.wait_for("\\S", Duration::from_secs(10)).await.expect("No prompt");
+    terminal.write(b"echo hi\n").await.unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !terminal.screen().lines().any(|line| line.trim() == "hi") {
+        assert!(Instant::now() < deadline, "Output never appeared:\n{}", terminal.screen());
+        if !terminal.pump().await.unwrap() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    let grid = terminal.grid();
+    let grid = grid.lock().unwrap();
+    assert!(grid.visible_lines().any(|line| line.trim() == "hi"));
+}
+
+#[tokio::test]
+async fn test_local_headless_wait_for_times_out() {
+    let config = LocalHeadlessConfig {
+        shell: ShellConfig {
+            program: Some("/bin/sh".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut terminal = run_headless_local(config).expect("Failed to spawn shell");
+
+    assert!(terminal.wait_for("^never printed$", Duration::from_millis(200)).await.is_err());
+    assert_eq!(terminal.grid().lock().unwrap().visible_lines().count(), 24);
+}
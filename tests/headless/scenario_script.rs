@@ -0,0 +1,41 @@
+//! Headless Command Script Test
+//!
+//! Runs a small script against a real shell: inject a command, wait for its
+//! output, then capture the screen to a file.
+
+use titi::config::ShellConfig;
+use titi::headless::script::{parse_script, run_script};
+use titi::terminal::Terminal;
+
+#[tokio::test]
+async fn test_script_inject_wait_capture() {
+    let dir = std::env::temp_dir().join(format!("titi-script-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        ..Default::default()
+    };
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn shell");
+
+    // Wait for the prompt so the shell has set up the tty before typing
+    let commands = parse_script("wait_for \\S\ninject echo hi\nwait_for ^hi$\ncapture screen.txt\n").unwrap();
+    run_script(&mut terminal, &commands, Some(&dir)).await.expect("Script failed");
+
+    let captured = std::fs::read_to_string(dir.join("screen.txt")).unwrap();
+    assert!(captured.lines().any(|line| line.trim() == "hi"), "Capture missing output: {:?}", captured);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_script_capture_requires_directory() {
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        ..Default::default()
+    };
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn shell");
+
+    let commands = parse_script("capture screen.txt\n").unwrap();
+    assert!(run_script(&mut terminal, &commands, None).await.is_err());
+}
@@ -481,7 +481,8 @@ async fn test_session_recovery_after_disconnect() {
 
     sleep(Duration::from_millis(50)).await;
 
-    // Simulate disconnect by dropping client1
+    // Detach so the session survives client1 going away
+    client1.detach().await.expect("Detach failed");
     drop(client1);
     sleep(Duration::from_millis(100)).await;
 
@@ -686,3 +687,640 @@ async fn test_rapid_connection_cycling() {
     server_handle.abort();
     sleep(Duration::from_millis(100)).await;
 }
+
+#[tokio::test]
+async fn test_pattern_subscription_across_panes() {
+    let port = 17391;
+    let (token, handle) = start_test_server_with_env(port).await;
+
+    // Observer watches every output channel without knowing pane ids
+    let mut observer = ServerClient::connect(&format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect observer");
+    observer.authenticate(&token).await.expect("Observer auth failed");
+    observer.psubscribe("*/output").await.expect("PSUBSCRIBE failed");
+
+    let mut agent = ServerClient::connect(&format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect agent");
+    agent.authenticate(&token).await.expect("Agent auth failed");
+    agent.create_session(Some("psub")).await.expect("Session failed");
+
+    agent.create_pane(Some("one")).await.expect("Pane failed");
+    agent.create_pane(Some("two")).await.expect("Pane failed");
+
+    // Publishes are fire-and-forget, so issue them after all request/response commands
+    agent.publish_to_channel("psub/pane-one/output", "from-pane-one").await.expect("Publish failed");
+    agent.publish_to_channel("psub/pane-two/output", "from-pane-two").await.expect("Publish failed");
+    agent.publish_to_channel("psub/pane-two/input", "not-output").await.expect("Publish failed");
+
+    sleep(Duration::from_millis(100)).await;
+
+    let mut received = Vec::new();
+    while let Some(message) = observer.read_pattern("*/output").await.expect("PRPOP failed") {
+        received.push(message);
+    }
+
+    assert_eq!(
+        received,
+        vec![
+            ("psub/pane-one/output".to_string(), "from-pane-one".to_string()),
+            ("psub/pane-two/output".to_string(), "from-pane-two".to_string()),
+        ]
+    );
+
+    // Nothing is delivered after unsubscribing
+    observer.punsubscribe("*/output").await.expect("PUNSUBSCRIBE failed");
+    agent.publish_output("after-unsubscribe").await.expect("Publish failed");
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(observer.read_pattern("*/output").await.expect("PRPOP failed"), None);
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_resp_client_round_trip() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let port = 17392;
+    let (token, handle) = start_test_server_with_env(port).await;
+
+    // Talk RESP2 directly, the way redis-cli does
+    let mut stream = tokio::net::TcpStream::connect(format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect");
+
+    let encode = |parts: &[&str]| {
+        let mut out = format!("*{}\r\n", parts.len());
+        for part in parts {
+            out.push_str(&format!("${}\r\n{}\r\n", part.len(), part));
+        }
+        out
+    };
+
+    let requests = [
+        encode(&["AUTH", &token]),
+        encode(&["PUBLISH", "resp/output", "hello world"]),
+        encode(&["LLEN", "resp/output"]),
+        encode(&["RPOP", "resp/output"]),
+        encode(&["RPOP", "resp/output"]),
+    ];
+    for request in &requests {
+        stream.write_all(request.as_bytes()).await.expect("Write failed");
+    }
+
+    let expected = "+OK\r\n+OK published to 0 subscribers\r\n:1\r\n$11\r\nhello world\r\n$-1\r\n";
+    let mut buf = vec![0u8; expected.len()];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf))
+        .await
+        .expect("Timed out waiting for responses")
+        .expect("Read failed");
+
+    assert_eq!(String::from_utf8(buf).unwrap(), expected);
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_rate_limited_connection_stays_open() {
+    use titi::redititi_server::{RateLimit, RateLimits};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let port = 17393;
+    let token = "test_token_12345678901234567890123456789012345678901234567890123456".to_string();
+    std::env::set_var("TITI_TOKEN", &token);
+
+    let auth = TokenAuth::new().expect("Failed to create auth");
+    let server = RedititiTcpServer::new(format!("127.0.0.1:{}", port), auth).with_rate_limits(RateLimits {
+        write: Some(RateLimit::new(10, 5.0)),
+        read: None,
+    });
+    let handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let stream = tokio::net::TcpStream::connect(format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect");
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    async fn read_line(reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.expect("Read failed");
+        line
+    }
+
+    writer.write_all(format!("AUTH {}\n", token).as_bytes()).await.unwrap();
+    assert_eq!(read_line(&mut reader).await, "+OK\n");
+
+    // Flood well past the burst of 10
+    for i in 0..30 {
+        writer.write_all(format!("PUBLISH flood msg{}\n", i).as_bytes()).await.unwrap();
+    }
+    let mut accepted = 0;
+    let mut limited = 0;
+    for _ in 0..30 {
+        let line = read_line(&mut reader).await;
+        if line.starts_with("+OK") {
+            accepted += 1;
+        } else if line == "-ERR rate limited\n" {
+            limited += 1;
+        }
+    }
+    assert!(accepted >= 10, "Burst should be accepted, got {}", accepted);
+    assert!(limited > 0, "Flood should be rate limited");
+    assert_eq!(accepted + limited, 30);
+
+    // Reads have their own budget and the connection is still usable
+    writer.write_all(b"LLEN flood\n").await.unwrap();
+    assert_eq!(read_line(&mut reader).await, format!("\"{}\"\n", accepted));
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+/// Test that a client's sessions are removed when it disconnects, unless
+/// they are persistent or the client detached
+#[tokio::test]
+async fn test_sessions_cleaned_up_on_disconnect() {
+    let port = 17394;
+    let (token, server_handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut dropped = ServerClient::connect(&addr).await.expect("Connection failed");
+    dropped.authenticate(&token).await.expect("Auth failed");
+    dropped.create_session(Some("ephemeral")).await.expect("Session failed");
+    dropped.create_persistent_session(Some("durable")).await.expect("Session failed");
+
+    let mut detached = ServerClient::connect(&addr).await.expect("Connection failed");
+    detached.authenticate(&token).await.expect("Auth failed");
+    detached.create_session(Some("detached")).await.expect("Session failed");
+
+    let mut observer = ServerClient::connect(&addr).await.expect("Observer connection failed");
+    observer.authenticate(&token).await.expect("Observer auth failed");
+
+    let mut sessions = observer.list_sessions().await.expect("List failed");
+    sessions.sort();
+    assert_eq!(sessions, vec!["detached", "durable", "ephemeral"]);
+
+    detached.detach().await.expect("Detach failed");
+    drop(detached);
+    drop(dropped);
+    sleep(Duration::from_millis(200)).await;
+
+    let mut sessions = observer.list_sessions().await.expect("List failed");
+    sessions.sort();
+    assert_eq!(sessions, vec!["detached", "durable"]);
+
+    server_handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_unsubscribe_stops_delivery() {
+    let port = 17395;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut terminal_client = ServerClient::connect(&addr).await.expect("Failed to connect terminal");
+    terminal_client.authenticate(&token).await.expect("Auth failed");
+    terminal_client.create_session(Some("unsub-test")).await.expect("Session failed");
+    terminal_client.create_pane(Some("pane1")).await.expect("Pane failed");
+    terminal_client.subscribe_input().await.expect("Subscribe input failed");
+
+    let session_id = terminal_client.session_id().to_string();
+    let pane_id = terminal_client.pane_id().to_string();
+    let input_channel = format!("{}/pane-{}/input", session_id, pane_id);
+    assert!(terminal_client.is_subscribed(&input_channel));
+
+    let mut controller = ServerClient::connect(&addr).await.expect("Failed to connect controller");
+    controller.authenticate(&token).await.expect("Controller auth failed");
+
+    controller.inject_command(&session_id, &pane_id, "before").await.expect("Injection failed");
+    sleep(Duration::from_millis(50)).await;
+    let received = terminal_client.read_input().await.expect("Read failed");
+    assert!(received.is_some_and(|cmd| cmd.contains("before")));
+
+    terminal_client.unsubscribe_input().await.expect("Unsubscribe failed");
+    assert!(!terminal_client.is_subscribed(&input_channel));
+
+    controller.inject_command(&session_id, &pane_id, "after").await.expect("Injection failed");
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(terminal_client.read_input().await.expect("Read failed"), None);
+
+    // Unsubscribing again, or from a channel never subscribed to, is a no-op
+    terminal_client.unsubscribe_input().await.expect("Repeated unsubscribe failed");
+    terminal_client.unsubscribe("never/subscribed").await.expect("Unsubscribe of unknown channel failed");
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_reconnect_restores_subscriptions() {
+    let port = 17396;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut client = ServerClient::connect(&addr).await.expect("Failed to connect");
+    client.authenticate(&token).await.expect("Auth failed");
+    client.create_persistent_session(Some("resub-test")).await.expect("Session failed");
+    client.subscribe_output().await.expect("Subscribe output failed");
+    client.subscribe("resub-test/events").await.expect("Subscribe failed");
+
+    client.reconnect(&addr, &token).await.expect("Reconnect failed");
+
+    assert!(client.is_authenticated());
+    assert!(client.is_subscribed("resub-test/events"));
+    assert_eq!(client.session_id(), "resub-test");
+
+    // The new connection answers requests in order, so nothing was left unread
+    assert!(client.list_sessions().await.expect("List failed").contains(&"resub-test".to_string()));
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_pipelined_publishes_answered_in_order() {
+    let port = 17397;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut client = ServerClient::connect(&addr).await.expect("Failed to connect");
+    client.authenticate(&token).await.expect("Auth failed");
+
+    let commands: Vec<String> = (0..100).map(|i| format!("PUBLISH pipeline msg{}", i)).collect();
+    let commands: Vec<&str> = commands.iter().map(String::as_str).collect();
+    let responses = client.pipeline(&commands).await.expect("Pipeline failed");
+
+    assert_eq!(responses.len(), 100);
+    assert!(responses.iter().all(|r| r.starts_with("+OK")), "unexpected responses: {:?}", responses);
+
+    // The commands ran in the order they were sent
+    let drained = client
+        .pipeline(&["LLEN pipeline", "RPOP pipeline", "RPOP pipeline"])
+        .await
+        .expect("Pipeline failed");
+    assert_eq!(drained, vec!["\"100\"", "\"msg0\"", "\"msg1\""]);
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_closes_connections() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let port = 17398;
+    let token = "test_token_12345678901234567890123456789012345678901234567890123456".to_string();
+    std::env::set_var("TITI_TOKEN", &token);
+
+    let state_dir = std::env::temp_dir().join(format!("titi-shutdown-{}", std::process::id()));
+    std::fs::create_dir_all(&state_dir).unwrap();
+    let state_file = state_dir.join("state.json");
+
+    let auth = TokenAuth::new().expect("Failed to create auth");
+    let server = RedititiTcpServer::new(format!("127.0.0.1:{}", port), auth).with_persistence(&state_file);
+    let shutdown = server.shutdown_handle();
+    let handle = tokio::spawn(async move { server.run().await });
+    sleep(Duration::from_millis(200)).await;
+
+    let mut stream = tokio::net::TcpStream::connect(format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect");
+    stream
+        .write_all(format!("AUTH {}\nPUBLISH drain/output pending\n", token).as_bytes())
+        .await
+        .expect("Write failed");
+    sleep(Duration::from_millis(100)).await;
+
+    shutdown.shutdown();
+
+    // Replies already owed are delivered, then the server closes its side
+    let mut received = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut received))
+        .await
+        .expect("Connection was not closed")
+        .expect("Read failed");
+    assert_eq!(received, "+OK\n+OK published to 0 subscribers\n");
+
+    let result = tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("run did not return")
+        .expect("Server task panicked");
+    assert!(result.is_ok(), "run failed: {:?}", result);
+
+    // Queued messages were saved on the way out, and the port is free again
+    let queues = std::fs::read_to_string(state_dir.join("state.queues.json")).expect("Queues not saved");
+    assert!(queues.contains("pending"));
+    assert!(tokio::net::TcpStream::connect(format!("127.0.0.1:{}", port)).await.is_err());
+
+    let _ = std::fs::remove_dir_all(&state_dir);
+}
+
+#[tokio::test]
+async fn test_ping_pong() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let port = 17399;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let stream = tokio::net::TcpStream::connect(&addr).await.expect("Failed to connect");
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(format!("AUTH {}\nPING\n", token).as_bytes()).await.unwrap();
+
+    let mut replies = String::new();
+    for _ in 0..2 {
+        reader.read_line(&mut replies).await.expect("Read failed");
+    }
+    assert_eq!(replies, "+OK\n+PONG\n");
+
+    // The client skips replies to fire-and-forget calls while waiting for PONG
+    let mut client = ServerClient::connect(&addr).await.expect("Failed to connect");
+    client.authenticate(&token).await.expect("Auth failed");
+    client.publish_to_channel("ping-test/events", "unread").await.expect("Publish failed");
+    client.ping().await.expect("Ping failed");
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_silent_connection_closed() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let port = 17400;
+    let token = "test_token_12345678901234567890123456789012345678901234567890123456".to_string();
+    std::env::set_var("TITI_TOKEN", &token);
+
+    let auth = TokenAuth::new().expect("Failed to create auth");
+    let server = RedititiTcpServer::new(format!("127.0.0.1:{}", port), auth)
+        .with_connection_timeout(Duration::from_millis(300));
+    let handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // A client that keeps pinging stays connected
+    let mut pinging = ServerClient::connect(&format!("127.0.0.1:{}", port)).await.expect("Failed to connect");
+    pinging.authenticate(&token).await.expect("Auth failed");
+
+    let mut silent = tokio::net::TcpStream::connect(format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to connect");
+    silent.write_all(format!("AUTH {}\n", token).as_bytes()).await.expect("Write failed");
+
+    for _ in 0..5 {
+        sleep(Duration::from_millis(100)).await;
+        pinging.ping().await.expect("Ping failed");
+    }
+
+    let mut received = String::new();
+    tokio::time::timeout(Duration::from_secs(5), silent.read_to_string(&mut received))
+        .await
+        .expect("Silent connection was not closed")
+        .expect("Read failed");
+    assert_eq!(received, "+OK\n");
+    pinging.ping().await.expect("Pinging connection was closed");
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_keepalive_reconnects_after_server_restart() {
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    let port = 17401;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut client = ServerClient::connect(&addr).await.expect("Failed to connect");
+    client.authenticate(&token).await.expect("Auth failed");
+    client.subscribe("keepalive-test/events").await.expect("Subscribe failed");
+    let client = Arc::new(RwLock::new(client));
+    let keepalive = ServerClient::spawn_keepalive(&client, addr.clone(), token.clone(), Duration::from_millis(200));
+
+    // The old server goes away; its connections drop with it
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+    let (_, handle) = start_test_server_with_env(port).await;
+    sleep(Duration::from_millis(600)).await;
+
+    let client = client.read().await;
+    assert!(client.is_authenticated());
+    assert!(client.is_subscribed("keepalive-test/events"));
+    client.ping().await.expect("Ping after reconnect failed");
+
+    keepalive.abort();
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_run_command_waits_for_output() {
+    use std::time::Instant;
+    use titi::terminal::Terminal;
+
+    let port = 17402;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut terminal_client = ServerClient::connect(&addr).await.expect("Failed to connect terminal");
+    terminal_client.authenticate(&token).await.expect("Auth failed");
+    terminal_client.create_session(Some("run-command-test")).await.expect("Session failed");
+    terminal_client.create_pane(Some("pane1")).await.expect("Pane failed");
+    terminal_client.subscribe_input().await.expect("Subscribe input failed");
+    let target = format!("{}/pane-{}", terminal_client.session_id(), terminal_client.pane_id());
+    let mut terminal = Terminal::new_with_server(80, 24, terminal_client).expect("Failed to create terminal");
+
+    let mut controller = ServerClient::connect(&addr).await.expect("Failed to connect controller");
+    controller.authenticate(&token).await.expect("Controller auth failed");
+
+    // Stand in for the headless main loop
+    let pump = async {
+        loop {
+            terminal.poll_server_input().await.expect("Failed to poll input");
+            if let Ok(Some(data)) = terminal.read() {
+                terminal.process_output(&data);
+                terminal.publish_output_if_needed().await;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    // The typed command line echoes READY too, so match the output line only
+    let started = Instant::now();
+    let screen = tokio::select! {
+        result = controller.run_command(&target, "echo READY", "^READY$", Duration::from_secs(10)) => {
+            result.expect("run_command failed")
+        }
+        _ = pump => unreachable!(),
+    };
+
+    assert!(screen.lines().any(|line| line == "READY"), "Missing output: {:?}", screen);
+    assert!(started.elapsed() < Duration::from_secs(5), "Took {:?}", started.elapsed());
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_inject_raw_ctrl_c_interrupts_process() {
+    use std::time::Instant;
+    use titi::terminal::Terminal;
+
+    let port = 17403;
+    let (token, handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut terminal_client = ServerClient::connect(&addr).await.expect("Failed to connect terminal");
+    terminal_client.authenticate(&token).await.expect("Auth failed");
+    terminal_client.create_session(Some("inject-raw-test")).await.expect("Session failed");
+    terminal_client.create_pane(Some("pane1")).await.expect("Pane failed");
+    terminal_client.subscribe_input().await.expect("Subscribe input failed");
+    let (session_id, pane_id) = (terminal_client.session_id().to_string(), terminal_client.pane_id().to_string());
+    let target = format!("{}/pane-{}", session_id, pane_id);
+    let mut terminal = Terminal::new_with_server(80, 24, terminal_client).expect("Failed to create terminal");
+
+    let mut controller = ServerClient::connect(&addr).await.expect("Failed to connect controller");
+    controller.authenticate(&token).await.expect("Controller auth failed");
+
+    let pump = async {
+        loop {
+            terminal.poll_server_input().await.expect("Failed to poll input");
+            if let Ok(Some(data)) = terminal.read() {
+                terminal.process_output(&data);
+                terminal.publish_output_if_needed().await;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    // DONE only prints once the sleep is gone, well before it would finish
+    let started = Instant::now();
+    let control = async {
+        controller.inject_command(&session_id, &pane_id, "sleep 30").await.expect("Inject failed");
+        sleep(Duration::from_millis(500)).await;
+        controller.inject_raw(&target, b"\x03").await.expect("inject_raw failed");
+        controller.run_command(&target, "echo DONE", "^DONE$", Duration::from_secs(10)).await
+    };
+    let screen = tokio::select! {
+        result = control => result.expect("Ctrl+C did not interrupt sleep"),
+        _ = pump => unreachable!(),
+    };
+
+    assert!(screen.lines().any(|line| line == "DONE"), "Missing output: {:?}", screen);
+    assert!(started.elapsed() < Duration::from_secs(10), "Took {:?}", started.elapsed());
+
+    handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_write_draws_on_screen_without_shell() {
+    use titi::terminal::{Color, Terminal};
+
+    let port = 17404;
+    let (token, server_handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut terminal_client = ServerClient::connect(&addr).await.expect("Terminal client connection failed");
+    terminal_client.authenticate(&token).await.expect("Terminal auth failed");
+    terminal_client.create_session(Some("write-test")).await.expect("Session failed");
+    terminal_client.create_pane(Some("pane1")).await.expect("Pane failed");
+    terminal_client.subscribe_input().await.expect("Subscribe input failed");
+    let target = format!("{}/pane-{}", terminal_client.session_id(), terminal_client.pane_id());
+
+    let mut terminal = Terminal::new_with_server(80, 24, terminal_client).expect("Failed to create terminal");
+
+    let mut controller = ServerClient::connect(&addr).await.expect("Controller connection failed");
+    controller.authenticate(&token).await.expect("Controller auth failed");
+    controller
+        .write_to_screen(&target, "\x1b[31mWRITE_BANNER\x1b[0m")
+        .await
+        .expect("WRITE failed");
+
+    let mut shell_output = Vec::new();
+    for _ in 0..10 {
+        terminal.poll_server_input().await.expect("Failed to poll server input");
+        if let Ok(Some(data)) = terminal.read() {
+            shell_output.extend_from_slice(&data);
+            terminal.process_output(&data);
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    // The banner is on the screen, in red
+    {
+        let grid = terminal.grid();
+        let grid = grid.lock().unwrap();
+        let (_, rows) = grid.size();
+        let (x, y) = (0..rows)
+            .find_map(|y| grid.row_text(y).find("WRITE_BANNER").map(|x| (x, y)))
+            .expect("WRITE text missing from the screen");
+        assert_eq!(grid.get_cell(x, y).unwrap().style.fg, Color::Red);
+    }
+
+    // ...but the shell never saw it, so never echoed it
+    assert!(!String::from_utf8_lossy(&shell_output).contains("WRITE_BANNER"));
+
+    server_handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_dirty_lines_are_published_together() {
+    use titi::terminal::{CellChange, Grid, Terminal, TerminalParser};
+    use std::sync::{Arc, Mutex};
+
+    let port = 17405;
+    let (token, server_handle) = start_test_server_with_env(port).await;
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut terminal_client = ServerClient::connect(&addr).await.expect("Terminal client connection failed");
+    terminal_client.authenticate(&token).await.expect("Terminal auth failed");
+    terminal_client.create_session(Some("coalesce-test")).await.expect("Session failed");
+    terminal_client.create_pane(Some("pane1")).await.expect("Pane failed");
+    let session_id = terminal_client.session_id().to_string();
+    let pane_id = terminal_client.pane_id().to_string();
+
+    let mut terminal = Terminal::new_with_server(40, 10, terminal_client).expect("Failed to create terminal");
+
+    let mut controller = ServerClient::connect(&addr).await.expect("Controller connection failed");
+    controller.authenticate(&token).await.expect("Controller auth failed");
+
+    // Flush the initial full-screen redraw
+    terminal.publish_output_if_needed().await;
+    sleep(Duration::from_millis(100)).await;
+    while controller.read_from_channel(&session_id, &pane_id, "output").await.expect("Read failed").is_some() {}
+
+    terminal.process_output(b"\x1b[31mfirst\x1b[0m\r\nsecond\r\nthird\r\nfourth\r\nfifth");
+    terminal.publish_output_if_needed().await;
+    sleep(Duration::from_millis(100)).await;
+
+    // One message carries every changed line...
+    let payload = controller
+        .read_from_channel(&session_id, &pane_id, "output")
+        .await
+        .expect("Read failed")
+        .expect("Nothing published");
+    assert_eq!(controller.read_from_channel(&session_id, &pane_id, "output").await.expect("Read failed"), None);
+
+    // ...and replays to the same lines, in order
+    let screen = Arc::new(Mutex::new(Grid::new(40, 10)));
+    TerminalParser::new(screen.clone()).parse(&CellChange::decode(&payload));
+    let lines: Vec<String> = screen.lock().unwrap().visible_lines().take(5).collect();
+    assert_eq!(lines, vec!["first", "second", "third", "fourth", "fifth"]);
+
+    server_handle.abort();
+    sleep(Duration::from_millis(100)).await;
+}
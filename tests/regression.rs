@@ -295,6 +295,9 @@ fn regression_style_persistence() {
         underline: false,
         strikethrough: false,
         inverse: false,
+        overline: false,
+        framed: false,
+        encircled: false,
     };
 
     grid.set_style(bold_style);
@@ -328,8 +331,9 @@ fn regression_wrapping_at_edge() {
     }
 
     let (x, y) = grid.cursor_pos();
-    assert_eq!(x, 5, "Cursor should be at column 5");
+    assert_eq!(x, 4, "Cursor should stay on the last column");
     assert_eq!(y, 0, "Should still be on first line");
+    assert!(grid.wrap_pending(), "Wrap should be pending");
 
     // Next character should wrap
     grid.put_char('Y');
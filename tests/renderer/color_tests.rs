@@ -0,0 +1,26 @@
+use titi::config::ColorScheme;
+use titi::renderer::TextRenderer;
+use titi::terminal::Color;
+
+#[test]
+fn test_bold_red_is_bright_when_enabled() {
+    let colors = ColorScheme::default();
+
+    let on = TextRenderer::fg_color_to_rgba(&colors, &Color::Red, true, true);
+    let off = TextRenderer::fg_color_to_rgba(&colors, &Color::Red, true, false);
+
+    assert_eq!(on, colors.bright_red);
+    assert_eq!(off, colors.red);
+}
+
+#[test]
+fn test_bold_is_bright_leaves_other_colors_alone() {
+    let colors = ColorScheme::default();
+
+    assert_eq!(TextRenderer::fg_color_to_rgba(&colors, &Color::Red, false, true), colors.red);
+    assert_eq!(TextRenderer::fg_color_to_rgba(&colors, &Color::Default, true, true), colors.foreground);
+    assert_eq!(
+        TextRenderer::fg_color_to_rgba(&colors, &Color::Rgb(255, 0, 0), true, true),
+        [1.0, 0.0, 0.0, 1.0]
+    );
+}
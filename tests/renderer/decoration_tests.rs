@@ -0,0 +1,75 @@
+use titi::config::ColorScheme;
+use titi::renderer::TextRenderer;
+use titi::terminal::{CellStyle, Color, CursorShape};
+
+#[test]
+fn test_overline_quad_sits_at_top_of_cell() {
+    let style = CellStyle {
+        overline: true,
+        ..CellStyle::default()
+    };
+
+    let rects = TextRenderer::decoration_rects(&style, 10.0, 20.0, 8.0, 16.0);
+
+    assert_eq!(rects, vec![[10.0, 20.0, 8.0, 1.0]]);
+}
+
+#[test]
+fn test_decoration_quads_follow_style() {
+    assert!(TextRenderer::decoration_rects(&CellStyle::default(), 0.0, 0.0, 8.0, 16.0).is_empty());
+
+    let style = CellStyle {
+        underline: true,
+        strikethrough: true,
+        overline: true,
+        framed: true,
+        ..CellStyle::default()
+    };
+    let rects = TextRenderer::decoration_rects(&style, 0.0, 0.0, 8.0, 16.0);
+
+    // Overline, strikethrough and underline from top to bottom; framed isn't drawn
+    assert_eq!(rects.len(), 3);
+    assert!(rects.windows(2).all(|pair| pair[0][1] < pair[1][1]));
+    assert!(rects.iter().all(|rect| rect[1] + rect[3] <= 16.0));
+}
+
+#[test]
+fn test_cursor_quads_follow_shape() {
+    assert_eq!(TextRenderer::cursor_rect(CursorShape::Block, 16.0, 32.0, 8.0, 16.0), [16.0, 32.0, 8.0, 16.0]);
+    assert_eq!(TextRenderer::cursor_rect(CursorShape::Underline, 16.0, 32.0, 8.0, 16.0), [16.0, 46.0, 8.0, 2.0]);
+    assert_eq!(TextRenderer::cursor_rect(CursorShape::Bar, 16.0, 32.0, 8.0, 16.0), [16.0, 32.0, 1.0, 16.0]);
+}
+
+#[test]
+fn test_cursor_colors_reverse_inverts_cell() {
+    let colors = ColorScheme::default();
+    let style = CellStyle {
+        fg: Color::Red,
+        bg: Color::Blue,
+        ..CellStyle::default()
+    };
+
+    assert_eq!(TextRenderer::cursor_colors(&colors, true, &style, false), (colors.red, colors.blue));
+
+    // Default colors swap too
+    let (cursor, glyph) = TextRenderer::cursor_colors(&colors, true, &CellStyle::default(), false);
+    assert_eq!((cursor, glyph), (colors.foreground, colors.background));
+}
+
+#[test]
+fn test_cursor_colors_use_configured_color() {
+    let colors = ColorScheme {
+        cursor: Some([1.0, 0.5, 0.0, 1.0]),
+        ..ColorScheme::default()
+    };
+    let style = CellStyle {
+        fg: Color::Red,
+        ..CellStyle::default()
+    };
+
+    // The glyph over the block is drawn in the background so it stays legible
+    assert_eq!(
+        TextRenderer::cursor_colors(&colors, false, &style, false),
+        ([1.0, 0.5, 0.0, 1.0], colors.background)
+    );
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+use titi::metrics::{FrameTimer, MetricsCollector};
+use titi::terminal::Grid;
+
+/// Stand-in for the per-pane render work in `Renderer::render_panes`: walk
+/// every visible cell the way vertex generation does
+fn render_pane_stub(grid: &Grid) -> usize {
+    let (cols, rows) = grid.size();
+    let mut glyphs = 0;
+    for y in 0..rows {
+        for x in 0..cols {
+            if grid.get_cell(x, y).is_some_and(|cell| cell.c != ' ') {
+                glyphs += 1;
+            }
+        }
+    }
+    glyphs
+}
+
+#[test]
+fn test_render_cycles_record_frame_and_pane_timings() {
+    let metrics = MetricsCollector::new();
+    let grids = [Grid::new(80, 24), Grid::new(120, 40), Grid::new(40, 10)];
+
+    assert!(metrics.last_frame_time().is_none());
+    assert!(metrics.p99_frame_time().is_none());
+
+    for _ in 0..10 {
+        let mut timer = FrameTimer::start();
+        for (pane_id, grid) in grids.iter().enumerate() {
+            timer.time_pane(pane_id, || render_pane_stub(grid));
+        }
+        timer.finish(&metrics);
+    }
+
+    let perf = metrics.get_performance_metrics();
+    assert!(perf.fps > 0.0);
+    assert!(metrics.last_frame_time().is_some());
+
+    let panes = metrics.get_pane_render_times();
+    assert_eq!(panes.len(), grids.len());
+    for timing in panes.values() {
+        assert_eq!(timing.samples, 10);
+    }
+}
+
+#[test]
+fn test_p99_frame_time_tracks_slow_frames() {
+    let metrics = MetricsCollector::new();
+
+    for _ in 0..99 {
+        metrics.record_frame(Duration::from_millis(5));
+    }
+    metrics.record_frame(Duration::from_millis(50));
+
+    assert_eq!(metrics.last_frame_time(), Some(Duration::from_millis(50)));
+    assert_eq!(metrics.p99_frame_time(), Some(Duration::from_millis(5)));
+
+    metrics.record_frame(Duration::from_millis(50));
+    assert_eq!(metrics.p99_frame_time(), Some(Duration::from_millis(50)));
+}
@@ -0,0 +1,129 @@
+use titi::renderer::Renderer;
+use titi::terminal::{Grid, TerminalParser};
+use titi::config::Padding;
+use titi::Config;
+use std::sync::{Arc, Mutex};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 64;
+
+/// Offscreen renderer, or `None` on machines with no usable adapter (not
+/// even a software one), where there's nothing to test
+fn offscreen_renderer(config: &Config) -> Option<Renderer> {
+    match pollster::block_on(Renderer::new_offscreen(WIDTH, HEIGHT, config)) {
+        Ok(renderer) => Some(renderer),
+        Err(e) => {
+            eprintln!("skipping offscreen rendering test: {}", e);
+            None
+        }
+    }
+}
+
+fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+    let offset = ((y * WIDTH + x) * 4) as usize;
+    pixels[offset..offset + 4].try_into().unwrap()
+}
+
+#[test]
+fn test_offscreen_render_draws_glyphs() {
+    let config = Config::default();
+    let Some(mut renderer) = offscreen_renderer(&config) else {
+        return;
+    };
+    let (cell_width, cell_height) = renderer.cell_dimensions();
+
+    let grid = Arc::new(Mutex::new(Grid::new(8, 2)));
+    let mut parser = TerminalParser::new(grid.clone());
+    parser.parse(b"WM\r\n");
+
+    let pixels = renderer.render_to_buffer(&grid).unwrap();
+    assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+
+    // The bottom-right corner is untouched background
+    let background = pixel(&pixels, WIDTH - 1, HEIGHT - 1);
+
+    // Something other than background is drawn inside the first two cells
+    let glyph_area = (0..cell_height as u32)
+        .flat_map(|y| (0..(2.0 * cell_width) as u32).map(move |x| (x, y)));
+    assert!(glyph_area.into_iter().any(|(x, y)| pixel(&pixels, x, y) != background));
+}
+
+#[test]
+fn test_read_pixels_of_cleared_frame_is_uniform() {
+    let config = Config::default();
+    let Some(mut renderer) = offscreen_renderer(&config) else {
+        return;
+    };
+
+    // Only the cursor is drawn, in the top-left cell; the bottom half is all clear color
+    let grid = Arc::new(Mutex::new(Grid::new(8, 2)));
+    let pixels = renderer.render_to_buffer(&grid).unwrap();
+    let background = pixel(&pixels, 0, HEIGHT - 1);
+
+    assert!(pixels.chunks(4).skip((WIDTH * HEIGHT / 2) as usize).all(|p| p == background));
+}
+
+#[test]
+fn test_hidden_cursor_is_not_drawn() {
+    let config = Config::default();
+    let Some(mut renderer) = offscreen_renderer(&config) else {
+        return;
+    };
+    let (cell_width, cell_height) = renderer.cell_dimensions();
+    let center = ((cell_width / 2.0) as u32, (cell_height / 2.0) as u32);
+
+    // An empty grid: the block cursor in the top-left cell is all there is
+    let grid = Arc::new(Mutex::new(Grid::new(8, 2)));
+    let shown = renderer.render_to_buffer(&grid).unwrap();
+    let background = pixel(&shown, WIDTH - 1, HEIGHT - 1);
+    assert_ne!(pixel(&shown, center.0, center.1), background);
+
+    renderer.set_cursor_visible(false);
+    let hidden = renderer.render_to_buffer(&grid).unwrap();
+    assert!(hidden.chunks(4).all(|p| p == background));
+}
+
+#[test]
+fn test_padding_offsets_first_cell() {
+    let mut config = Config::default();
+    config.window.padding = Padding { top: 10.0, right: 10.0, bottom: 10.0, left: 10.0 };
+    let Some(mut renderer) = offscreen_renderer(&config) else {
+        return;
+    };
+    let (cell_width, cell_height) = renderer.cell_dimensions();
+
+    // The block cursor marks the first cell, which now starts at (10, 10)
+    let grid = Arc::new(Mutex::new(Grid::new(8, 2)));
+    let pixels = renderer.render_to_buffer(&grid).unwrap();
+    let background = pixel(&pixels, WIDTH - 1, HEIGHT - 1);
+
+    assert_eq!(pixel(&pixels, 9, 9), background);
+    assert_ne!(pixel(&pixels, 10, 10), background);
+    // Cell sizes are fractional, so skip the partly covered edge pixels
+    let (right, bottom) = (10.0 + cell_width, 10.0 + cell_height);
+    assert_ne!(pixel(&pixels, right as u32 - 1, bottom as u32 - 1), background);
+    assert_eq!(pixel(&pixels, right.ceil() as u32, 10), background);
+    assert_eq!(pixel(&pixels, 10, bottom.ceil() as u32), background);
+}
+
+#[test]
+fn test_double_width_line_doubles_cells() {
+    let config = Config::default();
+    let Some(mut renderer) = offscreen_renderer(&config) else {
+        return;
+    };
+    let (cell_width, cell_height) = renderer.cell_dimensions();
+    let (x, y) = ((cell_width * 1.5) as u32, (cell_height / 2.0) as u32);
+
+    // The block cursor fills the first cell, which covers two columns once
+    // the line is double width
+    let grid = Arc::new(Mutex::new(Grid::new(8, 2)));
+    let pixels = renderer.render_to_buffer(&grid).unwrap();
+    let background = pixel(&pixels, WIDTH - 1, HEIGHT - 1);
+    assert_eq!(pixel(&pixels, x, y), background);
+
+    TerminalParser::new(grid.clone()).parse(b"\x1b#6");
+    let pixels = renderer.render_to_buffer(&grid).unwrap();
+    assert_ne!(pixel(&pixels, x, y), background);
+    assert_eq!(pixel(&pixels, (cell_width * 2.0).ceil() as u32 + 1, y), background);
+}
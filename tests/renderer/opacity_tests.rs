@@ -0,0 +1,51 @@
+use titi::renderer::{choose_alpha_mode, Renderer};
+use titi::terminal::Grid;
+use titi::Config;
+use std::sync::{Arc, Mutex};
+use wgpu::CompositeAlphaMode::{Inherit, Opaque, PostMultiplied, PreMultiplied};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 64;
+
+/// Offscreen renderer, or `None` on machines with no usable adapter
+fn offscreen_renderer(config: &Config) -> Option<Renderer> {
+    match pollster::block_on(Renderer::new_offscreen(WIDTH, HEIGHT, config)) {
+        Ok(renderer) => Some(renderer),
+        Err(e) => {
+            eprintln!("skipping offscreen rendering test: {}", e);
+            None
+        }
+    }
+}
+
+#[test]
+fn test_clear_color_alpha_is_configured_opacity() {
+    let mut config = Config::default();
+    config.window.opacity = 0.5;
+    let Some(mut renderer) = offscreen_renderer(&config) else {
+        return;
+    };
+
+    assert_eq!(renderer.opacity(), 0.5);
+    assert_eq!(renderer.clear_color().a, 0.5);
+
+    // Drawn frames carry it too, in the untouched bottom-right corner
+    let grid = Arc::new(Mutex::new(Grid::new(8, 2)));
+    let pixels = renderer.render_to_buffer(&grid).unwrap();
+    let alpha = pixels[pixels.len() - 1];
+    assert!((127..=128).contains(&alpha), "alpha {}", alpha);
+
+    // Reloading the config applies a new opacity, clamped
+    config.window.opacity = 1.5;
+    renderer.apply_config(&config);
+    assert_eq!(renderer.clear_color().a, 1.0);
+}
+
+#[test]
+fn test_alpha_mode_falls_back_to_opaque() {
+    assert_eq!(choose_alpha_mode(&[Opaque, PreMultiplied], true), PreMultiplied);
+    assert_eq!(choose_alpha_mode(&[Opaque, PostMultiplied], true), PostMultiplied);
+    assert_eq!(choose_alpha_mode(&[Opaque], true), Opaque);
+    assert_eq!(choose_alpha_mode(&[PreMultiplied, Opaque], false), Opaque);
+    assert_eq!(choose_alpha_mode(&[Inherit], true), Inherit);
+}
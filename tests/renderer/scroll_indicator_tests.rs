@@ -0,0 +1,42 @@
+use titi::config::ScrollIndicator;
+use titi::renderer::TextRenderer;
+
+const VIEWPORT: (u32, u32, u32, u32) = (100, 50, 640, 480);
+const CELL: (f32, f32) = (8.0, 16.0);
+
+#[test]
+fn test_no_indicator_on_live_screen() {
+    for style in [ScrollIndicator::Scrollbar, ScrollIndicator::Position, ScrollIndicator::Off] {
+        assert!(TextRenderer::scroll_indicator_rects(style, VIEWPORT, CELL, 0, 2000, 24).is_empty());
+    }
+}
+
+#[test]
+fn test_scrollbar_tracks_position_in_history() {
+    // Scrolled all the way back, the thumb sits at the top of the track
+    let rects = TextRenderer::scroll_indicator_rects(ScrollIndicator::Scrollbar, VIEWPORT, CELL, 2000, 2000, 24);
+    let [track, thumb] = [rects[0], rects[1]];
+    assert_eq!(track, [734.0, 50.0, 6.0, 480.0]);
+    assert_eq!(thumb[1], 50.0);
+    assert!(thumb[3] >= 8.0 && thumb[3] < 480.0);
+
+    // One line back, it nearly reaches the bottom
+    let rects = TextRenderer::scroll_indicator_rects(ScrollIndicator::Scrollbar, VIEWPORT, CELL, 1, 2000, 24);
+    let thumb = rects[1];
+    assert_eq!(thumb[0], track[0]);
+    assert!(thumb[1] + thumb[3] <= 530.0 && thumb[1] + thumb[3] > 529.0);
+}
+
+#[test]
+fn test_position_label_in_top_right_corner() {
+    assert_eq!(TextRenderer::scroll_position_label(120, 2000), "120/2000");
+
+    let rects = TextRenderer::scroll_indicator_rects(ScrollIndicator::Position, VIEWPORT, CELL, 120, 2000, 24);
+    // Eight characters plus half a cell either side
+    assert_eq!(rects, vec![[668.0, 50.0, 72.0, 16.0]]);
+}
+
+#[test]
+fn test_indicator_can_be_turned_off() {
+    assert!(TextRenderer::scroll_indicator_rects(ScrollIndicator::Off, VIEWPORT, CELL, 120, 2000, 24).is_empty());
+}
@@ -0,0 +1,51 @@
+use std::cell::Cell;
+use titi::renderer::retry_after_reconfigure;
+use wgpu::SurfaceError;
+
+/// Stand-in for `Surface::get_current_texture`, failing with each of
+/// `errors` in turn and then succeeding
+fn stub_surface(errors: Vec<SurfaceError>) -> impl FnMut() -> Result<&'static str, SurfaceError> {
+    let mut errors = errors.into_iter();
+    move || errors.next().map_or(Ok("frame"), Err)
+}
+
+#[test]
+fn test_lost_surface_is_reconfigured_and_retried() {
+    for error in [SurfaceError::Lost, SurfaceError::Outdated] {
+        let reconfigured = Cell::new(0);
+        let result = retry_after_reconfigure(stub_surface(vec![error]), || reconfigured.set(reconfigured.get() + 1));
+
+        assert_eq!(result.unwrap(), "frame");
+        assert_eq!(reconfigured.get(), 1);
+    }
+}
+
+#[test]
+fn test_surface_is_retried_only_once() {
+    let reconfigured = Cell::new(0);
+    let result = retry_after_reconfigure(
+        stub_surface(vec![SurfaceError::Lost, SurfaceError::Lost]),
+        || reconfigured.set(reconfigured.get() + 1),
+    );
+
+    assert!(matches!(result, Err(SurfaceError::Lost)));
+    assert_eq!(reconfigured.get(), 1);
+}
+
+#[test]
+fn test_out_of_memory_is_not_retried() {
+    let reconfigured = Cell::new(false);
+    let result = retry_after_reconfigure(stub_surface(vec![SurfaceError::OutOfMemory]), || reconfigured.set(true));
+
+    assert!(matches!(result, Err(SurfaceError::OutOfMemory)));
+    assert!(!reconfigured.get());
+}
+
+#[test]
+fn test_healthy_surface_is_left_alone() {
+    let reconfigured = Cell::new(false);
+    let result = retry_after_reconfigure(stub_surface(vec![]), || reconfigured.set(true));
+
+    assert_eq!(result.unwrap(), "frame");
+    assert!(!reconfigured.get());
+}
@@ -0,0 +1,21 @@
+use titi::renderer::TextRenderer;
+
+#[test]
+fn test_title_bar_shrinks_pane_viewport() {
+    let bounds = (100, 50, 640, 400);
+
+    // The terminal starts below the bar and loses its height
+    assert_eq!(TextRenderer::pane_content_viewport(bounds, 18), (100, 68, 640, 382));
+}
+
+#[test]
+fn test_viewport_unchanged_without_title_bar() {
+    let bounds = (0, 0, 800, 600);
+    assert_eq!(TextRenderer::pane_content_viewport(bounds, 0), bounds);
+}
+
+#[test]
+fn test_title_bar_taller_than_pane() {
+    // A sliver of a pane is all title bar, never underflowing
+    assert_eq!(TextRenderer::pane_content_viewport((0, 10, 200, 12), 18), (0, 22, 200, 0));
+}
@@ -0,0 +1,76 @@
+use titi::renderer::{GlyphAtlas, Renderer, TextRenderer, MAX_FONT_SIZE, MIN_FONT_SIZE};
+use titi::Config;
+
+/// Create a wgpu device without a window, or `None` when no adapter (not even
+/// a software fallback) is available on this machine
+fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::LowPower,
+        force_fallback_adapter: false,
+        compatible_surface: None,
+    }))?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+}
+
+#[test]
+fn test_cell_size_scales_with_font_size() {
+    let (small_w, small_h) = TextRenderer::cell_size_for_font(12.0);
+    let (large_w, large_h) = TextRenderer::cell_size_for_font(16.0);
+
+    assert!(large_w > small_w);
+    assert!(large_h > small_h);
+}
+
+#[test]
+fn test_zoom_is_clamped_to_font_size_range() {
+    let config = Config::default();
+    let mut renderer = match pollster::block_on(Renderer::new_offscreen(160, 64, &config)) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            println!("No GPU adapter available, skipping: {}", e);
+            return;
+        }
+    };
+    let default_cell = renderer.cell_dimensions();
+
+    // Zooming in far past the limit stops at the largest size
+    assert!(renderer.zoom(1000.0));
+    assert_eq!(renderer.font_size(), MAX_FONT_SIZE);
+    assert_eq!(renderer.cell_dimensions(), TextRenderer::cell_size_for_font(MAX_FONT_SIZE));
+    assert!(renderer.cell_dimensions().0 > default_cell.0);
+    assert!(!renderer.zoom(1.0), "Zooming in at the limit must not change the cell size");
+
+    // And likewise when zooming out
+    assert!(renderer.zoom(-1000.0));
+    assert_eq!(renderer.font_size(), MIN_FONT_SIZE);
+    assert_eq!(renderer.cell_dimensions(), TextRenderer::cell_size_for_font(MIN_FONT_SIZE));
+    assert!(renderer.cell_dimensions().0 < default_cell.0);
+    assert!(!renderer.zoom(-1.0));
+
+    assert!(renderer.reset_zoom());
+    assert_eq!(renderer.font_size(), config.font.size);
+    assert_eq!(renderer.cell_dimensions(), default_cell);
+}
+
+#[test]
+fn test_zoom_invalidates_glyph_atlas() {
+    let Some((device, queue)) = headless_device() else {
+        println!("No GPU adapter available, skipping");
+        return;
+    };
+
+    let mut atlas = GlyphAtlas::new(&device, 14.0);
+    atlas.get_or_cache_glyph(&queue, 'A', false, false);
+    atlas.get_or_cache_glyph(&queue, 'B', false, false);
+    assert_eq!(atlas.cached_glyph_count(), 2);
+
+    atlas.set_font_size(18.0);
+
+    assert_eq!(atlas.font_size(), 18.0);
+    assert_eq!(atlas.cached_glyph_count(), 0, "Zooming must drop glyphs cached at the old size");
+
+    // Glyphs are re-rasterized at the new size on demand
+    assert!(atlas.get_or_cache_glyph(&queue, 'A', false, false).is_some());
+    assert_eq!(atlas.cached_glyph_count(), 1);
+}
@@ -60,10 +60,8 @@ fn test_stress_pane_lifecycle() {
         let id = pane_manager.create_pane(80, 24).expect("Failed to create pane");
 
         // Write some data
-        if let Some(pane) = pane_manager.get_pane_mut(id) {
-            let msg = format!("Cycle {}\n", i);
-            pane.terminal.write(msg.as_bytes()).expect("Write failed");
-        }
+        let msg = format!("Cycle {}\n", i);
+        pane_manager.write_to_pane(id, msg.as_bytes()).expect("Write failed");
 
         // Destroy pane
         pane_manager.close_pane(id);
@@ -153,10 +153,8 @@ fn stress_pane_manager(operations: usize) {
             pane_ids.push(id);
 
             // Write some data
-            if let Some(pane) = pane_manager.get_pane_mut(id) {
-                let data = format!("Test data {}\n", i);
-                let _ = pane.terminal.write(data.as_bytes());
-            }
+            let data = format!("Test data {}\n", i);
+            let _ = pane_manager.write_to_pane(id, data.as_bytes());
         }
 
         // Periodically destroy panes
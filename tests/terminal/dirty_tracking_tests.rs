@@ -222,6 +222,48 @@ fn test_dirty_tracking_with_wrapping() {
     assert!(grid.dirty_cells().contains(&(0, 1)), "Should wrap to next line");
 }
 
+#[test]
+fn test_region_scroll_marks_only_region_rows() {
+    let mut grid = Grid::new(10, 6);
+    grid.set_scroll_region(1, 3);
+    grid.clear_dirty();
+
+    grid.scroll_up(1);
+
+    assert!(!grid.is_all_dirty(), "Rows outside the region did not move");
+    assert_eq!(grid.dirty_cells().len(), 30, "Should mark the three region rows");
+    for row in 1..=3 {
+        assert!(grid.dirty_cells().contains(&(0, row)), "Row {} should be dirty", row);
+    }
+    assert!(!grid.dirty_cells().contains(&(0, 0)));
+    assert!(!grid.dirty_cells().contains(&(0, 4)));
+
+    grid.clear_dirty();
+    grid.scroll_down(1);
+    assert_eq!(grid.dirty_cells().len(), 30, "Reverse scroll should mark the region rows");
+}
+
+#[test]
+fn test_insert_and_delete_lines_mark_shifted_rows() {
+    let mut grid = Grid::new(10, 6);
+    grid.set_cursor(0, 2);
+    grid.clear_dirty();
+
+    grid.insert_lines(1);
+    assert!(!grid.is_all_dirty());
+    assert_eq!(grid.dirty_cells().len(), 40, "Rows 2..6 shift down");
+    assert!(!grid.dirty_cells().contains(&(0, 1)));
+
+    grid.clear_dirty();
+    grid.delete_lines(2);
+    assert_eq!(grid.dirty_cells().len(), 40, "Rows 2..6 shift up");
+
+    grid.clear_dirty();
+    grid.set_cursor(0, 0);
+    grid.scroll_down(1);
+    assert!(grid.is_all_dirty(), "Scrolling the whole screen should mark all dirty");
+}
+
 #[test]
 fn test_dirty_tracking_with_scrolling_content() {
     let mut grid = Grid::new(5, 3);
@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+use titi::config::ShellConfig;
+use titi::terminal::{Terminal, TerminalEvent};
+
+#[cfg(unix)]
+fn cat_terminal() -> Terminal {
+    let shell = ShellConfig {
+        program: Some("/bin/cat".to_string()),
+        ..Default::default()
+    };
+    Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command")
+}
+
+#[test]
+#[cfg(unix)]
+fn test_subscriber_receives_output() {
+    let mut terminal = cat_terminal();
+    let events = terminal.subscribe();
+
+    terminal.process_output(b"hello\r\n");
+
+    match events.recv_timeout(Duration::from_secs(1)) {
+        Ok(TerminalEvent::Output(data)) => assert_eq!(data, b"hello\r\n"),
+        other => panic!("Expected an Output event, got {:?}", other),
+    }
+    assert!(terminal.grid().lock().unwrap().visible_lines().next().unwrap().starts_with("hello"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_every_subscriber_sees_events_in_order() {
+    let mut terminal = cat_terminal();
+    let first = terminal.subscribe();
+    terminal.process_output(b"early");
+
+    // A late subscriber only sees what happens after it subscribed
+    let second = terminal.subscribe();
+    terminal.resize(100, 30).expect("Resize failed");
+    terminal.process_output(b"late");
+
+    let collect = |events: &crossbeam_channel::Receiver<TerminalEvent>| events.try_iter().collect::<Vec<_>>();
+    assert_eq!(
+        collect(&first),
+        vec![
+            TerminalEvent::Output(b"early".to_vec()),
+            TerminalEvent::Resize(100, 30),
+            TerminalEvent::Output(b"late".to_vec()),
+        ]
+    );
+    assert_eq!(collect(&second).len(), 2);
+
+    // Dropped subscribers don't stop delivery to the rest
+    drop(second);
+    terminal.process_output(b"more");
+    assert_eq!(collect(&first).len(), 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_exit_event_after_output_is_read() {
+    let shell = ShellConfig {
+        program: Some("/bin/echo".to_string()),
+        args: vec!["bye".to_string()],
+        ..Default::default()
+    };
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+    let events = terminal.subscribe();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut exited = false;
+    while !exited && Instant::now() < deadline {
+        match terminal.read() {
+            Ok(Some(data)) => terminal.process_output(&data),
+            _ => std::thread::sleep(Duration::from_millis(10)),
+        }
+        exited = events.try_iter().any(|event| matches!(event, TerminalEvent::Exit));
+    }
+
+    assert!(exited, "Exit should be emitted once the child is gone");
+    assert!(terminal.grid().lock().unwrap().visible_lines().any(|line| line.starts_with("bye")));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_device_attributes_answered_on_pty() {
+    let mut terminal = cat_terminal();
+    terminal.process_output(b"\x1b[c");
+
+    // The reply reaches cat's input, which the tty echoes back
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut echoed = Vec::new();
+    while !String::from_utf8_lossy(&echoed).contains("[?62;1;6c") && Instant::now() < deadline {
+        match terminal.read() {
+            Ok(Some(data)) => echoed.extend_from_slice(&data),
+            _ => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    assert!(
+        String::from_utf8_lossy(&echoed).contains("[?62;1;6c"),
+        "DA reply not written to the PTY: {:?}",
+        String::from_utf8_lossy(&echoed)
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_parse_budget_spreads_output_over_passes() {
+    let mut terminal = cat_terminal();
+    terminal.set_parse_budget(Some(100));
+    let events = terminal.subscribe();
+
+    // 24 numbered lines, the last one unterminated so it stays on screen
+    let output: String = (0..24).map(|n| format!("{:03}{}\r\n", n, "x".repeat(60))).collect();
+    let output = output.trim_end();
+    terminal.process_output(output.as_bytes());
+    assert_eq!(terminal.pending_output(), output.len() - 100);
+
+    let mut passes = 1;
+    while terminal.parse_pending() {
+        passes += 1;
+    }
+    assert_eq!(passes, output.len().div_ceil(100));
+    assert_eq!(terminal.pending_output(), 0);
+
+    // Every byte reached the grid and subscribers, in order
+    let emitted: Vec<u8> = events
+        .try_iter()
+        .flat_map(|event| match event {
+            TerminalEvent::Output(data) => data,
+            other => panic!("Unexpected event {:?}", other),
+        })
+        .collect();
+    assert_eq!(emitted, output.as_bytes());
+    let grid = terminal.grid();
+    let last = grid.lock().unwrap().visible_lines().last().unwrap();
+    assert!(last.starts_with("023xxx"), "{:?}", last);
+}
@@ -1,4 +1,5 @@
-use titi::terminal::{CellStyle, Color, Grid};
+use titi::terminal::{CaptureFormat, CaptureMode, Cell, CellChange, CellStyle, Color, Grid, Selection, TerminalParser, DEFAULT_WORD_SEPARATORS};
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn test_grid_new_initializes_with_empty_cells() {
@@ -46,9 +47,11 @@ fn test_grid_cursor_wraps_at_end_of_line() {
         grid.put_char('X');
     }
 
+    // The cursor stays on the last column with a wrap pending
     let (x, y) = grid.cursor_pos();
-    assert_eq!(x, 5);
+    assert_eq!(x, 4);
     assert_eq!(y, 0);
+    assert!(grid.wrap_pending());
 
     // Next character should wrap
     grid.put_char('Y');
@@ -58,6 +61,37 @@ fn test_grid_cursor_wraps_at_end_of_line() {
     assert_eq!(grid.get_cell(0, 1).unwrap().c, 'Y');
 }
 
+#[test]
+fn test_grid_wrap_is_deferred_until_next_printable() {
+    let mut grid = Grid::new(5, 3);
+    grid.bulk_write_text("ABCDE");
+
+    // A carriage return cancels the pending wrap
+    grid.carriage_return();
+    assert_eq!(grid.cursor_pos(), (0, 0));
+    grid.put_char('Z');
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'Z');
+    assert_eq!(grid.get_cell(0, 1).unwrap().c, ' ');
+
+    // A newline after a full line moves down exactly one row
+    grid.bulk_write_text("BCDE");
+    grid.newline();
+    assert_eq!(grid.cursor_pos(), (0, 1));
+}
+
+#[test]
+fn test_grid_no_wrap_when_autowrap_disabled() {
+    let mut grid = Grid::new(5, 3);
+    grid.set_autowrap(false);
+
+    grid.bulk_write_text("ABCDEFG");
+    grid.put_char('H');
+
+    assert_eq!(grid.row_text(0), "ABCDH");
+    assert_eq!(grid.row_text(1), "");
+    assert_eq!(grid.cursor_pos(), (4, 0));
+}
+
 #[test]
 fn test_grid_newline_moves_cursor_down() {
     let mut grid = Grid::new(80, 24);
@@ -289,10 +323,16 @@ fn test_grid_save_and_restore_cursor() {
 fn test_grid_set_scroll_region() {
     let mut grid = Grid::new(80, 24);
 
-    grid.set_scroll_region(5, 15);
+    assert_eq!(grid.scroll_region(), (0, 23));
+    assert!(grid.set_scroll_region(5, 15));
+    assert_eq!(grid.scroll_region(), (5, 15));
+
+    assert!(!grid.set_scroll_region(15, 5));
+    assert!(!grid.set_scroll_region(30, 40));
+    assert_eq!(grid.scroll_region(), (5, 15));
 
-    // Scrolling should only affect the region
-    // This is tested indirectly through scroll operations
+    assert!(grid.set_scroll_region(20, 40));
+    assert_eq!(grid.scroll_region(), (20, 23));
 }
 
 #[test]
@@ -356,3 +396,524 @@ fn test_grid_scrolling_with_region() {
     assert_eq!(grid.get_cell(0, 0).unwrap().c, 'X');
     assert_eq!(grid.get_cell(0, 9).unwrap().c, 'Y');
 }
+
+#[test]
+fn test_grid_scrollback_is_clamped_to_configured_limit() {
+    let mut grid = Grid::with_scrollback(10, 3, 5);
+
+    for _ in 0..20 {
+        grid.put_char('X');
+        grid.newline();
+    }
+
+    assert_eq!(grid.max_scrollback(), 5);
+    assert_eq!(grid.scrollback_len(), 5);
+}
+
+#[test]
+fn test_grid_zero_scrollback_disables_history() {
+    let mut grid = Grid::with_scrollback(10, 3, 0);
+
+    for _ in 0..10 {
+        grid.put_char('X');
+        grid.newline();
+    }
+
+    assert_eq!(grid.scrollback_len(), 0);
+    grid.scroll_back_up(5);
+    assert!(grid.is_at_bottom());
+}
+
+#[test]
+fn test_grid_clear_scrollback() {
+    let mut grid = Grid::new(10, 3);
+
+    for _ in 0..10 {
+        grid.put_char('X');
+        grid.newline();
+    }
+    grid.scroll_back_up(3);
+    assert!(grid.scrollback_len() > 0);
+
+    grid.clear_scrollback();
+
+    assert_eq!(grid.scrollback_len(), 0);
+    assert!(grid.is_at_bottom());
+}
+
+#[test]
+fn test_grid_shrinking_scrollback_limit_drops_oldest_lines() {
+    let mut grid = Grid::new(10, 3);
+
+    for i in 0..10 {
+        grid.put_char(char::from(b'0' + i as u8));
+        grid.newline();
+    }
+    assert_eq!(grid.scrollback_len(), 8);
+
+    grid.set_max_scrollback(2);
+
+    assert_eq!(grid.scrollback_len(), 2);
+    // The newest history lines are kept
+    grid.scroll_back_up(2);
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, '6');
+}
+
+#[test]
+fn test_grid_visible_buffer_matches_cells() {
+    let mut grid = Grid::new(10, 3);
+    for i in 0..6 {
+        grid.put_char(char::from(b'0' + i as u8));
+        grid.newline();
+    }
+
+    let (cells, cols, rows) = grid.visible_buffer();
+    assert_eq!((cols, rows), (10, 3));
+    assert_eq!(cells.len(), cols * rows);
+    assert_eq!(cells[0], *grid.get_cell(0, 0).unwrap());
+    assert_eq!(cells[cols].c, '5');
+
+    // Scrolled back, the buffer shows the history like get_cell
+    grid.scroll_back_up(2);
+    let (cells, cols, rows) = grid.visible_buffer();
+    assert_eq!(cells.len(), cols * rows);
+    assert_eq!(cells[0], *grid.get_cell(0, 0).unwrap());
+    assert_eq!(cells[0].c, '2');
+}
+
+#[test]
+fn test_grid_capture_text_to_file() {
+    let mut grid = Grid::new(20, 5);
+    grid.bulk_write_text("$ echo hello");
+    grid.carriage_return();
+    grid.newline();
+    grid.bulk_write_text("hello");
+
+    let dir = std::env::temp_dir().join(format!("titi-capture-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("screen.txt");
+
+    grid.capture_to_file(&path, CaptureFormat::Text).unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+
+    // Trailing spaces and blank rows are dropped
+    assert_eq!(content, "$ echo hello\nhello\n");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_grid_capture_ansi_preserves_style() {
+    let mut grid = Grid::new(20, 2);
+    grid.bulk_write_text("ok ");
+    grid.set_style(CellStyle {
+        fg: Color::Red,
+        bold: true,
+        ..CellStyle::default()
+    });
+    grid.bulk_write_text("FAIL");
+    grid.set_style(CellStyle::default());
+
+    assert_eq!(grid.capture(CaptureMode::Visible, CaptureFormat::Ansi), "ok \x1b[1;31mFAIL\x1b[0m\n");
+    assert_eq!(grid.capture(CaptureMode::Visible, CaptureFormat::Text), "ok FAIL\n");
+}
+
+#[test]
+fn test_grid_capture_ansi_coalesces_runs() {
+    let mut grid = Grid::new(20, 2);
+    grid.set_style(CellStyle {
+        fg: Color::Red,
+        bold: true,
+        ..CellStyle::default()
+    });
+    grid.bulk_write_text("ERROR");
+    grid.set_style(CellStyle {
+        fg: Color::Red,
+        bold: true,
+        underline: true,
+        ..CellStyle::default()
+    });
+    grid.bulk_write_text("!");
+    grid.set_style(CellStyle {
+        fg: Color::Green,
+        ..CellStyle::default()
+    });
+    grid.bulk_write_text("ok");
+    grid.set_style(CellStyle::default());
+    grid.bulk_write_text(" done");
+
+    let captured = grid.capture_ansi(CaptureMode::Line);
+    assert!(captured.starts_with("\x1b[1;31mERROR"));
+    assert_eq!(captured, "\x1b[1;31mERROR\x1b[4m!\x1b[0;32mok\x1b[0m done\n");
+    assert_eq!(captured.matches("\x1b[").count(), 4);
+}
+
+#[test]
+fn test_grid_take_changes_returns_written_cells() {
+    let mut grid = Grid::new(20, 4);
+    // A new grid starts fully invalidated
+    assert_eq!(grid.take_changes().len(), 80);
+
+    grid.set_cursor(3, 2);
+    grid.put_char('o');
+    grid.put_char('k');
+
+    let changes = grid.take_changes();
+    assert_eq!(changes.len(), 2);
+    assert_eq!((changes[0].row, changes[0].col, changes[0].c), (2, 3, 'o'));
+    assert_eq!((changes[1].row, changes[1].col, changes[1].c), (2, 4, 'k'));
+    assert!(grid.take_changes().is_empty());
+}
+
+#[test]
+fn test_cell_changes_round_trip_through_parser() {
+    let mut source = Grid::new(20, 4);
+    source.take_changes();
+    source.set_cursor(1, 1);
+    source.set_style(CellStyle {
+        fg: Color::Red,
+        bold: true,
+        ..CellStyle::default()
+    });
+    source.bulk_write_text("a  %b");
+    source.set_style(CellStyle::default());
+    source.set_cursor(0, 3);
+    source.put_char('z');
+
+    let encoded = CellChange::encode(&source.take_changes());
+    assert!(!encoded.contains(char::is_whitespace));
+
+    let viewer = Arc::new(Mutex::new(Grid::new(20, 4)));
+    TerminalParser::new(viewer.clone()).parse(&CellChange::decode(&encoded));
+
+    let viewer = viewer.lock().unwrap();
+    assert_eq!(viewer.capture(CaptureMode::Visible, CaptureFormat::Ansi), source.capture(CaptureMode::Visible, CaptureFormat::Ansi));
+    assert_eq!(viewer.get_cell(3, 1).unwrap().style, source.get_cell(3, 1).unwrap().style);
+}
+
+fn write_lines(grid: &mut Grid, lines: &[&str]) {
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            grid.newline();
+        }
+        grid.bulk_write_text(line);
+    }
+}
+
+#[test]
+fn test_grid_capture_line() {
+    let mut grid = Grid::new(20, 4);
+    write_lines(&mut grid, &["first", "second", "third"]);
+    grid.set_cursor(3, 1);
+
+    assert_eq!(grid.capture(CaptureMode::Line, CaptureFormat::Text), "second\n");
+
+    grid.set_cursor(0, 3);
+    assert_eq!(grid.capture(CaptureMode::Line, CaptureFormat::Text), "\n");
+}
+
+#[test]
+fn test_grid_capture_region() {
+    let mut grid = Grid::new(10, 4);
+    write_lines(&mut grid, &["abcdefghij", "klmnopqrst", "uvwxyz0123", "456789ABCD"]);
+
+    let region = CaptureMode::Region { x: 2, y: 1, width: 3, height: 2 };
+    assert_eq!(grid.capture(region, CaptureFormat::Text), "mno\nwxy\n");
+
+    // Regions hanging off the edge are clipped to the screen
+    let region = CaptureMode::Region { x: 8, y: 3, width: 5, height: 5 };
+    assert_eq!(grid.capture(region, CaptureFormat::Text), "CD\n");
+
+    let region = CaptureMode::Region { x: 10, y: 0, width: 2, height: 1 };
+    assert_eq!(grid.capture(region, CaptureFormat::Text), "");
+}
+
+#[test]
+fn test_grid_capture_scrollback_and_full() {
+    let mut grid = Grid::new(10, 2);
+    write_lines(&mut grid, &["one", "two", "three", "four"]);
+
+    assert_eq!(grid.capture(CaptureMode::Visible, CaptureFormat::Text), "three\nfour\n");
+    assert_eq!(grid.capture(CaptureMode::Scrollback { lines: 1 }, CaptureFormat::Text), "two\n");
+    assert_eq!(grid.capture(CaptureMode::Full, CaptureFormat::Text), "one\ntwo\nthree\nfour\n");
+}
+
+#[test]
+fn test_grid_row_text_trims_trailing_spaces() {
+    let mut grid = Grid::new(80, 24);
+    grid.bulk_write_text("hi there   ");
+
+    assert_eq!(grid.row_text(0), "hi there");
+    assert_eq!(grid.row_text(1), "");
+}
+
+#[test]
+fn test_grid_visible_lines_covers_every_row() {
+    let mut grid = Grid::new(80, 24);
+    grid.bulk_write_text("first");
+
+    let lines: Vec<String> = grid.visible_lines().collect();
+    assert_eq!(lines.len(), 24);
+    assert_eq!(lines[0], "first");
+    assert!(lines[1..].iter().all(|line| line.is_empty()));
+}
+
+#[test]
+fn test_grid_styled_row_keeps_styles() {
+    let mut grid = Grid::new(4, 1);
+    grid.set_style(CellStyle {
+        bg: Color::Blue,
+        ..CellStyle::default()
+    });
+    grid.bulk_write_text("a ");
+
+    let row = grid.styled_row(0);
+    assert_eq!(row.len(), 4);
+    assert_eq!(row[0].0, 'a');
+    assert_eq!(row[1].1.bg, Color::Blue);
+    assert_eq!(row[3], (' ', CellStyle::default()));
+}
+
+#[test]
+fn test_word_at_stops_at_separators() {
+    let mut grid = Grid::new(40, 5);
+    grid.bulk_write_text("ls -la /tmp/foo(bar) x");
+
+    let word = |col| {
+        let span = grid.word_at(col, 0, DEFAULT_WORD_SEPARATORS).unwrap();
+        assert_eq!(span.start.1, 0);
+        assert_eq!(span.end.1, 0);
+        (span.start.0, span.end.0)
+    };
+
+    assert_eq!(word(0), (0, 1)); // "ls"
+    assert_eq!(word(4), (3, 5)); // "-la"
+    assert_eq!(word(9), (7, 14)); // "/tmp/foo" up to '('
+    assert_eq!(word(17), (16, 18)); // "bar" between the parens
+    assert_eq!(word(21), (21, 21)); // "x" at the end of the text
+    assert_eq!(word(15), (15, 15)); // a separator selects itself
+    assert_eq!(word(30), (30, 30)); // so does blank space
+
+    // Custom separators split on '/' as well
+    let span = grid.word_at(9, 0, " /(").unwrap();
+    assert_eq!((span.start.0, span.end.0), (8, 10)); // "foo"
+
+    assert_eq!(grid.word_at(40, 0, DEFAULT_WORD_SEPARATORS), None);
+}
+
+#[test]
+fn test_line_span_joins_wrapped_rows() {
+    let mut grid = Grid::new(10, 5);
+    grid.bulk_write_text("first");
+    grid.newline();
+    // 25 characters wrap across three rows
+    grid.bulk_write_text("abcdefghijklmnopqrstuvwxy");
+    grid.newline();
+    grid.bulk_write_text("last");
+
+    assert!(!grid.is_wrapped(0));
+    assert!(grid.is_wrapped(1));
+    assert!(grid.is_wrapped(2));
+    assert!(!grid.is_wrapped(3));
+
+    let expected = Selection { start: (0, 1), end: (9, 3) };
+    for row in 1..=3 {
+        assert_eq!(grid.line_span(row), Some(expected));
+    }
+    assert_eq!(grid.line_span(0), Some(Selection { start: (0, 0), end: (9, 0) }));
+    assert_eq!(grid.line_span(4), Some(Selection { start: (0, 4), end: (9, 4) }));
+
+    grid.set_selection(grid.line_span(2));
+    assert_eq!(grid.selected_text().as_deref(), Some("abcdefghijklmnopqrstuvwxy"));
+}
+
+#[test]
+fn test_output_over_selection_clears_it() {
+    let grid = Arc::new(Mutex::new(Grid::new(20, 5)));
+    let mut parser = TerminalParser::new(grid.clone());
+    parser.parse(b"first line\r\nsecond line\r\n");
+
+    let selection = grid.lock().unwrap().line_span(0);
+    grid.lock().unwrap().set_selection(selection);
+
+    // Output elsewhere leaves it alone
+    parser.parse(b"prompt$ ");
+    assert_eq!(grid.lock().unwrap().selected_text().as_deref(), Some("first line"));
+
+    // Overwriting the selected cells drops it
+    parser.parse(b"\x1b[1;3Hxx");
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.selection(), None);
+    assert_eq!(grid.selected_text(), None);
+}
+
+#[test]
+fn test_scrolling_clears_selection() {
+    let mut grid = Grid::new(10, 3);
+    grid.bulk_write_text("one");
+    grid.newline();
+    grid.bulk_write_text("two");
+
+    // Content scrolling under the selection moves the text it marked
+    grid.set_selection(grid.line_span(1));
+    grid.newline();
+    grid.newline();
+    assert_eq!(grid.selection(), None);
+
+    // So does scrolling the view into history
+    grid.set_selection(grid.line_span(0));
+    grid.scroll_back_up(1);
+    assert_eq!(grid.selection(), None);
+}
+
+#[test]
+fn test_wrap_flags_follow_scrolling() {
+    let mut grid = Grid::new(10, 3);
+    grid.bulk_write_text("abcdefghijklm");
+    grid.newline();
+    grid.bulk_write_text("x");
+    assert!(grid.is_wrapped(0));
+
+    // Scrolling pushes the wrapped row into history; its flag goes with it
+    grid.newline();
+    assert_eq!(grid.row_text(0), "klm");
+    assert!(!grid.is_wrapped(0));
+    assert_eq!(grid.line_span(0), Some(Selection { start: (0, 0), end: (9, 0) }));
+}
+
+#[test]
+fn test_detect_urls_across_wrapped_lines() {
+    let mut grid = Grid::new(20, 5);
+    // The URL starts on row 0 and wraps onto row 1
+    grid.bulk_write_text("see https://example.com/a/b/c.");
+    grid.newline();
+    grid.bulk_write_text("docs: (www.rust-lang.org) file:///tmp/x");
+
+    let urls = grid.detect_urls();
+    let found: Vec<&str> = urls.iter().map(|(_, url)| url.as_str()).collect();
+    assert_eq!(found, ["https://example.com/a/b/c", "www.rust-lang.org", "file:///tmp/x"]);
+
+    // 4 + 25 characters: from column 4 of row 0 to column 8 of row 1
+    assert_eq!(urls[0].0, Selection { start: (4, 0), end: (8, 1) });
+    assert!(urls[0].0.contains(2, 1));
+    assert!(!urls[0].0.contains(9, 1));
+}
+
+#[test]
+fn test_detect_urls_keeps_balanced_parens() {
+    let mut grid = Grid::new(80, 2);
+    grid.bulk_write_text("https://en.wikipedia.org/wiki/Rust_(language), then");
+
+    let urls = grid.detect_urls();
+    assert_eq!(urls.len(), 1);
+    assert_eq!(urls[0].1, "https://en.wikipedia.org/wiki/Rust_(language)");
+}
+
+#[test]
+fn test_alternate_screen_restores_normal_screen() {
+    let grid = Arc::new(Mutex::new(Grid::with_scrollback(10, 3, 100)));
+    let mut parser = TerminalParser::new(grid.clone());
+    parser.parse(b"shell\x1b[?1049h");
+
+    {
+        let g = grid.lock().unwrap();
+        assert!(g.alternate_screen());
+        assert_eq!(g.get_cell(0, 0).unwrap().c, ' ');
+    }
+
+    // Scrolling the alternate screen leaves no history
+    parser.parse(b"\x1b[Hvim\r\n\r\n\r\n\r\n");
+    assert_eq!(grid.lock().unwrap().scrollback_len(), 0);
+
+    parser.parse(b"\x1b[?1049l");
+    let g = grid.lock().unwrap();
+    assert!(!g.alternate_screen());
+    assert_eq!(g.get_cell(0, 0).unwrap().c, 's');
+    assert_eq!(g.cursor_pos(), (5, 0));
+}
+
+#[test]
+fn test_snapshot_restore_round_trip() {
+    let grid = Arc::new(Mutex::new(Grid::new(20, 5)));
+    let mut parser = TerminalParser::new(grid.clone());
+    parser.parse(b"\x1b[1;31mred\x1b[0m plain\r\nsecond\x1b[2;4r\x1b[?25l\x1b]2;title\x07");
+
+    let snapshot = grid.lock().unwrap().snapshot();
+    let original: Vec<Cell> = grid.lock().unwrap().cells().to_vec();
+    let cursor = grid.lock().unwrap().cursor_pos();
+
+    parser.parse(b"\x1b[2J\x1b[Hchanged\x1b[r\x1b[?25h\x1b]2;other\x07");
+    assert_ne!(grid.lock().unwrap().cells(), &original[..]);
+
+    let mut g = grid.lock().unwrap();
+    g.restore(&snapshot);
+    assert_eq!(g.cells(), &original[..]);
+    assert_eq!(g.cursor_pos(), cursor);
+    assert_eq!(g.get_cell(0, 0).unwrap().style.fg, Color::Red);
+    assert_eq!(g.scroll_region(), (1, 3));
+    assert!(!g.cursor_visible());
+    assert_eq!(g.title(), Some("title"));
+}
+
+#[test]
+fn test_snapshot_restore_size_and_scrollback() {
+    let mut grid = Grid::with_scrollback(10, 2, 100);
+    for c in ['a', 'b', 'c'] {
+        grid.put_char(c);
+        grid.newline();
+    }
+    let with_history = grid.snapshot_with_scrollback();
+    let without_history = grid.snapshot();
+    assert!(with_history.has_scrollback());
+    assert!(!without_history.has_scrollback());
+
+    // Snapshots survive serialization
+    let json = serde_json::to_string(&with_history).unwrap();
+    let with_history: titi::terminal::GridSnapshot = serde_json::from_str(&json).unwrap();
+
+    grid.clear_scrollback();
+    grid.resize(40, 10);
+    grid.restore(&without_history);
+    assert_eq!(grid.size(), (10, 2));
+    assert_eq!(grid.scrollback_len(), 0);
+
+    grid.restore(&with_history);
+    assert_eq!(grid.scrollback_len(), 2);
+    assert_eq!(grid.snapshot_with_scrollback(), with_history);
+}
+
+#[test]
+fn test_resize_keeps_rows_pushed_off_the_top_in_scrollback() {
+    let mut grid = Grid::new(10, 5);
+    for (row, c) in ['a', 'b', 'c', 'd', 'e'].into_iter().enumerate() {
+        grid.set_cursor(0, row);
+        grid.put_char(c);
+    }
+
+    // The cursor is on the last row, so the top two rows go to scrollback
+    grid.resize(10, 3);
+    assert_eq!(grid.scrollback_len(), 2);
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'c');
+    assert_eq!(grid.cursor_pos(), (1, 2));
+    grid.scroll_back_up(2);
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'a');
+    assert_eq!(grid.get_cell(0, 1).unwrap().c, 'b');
+    grid.scroll_to_bottom();
+
+    // Growing again pulls them back above the cursor
+    grid.resize(10, 5);
+    assert_eq!(grid.scrollback_len(), 0);
+    assert_eq!(grid.row_text(0), "a");
+    assert_eq!(grid.row_text(4), "e");
+    assert_eq!(grid.cursor_pos(), (1, 4));
+}
+
+#[test]
+fn test_resize_drops_blank_rows_below_the_cursor_first() {
+    let mut grid = Grid::new(10, 5);
+    grid.put_char('a');
+
+    grid.resize(10, 3);
+    assert_eq!(grid.scrollback_len(), 0);
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'a');
+}
@@ -0,0 +1,42 @@
+use titi::terminal::keys::{chord_sequence, key_sequence, KeyModifiers};
+use titi::terminal::Terminal;
+
+#[test]
+fn test_named_keys() {
+    assert_eq!(key_sequence("Up", KeyModifiers::NONE), Some(b"\x1b[A".to_vec()));
+    assert_eq!(key_sequence("enter", KeyModifiers::NONE), Some(b"\r".to_vec()));
+    assert_eq!(key_sequence("Tab", KeyModifiers::NONE), Some(b"\t".to_vec()));
+    assert_eq!(key_sequence("PageDown", KeyModifiers::NONE), Some(b"\x1b[6~".to_vec()));
+    assert_eq!(key_sequence("F1", KeyModifiers::NONE), Some(b"\x1bOP".to_vec()));
+    assert_eq!(key_sequence("Hyper", KeyModifiers::NONE), None);
+}
+
+#[test]
+fn test_ctrl_letters_send_control_characters() {
+    assert_eq!(key_sequence("c", KeyModifiers::CTRL), Some(b"\x03".to_vec()));
+    assert_eq!(key_sequence("A", KeyModifiers::CTRL), Some(b"\x01".to_vec()));
+    assert_eq!(key_sequence("c", KeyModifiers::NONE), Some(b"c".to_vec()));
+    // Only letters have a control character
+    assert_eq!(key_sequence("1", KeyModifiers::CTRL), Some(b"1".to_vec()));
+}
+
+#[test]
+fn test_alt_prefixes_escape() {
+    assert_eq!(key_sequence("f", KeyModifiers::ALT), Some(b"\x1bf".to_vec()));
+    assert_eq!(chord_sequence("Ctrl+Alt+x"), Some(b"\x1b\x18".to_vec()));
+}
+
+#[test]
+fn test_chords() {
+    assert_eq!(chord_sequence("Ctrl+C"), Some(b"\x03".to_vec()));
+    assert_eq!(chord_sequence("Down"), Some(b"\x1b[B".to_vec()));
+    assert_eq!(chord_sequence("Shift+Down"), None);
+    assert_eq!(chord_sequence(""), None);
+}
+
+#[test]
+fn test_send_key_rejects_unknown_names() {
+    let mut terminal = Terminal::new(80, 24).unwrap();
+    assert!(terminal.send_key("Enter", KeyModifiers::NONE).is_ok());
+    assert!(terminal.send_key("Hyper", KeyModifiers::NONE).is_err());
+}
@@ -0,0 +1,99 @@
+use titi::terminal::mouse::{encode_alternate_scroll, encode_mouse, MouseAction, MouseButton, MouseTracking};
+use titi::terminal::{Grid, TerminalParser};
+use std::sync::{Arc, Mutex};
+
+fn modes_after(sequence: &[u8]) -> (MouseTracking, bool) {
+    let grid = Arc::new(Mutex::new(Grid::new(80, 24)));
+    let mut parser = TerminalParser::new(grid.clone());
+    parser.parse(sequence);
+
+    let grid = grid.lock().unwrap();
+    (grid.mouse_tracking(), grid.sgr_mouse())
+}
+
+#[test]
+fn test_decset_enables_sgr_click_tracking() {
+    assert_eq!(modes_after(b""), (MouseTracking::Off, false));
+    assert_eq!(modes_after(b"\x1b[?1000h\x1b[?1006h"), (MouseTracking::Click, true));
+    assert_eq!(modes_after(b"\x1b[?1002h"), (MouseTracking::Drag, false));
+    assert_eq!(modes_after(b"\x1b[?1000h\x1b[?1006h\x1b[?1000l\x1b[?1006l"), (MouseTracking::Off, false));
+}
+
+#[test]
+fn test_sgr_left_click_encoding() {
+    let (tracking, sgr) = modes_after(b"\x1b[?1000h\x1b[?1006h");
+
+    assert_eq!(
+        encode_mouse(tracking, sgr, MouseButton::Left, MouseAction::Press, 5, 3),
+        Some(b"\x1b[<0;5;3M".to_vec())
+    );
+    assert_eq!(
+        encode_mouse(tracking, sgr, MouseButton::Left, MouseAction::Release, 5, 3),
+        Some(b"\x1b[<0;5;3m".to_vec())
+    );
+    assert_eq!(
+        encode_mouse(tracking, sgr, MouseButton::WheelDown, MouseAction::Press, 5, 3),
+        Some(b"\x1b[<65;5;3M".to_vec())
+    );
+}
+
+#[test]
+fn test_drag_reported_only_in_drag_mode() {
+    assert_eq!(encode_mouse(MouseTracking::Click, true, MouseButton::Left, MouseAction::Drag, 6, 3), None);
+    assert_eq!(
+        encode_mouse(MouseTracking::Drag, true, MouseButton::Left, MouseAction::Drag, 6, 3),
+        Some(b"\x1b[<32;6;3M".to_vec())
+    );
+    assert_eq!(encode_mouse(MouseTracking::Off, true, MouseButton::Left, MouseAction::Press, 1, 1), None);
+}
+
+#[test]
+fn test_legacy_encoding() {
+    assert_eq!(
+        encode_mouse(MouseTracking::Click, false, MouseButton::Right, MouseAction::Press, 5, 3),
+        Some(vec![0x1b, b'[', b'M', 32 + 2, 32 + 5, 32 + 3])
+    );
+    assert_eq!(
+        encode_mouse(MouseTracking::Click, false, MouseButton::Right, MouseAction::Release, 500, 3),
+        Some(vec![0x1b, b'[', b'M', 32 + 3, 32 + 223, 32 + 3])
+    );
+}
+
+fn alternate_scroll_after(sequence: &[u8], button: MouseButton, lines: usize) -> Option<Vec<u8>> {
+    let grid = Arc::new(Mutex::new(Grid::new(80, 24)));
+    let mut parser = TerminalParser::new(grid.clone());
+    parser.parse(sequence);
+
+    let grid = grid.lock().unwrap();
+    encode_alternate_scroll(grid.mouse_tracking(), grid.alternate_screen(), grid.alternate_scroll(), button, lines)
+}
+
+#[test]
+fn test_alternate_scroll_sends_arrow_keys() {
+    assert_eq!(
+        alternate_scroll_after(b"\x1b[?1049h\x1b[?1007h", MouseButton::WheelUp, 3),
+        Some(b"\x1b[A\x1b[A\x1b[A".to_vec())
+    );
+    assert_eq!(
+        alternate_scroll_after(b"\x1b[?1049h\x1b[?1007h", MouseButton::WheelDown, 1),
+        Some(b"\x1b[B".to_vec())
+    );
+}
+
+#[test]
+fn test_alternate_scroll_needs_alt_screen_and_no_tracking() {
+    // Normal screen
+    assert_eq!(alternate_scroll_after(b"\x1b[?1007h", MouseButton::WheelUp, 3), None);
+    // Mode off
+    assert_eq!(alternate_scroll_after(b"\x1b[?1049h", MouseButton::WheelUp, 3), None);
+    // Mouse reporting wins
+    assert_eq!(
+        alternate_scroll_after(b"\x1b[?1049h\x1b[?1007h\x1b[?1000h", MouseButton::WheelUp, 3),
+        None
+    );
+    // Back on the normal screen
+    assert_eq!(
+        alternate_scroll_after(b"\x1b[?1049h\x1b[?1007h\x1b[?1049l", MouseButton::WheelUp, 3),
+        None
+    );
+}
@@ -1,5 +1,6 @@
-use titi::terminal::{Color, Grid, TerminalParser};
+use titi::terminal::{CellStyle, Color, CursorShape, Grid, LineSize, TerminalParser, SYNCHRONIZED_OUTPUT_TIMEOUT};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 fn create_parser() -> (TerminalParser, Arc<Mutex<Grid>>) {
     let grid = Arc::new(Mutex::new(Grid::new(80, 24)));
@@ -153,6 +154,45 @@ fn test_parser_erase_display() {
     }
 }
 
+#[test]
+fn test_parser_erase_display_uses_current_background() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[1;31mXX\x1b[44m\x1b[2J");
+
+    let grid = grid.lock().unwrap();
+    let (cols, rows) = grid.size();
+    for y in 0..rows {
+        for x in 0..cols {
+            let cell = grid.get_cell(x, y).unwrap();
+            assert_eq!(cell.c, ' ');
+            assert_eq!(cell.style.bg, Color::Blue);
+            // Only the background carries over
+            assert_eq!(cell.style.fg, Color::Default);
+            assert!(!cell.style.bold);
+        }
+    }
+}
+
+#[test]
+fn test_parser_erase_line_and_scroll_use_current_background() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[42m\x1b[K");
+    {
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.get_cell(0, 0).unwrap().style.bg, Color::Green);
+        assert_eq!(grid.get_cell(0, 1).unwrap().style.bg, Color::Default);
+    }
+
+    // Scroll by moving past the bottom row; the new row is blanked in green
+    let (_, rows) = grid.lock().unwrap().size();
+    parser.parse(format!("\x1b[{};1H\n", rows).as_bytes());
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.get_cell(0, rows - 1).unwrap().style.bg, Color::Green);
+}
+
 #[test]
 fn test_parser_erase_line() {
     let (mut parser, grid) = create_parser();
@@ -248,16 +288,147 @@ fn test_parser_sgr_text_attributes() {
     assert!(grid.get_cell(12, 0).unwrap().style.underline);
 }
 
+#[test]
+fn test_parser_sgr_overline_framed_encircled() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[53mA\x1b[55mB\x1b[51;52mC\x1b[54mD");
+
+    let grid = grid.lock().unwrap();
+    assert!(grid.get_cell(0, 0).unwrap().style.overline);
+    assert!(!grid.get_cell(1, 0).unwrap().style.overline);
+    let framed = grid.get_cell(2, 0).unwrap().style;
+    assert!(framed.framed && framed.encircled);
+    let plain = grid.get_cell(3, 0).unwrap().style;
+    assert!(!plain.framed && !plain.encircled);
+}
+
+#[test]
+fn test_parser_osc_title() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b]2;vim: notes.txt\x07");
+    assert_eq!(grid.lock().unwrap().title(), Some("vim: notes.txt"));
+
+    // OSC 0 also sets the title; semicolons belong to the title
+    parser.parse(b"\x1b]0;a;b\x1b\\");
+    assert_eq!(grid.lock().unwrap().title(), Some("a;b"));
+
+    parser.parse(b"\x1b]2;\x07");
+    assert_eq!(grid.lock().unwrap().title(), None);
+}
+
+#[test]
+fn test_parser_cursor_shape() {
+    let (mut parser, grid) = create_parser();
+    assert_eq!(grid.lock().unwrap().cursor_shape(), None);
+
+    parser.parse(b"\x1b[4 q");
+    assert_eq!(grid.lock().unwrap().cursor_shape(), Some(CursorShape::Underline));
+
+    parser.parse(b"\x1b[5 q");
+    assert_eq!(grid.lock().unwrap().cursor_shape(), Some(CursorShape::Bar));
+
+    // 0 hands the cursor back to the configured default
+    parser.parse(b"\x1b[0 q");
+    assert_eq!(grid.lock().unwrap().cursor_shape(), None);
+}
+
+#[test]
+fn test_parser_insert_delete_and_scroll_lines() {
+    let (mut parser, grid) = create_parser();
+    parser.parse(b"one\r\ntwo\r\nthree");
+
+    // IL at row 2 pushes "two" and "three" down
+    parser.parse(b"\x1b[2;1H\x1b[L");
+    assert_eq!(grid.lock().unwrap().row_text(1), "");
+    assert_eq!(grid.lock().unwrap().row_text(2), "two");
+
+    // DL removes the blank line again
+    parser.parse(b"\x1b[M");
+    assert_eq!(grid.lock().unwrap().row_text(1), "two");
+    assert_eq!(grid.lock().unwrap().row_text(2), "three");
+
+    // SD then SU move the whole screen
+    parser.parse(b"\x1b[2T");
+    assert_eq!(grid.lock().unwrap().row_text(2), "one");
+    parser.parse(b"\x1b[2S");
+    assert_eq!(grid.lock().unwrap().row_text(0), "one");
+}
+
+#[test]
+fn test_parser_erase_characters() {
+    let (mut parser, grid) = create_parser();
+    parser.parse(b"ABCDEF");
+
+    // ECH 3 at column 2 blanks C, D and E in the current background
+    parser.parse(b"\x1b[1;3H\x1b[44m\x1b[3X");
+    {
+        let grid = grid.lock().unwrap();
+        let chars: String = (0..6).map(|x| grid.get_cell(x, 0).unwrap().c).collect();
+        assert_eq!(chars, "AB   F");
+        assert_eq!(grid.get_cell(2, 0).unwrap().style.bg, Color::Blue);
+        assert_eq!(grid.get_cell(5, 0).unwrap().style.bg, Color::Default);
+        assert_eq!(grid.cursor_pos(), (2, 0));
+    }
+
+    // The count defaults to 1 and stops at the end of the line
+    parser.parse(b"\x1b[0m\x1b[1;1H\x1b[X");
+    assert_eq!(grid.lock().unwrap().row_text(0), " B   F");
+    parser.parse(b"\x1b[1;6H\x1b[99X");
+    assert_eq!(grid.lock().unwrap().row_text(0), " B");
+}
+
+#[test]
+fn test_parser_save_restore_private_modes() {
+    use titi::terminal::mouse::MouseTracking;
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[?1000h\x1b[?1000;2004s\x1b[?1000l\x1b[?2004h");
+    assert_eq!(grid.lock().unwrap().mouse_tracking(), MouseTracking::Off);
+    assert!(grid.lock().unwrap().bracketed_paste());
+
+    parser.parse(b"\x1b[?1000;2004r");
+    assert_eq!(grid.lock().unwrap().mouse_tracking(), MouseTracking::Click);
+    assert!(!grid.lock().unwrap().bracketed_paste());
+
+    // Saves nest; a restore with nothing saved leaves the mode alone
+    parser.parse(b"\x1b[?25s\x1b[?25l\x1b[?25s\x1b[?25h\x1b[?25r");
+    assert!(!grid.lock().unwrap().cursor_visible());
+    parser.parse(b"\x1b[?25r");
+    assert!(grid.lock().unwrap().cursor_visible());
+    parser.parse(b"\x1b[?25l\x1b[?25r");
+    assert!(!grid.lock().unwrap().cursor_visible());
+}
+
 #[test]
 fn test_parser_sgr_256_color() {
     let (mut parser, grid) = create_parser();
 
-    // 256-color foreground: ESC[38;5;COLORm
-    parser.parse(b"\x1b[38;5;196mX"); // Bright red (color 196)
+    // 256-color foreground: ESC[38;5;COLORm, matching xterm's palette
+    parser.parse(b"\x1b[38;5;196mA"); // Bright red
+    parser.parse(b"\x1b[38;5;208mB"); // Orange
+    parser.parse(b"\x1b[38;5;16mC");  // Cube black
+    parser.parse(b"\x1b[38;5;231mD"); // Cube white
+    parser.parse(b"\x1b[38;5;67mE");  // Steel blue
+    parser.parse(b"\x1b[38;5;232mF"); // Darkest gray
+    parser.parse(b"\x1b[38;5;255mG"); // Lightest gray
+    parser.parse(b"\x1b[38;5;9mH");   // Named bright red
 
-    let _grid = grid.lock().unwrap();
-    // Should map to an RGB or named color
-    // The exact color depends on the 256-color palette implementation
+    let grid = grid.lock().unwrap();
+    let expected = [
+        Color::Rgb(255, 0, 0),
+        Color::Rgb(255, 135, 0),
+        Color::Rgb(0, 0, 0),
+        Color::Rgb(255, 255, 255),
+        Color::Rgb(95, 135, 175),
+        Color::Rgb(8, 8, 8),
+        Color::Rgb(238, 238, 238),
+        Color::BrightRed,
+    ];
+    for (x, color) in expected.iter().enumerate() {
+        assert_eq!(grid.get_cell(x, 0).unwrap().style.fg, *color, "cell {}", x);
+    }
 }
 
 #[test]
@@ -280,13 +451,25 @@ fn test_parser_sgr_rgb_color() {
 
 #[test]
 fn test_parser_scroll_region() {
-    let (mut parser, _grid) = create_parser();
+    let (mut parser, grid) = create_parser();
 
     // Set scroll region from row 5 to row 15
     parser.parse(b"\x1b[5;15r");
-
-    // This should set the internal scroll region
-    // Verification through scrolling behavior
+    assert_eq!(grid.lock().unwrap().scroll_region(), (4, 14));
+
+    // Inverted and single-row regions are ignored
+    parser.parse(b"\x1b[15;5r");
+    assert_eq!(grid.lock().unwrap().scroll_region(), (4, 14));
+    parser.parse(b"\x1b[7;7r");
+    assert_eq!(grid.lock().unwrap().scroll_region(), (4, 14));
+
+    // Out of range bottoms are clamped; missing or 0 mean the last row
+    parser.parse(b"\x1b[2;99r");
+    assert_eq!(grid.lock().unwrap().scroll_region(), (1, 23));
+    parser.parse(b"\x1b[3;0r");
+    assert_eq!(grid.lock().unwrap().scroll_region(), (2, 23));
+    parser.parse(b"\x1b[r");
+    assert_eq!(grid.lock().unwrap().scroll_region(), (0, 23));
 }
 
 #[test]
@@ -304,6 +487,140 @@ fn test_parser_save_restore_cursor() {
     assert_eq!(y, 9);  // Row 10, 0-indexed = 9
 }
 
+#[test]
+fn test_parser_repeat_character() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"X\x1b[4b");
+
+    let grid = grid.lock().unwrap();
+    for x in 0..5 {
+        assert_eq!(grid.get_cell(x, 0).unwrap().c, 'X');
+    }
+    assert_eq!(grid.get_cell(5, 0).unwrap().c, ' ');
+    assert_eq!(grid.cursor_pos(), (5, 0));
+}
+
+#[test]
+fn test_parser_repeat_after_cursor_move_is_ignored() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"X\x1b[1;10H\x1b[3b");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.get_cell(9, 0).unwrap().c, ' ');
+    assert_eq!(grid.cursor_pos(), (9, 0));
+}
+
+fn create_narrow_parser() -> (TerminalParser, Arc<Mutex<Grid>>) {
+    let grid = Arc::new(Mutex::new(Grid::new(5, 3)));
+    let parser = TerminalParser::new(grid.clone());
+    (parser, grid)
+}
+
+#[test]
+fn test_parser_printable_after_last_column_wraps() {
+    let (mut parser, grid) = create_narrow_parser();
+
+    parser.parse(b"ABCDE");
+    assert_eq!(grid.lock().unwrap().cursor_pos(), (4, 0));
+
+    parser.parse(b"F");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.row_text(0), "ABCDE");
+    assert_eq!(grid.row_text(1), "F");
+    assert_eq!(grid.cursor_pos(), (1, 1));
+}
+
+#[test]
+fn test_parser_newline_after_last_column() {
+    let (mut parser, grid) = create_narrow_parser();
+
+    parser.parse(b"ABCDE\nF");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.row_text(1), "F");
+    assert_eq!(grid.row_text(2), "");
+    assert_eq!(grid.cursor_pos(), (1, 1));
+}
+
+#[test]
+fn test_parser_cursor_move_after_last_column_cancels_wrap() {
+    let (mut parser, grid) = create_narrow_parser();
+
+    // Cursor back moves from the last column, not from past it
+    parser.parse(b"ABCDE\x1b[DX");
+    {
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.row_text(0), "ABCXE");
+        assert_eq!(grid.row_text(1), "");
+        assert!(!grid.wrap_pending());
+    }
+
+    // A pending wrap reports the cursor on the last column
+    parser.parse(b"\x1b[1;5HZ\x1b[6n");
+    {
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.row_text(0), "ABCXZ");
+        assert!(grid.wrap_pending());
+    }
+    assert_eq!(parser.take_responses(), b"\x1b[1;5R");
+}
+
+#[test]
+fn test_parser_bracketed_paste_mode() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[?2004h");
+    assert!(grid.lock().unwrap().bracketed_paste());
+
+    parser.parse(b"\x1b[?2004l");
+    assert!(!grid.lock().unwrap().bracketed_paste());
+}
+
+#[test]
+fn test_parser_synchronized_output_freezes_grid() {
+    let (mut parser, grid) = create_parser();
+    assert!(!grid.lock().unwrap().is_frozen());
+
+    // The frame is still parsed into the grid while frozen
+    parser.parse(b"\x1b[?2026h\x1b[Hframe");
+    {
+        let grid = grid.lock().unwrap();
+        assert!(grid.is_frozen());
+        assert_eq!(grid.row_text(0), "frame");
+        // The freeze lapses on its own if the application never ends it
+        let until = grid.frozen_until().unwrap();
+        assert!(until <= Instant::now() + SYNCHRONIZED_OUTPUT_TIMEOUT);
+    }
+
+    parser.parse(b"\x1b[?2026l");
+    assert!(!grid.lock().unwrap().is_frozen());
+    assert_eq!(grid.lock().unwrap().frozen_until(), None);
+}
+
+#[test]
+fn test_parser_autowrap_mode() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[?7l");
+    assert!(!grid.lock().unwrap().autowrap());
+    parser.parse(b"\x1b[1;79HXYZ");
+    {
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.get_cell(78, 0).unwrap().c, 'X');
+        assert_eq!(grid.get_cell(79, 0).unwrap().c, 'Z');
+        assert_eq!(grid.get_cell(0, 1).unwrap().c, ' ');
+    }
+
+    parser.parse(b"\x1b[?7h\x1b[1;80HAB");
+    let grid = grid.lock().unwrap();
+    assert!(grid.autowrap());
+    assert_eq!(grid.get_cell(79, 0).unwrap().c, 'A');
+    assert_eq!(grid.get_cell(0, 1).unwrap().c, 'B');
+}
+
 #[test]
 fn test_parser_complex_sequence() {
     let (mut parser, grid) = create_parser();
@@ -374,3 +691,259 @@ fn test_parser_multiple_sgr_params() {
     assert_eq!(cell.style.fg, Color::Red);
     assert_eq!(cell.style.bg, Color::Green);
 }
+
+#[test]
+fn test_parser_soft_reset_restores_defaults() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[5;15r\x1b[1;31m\x1b[?7l\x1b[?25lText");
+    {
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.scroll_region(), (4, 14));
+        assert!(!grid.cursor_visible());
+    }
+
+    parser.parse(b"\x1b[!p");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.scroll_region(), (0, 23));
+    assert_eq!(grid.get_current_style(), CellStyle::default());
+    assert!(grid.autowrap());
+    assert!(grid.cursor_visible());
+    // The screen itself is left alone
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'T');
+    assert_eq!(grid.get_cell(0, 0).unwrap().style.fg, Color::Red);
+}
+
+#[test]
+fn test_parser_soft_reset_leaves_alternate_screen() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"primary\x1b[?1049h\x1b[Halternate");
+    assert!(grid.lock().unwrap().alternate_screen());
+
+    parser.parse(b"\x1b[!p");
+
+    let grid = grid.lock().unwrap();
+    assert!(!grid.alternate_screen());
+    assert_eq!(grid.row_text(0), "primary");
+}
+
+#[test]
+fn test_parser_full_reset_clears_screen_and_scrollback() {
+    let (mut parser, grid) = create_parser();
+
+    for i in 0..30 {
+        parser.parse(format!("\x1b[32mline {}\r\n", i).as_bytes());
+    }
+    parser.parse(b"\x1b[3;10r\x1b]2;title\x07");
+    assert!(grid.lock().unwrap().scrollback_len() > 0);
+
+    parser.parse(b"\x1bc");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.scrollback_len(), 0);
+    assert_eq!(grid.cursor_pos(), (0, 0));
+    assert_eq!(grid.scroll_region(), (0, 23));
+    assert_eq!(grid.get_current_style(), CellStyle::default());
+    assert_eq!(grid.title(), None);
+    assert!(grid.visible_lines().all(|line| line.trim().is_empty()));
+}
+
+#[test]
+fn test_parser_sgr_with_intermediate_is_ignored() {
+    let (mut parser, grid) = create_parser();
+
+    // xterm modifyOtherKeys (`CSI > 4 ; 1 m`) is not SGR
+    parser.parse(b"\x1b[>4;1mX");
+
+    let grid = grid.lock().unwrap();
+    assert!(!grid.get_cell(0, 0).unwrap().style.bold);
+    assert!(!grid.get_cell(0, 0).unwrap().style.underline);
+}
+
+#[test]
+fn test_parser_line_size_sequences() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b#6Wide\r\n\x1b#3Big\r\n\x1b#4Big\r\nNormal");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.line_size(0), LineSize::DoubleWidth);
+    assert_eq!(grid.line_size(1), LineSize::DoubleHeightTop);
+    assert_eq!(grid.line_size(2), LineSize::DoubleHeightBottom);
+    assert_eq!(grid.line_size(3), LineSize::Single);
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'W');
+}
+
+#[test]
+fn test_double_width_line_wraps_at_half_width() {
+    let grid = Arc::new(Mutex::new(Grid::new(10, 4)));
+    let mut parser = TerminalParser::new(grid.clone());
+
+    parser.parse(b"\x1b#6abcdefg");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.get_cell(4, 0).unwrap().c, 'e');
+    assert_eq!(grid.get_cell(5, 0).unwrap().c, ' ');
+    assert_eq!(grid.get_cell(0, 1).unwrap().c, 'f');
+    assert_eq!(grid.line_size(1), LineSize::Single);
+}
+
+#[test]
+fn test_line_size_scrolls_with_its_line_and_resets() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b#6top\r\n");
+    parser.parse(b"\x1b[24;1H\n");
+    assert_eq!(grid.lock().unwrap().line_size(0), LineSize::Single);
+
+    parser.parse(b"\x1b[2;1H\x1b#6\x1b[S");
+    assert_eq!(grid.lock().unwrap().line_size(0), LineSize::DoubleWidth);
+
+    parser.parse(b"\x1b#5");
+    assert_eq!(grid.lock().unwrap().line_size(0), LineSize::DoubleWidth);
+    parser.parse(b"\x1b[1;1H\x1b#5");
+    assert_eq!(grid.lock().unwrap().line_size(0), LineSize::Single);
+
+    parser.parse(b"\x1b#6\x1b[2J");
+    assert_eq!(grid.lock().unwrap().line_size(0), LineSize::Single);
+}
+
+#[test]
+fn test_parser_8bit_csi_matches_7bit() {
+    let (mut seven_bit, seven_grid) = create_parser();
+    seven_bit.parse(b"\x1b[5;10HX");
+
+    // Raw C1 byte, and the same control encoded as UTF-8
+    for sequence in [&b"\x9b5;10HX"[..], "\u{9b}5;10HX".as_bytes()] {
+        let (mut parser, grid) = create_parser();
+        parser.parse(sequence);
+
+        let grid = grid.lock().unwrap();
+        assert_eq!(grid.cursor_pos(), seven_grid.lock().unwrap().cursor_pos());
+        assert_eq!(grid.get_cell(9, 4).unwrap().c, 'X');
+    }
+}
+
+#[test]
+fn test_parser_8bit_osc_and_st() {
+    let (mut parser, grid) = create_parser();
+    parser.parse(b"\x9d2;eight bit\x9cok");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.title(), Some("eight bit"));
+    assert_eq!(grid.get_cell(0, 0).unwrap().c, 'o');
+}
+
+#[test]
+fn test_parser_utf8_not_mistaken_for_c1() {
+    let (mut parser, grid) = create_parser();
+    // '€' is E2 82 AC and '¡' is C2 A1: continuation bytes in the C1 range
+    // and a C2 lead must still decode as text, even split across calls
+    parser.parse("\x1b[m€".as_bytes());
+    parser.parse(&"¡é".as_bytes()[..1]);
+    parser.parse(&"¡é".as_bytes()[1..]);
+
+    let grid = grid.lock().unwrap();
+    let text: String = (0..3).map(|x| grid.get_cell(x, 0).unwrap().c).collect();
+    assert_eq!(text, "€¡é");
+}
+
+#[test]
+fn test_parser_osc7_working_directory() {
+    let (mut parser, grid) = create_parser();
+    assert_eq!(grid.lock().unwrap().current_dir(), None);
+
+    parser.parse(b"\x1b]7;file://myhost/home/user/My%20Projects/caf%C3%A9\x07");
+    assert_eq!(
+        grid.lock().unwrap().current_dir(),
+        Some(std::path::Path::new("/home/user/My Projects/café"))
+    );
+
+    // An empty host, ST-terminated
+    parser.parse(b"\x1b]7;file:///tmp\x1b\\");
+    assert_eq!(grid.lock().unwrap().current_dir(), Some(std::path::Path::new("/tmp")));
+
+    // Anything but a file URL is ignored
+    parser.parse(b"\x1b]7;https://example.com/x\x07\x1b]7;file://host/bad%zz\x07");
+    assert_eq!(grid.lock().unwrap().current_dir(), Some(std::path::Path::new("/tmp")));
+}
+
+#[test]
+fn test_parser_device_attributes() {
+    let (mut parser, _grid) = create_parser();
+    assert!(parser.take_responses().is_empty());
+
+    parser.parse(b"\x1b[c");
+    assert_eq!(parser.take_responses(), b"\x1b[?62;1;6c");
+    assert!(parser.take_responses().is_empty());
+
+    parser.parse(b"\x1b[0c\x1b[>c");
+    let mut expected = b"\x1b[?62;1;6c".to_vec();
+    expected.extend_from_slice(titi::terminal::secondary_device_attributes().as_bytes());
+    assert_eq!(parser.take_responses(), expected);
+    assert!(titi::terminal::secondary_device_attributes().starts_with("\x1b[>1;"));
+}
+
+#[test]
+fn test_parser_device_status_report() {
+    let (mut parser, _grid) = create_parser();
+
+    parser.parse(b"\x1b[5n\x1b[3;7H\x1b[6n");
+    assert_eq!(parser.take_responses(), b"\x1b[0n\x1b[3;7R");
+}
+
+#[test]
+fn test_parser_restores_cursor_saved_below_a_shrunk_screen() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[21;80H\x1b[s");
+    grid.lock().unwrap().resize(80, 10);
+    parser.parse(b"\x1b[uab");
+
+    // The saved cursor moved up with its line, so 'a' lands in the last
+    // column and 'b' wraps onto a new bottom line
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.get_cell(79, 8).unwrap().c, 'a');
+    assert_eq!(grid.get_cell(0, 9).unwrap().c, 'b');
+    assert_eq!(grid.cursor_pos(), (1, 9));
+}
+
+#[test]
+fn test_parser_erases_at_cursor_restored_after_shrink() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[21;1H\x1b[s");
+    grid.lock().unwrap().resize(80, 10);
+    parser.parse(b"\x1b[u\x1b[5X");
+
+    assert_eq!(grid.lock().unwrap().cursor_pos(), (0, 9));
+}
+
+#[test]
+fn test_parser_leaves_alternate_screen_resized_while_active() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[21;75H\x1b[?1049h");
+    grid.lock().unwrap().resize(80, 10);
+    parser.parse(b"\x1b[?1049l0123456789");
+
+    // The cursor saved on entry is clamped to the smaller screen
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.row_text(8).trim_start(), "012345");
+    assert_eq!(grid.row_text(9), "6789");
+}
+
+#[test]
+fn test_parser_resize_resets_scroll_region() {
+    let (mut parser, grid) = create_parser();
+
+    parser.parse(b"\x1b[15;20r");
+    grid.lock().unwrap().resize(80, 10);
+    parser.parse(b"\x1b[10;1Hx\n\n\n");
+
+    let grid = grid.lock().unwrap();
+    assert_eq!(grid.cursor_pos(), (0, 9));
+    assert_eq!(grid.row_text(6), "x");
+}
@@ -0,0 +1,56 @@
+use titi::terminal::paste::{paste_chunks, paste_confirmation_prompt, paste_needs_confirmation, sanitize_paste};
+
+#[test]
+fn test_sanitize_strips_control_characters() {
+    let pasted = "echo hi\x1b[201~; rm -rf ~\x03\tdone";
+
+    assert_eq!(sanitize_paste(pasted, false), "echo hi[201~; rm -rf ~\tdone");
+}
+
+#[test]
+fn test_sanitize_keeps_newlines_only_when_bracketed() {
+    let pasted = "line one\r\nline two\n";
+
+    assert_eq!(sanitize_paste(pasted, false), "line one line two ");
+    assert_eq!(sanitize_paste(pasted, true), pasted);
+
+    // Each break becomes one space, so commands don't run together
+    assert_eq!(sanitize_paste("echo a\necho b", false), "echo a echo b");
+    assert_eq!(sanitize_paste("a\r\rb\n\nc", false), "a  b  c");
+}
+
+#[test]
+fn test_paste_chunks_respect_limit_and_char_boundaries() {
+    let text = "é".repeat(5000);
+    let chunks = paste_chunks(&text, 4096);
+
+    assert!(chunks.iter().all(|chunk| chunk.len() <= 4096));
+    assert_eq!(chunks.concat(), text);
+    assert!(paste_chunks("", 4096).is_empty());
+}
+
+#[test]
+fn test_multiline_or_long_paste_needs_confirmation() {
+    // Without bracketed paste a multiline paste asks first, a short line doesn't
+    assert!(paste_needs_confirmation("make\nmake install\n", false, 4096));
+    assert!(paste_needs_confirmation("ls\r", false, 4096));
+    assert!(!paste_needs_confirmation("git status", false, 4096));
+
+    // Long single lines ask too
+    assert!(paste_needs_confirmation(&"x".repeat(4097), false, 4096));
+    assert!(!paste_needs_confirmation(&"x".repeat(4096), false, 4096));
+
+    // Bracketed paste delivers the text as one unit, and 0 turns the check off
+    assert!(!paste_needs_confirmation("make\nmake install\n", true, 4096));
+    assert!(!paste_needs_confirmation("make\nmake install\n", false, 0));
+}
+
+#[test]
+fn test_confirmation_prompt_describes_paste() {
+    let prompt = paste_confirmation_prompt("make\nmake install\n");
+
+    assert_eq!(prompt[0], "Paste 2 lines (18 bytes)?");
+    assert_eq!(prompt[1], "Line breaks become spaces without bracketed paste.");
+    assert_eq!(prompt.len(), 3);
+    assert_eq!(paste_confirmation_prompt(&"x".repeat(5000))[0], "Paste 1 line (5000 bytes)?");
+}
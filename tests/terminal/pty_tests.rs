@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+use titi::config::ShellConfig;
+use titi::terminal::{Pty, Terminal, TerminalEvent, PTY_BUFFER_CAPACITY};
+
+/// Read PTY output into the grid until `needle` shows up on screen, the child
+/// exits, or the timeout elapses
+fn wait_for_text(terminal: &mut Terminal, needle: &str, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    while Instant::now() < deadline {
+        match terminal.read() {
+            Ok(Some(data)) => terminal.process_output(&data),
+            Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            Err(_) => break,
+        }
+
+        if screen_text(terminal).contains(needle) {
+            return true;
+        }
+    }
+
+    screen_text(terminal).contains(needle)
+}
+
+fn screen_text(terminal: &Terminal) -> String {
+    let grid = terminal.grid();
+    let grid = grid.lock().unwrap();
+    let (cols, rows) = grid.size();
+    let mut text = String::new();
+    for row in 0..rows {
+        for col in 0..cols {
+            if let Some(cell) = grid.get_cell(col, row) {
+                text.push(cell.c);
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pty_runs_custom_command() {
+    let shell = ShellConfig {
+        program: Some("/bin/echo".to_string()),
+        args: vec!["hello-from-titi".to_string()],
+        ..Default::default()
+    };
+
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+
+    assert!(
+        wait_for_text(&mut terminal, "hello-from-titi", Duration::from_secs(5)),
+        "Command output should appear in the grid"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pty_applies_env_and_working_directory() {
+    let mut env = std::collections::HashMap::new();
+    env.insert("TITI_TEST_VAR".to_string(), "custom-value".to_string());
+
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        args: vec!["-c".to_string(), "echo \"$TITI_TEST_VAR:$(pwd)\"".to_string()],
+        env,
+        working_directory: Some(std::path::PathBuf::from("/tmp")),
+        ..Default::default()
+    };
+
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+
+    assert!(
+        wait_for_text(&mut terminal, "custom-value:/tmp", Duration::from_secs(5)),
+        "Child should see configured env and cwd"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pty_advertises_default_term() {
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        args: vec!["-c".to_string(), "echo \"term=$TERM color=$COLORTERM\"".to_string()],
+        ..Default::default()
+    };
+
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+
+    assert!(
+        wait_for_text(&mut terminal, "term=xterm-256color color=truecolor", Duration::from_secs(5)),
+        "Child should see TERM=xterm-256color and COLORTERM=truecolor"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pty_term_is_configurable() {
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        args: vec!["-c".to_string(), "echo \"term=$TERM\"".to_string()],
+        term: "vt100".to_string(),
+        ..Default::default()
+    };
+
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+
+    assert!(
+        wait_for_text(&mut terminal, "term=vt100", Duration::from_secs(5)),
+        "Child should see the configured TERM"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_resize_emits_event() {
+    let shell = ShellConfig {
+        program: Some("/bin/cat".to_string()),
+        ..Default::default()
+    };
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+    let events = terminal.subscribe();
+
+    terminal.resize(100, 30).expect("Resize failed");
+
+    match events.recv_timeout(Duration::from_secs(1)) {
+        Ok(TerminalEvent::Resize(cols, rows)) => assert_eq!((cols, rows), (100, 30)),
+        other => panic!("Expected a Resize event, got {:?}", other),
+    }
+    assert_eq!(terminal.grid().lock().unwrap().size(), (100, 30));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_resize_delivers_sigwinch() {
+    // The trap runs between sleeps and prints the size the child now sees
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        args: vec![
+            "-c".to_string(),
+            "trap 'echo \"winch $(stty size)\"' WINCH; echo ready; while :; do sleep 0.1; done".to_string(),
+        ],
+        ..Default::default()
+    };
+    let mut terminal = Terminal::with_shell(80, 24, &shell).expect("Failed to spawn command");
+    assert!(wait_for_text(&mut terminal, "ready", Duration::from_secs(5)));
+
+    terminal.resize(100, 40).expect("Resize failed");
+
+    assert!(
+        wait_for_text(&mut terminal, "winch 40 100", Duration::from_secs(5)),
+        "SIGWINCH handler should observe the new size"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pty_reports_resized_size() {
+    let shell = ShellConfig {
+        program: Some("/bin/cat".to_string()),
+        ..Default::default()
+    };
+    let mut pty = Pty::with_options(80, 24, &shell).expect("Failed to spawn command");
+    assert_eq!(pty.size().unwrap(), (80, 24));
+
+    pty.resize(132, 50).expect("Resize failed");
+    assert_eq!(pty.size().unwrap(), (132, 50));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_large_output_read_without_loss() {
+    const OUTPUT_BYTES: usize = 6 * 1024 * 1024;
+
+    let shell = ShellConfig {
+        program: Some("/bin/sh".to_string()),
+        args: vec![
+            "-c".to_string(),
+            format!("head -c {} /dev/zero | tr '\\0' x; echo done", OUTPUT_BYTES),
+        ],
+        ..Default::default()
+    };
+    let mut pty = Pty::with_options(80, 24, &shell).expect("Failed to spawn command");
+
+    // Nobody reads for a while; the reader thread fills its buffer and stops there
+    std::thread::sleep(Duration::from_millis(500));
+
+    let deadline = Instant::now() + Duration::from_secs(20);
+    let mut received = Vec::with_capacity(OUTPUT_BYTES + 16);
+    while !received.ends_with(b"done\r\n") && Instant::now() < deadline {
+        match pty.read() {
+            Ok(Some(data)) => received.extend_from_slice(&data),
+            Ok(None) => std::thread::sleep(Duration::from_millis(1)),
+            Err(e) => panic!("PTY read failed: {}", e),
+        }
+    }
+
+    assert_eq!(received.iter().filter(|&&b| b == b'x').count(), OUTPUT_BYTES);
+    assert!(received.ends_with(b"done\r\n"), "output should be complete before the deadline");
+    assert!(pty.high_water_mark() > 0);
+    assert!(pty.high_water_mark() <= PTY_BUFFER_CAPACITY);
+}
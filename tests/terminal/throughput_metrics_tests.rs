@@ -0,0 +1,25 @@
+//! Throughput counters recorded into the global `METRICS`
+//!
+//! Kept in its own test binary so no other test parses concurrently and
+//! moves the counters.
+
+use std::sync::{Arc, Mutex};
+use titi::metrics::METRICS;
+use titi::terminal::{Grid, TerminalParser};
+
+#[test]
+fn test_parse_advances_parsed_bytes_counter() {
+    let grid = Arc::new(Mutex::new(Grid::new(80, 24)));
+    let mut parser = TerminalParser::new(grid);
+
+    let plain = b"hello world\r\n";
+    let styled = b"\x1b[1;31mred\x1b[0m";
+
+    let before = METRICS.bytes_parsed();
+    parser.parse(plain);
+    parser.parse(styled);
+
+    assert_eq!(METRICS.bytes_parsed() - before, (plain.len() + styled.len()) as u64);
+    assert!(METRICS.parse_rate() > 0.0);
+    assert!(METRICS.render_prometheus().contains("titi_bytes_parsed_total"));
+}